@@ -0,0 +1,286 @@
+//! Row-by-row writes (`add transaction`, `set transaction`): input parsing,
+//! pre-flight checks and per-row reporting.
+//!
+//! Every row is checked against the exported accounts and categories before
+//! anything is written. Rows that fail a check or the write itself are
+//! reported and skipped; the other rows are still written. One JSON object
+//! per row goes to stdout and a summary to stderr; [`RowsFailed`] makes the
+//! process exit with status 2 when some rows failed.
+
+use std::fmt;
+use std::io::Write;
+
+use moneymoney::add_transaction::AddTransactionParams;
+use moneymoney::export_accounts::MoneymoneyAccount;
+use moneymoney::export_categories::{find_category, MoneymoneyCategory};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Parse a JSON object, a JSON array of objects, or NDJSON (one object per
+/// line) into rows.
+pub(crate) fn parse_rows<T: DeserializeOwned>(raw: &str) -> Result<Vec<T>, String> {
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(raw) {
+        let items = match value {
+            serde_json::Value::Array(items) => items,
+            single => vec![single],
+        };
+        return items
+            .into_iter()
+            .enumerate()
+            .map(|(i, item)| {
+                serde_json::from_value(item).map_err(|e| format!("row {}: {e}", i + 1))
+            })
+            .collect();
+    }
+    raw.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("line {}: {e}", i + 1)))
+        .collect()
+}
+
+/// Check that a row's account and category exist before writing it.
+pub(crate) fn check_add_row(
+    params: &AddTransactionParams,
+    accounts: &[MoneymoneyAccount],
+    categories: &[MoneymoneyCategory],
+) -> Result<(), String> {
+    match accounts.iter().find(|a| a.matches(&params.to_account)) {
+        None => return Err(format!("unknown account `{}`", params.to_account)),
+        Some(account) if account.group => {
+            return Err(format!("`{}` is an account group", params.to_account))
+        }
+        Some(_) => {}
+    }
+    check_category(params.category.as_deref(), categories)?;
+    if !params.amount.is_finite() {
+        return Err(format!("invalid amount {}", params.amount));
+    }
+    Ok(())
+}
+
+/// Check that a category to assign exists, if one is given.
+pub(crate) fn check_category(
+    category: Option<&str>,
+    categories: &[MoneymoneyCategory],
+) -> Result<(), String> {
+    match category {
+        Some(category) if find_category(categories, category).is_none() => {
+            Err(format!("unknown category `{category}`"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Outcome of one row.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RowStatus {
+    Added,
+    Updated,
+    Failed,
+}
+
+/// One line of the per-row report.
+#[derive(Serialize, Debug)]
+pub(crate) struct RowReport {
+    /// 1-based position in the input.
+    pub(crate) row: usize,
+    /// Transaction id, when the row refers to an existing transaction.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) id: Option<u64>,
+    pub(crate) status: RowStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+/// Collects row outcomes, writing each as a compact JSON line.
+pub(crate) struct Report<W: Write> {
+    out: W,
+    total: usize,
+    failed: usize,
+}
+
+impl<W: Write> Report<W> {
+    pub(crate) fn new(out: W) -> Self {
+        Self {
+            out,
+            total: 0,
+            failed: 0,
+        }
+    }
+
+    /// Record the result of row `row` (1-based); `ok` is the status on success.
+    pub(crate) fn record(
+        &mut self,
+        row: usize,
+        id: Option<u64>,
+        ok: RowStatus,
+        result: Result<(), String>,
+    ) -> std::io::Result<()> {
+        self.total += 1;
+        let report = match result {
+            Ok(()) => RowReport {
+                row,
+                id,
+                status: ok,
+                error: None,
+            },
+            Err(error) => {
+                self.failed += 1;
+                RowReport {
+                    row,
+                    id,
+                    status: RowStatus::Failed,
+                    error: Some(error),
+                }
+            }
+        };
+        serde_json::to_writer(&mut self.out, &report)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()
+    }
+
+    /// Print the summary to stderr; an error if any row failed.
+    pub(crate) fn finish(self, verb: &str) -> Result<(), RowsFailed> {
+        eprintln!(
+            "{verb} {} of {} transaction(s), {} failed",
+            self.total - self.failed,
+            self.total,
+            self.failed
+        );
+        if self.failed == 0 {
+            Ok(())
+        } else {
+            Err(RowsFailed {
+                failed: self.failed,
+                total: self.total,
+            })
+        }
+    }
+}
+
+/// Some rows of a batch failed; the rest were written.
+#[derive(Debug)]
+pub(crate) struct RowsFailed {
+    pub(crate) failed: usize,
+    pub(crate) total: usize,
+}
+
+impl RowsFailed {
+    /// Exit status for a partially failed batch (1 is used for fatal errors).
+    pub(crate) const EXIT_CODE: i32 = 2;
+}
+
+impl fmt::Display for RowsFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} rows failed", self.failed, self.total)
+    }
+}
+
+impl std::error::Error for RowsFailed {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use moneymoney::export_accounts::MoneymoneyAccountType;
+
+    fn account(name: &str, group: bool) -> MoneymoneyAccount {
+        MoneymoneyAccount {
+            account_number: String::new(),
+            attributes: plist::Dictionary::new(),
+            balance: None,
+            bank_code: String::new(),
+            currency: "EUR".to_string(),
+            group,
+            icon: plist::Data::new(Vec::new()),
+            indentation: 0,
+            name: name.to_string(),
+            owner: String::new(),
+            portfolio: false,
+            refresh_timestamp: chrono::Utc::now(),
+            r#type: MoneymoneyAccountType::Cash,
+            uuid: uuid::Uuid::new_v4(),
+            sub_account: None,
+            iban: None,
+            bic: None,
+            comment: None,
+            balance_date: None,
+        }
+    }
+
+    fn category(name: &str) -> MoneymoneyCategory {
+        MoneymoneyCategory {
+            uuid: uuid::Uuid::new_v4(),
+            name: name.to_string(),
+            budget: None,
+            currency: iso_currency::Currency::EUR,
+            default: false,
+            group: false,
+            icon: plist::Data::new(Vec::new()),
+            indentation: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_rows_json_and_ndjson() {
+        let one: Vec<serde_json::Value> = parse_rows(r#"{"amount": 1}"#).unwrap();
+        assert_eq!(one.len(), 1);
+        let many: Vec<serde_json::Value> = parse_rows(r#"[{"amount": 1}, {"amount": 2}]"#).unwrap();
+        assert_eq!(many.len(), 2);
+        let lines: Vec<serde_json::Value> =
+            parse_rows("{\"amount\": 1}\n\n{\"amount\": 2}\n{\"amount\": 3}\n").unwrap();
+        assert_eq!(lines.len(), 3);
+        let err = parse_rows::<serde_json::Value>("{\"amount\": 1}\n{oops}\n").unwrap_err();
+        assert!(err.starts_with("line 2:"), "{err}");
+    }
+
+    #[test]
+    fn test_check_add_row() {
+        let accounts = [account("Bargeld", false), account("Privat", true)];
+        let categories = [category("Lebensmittel")];
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+
+        let ok =
+            AddTransactionParams::new("Bargeld", date, "Bäcker", -3.5).category("Lebensmittel");
+        assert_eq!(check_add_row(&ok, &accounts, &categories), Ok(()));
+
+        let unknown = AddTransactionParams::new("Sparbuch", date, "Bäcker", -3.5);
+        assert_eq!(
+            check_add_row(&unknown, &accounts, &categories),
+            Err("unknown account `Sparbuch`".to_string())
+        );
+        let group = AddTransactionParams::new("Privat", date, "Bäcker", -3.5);
+        assert_eq!(
+            check_add_row(&group, &accounts, &categories),
+            Err("`Privat` is an account group".to_string())
+        );
+        let category = AddTransactionParams::new("Bargeld", date, "Bäcker", -3.5).category("Kino");
+        assert_eq!(
+            check_add_row(&category, &accounts, &categories),
+            Err("unknown category `Kino`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_report_lines_and_summary() {
+        let mut out = Vec::new();
+        let mut report = Report::new(&mut out);
+        report.record(1, None, RowStatus::Added, Ok(())).unwrap();
+        report
+            .record(2, None, RowStatus::Added, Err("unknown account `X`".to_string()))
+            .unwrap();
+        report
+            .record(3, Some(42), RowStatus::Updated, Ok(()))
+            .unwrap();
+        let failed = report.finish("added").unwrap_err();
+        assert_eq!(failed.to_string(), "1 of 3 rows failed");
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"row\":1,\"status\":\"added\"}\n\
+             {\"row\":2,\"status\":\"failed\",\"error\":\"unknown account `X`\"}\n\
+             {\"row\":3,\"id\":42,\"status\":\"updated\"}\n"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! `categorize suggest`: suggestion rows and the interactive review.
+
+use std::io::{self, BufRead, Write};
+
+use chrono::NaiveDate;
+use moneymoney::classify::Suggestion;
+use moneymoney::export_transactions::MoneymoneyTransaction;
+use serde::Serialize;
+
+/// One line of `categorize suggest` output.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SuggestionRow<'a> {
+    pub(crate) id: u64,
+    pub(crate) booking_date: NaiveDate,
+    pub(crate) name: &'a str,
+    pub(crate) amount: f64,
+    pub(crate) currency: &'a str,
+    pub(crate) category: &'a str,
+    pub(crate) confidence: f64,
+}
+
+impl<'a> SuggestionRow<'a> {
+    pub(crate) fn new(transaction: &'a MoneymoneyTransaction, suggestion: &'a Suggestion) -> Self {
+        Self {
+            id: transaction.id,
+            booking_date: transaction.booking_date.date_naive(),
+            name: &transaction.name,
+            amount: transaction.amount,
+            currency: &transaction.currency,
+            category: &suggestion.category,
+            confidence: suggestion.confidence,
+        }
+    }
+}
+
+/// Ask about each row on `prompt`, reading answers from `input`: `y` accepts,
+/// `n` or an empty line rejects, `q` or the end of input stops. Returns the
+/// indices of the accepted rows.
+pub(crate) fn review<R: BufRead, W: Write>(
+    rows: &[SuggestionRow<'_>],
+    mut input: R,
+    mut prompt: W,
+) -> io::Result<Vec<usize>> {
+    let mut accepted = Vec::new();
+    let mut line = String::new();
+    'rows: for (i, row) in rows.iter().enumerate() {
+        loop {
+            write!(
+                prompt,
+                "[{}/{}] {}  {}  {:.2} {} → {} ({:.0}%) [y/n/q] ",
+                i + 1,
+                rows.len(),
+                row.booking_date,
+                row.name,
+                row.amount,
+                row.currency,
+                row.category,
+                row.confidence * 100.0
+            )?;
+            prompt.flush()?;
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(prompt)?;
+                break 'rows;
+            }
+            match line.trim().to_lowercase().as_str() {
+                "y" | "yes" => {
+                    accepted.push(i);
+                    break;
+                }
+                "" | "n" | "no" => break,
+                "q" | "quit" => break 'rows,
+                _ => writeln!(prompt, "answer y (accept), n (reject) or q (quit)")?,
+            }
+        }
+    }
+    Ok(accepted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_review_answers() {
+        let row = |id| SuggestionRow {
+            id,
+            booking_date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            name: "REWE",
+            amount: -12.5,
+            currency: "EUR",
+            category: "Lebensmittel",
+            confidence: 0.93,
+        };
+        let rows = [row(1), row(2), row(3), row(4), row(5)];
+        let mut prompt = Vec::new();
+        let accepted = review(&rows, "y\n\nmaybe\nYES\nq\n".as_bytes(), &mut prompt).unwrap();
+        assert_eq!(accepted, [0, 2]);
+        let prompt = String::from_utf8(prompt).unwrap();
+        assert!(
+            prompt.starts_with("[1/5] 2024-03-01  REWE  -12.50 EUR → Lebensmittel (93%) [y/n/q] "),
+            "{prompt}"
+        );
+        assert!(prompt.contains("answer y (accept), n (reject) or q (quit)\n[3/5]"), "{prompt}");
+        assert!(!prompt.contains("[5/5]"), "{prompt}");
+
+        // The end of input stops like `q`.
+        assert_eq!(review(&rows, "n\ny\n".as_bytes(), io::sink()).unwrap(), [1]);
+    }
+}
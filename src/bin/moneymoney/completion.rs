@@ -0,0 +1,282 @@
+//! Shell completion: the `completions <shell>` registration scripts and the
+//! account and category candidates offered while completing.
+//!
+//! The scripts call back into `moneymoney` on every <kbd>Tab</kbd> (see
+//! [`clap_complete::CompleteEnv`]), so flags, subcommands and `long_help`
+//! stay in sync with the binary. Account and category values come from a
+//! small cache of the last `export_accounts` / `export_categories` result,
+//! refreshed from MoneyMoney when it is older than [`MAX_AGE`], plus the
+//! default profile's aliases.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use clap::ValueEnum;
+use clap_complete::env::Shells;
+use clap_complete::CompletionCandidate;
+use moneymoney::export_accounts::MoneymoneyAccount;
+use moneymoney::export_categories::MoneymoneyCategory;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Aliases, Profile};
+
+/// Environment variable the registration scripts set to request completions.
+pub(crate) const COMPLETE_VAR: &str = "COMPLETE";
+
+/// How long the cached accounts and categories are used before refreshing.
+const MAX_AGE: TimeDelta = TimeDelta::days(1);
+
+/// Shells `completions` writes a registration script for.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+    Elvish,
+}
+
+impl Shell {
+    fn name(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::Powershell => "powershell",
+            Shell::Elvish => "elvish",
+        }
+    }
+}
+
+/// Write the script that registers `moneymoney` completions with `shell`.
+pub(crate) fn write_registration(shell: Shell, out: &mut dyn Write) -> io::Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(shell.name())
+        .expect("every Shell is a clap_complete builtin");
+    completer.write_registration(COMPLETE_VAR, "moneymoney", "moneymoney", "moneymoney", out)
+}
+
+/// `$XDG_CACHE_HOME/moneymoney/completion.json`, else `~/.cache/moneymoney/completion.json`.
+fn cache_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(base.join("moneymoney").join("completion.json"))
+}
+
+/// What completion needs from the last account and category exports.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+struct Cache {
+    refreshed: Option<DateTime<Utc>>,
+    accounts: Vec<CachedAccount>,
+    /// Backslash-separated category paths.
+    categories: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct CachedAccount {
+    name: String,
+    /// IBAN without spaces, or the UUID for accounts without one.
+    id: String,
+}
+
+impl Cache {
+    fn from_exports(
+        accounts: &[MoneymoneyAccount],
+        categories: &[MoneymoneyCategory],
+        now: DateTime<Utc>,
+    ) -> Self {
+        let accounts = accounts
+            .iter()
+            .filter(|a| !a.group)
+            .map(|a| CachedAccount {
+                name: a.name.clone(),
+                id: match a.iban.as_deref().filter(|iban| !iban.trim().is_empty()) {
+                    Some(iban) => iban.split_whitespace().collect(),
+                    None => a.uuid.to_string(),
+                },
+            })
+            .collect();
+        let categories = moneymoney::export_categories::category_paths(categories)
+            .into_iter()
+            .zip(categories)
+            .filter(|(_, c)| !c.group)
+            .map(|(path, _)| path)
+            .collect();
+        Self {
+            refreshed: Some(now),
+            accounts,
+            categories,
+        }
+    }
+
+    /// The cache file, refreshed from MoneyMoney if it is missing or stale.
+    ///
+    /// Completion must never fail, so a failed refresh falls back to the
+    /// stale cache (or nothing) and write errors are ignored.
+    fn current() -> Self {
+        let path = cache_path();
+        let mut cached: Option<Self> = path
+            .as_deref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|raw| serde_json::from_slice(&raw).ok());
+        let now = Utc::now();
+        if let Some(cache) =
+            cached.take_if(|cache| cache.refreshed.is_some_and(|at| now - at < MAX_AGE))
+        {
+            return cache;
+        }
+        let fresh = moneymoney::export_accounts().and_then(|accounts| {
+            let categories = moneymoney::export_categories()?;
+            Ok(Self::from_exports(&accounts, &categories, now))
+        });
+        match fresh {
+            Ok(fresh) => {
+                if let Some(path) = path {
+                    let _ = fresh.save(&path);
+                }
+                fresh
+            }
+            Err(_) => cached.unwrap_or_default(),
+        }
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_vec(self)?)
+    }
+
+    /// Account names, for flags that take a name, IBAN or UUID.
+    fn account_names(&self, aliases: &Aliases) -> Vec<CompletionCandidate> {
+        let accounts = self
+            .accounts
+            .iter()
+            .map(|a| CompletionCandidate::new(&a.name).help(Some(a.id.clone().into())));
+        alias_candidates(&aliases.accounts)
+            .chain(accounts)
+            .collect()
+    }
+
+    /// IBANs (or UUIDs), for flags that only take those.
+    fn account_ids(&self, aliases: &Aliases) -> Vec<CompletionCandidate> {
+        let accounts = self
+            .accounts
+            .iter()
+            .map(|a| CompletionCandidate::new(&a.id).help(Some(a.name.clone().into())));
+        alias_candidates(&aliases.accounts)
+            .chain(accounts)
+            .collect()
+    }
+
+    /// Full category paths, for flags that take a UUID, name or path.
+    fn category_paths(&self, aliases: &Aliases) -> Vec<CompletionCandidate> {
+        let paths = self.categories.iter().map(CompletionCandidate::new);
+        alias_candidates(&aliases.categories).chain(paths).collect()
+    }
+
+    /// Category names (the last path segment), for flags that match by name.
+    fn category_names(&self, aliases: &Aliases) -> Vec<CompletionCandidate> {
+        let mut names: Vec<&str> = self
+            .categories
+            .iter()
+            .map(|path| path.rsplit('\\').next().unwrap_or(path))
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        let names = names.into_iter().map(CompletionCandidate::new);
+        alias_candidates(&aliases.categories).chain(names).collect()
+    }
+}
+
+fn alias_candidates(
+    aliases: &std::collections::BTreeMap<String, String>,
+) -> impl Iterator<Item = CompletionCandidate> + '_ {
+    aliases
+        .iter()
+        .map(|(alias, target)| CompletionCandidate::new(alias).help(Some(target.clone().into())))
+}
+
+/// The default profile's aliases; completion can't see `--profile`.
+fn aliases() -> Aliases {
+    Profile::load(None, None)
+        .map(|profile| profile.aliases)
+        .unwrap_or_default()
+}
+
+pub(crate) fn account_names() -> Vec<CompletionCandidate> {
+    Cache::current().account_names(&aliases())
+}
+
+pub(crate) fn account_ids() -> Vec<CompletionCandidate> {
+    Cache::current().account_ids(&aliases())
+}
+
+pub(crate) fn category_paths() -> Vec<CompletionCandidate> {
+    Cache::current().category_paths(&aliases())
+}
+
+pub(crate) fn category_names() -> Vec<CompletionCandidate> {
+    Cache::current().category_names(&aliases())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(candidates: Vec<CompletionCandidate>) -> Vec<String> {
+        candidates
+            .iter()
+            .map(|c| c.get_value().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn offers_cached_accounts_categories_and_aliases() {
+        let cache = Cache {
+            refreshed: None,
+            accounts: vec![CachedAccount {
+                name: "Girokonto".into(),
+                id: "DE89370400440532013000".into(),
+            }],
+            categories: vec![
+                "Lebensmittel".into(),
+                "Lebensmittel\\Supermarkt".into(),
+                "Freizeit\\Supermarkt".into(),
+            ],
+        };
+        let mut aliases = Aliases::default();
+        aliases.accounts.insert("giro".into(), "Girokonto".into());
+
+        assert_eq!(values(cache.account_names(&aliases)), ["giro", "Girokonto"]);
+        assert_eq!(values(cache.account_ids(&aliases)), ["giro", "DE89370400440532013000"]);
+        assert_eq!(
+            values(cache.category_names(&Aliases::default())),
+            ["Lebensmittel", "Supermarkt"]
+        );
+        assert_eq!(values(cache.category_paths(&Aliases::default())).len(), 3);
+
+        let path = std::env::temp_dir()
+            .join(format!("moneymoney-completion-{}", std::process::id()))
+            .join("completion.json");
+        cache.save(&path).unwrap();
+        let loaded: Cache = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(loaded, cache);
+    }
+
+    #[test]
+    fn registration_calls_back_into_the_binary() {
+        for shell in Shell::value_variants() {
+            let mut script = Vec::new();
+            write_registration(*shell, &mut script).unwrap();
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains("moneymoney"), "{shell:?}");
+            assert!(script.contains(COMPLETE_VAR), "{shell:?}");
+        }
+    }
+}
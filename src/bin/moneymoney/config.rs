@@ -0,0 +1,516 @@
+//! `~/.config/moneymoney/config.toml`: named profiles of default flags.
+//!
+//! ```toml
+//! default-profile = "home"
+//!
+//! [profiles.home]
+//! format = "table"
+//! period = "this-month"
+//! accounts = ["Girokonto", "Kreditkarte"]
+//! exclude-accounts = ["Depot"]
+//! redact = ["iban", "account-number", "owner"]
+//! snapshot-dir = "~/Documents/MoneyMoney Snapshots"
+//! rules = "~/Documents/moneymoney-rules.yaml"
+//! csv = { delimiter = ";", decimal-separator = ",", header = true }
+//! aliases.accounts = { giro = "DE89370400440532013000" }
+//! aliases.categories = { food = "Lebensmittel\\Supermarkt" }
+//!
+//! [[profiles.home.hooks]]
+//! url = "https://bot.example.org/moneymoney"
+//! ```
+//!
+//! `hooks` are where `watch` delivers events, see [`crate::hooks`].
+//!
+//! A profile only fills in what the command line leaves open: an explicit
+//! `--format` or `--from-date` always wins, and a `format` a command can't
+//! produce (`ofx` for `export accounts`) leaves that command's own default.
+//! [`Profile::apply`] does that on
+//! the parsed [`Cli`]; account scope and redaction are applied by
+//! [`Source`](crate::Source) through [`Profile::scope`].
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use moneymoney::date_range::DateRange;
+use serde::{Deserialize, Serialize};
+
+use crate::hooks::HookConfig;
+use crate::source::Scope;
+use crate::{
+    CategorizeTarget, Cli, Cmd, ExportTarget, OutputFormat, RulesTarget, SetTarget, ShapeArgs,
+};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The whole config file.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct ConfigFile {
+    /// Profile used without `--profile`.
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// One named profile; every setting is optional.
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct Profile {
+    /// Default `--format` for exports and `query`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) format: Option<OutputFormat>,
+    /// Default `--period` for `export transactions` without dates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) period: Option<String>,
+    /// Only read these accounts or account groups (name, IBAN or UUID).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) accounts: Vec<String>,
+    /// Never read these accounts or account groups.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) exclude_accounts: Vec<String>,
+    /// Fields to mask in everything read, e.g. `iban`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) redact: Vec<String>,
+    /// Where `snapshot` stores exports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) snapshot_dir: Option<PathBuf>,
+    /// Rule file for `rules apply`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) rules: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "CsvDialect::is_default")]
+    pub(crate) csv: CsvDialect,
+    #[serde(default, skip_serializing_if = "Aliases::is_empty")]
+    pub(crate) aliases: Aliases,
+    /// Where `watch` delivers events.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) hooks: Vec<HookConfig>,
+}
+
+/// `--format csv` settings.
+#[derive(Deserialize, Serialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct CsvDialect {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) delimiter: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) decimal_separator: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) header: Option<bool>,
+}
+
+impl CsvDialect {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Short names for accounts and categories, usable wherever a command takes one.
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Aliases {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) accounts: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) categories: BTreeMap<String, String>,
+}
+
+impl Aliases {
+    fn is_empty(&self) -> bool {
+        self.accounts.is_empty() && self.categories.is_empty()
+    }
+}
+
+/// `$XDG_CONFIG_HOME/moneymoney/config.toml`, else `~/.config/moneymoney/config.toml`.
+pub(crate) fn default_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(base.join("moneymoney").join("config.toml"))
+}
+
+/// `rules.toml` next to the [default config file](default_path).
+pub(crate) fn default_rules_path() -> Option<PathBuf> {
+    default_path().map(|path| path.with_file_name("rules.toml"))
+}
+
+/// `$XDG_DATA_HOME/moneymoney/snapshots`, else `~/.local/share/moneymoney/snapshots`.
+pub(crate) fn default_snapshot_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+    Some(base.join("moneymoney").join("snapshots"))
+}
+
+/// `category-model.json` next to the [default snapshot store](default_snapshot_dir).
+pub(crate) fn default_model_path() -> Option<PathBuf> {
+    default_snapshot_dir().map(|dir| dir.with_file_name("category-model.json"))
+}
+
+/// `$XDG_STATE_HOME/moneymoney/watch.json`, else `~/.local/state/moneymoney/watch.json`.
+pub(crate) fn default_cursor_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))?;
+    Some(base.join("moneymoney").join("watch.json"))
+}
+
+/// `path` with a leading `~` replaced by `$HOME`.
+pub(crate) fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+impl Profile {
+    /// The profile named `name`, else the file's `default-profile`, from
+    /// `path` (must exist) or the default path (may be missing).
+    ///
+    /// Without a file or a selected profile, the empty profile is returned.
+    pub(crate) fn load(path: Option<&Path>, name: Option<&str>) -> Result<Self, BoxError> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Self::select(ConfigFile::default(), name),
+            },
+        };
+        let raw = match std::fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => {
+                return Self::select(ConfigFile::default(), name);
+            }
+            Err(e) => return Err(format!("{}: {e}", path.display()).into()),
+        };
+        Self::parse(&raw, name).map_err(|e| format!("{}: {e}", path.display()).into())
+    }
+
+    /// Select a profile from the TOML in `raw`, see [`Profile::load`].
+    pub(crate) fn parse(raw: &str, name: Option<&str>) -> Result<Self, BoxError> {
+        let file: ConfigFile = toml::from_str(raw)?;
+        Self::select(file, name)
+    }
+
+    fn select(mut file: ConfigFile, name: Option<&str>) -> Result<Self, BoxError> {
+        let Some(name) = name.map(str::to_string).or(file.default_profile.take()) else {
+            return Ok(Self::default());
+        };
+        let profile = file.profiles.remove(&name).ok_or_else(|| {
+            let known: Vec<&str> = file.profiles.keys().map(String::as_str).collect();
+            format!(
+                "no profile `{name}` in the config file (profiles: {})",
+                if known.is_empty() {
+                    "none".to_string()
+                } else {
+                    known.join(", ")
+                }
+            )
+        })?;
+        if let Some(period) = &profile.period {
+            DateRange::parse_local(period).map_err(|e| format!("profile `{name}`: period: {e}"))?;
+        }
+        Ok(profile)
+    }
+
+    /// `snapshot-dir`, with a leading `~/` expanded.
+    pub(crate) fn snapshot_dir(&self) -> Option<PathBuf> {
+        self.snapshot_dir.as_deref().map(expand_home)
+    }
+
+    /// `rules`, with a leading `~/` expanded.
+    pub(crate) fn rules(&self) -> Option<PathBuf> {
+        self.rules.as_deref().map(expand_home)
+    }
+
+    /// Accounts to read and fields to redact, for read commands.
+    pub(crate) fn scope(&self) -> Scope {
+        Scope {
+            include: self.resolve_accounts(&self.accounts),
+            exclude: self.resolve_accounts(&self.exclude_accounts),
+            redact: self.redact.clone(),
+        }
+    }
+
+    fn resolve_accounts(&self, idents: &[String]) -> Vec<String> {
+        idents.iter().map(|ident| self.account(ident)).collect()
+    }
+
+    /// `ident`, or what it is an account alias for.
+    fn account(&self, ident: &str) -> String {
+        self.aliases
+            .accounts
+            .get(ident)
+            .cloned()
+            .unwrap_or_else(|| ident.to_string())
+    }
+
+    /// `ident`, or what it is a category alias for.
+    pub(crate) fn category(&self, ident: &str) -> String {
+        self.aliases
+            .categories
+            .get(ident)
+            .cloned()
+            .unwrap_or_else(|| ident.to_string())
+    }
+
+    fn resolve_account(&self, ident: &mut Option<String>) {
+        if let Some(ident) = ident {
+            *ident = self.account(ident);
+        }
+    }
+
+    fn resolve_category(&self, ident: &mut Option<String>) {
+        if let Some(ident) = ident {
+            *ident = self.category(ident);
+        }
+    }
+
+    /// Fill in what `cli` leaves open: `--format` where the command can
+    /// produce the profile's, the CSV dialect and the `export transactions`
+    /// period; and resolve aliases in account and category arguments.
+    /// `matches` tells explicit flags from defaults.
+    pub(crate) fn apply(&self, cli: &mut Cli, matches: &ArgMatches) -> Result<(), BoxError> {
+        use OutputFormat::{Csv, Json, Ndjson, Ofx, Parquet, Qif, Table};
+
+        let explicit_format = leaf(matches)
+            .try_get_raw("format")
+            .is_ok_and(|_| leaf(matches).value_source("format") == Some(ValueSource::CommandLine));
+        let format = |format: &mut OutputFormat,
+                      shape: &mut ShapeArgs,
+                      supported: &[OutputFormat]| {
+            if let Some(default) = self.format {
+                if !explicit_format && shape.template.is_none() && supported.contains(&default) {
+                    *format = default;
+                }
+            }
+            shape.csv_delimiter = shape.csv_delimiter.or(self.csv.delimiter);
+            shape.csv_decimal_separator =
+                shape.csv_decimal_separator.or(self.csv.decimal_separator);
+            shape.csv_no_header |= self.csv.header == Some(false);
+        };
+        match &mut cli.command {
+            Cmd::Export { target } => match target {
+                ExportTarget::Accounts(args) => {
+                    format(&mut args.format, &mut args.shape, &[Json, Ndjson, Table, Csv, Parquet])
+                }
+                ExportTarget::Categories(args) => {
+                    format(&mut args.format, &mut args.shape, &[Json, Ndjson, Table, Csv])
+                }
+                ExportTarget::Transactions(args) => {
+                    format(&mut args.format, &mut args.shape, OutputFormat::value_variants());
+                    self.resolve_account(&mut args.from_account);
+                    self.resolve_category(&mut args.from_category);
+                    if args.from_date.is_none() && args.to_date.is_none() && args.period.is_none() {
+                        if let Some(period) = &self.period {
+                            args.period = Some(DateRange::parse_local(period)?);
+                        }
+                    }
+                }
+                ExportTarget::Portfolio(args) => {
+                    format(&mut args.format, &mut args.shape, &[Json, Ndjson, Table, Csv, Parquet]);
+                    self.resolve_account(&mut args.from_account);
+                }
+            },
+            Cmd::Query(args) => {
+                format(
+                    &mut args.format,
+                    &mut args.shape,
+                    &[Json, Ofx, Qif, Parquet, Ndjson, Table, Csv],
+                );
+                self.resolve_account(&mut args.from_account);
+            }
+            Cmd::Add {
+                target: crate::AddTarget::Transaction(args),
+            } => {
+                self.resolve_account(&mut args.to_account);
+                self.resolve_category(&mut args.category);
+            }
+            Cmd::Set {
+                target: SetTarget::Transaction(args),
+            } => {
+                self.resolve_account(&mut args.from_account);
+                self.resolve_category(&mut args.from_category);
+                self.resolve_category(&mut args.category);
+            }
+            #[cfg(feature = "experimental")]
+            Cmd::Create { target } => match target {
+                crate::CreateTarget::BankTransfer(_) => {}
+                crate::CreateTarget::DirectDebit(args) => {
+                    self.resolve_account(&mut args.from_account)
+                }
+            },
+            Cmd::Rules {
+                target: RulesTarget::Apply(args),
+            } => self.resolve_account(&mut args.from_account),
+            Cmd::Categorize {
+                target: CategorizeTarget::Suggest(args),
+            } => self.resolve_account(&mut args.from_account),
+            Cmd::Categorize { .. }
+            | Cmd::Sync { .. }
+            | Cmd::Snapshot(_)
+            | Cmd::Diff(_)
+            | Cmd::Watch(_)
+            | Cmd::Config { .. }
+            | Cmd::Completions { .. }
+            | Cmd::Man { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+/// The matches of the innermost subcommand.
+fn leaf(matches: &ArgMatches) -> &ArgMatches {
+    match matches.subcommand() {
+        Some((_, sub)) => leaf(sub),
+        None => matches,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    const CONFIG: &str = r#"
+default-profile = "home"
+
+[profiles.home]
+format = "table"
+period = "2024-Q1"
+accounts = ["giro"]
+exclude-accounts = ["Depot"]
+redact = ["iban"]
+snapshot-dir = "/tmp/snapshots"
+rules = "/tmp/rules.yaml"
+csv = { delimiter = ";", decimal-separator = ",", header = false }
+aliases.accounts = { giro = "DE89370400440532013000" }
+aliases.categories = { food = "Lebensmittel\\Supermarkt" }
+
+[[profiles.home.hooks]]
+url = "http://127.0.0.1:8080/moneymoney"
+secret = "s3cret"
+events = ["new"]
+
+[profiles.work]
+format = "ndjson"
+"#;
+
+    fn cli(profile: &Profile, argv: &[&str]) -> Cli {
+        let matches = Cli::command()
+            .try_get_matches_from(["moneymoney"].iter().chain(argv))
+            .unwrap();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap();
+        profile.apply(&mut cli, &matches).unwrap();
+        cli
+    }
+
+    #[test]
+    fn selects_profiles() {
+        let home = Profile::parse(CONFIG, None).unwrap();
+        assert_eq!(home.format, Some(OutputFormat::Table));
+        assert_eq!(home.snapshot_dir, Some(PathBuf::from("/tmp/snapshots")));
+        assert_eq!(home.rules(), Some(PathBuf::from("/tmp/rules.yaml")));
+        assert_eq!(home.csv.delimiter, Some(';'));
+        assert_eq!(home.hooks[0].events, ["new"]);
+        assert!(!toml::to_string(&home).unwrap().contains("s3cret"));
+        let work = Profile::parse(CONFIG, Some("work")).unwrap();
+        assert_eq!(work.format, Some(OutputFormat::Ndjson));
+        assert_eq!(Profile::parse("", None).unwrap(), Profile::default());
+
+        let err = Profile::parse(CONFIG, Some("play")).unwrap_err();
+        assert_eq!(err.to_string(), "no profile `play` in the config file (profiles: home, work)");
+        let err = Profile::parse("[profiles.x]\nfromat = \"json\"", Some("x")).unwrap_err();
+        assert!(err.to_string().contains("unknown field `fromat`"), "{err}");
+        let err = Profile::parse("[profiles.x]\nperiod = \"soon\"", Some("x")).unwrap_err();
+        assert!(err.to_string().starts_with("profile `x`: period: "), "{err}");
+        let err = Profile::parse("[profiles.x]\nformat = \"xml\"", Some("x")).unwrap_err();
+        assert!(err.to_string().contains("unknown variant `xml`"), "{err}");
+    }
+
+    #[test]
+    fn fills_in_defaults_and_aliases() {
+        let home = Profile::parse(CONFIG, None).unwrap();
+        let Cmd::Export {
+            target: ExportTarget::Transactions(args),
+        } = cli(
+            &home,
+            &[
+                "export",
+                "transactions",
+                "--from-account",
+                "giro",
+                "--from-category",
+                "food",
+            ],
+        )
+        .command
+        else {
+            panic!("expected Export::Transactions");
+        };
+        assert!(args.format == OutputFormat::Table);
+        assert_eq!(args.period.map(|p| p.start), chrono::NaiveDate::from_ymd_opt(2024, 1, 1));
+        assert_eq!(args.from_account.as_deref(), Some("DE89370400440532013000"));
+        assert_eq!(args.from_category.as_deref(), Some("Lebensmittel\\Supermarkt"));
+        assert_eq!(args.shape.csv_delimiter, Some(';'));
+        assert_eq!(args.shape.csv_decimal_separator, Some(','));
+        assert!(args.shape.csv_no_header);
+
+        // The command line wins.
+        let Cmd::Export {
+            target: ExportTarget::Transactions(args),
+        } = cli(
+            &home,
+            &[
+                "export",
+                "transactions",
+                "--from-date",
+                "2024-05-01",
+                "--format",
+                "json",
+                "--csv-delimiter",
+                ",",
+            ],
+        )
+        .command
+        else {
+            panic!("expected Export::Transactions");
+        };
+        assert!(args.format == OutputFormat::Json);
+        assert!(args.period.is_none());
+        assert_eq!(args.shape.csv_delimiter, Some(','));
+
+        // `--template` replaces the format, so the profile's doesn't apply.
+        let Cmd::Query(args) = cli(&home, &["query", "booked", "--template", "{{ name }}"]).command
+        else {
+            panic!("expected Query");
+        };
+        assert!(args.format == OutputFormat::Json);
+
+        // A default the command can't produce leaves the command's own.
+        let ofx = Profile::parse("[profiles.x]\nformat = \"ofx\"", Some("x")).unwrap();
+        let Cmd::Export {
+            target: ExportTarget::Accounts(args),
+        } = cli(&ofx, &["export", "accounts"]).command
+        else {
+            panic!("expected Export::Accounts");
+        };
+        assert!(args.format == OutputFormat::Json);
+        let Cmd::Export {
+            target: ExportTarget::Transactions(args),
+        } = cli(&ofx, &["export", "transactions"]).command
+        else {
+            panic!("expected Export::Transactions");
+        };
+        assert!(args.format == OutputFormat::Ofx);
+
+        let scope = home.scope();
+        assert_eq!(scope.include, ["DE89370400440532013000"]);
+        assert_eq!(scope.exclude, ["Depot"]);
+        assert_eq!(scope.redact, ["iban"]);
+    }
+}
@@ -0,0 +1,507 @@
+//! Where `watch` delivers its events: a local command or an HTTP endpoint.
+//!
+//! Hooks are configured per profile; `--hook-command` adds one for a single
+//! run:
+//!
+//! ```toml
+//! [[profiles.home.hooks]]
+//! name = "chat-bot"
+//! events = ["new", "changed"]
+//! filter = 'booked and (amount <= -500 or payee:"ACME GmbH")'
+//! url = "https://bot.example.org/moneymoney"
+//! secret-env = "MONEYMONEY_HOOK_SECRET"
+//! retries = 5
+//! dead-letter = "~/.local/state/moneymoney/dead-letter.ndjson"
+//!
+//! [[profiles.home.hooks]]
+//! command = "terminal-notifier -title MoneyMoney -message \"$(jq -r .transaction.name)\""
+//! ```
+//!
+//! A command gets the event JSON on stdin and its kind in
+//! `MONEYMONEY_EVENT`. An HTTP hook POSTs the event JSON with the headers
+//! `X-MoneyMoney-Event`, `X-MoneyMoney-Delivery` (the same id on every
+//! attempt) and, with a secret, `X-MoneyMoney-Signature: sha256=<hex>`, the
+//! HMAC-SHA256 of the body. Failed deliveries are retried with exponential
+//! backoff (not after an HTTP 4xx other than 408 and 429); events that still
+//! fail are appended to the `dead-letter` file, one JSON line each.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use moneymoney::query::Query;
+use moneymoney::watch::WatchEvent;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::config::expand_home;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Event kinds a hook can subscribe to.
+const EVENTS: [&str; 3] = ["new", "changed", "disappeared"];
+
+/// Retries after the first attempt, unless configured.
+const DEFAULT_RETRIES: u32 = 3;
+
+/// Wait before the first retry; doubled for each further one.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Give up on an HTTP request after this long.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// One `[[profiles.<name>.hooks]]` entry.
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct HookConfig {
+    /// Shown in warnings and dead letters (default: the command or URL).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<String>,
+    /// Event kinds to deliver; all if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) events: Vec<String>,
+    /// Only deliver events whose transaction matches this query;
+    /// `disappeared` events never do.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) filter: Option<String>,
+    /// Shell command to pipe the event to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) command: Option<String>,
+    /// Endpoint to POST the event to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) url: Option<String>,
+    /// HMAC key for the signature header; never printed by `config show`.
+    #[serde(skip_serializing)]
+    pub(crate) secret: Option<String>,
+    /// Environment variable holding the HMAC key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) secret_env: Option<String>,
+    /// Retries after a failed attempt (default: 3).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) retries: Option<u32>,
+    /// Wait before the first retry, doubled for each further one: `1s`, `1m`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) retry_delay: Option<String>,
+    /// NDJSON file for events that could not be delivered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) dead_letter: Option<PathBuf>,
+}
+
+enum Target {
+    Command(String),
+    Http {
+        url: String,
+        secret: Option<Vec<u8>>,
+        agent: ureq::Agent,
+    },
+}
+
+/// A hook ready to deliver events.
+pub(crate) struct Hook {
+    name: String,
+    events: Vec<String>,
+    filter: Option<Query>,
+    target: Target,
+    retries: u32,
+    retry_delay: Duration,
+    dead_letter: Option<PathBuf>,
+}
+
+/// How delivering an event ended.
+#[derive(Debug, PartialEq)]
+enum Delivery {
+    Delivered,
+    /// Undeliverable, with the last error.
+    Failed(String),
+}
+
+/// Why an attempt failed, and whether trying again could help.
+struct Failure {
+    error: String,
+    retry: bool,
+}
+
+impl Hook {
+    /// A hook for `--hook-command`: every event, default retries, no dead letters.
+    pub(crate) fn command(command: &str) -> Self {
+        Hook {
+            name: command.to_string(),
+            events: Vec::new(),
+            filter: None,
+            target: Target::Command(command.to_string()),
+            retries: DEFAULT_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            dead_letter: None,
+        }
+    }
+
+    /// Check `config` and prepare it.
+    pub(crate) fn from_config(config: &HookConfig) -> Result<Self, BoxError> {
+        let target = match (&config.command, &config.url) {
+            (Some(command), None) => Target::Command(command.clone()),
+            (None, Some(url)) => {
+                let secret = match (&config.secret, &config.secret_env) {
+                    (Some(secret), None) => Some(secret.clone().into_bytes()),
+                    (None, Some(var)) => Some(
+                        std::env::var(var)
+                            .map_err(|_| format!("secret-env: {var} is not set"))?
+                            .into_bytes(),
+                    ),
+                    (None, None) => None,
+                    (Some(_), Some(_)) => return Err("set secret or secret-env, not both".into()),
+                };
+                let agent = ureq::Agent::config_builder()
+                    .timeout_global(Some(HTTP_TIMEOUT))
+                    .build()
+                    .into();
+                Target::Http {
+                    url: url.clone(),
+                    secret,
+                    agent,
+                }
+            }
+            _ => return Err("set either command or url".into()),
+        };
+        if let Some(unknown) = config.events.iter().find(|e| !EVENTS.contains(&e.as_str())) {
+            return Err(format!(
+                "events: unknown event `{unknown}` (expected {})",
+                EVENTS.join(", ")
+            )
+            .into());
+        }
+        let filter = match &config.filter {
+            Some(filter) => Some(Query::parse(filter).map_err(|e| format!("filter: {e}"))?),
+            None => None,
+        };
+        let retry_delay = match &config.retry_delay {
+            Some(delay) => crate::parse_interval(delay).map_err(|e| format!("retry-delay: {e}"))?,
+            None => DEFAULT_RETRY_DELAY,
+        };
+        let name = config
+            .name
+            .clone()
+            .or_else(|| config.command.clone())
+            .or_else(|| config.url.clone())
+            .unwrap_or_default();
+        Ok(Hook {
+            name,
+            events: config.events.clone(),
+            filter,
+            target,
+            retries: config.retries.unwrap_or(DEFAULT_RETRIES),
+            retry_delay,
+            dead_letter: config.dead_letter.as_deref().map(expand_home),
+        })
+    }
+
+    /// Whether the hook subscribed to `event` and its filter matches.
+    fn wants(&self, event: &WatchEvent) -> bool {
+        if !self.events.is_empty() && !self.events.iter().any(|e| e == event.kind()) {
+            return false;
+        }
+        match (&self.filter, event) {
+            (None, _) => true,
+            (Some(filter), WatchEvent::New { transaction })
+            | (Some(filter), WatchEvent::Changed { transaction, .. }) => {
+                filter.matches(transaction)
+            }
+            (Some(_), WatchEvent::Disappeared { .. }) => false,
+        }
+    }
+
+    /// Deliver `event`, retrying failed attempts. An event that can't be
+    /// delivered is written to the dead-letter file; only failing to write
+    /// it is an error.
+    fn deliver(&self, event: &WatchEvent) -> Result<Delivery, BoxError> {
+        let body = serde_json::to_vec(event)?;
+        let delivery = uuid::Uuid::new_v4().to_string();
+        let mut attempts = 0;
+        let mut delay = self.retry_delay;
+        loop {
+            attempts += 1;
+            let failure = match self.attempt(event, &body, &delivery) {
+                Ok(()) => return Ok(Delivery::Delivered),
+                Err(failure) => failure,
+            };
+            if failure.retry && attempts <= self.retries {
+                std::thread::sleep(delay);
+                delay *= 2;
+                continue;
+            }
+            let error = format!("{} after {attempts} attempt(s)", failure.error);
+            return Ok(Delivery::Failed(match &self.dead_letter {
+                Some(path) => {
+                    self.bury(path, event, attempts, &failure.error)?;
+                    format!("{error}, written to {}", path.display())
+                }
+                None => error,
+            }));
+        }
+    }
+
+    fn attempt(&self, event: &WatchEvent, body: &[u8], delivery: &str) -> Result<(), Failure> {
+        let failure = |error: String, retry: bool| Failure { error, retry };
+        match &self.target {
+            Target::Command(command) => {
+                let mut child = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .env("MONEYMONEY_EVENT", event.kind())
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .map_err(|e| failure(e.to_string(), false))?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    // A command that doesn't read its input is fine.
+                    let _ = stdin.write_all(body).and_then(|()| stdin.write_all(b"\n"));
+                }
+                let status = child.wait().map_err(|e| failure(e.to_string(), true))?;
+                if !status.success() {
+                    return Err(failure(format!("command failed ({status})"), true));
+                }
+                Ok(())
+            }
+            Target::Http { url, secret, agent } => {
+                let mut request = agent
+                    .post(url)
+                    .header("Content-Type", "application/json")
+                    .header("User-Agent", concat!("moneymoney/", env!("CARGO_PKG_VERSION")))
+                    .header("X-MoneyMoney-Event", event.kind())
+                    .header("X-MoneyMoney-Delivery", delivery);
+                if let Some(secret) = secret {
+                    request = request.header("X-MoneyMoney-Signature", signature(secret, body));
+                }
+                match request.send(body) {
+                    Ok(_) => Ok(()),
+                    Err(ureq::Error::StatusCode(status)) => Err(failure(
+                        format!("HTTP {status}"),
+                        status >= 500 || status == 408 || status == 429,
+                    )),
+                    Err(e) => Err(failure(e.to_string(), true)),
+                }
+            }
+        }
+    }
+
+    /// Append `event` with why it failed to the dead-letter file.
+    fn bury(
+        &self,
+        path: &std::path::Path,
+        event: &WatchEvent,
+        attempts: u32,
+        error: &str,
+    ) -> Result<(), BoxError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let line = serde_json::json!({
+            "hook": self.name,
+            "failedAt": chrono::Utc::now(),
+            "attempts": attempts,
+            "error": error,
+            "event": event,
+        });
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{line}")?;
+        Ok(())
+    }
+}
+
+/// `sha256=<hex>`, the HMAC-SHA256 of `body` keyed with `secret`.
+fn signature(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(body);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// The hooks of a `watch` run.
+pub(crate) struct Hooks(Vec<Hook>);
+
+impl Hooks {
+    /// The profile's hooks plus `--hook-command`.
+    pub(crate) fn new(configs: &[HookConfig], command: Option<&str>) -> Result<Self, BoxError> {
+        let mut hooks = configs
+            .iter()
+            .enumerate()
+            .map(|(i, config)| {
+                Hook::from_config(config).map_err(|e| format!("hooks[{i}]: {e}").into())
+            })
+            .collect::<Result<Vec<_>, BoxError>>()?;
+        hooks.extend(command.map(Hook::command));
+        Ok(Hooks(hooks))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Deliver `event` to every hook that wants it. Undeliverable events are
+    /// reported on stderr; failing to write a dead letter is an error, which
+    /// is returned after the remaining hooks had their turn.
+    pub(crate) fn dispatch(&self, event: &WatchEvent) -> Result<(), BoxError> {
+        let mut lost = None;
+        for hook in self.0.iter().filter(|hook| hook.wants(event)) {
+            match hook.deliver(event) {
+                Ok(Delivery::Delivered) => {}
+                Ok(Delivery::Failed(e)) => eprintln!(
+                    "warning: hook {}: {} event {}: {e}",
+                    hook.name,
+                    event.kind(),
+                    event.id()
+                ),
+                Err(e) => {
+                    lost.get_or_insert(e);
+                }
+            }
+        }
+        lost.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    fn event(amount: f64) -> WatchEvent {
+        let transaction = serde_json::from_value(serde_json::json!({
+            "id": 7,
+            "bookingDate": "2024-03-01T12:00:00Z",
+            "valueDate": "2024-03-01T12:00:00Z",
+            "name": "ACME GmbH",
+            "amount": amount,
+            "currency": "EUR",
+            "accountUuid": "adcf1f45-4add-4e74-9958-a9907e0f8060",
+            "booked": true,
+            "categoryUuid": "00000000-0000-0000-0000-000000000000",
+            "checkmark": false,
+        }))
+        .unwrap();
+        WatchEvent::New { transaction }
+    }
+
+    fn hook(toml: &str) -> Hook {
+        let config: HookConfig = toml::from_str(toml).unwrap();
+        let mut hook = Hook::from_config(&config).unwrap();
+        hook.retry_delay = Duration::from_millis(1);
+        hook
+    }
+
+    /// Headers (lowercase names) and body of a received request.
+    type Received = (Vec<(String, String)>, String);
+
+    /// Answer requests with `statuses` in turn, passing on what was received.
+    fn server(statuses: &'static [u16]) -> (String, mpsc::Receiver<Received>) {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", server.server_addr().to_ip().unwrap());
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for status in statuses {
+                let mut request = server.recv().unwrap();
+                let headers = request
+                    .headers()
+                    .iter()
+                    .map(|h| (h.field.to_string().to_lowercase(), h.value.to_string()))
+                    .collect();
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                tx.send((headers, body)).unwrap();
+                request
+                    .respond(tiny_http::Response::empty(*status))
+                    .unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> &'a str {
+        headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn posts_signed_events_and_retries_server_errors() {
+        let (url, received) = server(&[503, 200]);
+        let hook = hook(&format!("url = \"{url}\"\nsecret = \"s3cret\"\nretries = 1"));
+        let event = event(2500.0);
+        assert_eq!(hook.deliver(&event).unwrap(), Delivery::Delivered);
+
+        let (first, body) = received.recv().unwrap();
+        let (second, again) = received.recv().unwrap();
+        assert_eq!(body, again);
+        assert_eq!(body, serde_json::to_string(&event).unwrap());
+        assert_eq!(header(&first, "x-moneymoney-event"), "new");
+        assert_eq!(
+            header(&first, "x-moneymoney-delivery"),
+            header(&second, "x-moneymoney-delivery")
+        );
+        assert_eq!(header(&first, "x-moneymoney-signature"), signature(b"s3cret", body.as_bytes()));
+        // RFC 4231 test case 2.
+        assert_eq!(
+            signature(b"Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn dead_letters_undeliverable_events() {
+        let dir = std::env::temp_dir().join(format!("moneymoney-hooks-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let dead_letter = dir.join("dead-letter.ndjson");
+        let (url, received) = server(&[400]);
+        let http = hook(&format!(
+            "name = \"bot\"\nurl = \"{url}\"\ndead-letter = \"{}\"",
+            dead_letter.display()
+        ));
+        let Delivery::Failed(err) = http.deliver(&event(-600.0)).unwrap() else {
+            panic!("expected a failure");
+        };
+        assert!(err.starts_with("HTTP 400 after 1 attempt(s), written to "), "{err}");
+        assert!(received.recv().is_ok());
+
+        let command = hook(&format!(
+            "command = \"cat > /dev/null; exit 3\"\nretries = 2\ndead-letter = \"{}\"",
+            dead_letter.display()
+        ));
+        assert_ne!(command.deliver(&event(-600.0)).unwrap(), Delivery::Delivered);
+
+        let letters: Vec<serde_json::Value> = std::fs::read_to_string(&dead_letter)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(letters.len(), 2);
+        assert_eq!(letters[0]["hook"], "bot");
+        assert_eq!(letters[0]["event"]["transaction"]["amount"], -600.0);
+        assert_eq!(letters[1]["attempts"], 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn selects_events_by_kind_and_filter() {
+        let large = hook("command = \"true\"\nevents = [\"new\"]\nfilter = \"amount <= -500\"");
+        assert!(large.wants(&event(-600.0)));
+        assert!(!large.wants(&event(-20.0)));
+        let gone = WatchEvent::Disappeared {
+            id: 7,
+            booking_date: chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+        };
+        assert!(!large.wants(&gone));
+        assert!(hook("command = \"true\"").wants(&gone));
+
+        let invalid = |toml: &str| {
+            let config: HookConfig = toml::from_str(toml).unwrap();
+            Hook::from_config(&config).err().unwrap().to_string()
+        };
+        assert_eq!(invalid("command = \"true\"\nurl = \"http://x\""), "set either command or url");
+        assert!(invalid("command = \"true\"\nevents = [\"booked\"]").contains("`booked`"));
+        assert!(invalid("command = \"true\"\nfilter = \"amount <\"").starts_with("filter: "));
+        assert!(invalid("url = \"http://x\"\nsecret-env = \"MONEYMONEY_TEST_UNSET\"")
+            .contains("is not set"));
+    }
+}
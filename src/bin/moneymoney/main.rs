@@ -1,21 +1,149 @@
 //! Command-line interface to MoneyMoney (macOS).
 //!
-//! Build with `--features experimental` to enable `create bank-transfer`.
+//! Commands that write rows (`add transaction`, `set transaction`, `rules apply`,
+//! `categorize suggest --interactive`) exit with status 2 when some rows failed and the rest
+//! were written, and 1 on any other error.
+//!
+//! Build with `--features experimental` to enable `create bank-transfer`
+//! and `create direct-debit` (including `--pain001` / `--pain008` SEPA file
+//! output).
 
-use std::io::{self, Write};
-#[cfg(feature = "experimental")]
-use std::{io::Read, path::PathBuf};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use chrono::NaiveDate;
-use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap::{ArgGroup, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::{ArgValueCandidates, CompleteEnv};
+use moneymoney::classify::Classifier;
+use moneymoney::date_range::DateRange;
+use moneymoney::export_accounts::MoneymoneyAccount;
 use moneymoney::export_portfolio::ExportPortfolioParams;
-use moneymoney::export_transactions::ExportTransactionsParams;
+use moneymoney::export_transactions::{
+    ExportTransactionsParams, MoneymoneyTransaction, TransactionsResponse,
+};
+use moneymoney::filter::{sort_transactions, Sign, SortOrder, TransactionFilter};
+use moneymoney::offline::SavedExports;
+use moneymoney::query::Query;
+use moneymoney::rules::RuleSet;
+use moneymoney::set_transaction::SetTransactionParams;
+use moneymoney::snapshot::{Retention, SnapshotStore};
+use moneymoney::watch::{Cursor, WatchEvent, Watcher};
+use regex::Regex;
 use serde::Serialize;
 
+mod batch;
+mod categorize;
+mod completion;
+mod config;
+mod hooks;
+mod projection;
+mod source;
+mod table;
+
+use config::Profile;
+use projection::{CsvOptions, Fields, OutputTemplate, Shape, ShapedSink};
+use source::Source;
+
 /// Clap `long_help` for the `--format` flag on export subcommands.
 const EXPORT_FORMAT_LONG_HELP: &str =
-    "Serialization format written to stdout. `json` is the default; additional formats \
-     (e.g. CSV) may be added in future releases.";
+    "Serialization format written to stdout. `json` is the default and works for every export. \
+     `ofx` (OFX 2.2 statement), `qif` (Quicken Interchange Format) and `camt053` (ISO 20022 \
+     camt.053 statement with reconstructed opening/closing balances) are only available for \
+     `export transactions`. `parquet` (Apache Parquet with typed timestamp, decimal and \
+     dictionary-encoded columns) works for transactions, accounts and portfolio; combine it \
+     with `--output`. `ndjson` writes one compact JSON object per line as items are converted, \
+     for every export; transaction exports start with a `{\"creator\": ...}` header line. \
+     `table` prints aligned columns with totals per currency for every export, colored and \
+     fitted to the width when writing to a terminal (set `NO_COLOR` to disable colors); \
+     account and category tables always include groups to show the tree.";
+
+/// Clap `long_help` for the `--output` flag on export subcommands.
+const OUTPUT_LONG_HELP: &str =
+    "Write the export to this file instead of stdout. The file is created or truncated.";
+
+/// Clap `long_help` for the global `--input` flag.
+const INPUT_LONG_HELP: &str =
+    "Read exports from saved files instead of asking MoneyMoney, so every read command also \
+     works on Linux or without the app running. Each file holds one export: the plist \
+     MoneyMoney returns, JSON or NDJSON written by `moneymoney export`, or a snapshot manifest \
+     (`<dir>/snapshots/<id>.json`, see `moneymoney snapshot`) for all four. The kind \
+     (accounts, categories, transactions, portfolio) is detected from the content. Repeat the flag to \
+     pass several, e.g. transactions plus the accounts that OFX, QIF and camt.053 need. Date, \
+     account and category filters are applied to the saved data. Commands that write to \
+     MoneyMoney refuse `--input`.";
+
+/// Clap `long_about` for `completions`.
+const COMPLETIONS_LONG_ABOUT: &str = "Print a shell completion script.
+
+The script asks `moneymoney` for candidates on every Tab, so it always matches the installed \
+version. Accounts and categories are completed from a cache of the last export \
+(`$XDG_CACHE_HOME/moneymoney/completion.json` or `~/.cache/moneymoney/completion.json`), \
+refreshed from MoneyMoney once a day, plus the aliases of the default config profile.
+
+SETUP:
+    bash:        echo 'source <(moneymoney completions bash)' >> ~/.bashrc
+    zsh:         echo 'source <(moneymoney completions zsh)' >> ~/.zshrc
+    fish:        moneymoney completions fish > ~/.config/fish/completions/moneymoney.fish
+    powershell:  moneymoney completions powershell | Out-String | Invoke-Expression";
+
+/// Clap `long_help` for the global `--profile` flag.
+const PROFILE_LONG_HELP: &str = "Apply the defaults of this profile from the config file \
+     (`$XDG_CONFIG_HOME/moneymoney/config.toml` or `~/.config/moneymoney/config.toml`, see \
+     `--config`). Without `--profile`, the file's `default-profile` is used, if any. A profile \
+     sets `format`, the `csv` dialect (`delimiter`, `decimal-separator`, `header`), the \
+     `period` for `export transactions` without dates, the `accounts` and \
+     `exclude-accounts` that exports, `query` and `sync` read, `aliases.accounts` and \
+     `aliases.categories` accepted wherever an account or category is expected, the fields \
+     to `redact` in everything read (text keeps its last four characters), the \
+     `snapshot-dir` and the `rules` file for `rules apply`. Flags on the command line always \
+     win. `moneymoney config show` prints the selected profile.";
+
+/// Clap `long_help` for the `--period` flag.
+const PERIOD_LONG_HELP: &str =
+    "Set both ends of the date range at once: `today`, `yesterday`, `30d` (also `2w`, `6m`, \
+     `1y`), `this-month`, `last-month` (also `-week`, `-quarter`, `-year`), `mtd`, `qtd`, \
+     `ytd`, `2024`, `2024-Q3`, `2024-05`, or `<from>..<to>` such as `2024-01..last-month`. \
+     Relative expressions are resolved against the local date. `--from-date` and `--to-date` \
+     accept the same expressions and use the start and the end of the range respectively.";
+
+/// Clap `long_help` for the `query` expression.
+const QUERY_LONG_HELP: &str =
+    "Comparisons `field op value` joined with `and`, `or`, `not` and parentheses; adjacent \
+     comparisons are joined with `and`. Text fields (payee, purpose, category, comment, \
+     currency, iban, bank-code, booking-text, creditor-id, mandate-reference, \
+     end-to-end-reference) take `=`, `!=`, `~` (case-insensitive glob: `*`, `?`) and `:` \
+     (case-insensitive substring). Number fields (amount, id) and date fields (date, \
+     value-date) take `=`, `!=`, `<`, `<=`, `>`, `>=`; a date is YYYY-MM-DD or any `--period` \
+     expression, e.g. `date = last-month`. Boolean fields (booked, pending, checkmark) take \
+     `=` and `!=` with true/false, or stand alone. Quote values with spaces or operators.";
+
+/// Clap `long_help` for `--fields`.
+const FIELDS_LONG_HELP: &str =
+    "Keep only these fields of each item, comma-separated, in this order. Names are the keys \
+     of the JSON output (`bookingDate`); case, `-` and `_` are ignored, so `booking_date` \
+     works too. Use dots for nested fields, e.g. `balance.amount` on accounts. With `json` \
+     the output is an array of objects keyed by the given names, with `ndjson` one object per \
+     line (without the transaction `creator` header), with `table` one column per field.";
+
+/// Clap `long_help` for `--template`.
+const TEMPLATE_LONG_HELP: &str =
+    "Render each item with this MiniJinja (Jinja2-style) template and print one line per \
+     item, instead of `--format` output. Fields are the keys of the JSON output, e.g. \
+     `{{ bookingDate[:10] }}`, `{{ balance.amount }}` or `{{ '%.2f'|format(amount) }}`; \
+     missing and null values print as nothing. Use `$'...'` in the shell for tabs.";
+
+/// Export start for `query` when the query sets no lower `date` bound.
+const FULL_HISTORY_START: NaiveDate = match NaiveDate::from_ymd_opt(1900, 1, 1) {
+    Some(date) => date,
+    None => unreachable!(),
+};
+
+/// Clap `long_help` for the `--payee` transaction filter.
+const PAYEE_LONG_HELP: &str =
+    "Only include transactions whose payee (`name`) matches this regular expression, e.g. \
+     `'(?i)^rewe'`. Matching happens after the export, so combine it with the other filters \
+     to keep exports small.";
 
 #[derive(Parser)]
 #[clap(
@@ -26,6 +154,15 @@ const EXPORT_FORMAT_LONG_HELP: &str =
 struct Cli {
     #[clap(subcommand)]
     command: Cmd,
+    /// Read saved exports instead of MoneyMoney (repeatable)
+    #[clap(long, global = true, value_name = "FILE", long_help = INPUT_LONG_HELP)]
+    input: Vec<PathBuf>,
+    /// Apply the defaults of this config profile
+    #[clap(long, global = true, value_name = "NAME", long_help = PROFILE_LONG_HELP)]
+    profile: Option<String>,
+    /// Read the config from this file instead of ~/.config/moneymoney/config.toml
+    #[clap(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -35,6 +172,56 @@ enum Cmd {
         #[clap(subcommand)]
         target: ExportTarget,
     },
+    /// Search transactions with a query expression
+    Query(QueryArgs),
+    /// Add data to MoneyMoney
+    Add {
+        #[clap(subcommand)]
+        target: AddTarget,
+    },
+    /// Change data in MoneyMoney
+    Set {
+        #[clap(subcommand)]
+        target: SetTarget,
+    },
+    /// Categorize transactions with a rule file
+    Rules {
+        #[clap(subcommand)]
+        target: RulesTarget,
+    },
+    /// Suggest categories learned from the categorized history
+    Categorize {
+        #[clap(subcommand)]
+        target: CategorizeTarget,
+    },
+    /// Mirror MoneyMoney data into a local database
+    Sync {
+        #[clap(subcommand)]
+        target: SyncTarget,
+    },
+    /// Save accounts, categories, transactions and portfolio for later
+    Snapshot(SnapshotArgs),
+    /// Show what changed between two exports or snapshots
+    Diff(DiffArgs),
+    /// Report new and changed transactions as they come in
+    Watch(WatchArgs),
+    /// Show the config file and the selected profile
+    Config {
+        #[clap(subcommand)]
+        target: ConfigTarget,
+    },
+    /// Print a shell completion script
+    #[clap(long_about = COMPLETIONS_LONG_ABOUT)]
+    Completions {
+        #[clap(value_enum)]
+        shell: completion::Shell,
+    },
+    /// Print the man page, or write one page per subcommand with --dir
+    Man {
+        /// Write `moneymoney.1`, `moneymoney-export.1`, ... into this directory
+        #[clap(long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+    },
     /// Create payments (requires building with `--features experimental`)
     #[cfg(feature = "experimental")]
     Create {
@@ -77,6 +264,11 @@ struct ExportAccountsArgs {
         long_help = EXPORT_FORMAT_LONG_HELP
     )]
     format: OutputFormat,
+    /// Write to a file instead of stdout
+    #[clap(long, short = 'o', value_name = "FILE", long_help = OUTPUT_LONG_HELP)]
+    output: Option<PathBuf>,
+    #[clap(flatten)]
+    shape: ShapeArgs,
     /// Include per-account icon bytes (omitted by default)
     #[clap(
         long = "include-icon-data",
@@ -117,6 +309,11 @@ struct ExportCategoriesArgs {
         long_help = EXPORT_FORMAT_LONG_HELP
     )]
     format: OutputFormat,
+    /// Write to a file instead of stdout
+    #[clap(long, short = 'o', value_name = "FILE", long_help = OUTPUT_LONG_HELP)]
+    output: Option<PathBuf>,
+    #[clap(flatten)]
+    shape: ShapeArgs,
     /// Include per-category icon bytes (omitted by default)
     #[clap(
         long = "include-icon-data",
@@ -140,34 +337,56 @@ struct ExportCategoriesArgs {
     about = "Export transactions for a date range to stdout",
     long_about = "Export transactions for a date range to stdout.
 
-Output encoding is selected with `--format` (default: json). `--from-date` is required. \
-Other filters are optional; when omitted, MoneyMoney applies its own defaults (e.g. no \
+Output encoding is selected with `--format` (default: json). `--from-date` or `--period` \
+is required. Other filters are optional; when omitted, MoneyMoney applies its own defaults (e.g. no \
 end date limit, all accounts, all categories).",
     after_help = "EXAMPLES:
     moneymoney export transactions --from-date 2024-01-01
     moneymoney export transactions --from-date 2024-01-01 --to-date 2024-12-31
-    moneymoney export transactions --from-date 2024-06-01 --from-account <uuid-or-iban>"
+    moneymoney export transactions --period last-month --format qif > last-month.qif
+    moneymoney export transactions --from-date 30d
+    moneymoney export transactions --from-date 2024-06-01 --from-account <uuid-or-iban>
+    moneymoney export transactions --from-date 2024-01-01 --format ofx > statement.ofx
+    moneymoney export transactions --from-date 2024-01-01 --format qif > transactions.qif
+    moneymoney export transactions --from-date 2024-01-01 --to-date 2024-03-31 --format camt053
+    moneymoney export transactions --from-date 2015-01-01 --format parquet -o transactions.parquet"
 )]
 struct ExportTransactionsArgs {
-    /// Inclusive start of the date range (YYYY-MM-DD)
+    /// Inclusive start of the date range (YYYY-MM-DD, `30d`, `last-month`, ...)
     #[clap(
         long = "from-date",
-        value_name = "YYYY-MM-DD",
-        long_help = "Inclusive start date of the export range, in ISO 8601 calendar form (YYYY-MM-DD)."
+        value_name = "DATE",
+        value_parser = parse_start_date,
+        long_help = "Inclusive start date of the export range: a date in ISO 8601 calendar form \
+                     (YYYY-MM-DD) or the start of a range expression such as `30d` or `ytd` (see \
+                     `--period`). Required unless `--period` is given or the config profile \
+                     sets a `period`."
     )]
-    from_date: NaiveDate,
-    /// Inclusive end of the date range (YYYY-MM-DD)
+    from_date: Option<NaiveDate>,
+    /// Inclusive end of the date range (YYYY-MM-DD, `yesterday`, ...)
     #[clap(
         long = "to-date",
-        value_name = "YYYY-MM-DD",
-        long_help = "Inclusive end date of the export range (YYYY-MM-DD). When omitted, MoneyMoney does not \
-                     set an upper date bound."
+        value_name = "DATE",
+        value_parser = parse_end_date,
+        long_help = "Inclusive end date of the export range: YYYY-MM-DD or the end of a range \
+                     expression (see `--period`). When omitted, MoneyMoney does not set an upper \
+                     date bound."
     )]
     to_date: Option<NaiveDate>,
+    /// Date range for both ends (`last-month`, `2024-Q3`, `ytd`, ...)
+    #[clap(
+        long,
+        value_name = "RANGE",
+        value_parser = DateRange::parse_local,
+        conflicts_with_all = ["from_date", "to_date"],
+        long_help = PERIOD_LONG_HELP
+    )]
+    period: Option<DateRange>,
     /// Restrict to one account (UUID or IBAN)
     #[clap(
         long = "from-account",
         value_name = "UUID|IBAN",
+        add = ArgValueCandidates::new(completion::account_ids),
         long_help = "Only return transactions for this account. Accepts a MoneyMoney account UUID or IBAN. \
                      When omitted, transactions from all accounts are included."
     )]
@@ -176,10 +395,27 @@ struct ExportTransactionsArgs {
     #[clap(
         long = "from-category",
         value_name = "NAME",
+        add = ArgValueCandidates::new(completion::category_names),
         long_help = "Only return transactions assigned to this category name. When omitted, all categories \
                      are included."
     )]
     from_category: Option<String>,
+    #[clap(flatten)]
+    filter: FilterArgs,
+    /// Sort by date, value-date, amount, payee, category or id (`-` prefix: descending)
+    #[clap(
+        long,
+        value_name = "KEY",
+        allow_hyphen_values = true,
+        value_parser = str::parse::<SortOrder>,
+        long_help = "Sort the transactions by `date` (booking date), `value-date`, `amount`, \
+                     `payee`, `category` or `id`. Prefix the key with `-` for descending order, \
+                     e.g. `--sort=-amount`. Without `--sort`, MoneyMoney's order is kept."
+    )]
+    sort: Option<SortOrder>,
+    /// Keep at most N transactions, after filtering and sorting
+    #[clap(long, value_name = "N")]
+    limit: Option<usize>,
     /// Output serialization format (`json` by default)
     #[clap(
         long,
@@ -188,6 +424,187 @@ struct ExportTransactionsArgs {
         long_help = EXPORT_FORMAT_LONG_HELP
     )]
     format: OutputFormat,
+    /// Write to a file instead of stdout
+    #[clap(long, short = 'o', value_name = "FILE", long_help = OUTPUT_LONG_HELP)]
+    output: Option<PathBuf>,
+    #[clap(flatten)]
+    shape: ShapeArgs,
+}
+
+/// Client-side filters for `export transactions`, mapped to [`TransactionFilter`].
+#[derive(Args)]
+#[clap(next_help_heading = "Filters")]
+struct FilterArgs {
+    /// Smallest amount to include (signed, e.g. -100)
+    #[clap(long = "min-amount", value_name = "AMOUNT", allow_hyphen_values = true)]
+    min_amount: Option<f64>,
+    /// Largest amount to include (signed, e.g. -100)
+    #[clap(long = "max-amount", value_name = "AMOUNT", allow_hyphen_values = true)]
+    max_amount: Option<f64>,
+    /// Only income or only expenses
+    #[clap(long, value_enum)]
+    sign: Option<SignArg>,
+    /// Only booked transactions
+    #[clap(long = "booked-only", conflicts_with = "pending_only")]
+    booked_only: bool,
+    /// Only pending (not yet booked) transactions
+    #[clap(long = "pending-only")]
+    pending_only: bool,
+    /// Only checked (`on`) or unchecked (`off`) transactions
+    #[clap(long, value_enum)]
+    checkmark: Option<Checkmark>,
+    /// Restrict to payees matching a regular expression
+    #[clap(long, value_name = "REGEX", value_parser = Regex::new, long_help = PAYEE_LONG_HELP)]
+    payee: Option<Regex>,
+    /// Restrict to purposes matching a regular expression
+    #[clap(long, value_name = "REGEX", value_parser = Regex::new)]
+    purpose: Option<Regex>,
+    /// Only transactions without a category
+    #[clap(long)]
+    uncategorized: bool,
+    /// Only transactions in this currency (e.g. USD)
+    #[clap(long, value_name = "CODE")]
+    currency: Option<String>,
+    /// Only transactions with this counterpart IBAN or account number
+    #[clap(long = "counterpart-iban", value_name = "IBAN")]
+    counterpart_iban: Option<String>,
+}
+
+impl FilterArgs {
+    fn filter(&self) -> TransactionFilter {
+        TransactionFilter {
+            min_amount: self.min_amount,
+            max_amount: self.max_amount,
+            sign: self.sign.map(Sign::from),
+            booked: match (self.booked_only, self.pending_only) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            },
+            checkmark: self.checkmark.map(|c| c == Checkmark::On),
+            payee: self.payee.clone(),
+            purpose: self.purpose.clone(),
+            uncategorized: self.uncategorized,
+            currency: self.currency.clone(),
+            counterpart_iban: self.counterpart_iban.clone(),
+        }
+    }
+}
+
+/// `--fields` and `--template` for every export, see [`projection`].
+#[derive(Args)]
+#[clap(next_help_heading = "Output shape")]
+struct ShapeArgs {
+    /// Only these comma-separated fields (e.g. `name,balance.amount`)
+    #[clap(
+        long,
+        value_name = "FIELDS",
+        value_parser = str::parse::<Fields>,
+        long_help = FIELDS_LONG_HELP
+    )]
+    fields: Option<Fields>,
+    /// Render each item with a template (e.g. `'{{ name }}: {{ amount }}'`)
+    #[clap(
+        long,
+        value_name = "TEMPLATE",
+        value_parser = str::parse::<OutputTemplate>,
+        conflicts_with_all = ["fields", "format"],
+        long_help = TEMPLATE_LONG_HELP
+    )]
+    template: Option<OutputTemplate>,
+    /// `--format csv`: field separator (default `,`)
+    #[clap(long = "csv-delimiter", value_name = "CHAR")]
+    csv_delimiter: Option<char>,
+    /// `--format csv`: decimal separator for fractional numbers (default `.`)
+    #[clap(long = "csv-decimal-separator", value_name = "CHAR")]
+    csv_decimal_separator: Option<char>,
+    /// `--format csv`: leave out the header line
+    #[clap(long = "csv-no-header")]
+    csv_no_header: bool,
+}
+
+impl ShapeArgs {
+    /// The shape for `--fields` / `--template`, or every field for CSV.
+    fn shape(&self, format: OutputFormat) -> Option<Shape> {
+        match (&self.fields, &self.template) {
+            (_, Some(template)) => Some(Shape::Template(template.clone())),
+            (Some(fields), None) => Some(Shape::Fields(fields.clone())),
+            (None, None) if format == OutputFormat::Csv => Some(Shape::AllFields),
+            (None, None) => None,
+        }
+    }
+
+    fn csv(&self) -> CsvOptions {
+        let default = CsvOptions::default();
+        CsvOptions {
+            delimiter: self.csv_delimiter.unwrap_or(default.delimiter),
+            decimal_separator: self
+                .csv_decimal_separator
+                .unwrap_or(default.decimal_separator),
+            header: !self.csv_no_header,
+        }
+    }
+}
+
+/// Value for `--sign`.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum SignArg {
+    Income,
+    Expense,
+}
+
+impl From<SignArg> for Sign {
+    fn from(sign: SignArg) -> Self {
+        match sign {
+            SignArg::Income => Sign::Income,
+            SignArg::Expense => Sign::Expense,
+        }
+    }
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Search transactions with a query expression",
+    long_about = "Search transactions with a query expression.
+
+Transactions are exported from the earliest `date` bound the query sets (its whole history \
+without one), then matched on the client. Output encoding is selected with `--format` \
+(default: json).",
+    after_help = r#"EXAMPLES:
+    moneymoney query 'amount < -100 and category ~ "Food\*" and payee:"REWE" and date >= 2024-01-01'
+    moneymoney query 'date = last-month and not checkmark' --format table
+    moneymoney query 'creditor-id = DE98ZZZ09999999999' --sort=-date --limit 1"#
+)]
+struct QueryArgs {
+    /// The query, e.g. `amount < -100 and payee:REWE`
+    #[clap(value_name = "QUERY", long_help = QUERY_LONG_HELP)]
+    query: String,
+    /// Restrict to one account (UUID or IBAN)
+    #[clap(
+        long = "from-account",
+        value_name = "UUID|IBAN",
+        add = ArgValueCandidates::new(completion::account_ids)
+    )]
+    from_account: Option<String>,
+    /// Sort by date, value-date, amount, payee, category or id (`-` prefix: descending)
+    #[clap(
+        long,
+        value_name = "KEY",
+        allow_hyphen_values = true,
+        value_parser = str::parse::<SortOrder>
+    )]
+    sort: Option<SortOrder>,
+    /// Keep at most N transactions, after sorting
+    #[clap(long, value_name = "N")]
+    limit: Option<usize>,
+    /// Output serialization format (`json` by default)
+    #[clap(long, value_enum, default_value_t = OutputFormat::Json)]
+    format: OutputFormat,
+    /// Write to a file instead of stdout
+    #[clap(long, short = 'o', value_name = "FILE", long_help = OUTPUT_LONG_HELP)]
+    output: Option<PathBuf>,
+    #[clap(flatten)]
+    shape: ShapeArgs,
 }
 
 #[derive(Args)]
@@ -207,6 +624,7 @@ struct ExportPortfolioArgs {
     #[clap(
         long = "from-account",
         value_name = "UUID|IBAN",
+        add = ArgValueCandidates::new(completion::account_ids),
         long_help = "Only return securities held in this account. Accepts a MoneyMoney account UUID or IBAN. \
                      When omitted, securities from all portfolio accounts are included."
     )]
@@ -227,13 +645,909 @@ struct ExportPortfolioArgs {
         long_help = EXPORT_FORMAT_LONG_HELP
     )]
     format: OutputFormat,
+    /// Write to a file instead of stdout
+    #[clap(long, short = 'o', value_name = "FILE", long_help = OUTPUT_LONG_HELP)]
+    output: Option<PathBuf>,
+    #[clap(flatten)]
+    shape: ShapeArgs,
+}
+
+#[derive(Subcommand)]
+enum AddTarget {
+    /// Add transactions to offline accounts from flags or a JSON/NDJSON file
+    Transaction(AddTransactionArgs),
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Add transactions to offline accounts from flags or a JSON/NDJSON file",
+    long_about = "Add transactions to offline (manual) accounts.
+
+Pass one transaction with the flags below, or many with `--file`: a JSON object, a JSON \
+array, or NDJSON with one object per line, using the same fields as the library's \
+`AddTransactionParams` (`toAccount`, `onDate`, `to`, `amount`, `purpose`, `category`).
+
+Before anything is written, each row's account and category are looked up in MoneyMoney; \
+rows that don't match are skipped. One JSON line per row (`{\"row\": 1, \"status\": \
+\"added\"}` or `\"failed\"` with an `error`) goes to stdout and a summary to stderr. The \
+exit status is 0 when every row was added and 2 when some failed.",
+    after_help = "EXAMPLES:
+    moneymoney add transaction --to-account Bargeld --date 2024-03-01 --to Bäckerei --amount -3.50
+    moneymoney add transaction --to-account Bargeld --date 2024-03-01 --to Kino \\
+        --amount -12 --purpose 'Dune' --category 'Freizeit\\Kino'
+    moneymoney add transaction --file cash.ndjson"
+)]
+struct AddTransactionArgs {
+    /// JSON/NDJSON rows to add (`-` for stdin) instead of the flags
+    #[clap(long, short = 'f', value_name = "FILE", conflicts_with_all = ["to_account", "date", "to", "amount", "purpose", "category"])]
+    file: Option<PathBuf>,
+    /// Offline account (UUID, name, IBAN or account number)
+    #[clap(
+        long = "to-account",
+        value_name = "ACCOUNT",
+        required_unless_present = "file",
+        add = ArgValueCandidates::new(completion::account_names)
+    )]
+    to_account: Option<String>,
+    /// Booking date (YYYY-MM-DD)
+    #[clap(long, value_name = "YYYY-MM-DD", required_unless_present = "file")]
+    date: Option<NaiveDate>,
+    /// Payee (or payer for income)
+    #[clap(long, value_name = "NAME", required_unless_present = "file")]
+    to: Option<String>,
+    /// Amount, negative for expenses
+    #[clap(long, allow_hyphen_values = true, required_unless_present = "file")]
+    amount: Option<f64>,
+    /// Purpose text
+    #[clap(long)]
+    purpose: Option<String>,
+    /// Category (UUID, name or backslash-separated path); auto-categorized if omitted
+    #[clap(long, add = ArgValueCandidates::new(completion::category_paths))]
+    category: Option<String>,
+}
+
+impl AddTransactionArgs {
+    /// The rows to add: from `--file`, or the single row given by the flags.
+    fn rows(
+        self,
+    ) -> Result<
+        Vec<moneymoney::add_transaction::AddTransactionParams>,
+        Box<dyn std::error::Error + Send + Sync>,
+    > {
+        use moneymoney::add_transaction::AddTransactionParams;
+
+        if let Some(file) = self.file {
+            let raw = read_json_input(Some(file))?;
+            return Ok(
+                batch::parse_rows(&raw).map_err(|e| format!("invalid transaction input: {e}"))?
+            );
+        }
+        let (Some(to_account), Some(date), Some(to), Some(amount)) =
+            (self.to_account, self.date, self.to, self.amount)
+        else {
+            return Err(
+                "--to-account, --date, --to and --amount are required without --file".into()
+            );
+        };
+        let mut params = AddTransactionParams::new(to_account, date, to, amount);
+        params.purpose = self.purpose;
+        params.category = self.category;
+        Ok(vec![params])
+    }
+}
+
+#[derive(Subcommand)]
+enum SetTarget {
+    /// Change checkmark, category or comment of one or many transactions
+    Transaction(SetTransactionArgs),
+}
+
+/// Value for `--checkmark`.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum Checkmark {
+    On,
+    Off,
+}
+
+impl Checkmark {
+    fn as_str(self) -> &'static str {
+        match self {
+            Checkmark::On => "on",
+            Checkmark::Off => "off",
+        }
+    }
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Change checkmark, category or comment of one or many transactions",
+    long_about = "Change the checkmark, category or comment of transactions.
+
+With a transaction id (see `export transactions`), that transaction is changed. Without \
+one, the transactions matching the filters are selected, the same filters as \
+`export transactions` plus `--payee`. Bulk changes print a preview table and only apply \
+with `--yes`.
+
+Each change is reported as a JSON line on stdout (`{\"row\": 1, \"id\": 42, \"status\": \
+\"updated\"}`) with a summary on stderr. The exit status is 2 when some changes failed.",
+    after_help = "EXAMPLES:
+    moneymoney set transaction 4711 --checkmark on
+    moneymoney set transaction 4711 --category 'Freizeit\\Kino' --comment 'with Anna'
+    moneymoney set transaction --from-date 2020-01-01 --payee '(?i)^netflix' --category Abos
+    moneymoney set transaction --from-date 2020-01-01 --payee '(?i)^netflix' --category Abos --yes
+    moneymoney set transaction --period last-month --from-category Bargeld --checkmark on --yes",
+    group(ArgGroup::new("change").required(true).multiple(true).args(["checkmark", "category", "comment"]))
+)]
+struct SetTransactionArgs {
+    /// Transaction id; omit to select transactions with the filters
+    id: Option<u64>,
+    /// Set or clear the checkmark
+    #[clap(long, value_enum)]
+    checkmark: Option<Checkmark>,
+    /// Category to assign (UUID, name or backslash-separated path)
+    #[clap(long, add = ArgValueCandidates::new(completion::category_paths))]
+    category: Option<String>,
+    /// Comment to set (an empty string clears it)
+    #[clap(long)]
+    comment: Option<String>,
+    /// Bulk: inclusive start of the date range (YYYY-MM-DD, `30d`, ...)
+    #[clap(
+        long = "from-date",
+        value_name = "DATE",
+        value_parser = parse_start_date,
+        required_unless_present_any = ["id", "period"],
+        conflicts_with = "id"
+    )]
+    from_date: Option<NaiveDate>,
+    /// Bulk: inclusive end of the date range (YYYY-MM-DD, `yesterday`, ...)
+    #[clap(
+        long = "to-date",
+        value_name = "DATE",
+        value_parser = parse_end_date,
+        conflicts_with = "id"
+    )]
+    to_date: Option<NaiveDate>,
+    /// Bulk: date range for both ends (`last-month`, `2024-Q3`, ...)
+    #[clap(
+        long,
+        value_name = "RANGE",
+        value_parser = DateRange::parse_local,
+        conflicts_with_all = ["id", "from_date", "to_date"],
+        long_help = PERIOD_LONG_HELP
+    )]
+    period: Option<DateRange>,
+    /// Bulk: restrict to one account (UUID or IBAN)
+    #[clap(
+        long = "from-account",
+        value_name = "UUID|IBAN",
+        conflicts_with = "id",
+        add = ArgValueCandidates::new(completion::account_ids)
+    )]
+    from_account: Option<String>,
+    /// Bulk: restrict to one category name
+    #[clap(
+        long = "from-category",
+        value_name = "NAME",
+        conflicts_with = "id",
+        add = ArgValueCandidates::new(completion::category_names)
+    )]
+    from_category: Option<String>,
+    /// Bulk: restrict to payees matching a regular expression
+    #[clap(
+        long,
+        value_name = "REGEX",
+        value_parser = Regex::new,
+        conflicts_with = "id",
+        long_help = PAYEE_LONG_HELP
+    )]
+    payee: Option<Regex>,
+    /// Bulk: apply the changes instead of only previewing them
+    #[clap(long, short = 'y')]
+    yes: bool,
+}
+
+impl SetTransactionArgs {
+    /// The change to apply to transaction `id`.
+    fn params(&self, id: u64) -> SetTransactionParams {
+        SetTransactionParams {
+            id,
+            checkmark_to: self.checkmark.map(|c| c.as_str().to_string()),
+            category_to: self.category.clone(),
+            comment_to: self.comment.clone(),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum RulesTarget {
+    /// Categorize uncategorized transactions with the first matching rule
+    Apply(RulesApplyArgs),
+}
+
+#[derive(Args)]
+#[clap(
+    long_about = "Categorize uncategorized transactions with the first matching rule.
+
+Rules come from a TOML or YAML file (`--rules`, else the profile's `rules`, else `rules.toml` \
+next to the config file). Each rule matches on the payee (`name`), `purpose` and `booking-text` \
+(case-insensitive regular expressions), an `amount` or amount range, the counterparty \
+`account-number`, `creditor-id`, `mandate-reference` and the transaction's `account`, and sets \
+a `category`, `comment` and/or `checkmark`:
+
+    [[rules]]
+    name = \"Rent\"
+    when = { creditor-id = \"DE98ZZZ09999999999\", amount = { min = -1250, max = -1150 } }
+    set = { category = 'Wohnen\\Miete', comment = \"Miete\" }
+
+The uncategorized transactions of the date range (default: the last 90 days) are checked \
+against the rules in file order and the changes are printed as a plan; they are only applied \
+with `--yes`. Each change is then reported as a JSON line on stdout with a summary on stderr, \
+as with `set transaction`.",
+    after_help = "EXAMPLES:
+    moneymoney rules apply
+    moneymoney rules apply --rules ~/rules.yaml --period this-year
+    moneymoney rules apply --from-account Girokonto --yes"
+)]
+struct RulesApplyArgs {
+    /// Rule file, TOML or YAML (default: the profile's `rules`)
+    #[clap(long, value_name = "FILE")]
+    rules: Option<PathBuf>,
+    /// Inclusive start of the date range (default: 90 days ago)
+    #[clap(long = "from-date", value_name = "DATE", value_parser = parse_start_date)]
+    from_date: Option<NaiveDate>,
+    /// Inclusive end of the date range (YYYY-MM-DD, `yesterday`, ...)
+    #[clap(long = "to-date", value_name = "DATE", value_parser = parse_end_date)]
+    to_date: Option<NaiveDate>,
+    /// Date range for both ends (`last-month`, `2024-Q3`, ...)
+    #[clap(
+        long,
+        value_name = "RANGE",
+        value_parser = DateRange::parse_local,
+        conflicts_with_all = ["from_date", "to_date"],
+        long_help = PERIOD_LONG_HELP
+    )]
+    period: Option<DateRange>,
+    /// Restrict to one account (UUID or IBAN)
+    #[clap(
+        long = "from-account",
+        value_name = "UUID|IBAN",
+        add = ArgValueCandidates::new(completion::account_ids)
+    )]
+    from_account: Option<String>,
+    /// Apply the changes instead of only printing the plan
+    #[clap(long, short = 'y')]
+    yes: bool,
+}
+
+#[derive(Subcommand)]
+enum CategorizeTarget {
+    /// Learn categories from categorized transactions and save the model
+    Train(CategorizeTrainArgs),
+    /// Suggest categories for uncategorized transactions
+    Suggest(CategorizeSuggestArgs),
+    /// Report how often the model is right on held-out transactions
+    Evaluate(CategorizeEvaluateArgs),
+}
+
+/// Where the category model is stored.
+#[derive(Args)]
+struct ModelArgs {
+    /// Model file (default: ~/.local/share/moneymoney/category-model.json)
+    #[clap(long, value_name = "FILE")]
+    model: Option<PathBuf>,
+}
+
+impl ModelArgs {
+    fn path(&self) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+        self.model
+            .clone()
+            .or_else(config::default_model_path)
+            .ok_or_else(|| "pass --model (neither XDG_DATA_HOME nor HOME is set)".into())
+    }
+}
+
+#[derive(Args)]
+#[clap(
+    long_about = "Learn categories from categorized transactions and save the model.
+
+The model is a naive Bayes classifier over the words of payee and purpose, the amount (exact \
+and its order of magnitude) and the counterparty IBAN. It is saved to `--model`, by default \
+`$XDG_DATA_HOME/moneymoney/category-model.json` (`~/.local/share/moneymoney/...`), and holds \
+feature counts per category, not transactions. Train again now and then to pick up new \
+categorizations.",
+    after_help = "EXAMPLES:
+    moneymoney categorize train
+    moneymoney categorize train --from-date 2022-01-01
+    moneymoney categorize train --input transactions.json --model ~/model.json"
+)]
+struct CategorizeTrainArgs {
+    #[clap(flatten)]
+    model: ModelArgs,
+    /// Learn from transactions booked on or after this date (default: all)
+    #[clap(long = "from-date", value_name = "DATE", value_parser = parse_start_date)]
+    from_date: Option<NaiveDate>,
+}
+
+#[derive(Args)]
+#[clap(
+    long_about = "Suggest categories for uncategorized transactions.
+
+Each uncategorized transaction of the date range (default: the last 90 days) gets the most \
+likely category of the model saved by `categorize train`, with its confidence between 0 and \
+1. Suggestions below `--min-confidence` and transactions the model knows nothing about are \
+left out.
+
+With `--interactive`, each suggestion is asked about on the terminal: `y` assigns the \
+category, `n` skips it, `q` stops. The accepted ones are then written to MoneyMoney and \
+reported as JSON lines on stdout, as with `set transaction`.",
+    after_help = "EXAMPLES:
+    moneymoney categorize suggest --format table
+    moneymoney categorize suggest --period this-year --min-confidence 0.9
+    moneymoney categorize suggest --interactive"
+)]
+struct CategorizeSuggestArgs {
+    #[clap(flatten)]
+    model: ModelArgs,
+    /// Inclusive start of the date range (default: 90 days ago)
+    #[clap(long = "from-date", value_name = "DATE", value_parser = parse_start_date)]
+    from_date: Option<NaiveDate>,
+    /// Inclusive end of the date range (YYYY-MM-DD, `yesterday`, ...)
+    #[clap(long = "to-date", value_name = "DATE", value_parser = parse_end_date)]
+    to_date: Option<NaiveDate>,
+    /// Date range for both ends (`last-month`, `2024-Q3`, ...)
+    #[clap(
+        long,
+        value_name = "RANGE",
+        value_parser = DateRange::parse_local,
+        conflicts_with_all = ["from_date", "to_date"],
+        long_help = PERIOD_LONG_HELP
+    )]
+    period: Option<DateRange>,
+    /// Restrict to one account (UUID or IBAN)
+    #[clap(
+        long = "from-account",
+        value_name = "UUID|IBAN",
+        add = ArgValueCandidates::new(completion::account_ids)
+    )]
+    from_account: Option<String>,
+    /// Leave out suggestions less confident than this (0 to 1)
+    #[clap(long, value_name = "P", default_value = "0.5", value_parser = parse_fraction)]
+    min_confidence: f64,
+    /// Accept or reject each suggestion and write the accepted ones
+    #[clap(long, short = 'i', conflicts_with = "format")]
+    interactive: bool,
+    /// Output encoding: json, ndjson or table
+    #[clap(long, value_enum, default_value = "json")]
+    format: OutputFormat,
+}
+
+#[derive(Args)]
+#[clap(
+    long_about = "Report how often the model is right on held-out transactions.
+
+The categorized transactions are split by a hash of their id, so the split is the same on \
+every run: `--holdout` of them are set aside, a model is trained on the rest and asked for \
+the held-out ones. `accuracy` is the share whose category was the top suggestion; \
+`confidentAccuracy` the share among suggestions of at least `--min-confidence`, which is \
+what `categorize suggest` shows. The saved model is not changed.",
+    after_help = "EXAMPLES:
+    moneymoney categorize evaluate
+    moneymoney categorize evaluate --holdout 0.3 --min-confidence 0.9 --format table"
+)]
+struct CategorizeEvaluateArgs {
+    /// Use transactions booked on or after this date (default: all)
+    #[clap(long = "from-date", value_name = "DATE", value_parser = parse_start_date)]
+    from_date: Option<NaiveDate>,
+    /// Share of the categorized transactions to test on (0 to 1)
+    #[clap(long, value_name = "P", default_value = "0.2", value_parser = parse_fraction)]
+    holdout: f64,
+    /// Confidence threshold for `confidentAccuracy` (0 to 1)
+    #[clap(long, value_name = "P", default_value = "0.5", value_parser = parse_fraction)]
+    min_confidence: f64,
+    /// Output encoding: json or table
+    #[clap(long, value_enum, default_value = "json")]
+    format: OutputFormat,
+}
+
+/// A number from 0 to 1, for confidences and shares.
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+        _ => Err(format!("expected a number from 0 to 1, got {s:?}")),
+    }
+}
+
+#[derive(Subcommand)]
+enum SyncTarget {
+    /// Upsert accounts, categories, securities and transactions into SQLite
+    Sqlite(SyncSqliteArgs),
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Upsert accounts, categories, securities and transactions into SQLite",
+    long_about = "Upsert accounts, categories, securities and transactions into a SQLite database.
+
+The database is created on first use. Every run upserts by UUID / transaction id, records \
+which transactions were inserted, changed or deleted (`transaction_changes`) and stores a \
+balance snapshot per account (`balance_snapshots`). A summary of the run is written to \
+stdout as JSON.",
+    after_help = "EXAMPLES:
+    moneymoney sync sqlite ~/moneymoney.sqlite
+    moneymoney sync sqlite ~/moneymoney.sqlite --from-date 2024-01-01
+    sqlite3 ~/moneymoney.sqlite 'SELECT category, SUM(amount) FROM transactions \\
+        WHERE deleted_run IS NULL GROUP BY category'"
+)]
+struct SyncSqliteArgs {
+    /// Database file (created if missing)
+    path: PathBuf,
+    /// Re-export transactions from this date on (YYYY-MM-DD, `30d`, ...)
+    #[clap(
+        long = "from-date",
+        value_name = "DATE",
+        value_parser = parse_start_date,
+        long_help = "Start of the transaction window to export: YYYY-MM-DD or the start of a range \
+                     expression such as `30d` or `this-year`. Transactions in the window that are \
+                     missing from MoneyMoney are marked deleted. Defaults to the full history on \
+                     the first run and to 90 days before the previous run afterwards."
+    )]
+    from_date: Option<NaiveDate>,
+}
+
+#[derive(Args)]
+#[clap(
+    args_conflicts_with_subcommands = true,
+    about = "Save accounts, categories, transactions and portfolio for later",
+    long_about = "Save accounts, categories, recent transactions and the portfolio as a snapshot.
+
+MoneyMoney keeps no history of balances or market values; snapshots do. Each snapshot is a \
+manifest named after its UTC timestamp (`snapshots/20240601T120000Z.json`) that points to \
+content-addressed payloads (`objects/<sha256>.json`), so unchanged accounts, categories or \
+transactions are stored only once. The store is `--dir`, else the profile's `snapshot-dir`, \
+else `$XDG_DATA_HOME/moneymoney/snapshots` (`~/.local/share/moneymoney/snapshots`). Pass a \
+manifest to `--input` to run any read command against it, or load it with \
+`moneymoney::snapshot::Snapshot::load`. The new snapshot is written to stdout as JSON.",
+    after_help = "EXAMPLES:
+    moneymoney snapshot
+    moneymoney snapshot --period this-year --keep-daily 14 --keep-monthly 24
+    moneymoney snapshot list --format table
+    moneymoney snapshot show latest --format table
+    moneymoney snapshot prune --keep-last 10 --keep-weekly 8 --dry-run
+    moneymoney export accounts --format table \\
+        --input ~/.local/share/moneymoney/snapshots/snapshots/20240601T120000Z.json"
+)]
+struct SnapshotArgs {
+    #[clap(subcommand)]
+    action: Option<SnapshotAction>,
+    #[clap(flatten)]
+    take: SnapshotTakeArgs,
+}
+
+#[derive(Subcommand)]
+enum SnapshotAction {
+    /// List the snapshots, oldest first
+    List(SnapshotListArgs),
+    /// Show the balances and securities of one snapshot
+    Show(SnapshotShowArgs),
+    /// Delete snapshots a retention policy doesn't keep
+    Prune(SnapshotPruneArgs),
+}
+
+/// Where the snapshots are stored.
+#[derive(Args)]
+struct SnapshotDirArgs {
+    /// Snapshot store (default: the profile's `snapshot-dir`)
+    #[clap(long, value_name = "DIR")]
+    dir: Option<PathBuf>,
+}
+
+impl SnapshotDirArgs {
+    fn store(
+        &self,
+        profile: &Profile,
+    ) -> Result<SnapshotStore, Box<dyn std::error::Error + Send + Sync>> {
+        self.dir
+            .clone()
+            .or_else(|| profile.snapshot_dir())
+            .or_else(config::default_snapshot_dir)
+            .map(SnapshotStore::new)
+            .ok_or_else(|| "pass --dir (neither XDG_DATA_HOME nor HOME is set)".into())
+    }
+}
+
+/// [`Retention`] flags; all zero keeps everything.
+#[derive(Args)]
+struct RetentionArgs {
+    /// Keep the newest N snapshots
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    keep_last: usize,
+    /// Keep the newest snapshot of each of the last N days with snapshots
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    keep_daily: usize,
+    /// Keep the newest snapshot of each of the last N weeks with snapshots
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    keep_weekly: usize,
+    /// Keep the newest snapshot of each of the last N months with snapshots
+    #[clap(long, value_name = "N", default_value_t = 0)]
+    keep_monthly: usize,
+}
+
+impl RetentionArgs {
+    fn retention(&self) -> Retention {
+        Retention {
+            last: self.keep_last,
+            daily: self.keep_daily,
+            weekly: self.keep_weekly,
+            monthly: self.keep_monthly,
+        }
+    }
+}
+
+#[derive(Args)]
+struct SnapshotTakeArgs {
+    #[clap(flatten)]
+    store: SnapshotDirArgs,
+    /// Keep transactions from this date on (default: the last 90 days)
+    #[clap(long = "from-date", value_name = "DATE", value_parser = parse_start_date)]
+    from_date: Option<NaiveDate>,
+    /// Keep transactions of this range's start onwards (`this-year`, `2024-Q3`, ...)
+    #[clap(
+        long,
+        value_name = "RANGE",
+        value_parser = DateRange::parse_local,
+        conflicts_with = "from_date",
+        long_help = PERIOD_LONG_HELP
+    )]
+    period: Option<DateRange>,
+    /// Prune with these rules after saving
+    #[clap(flatten)]
+    retention: RetentionArgs,
+}
+
+#[derive(Args)]
+struct SnapshotListArgs {
+    #[clap(flatten)]
+    store: SnapshotDirArgs,
+    /// Output encoding: json, ndjson, table or csv
+    #[clap(long, value_enum, default_value = "json")]
+    format: OutputFormat,
+}
+
+#[derive(Args)]
+struct SnapshotShowArgs {
+    /// Snapshot id (`20240601T120000Z`), `latest` or `latest~N`
+    #[clap(value_name = "ID", default_value = "latest")]
+    id: String,
+    #[clap(flatten)]
+    store: SnapshotDirArgs,
+    /// Output encoding: json or table
+    #[clap(long, value_enum, default_value = "json")]
+    format: OutputFormat,
+}
+
+#[derive(Args)]
+struct SnapshotPruneArgs {
+    #[clap(flatten)]
+    store: SnapshotDirArgs,
+    #[clap(flatten)]
+    retention: RetentionArgs,
+    /// Only print the snapshots that would be deleted
+    #[clap(long)]
+    dry_run: bool,
+}
+
+/// Columns of `snapshot list` for table and CSV output.
+const SNAPSHOT_LIST_FIELDS: &str =
+    "id,takenAt,fromDate,accounts.items,categories.items,transactions.items,securities.items";
+
+#[derive(Args)]
+#[clap(
+    long_about = "Show what changed between two exports or snapshots.
+
+Each side is a file `--input` accepts (an export or a snapshot manifest) or a snapshot in the \
+store: its id, `latest`, or `latest~N` for the one N before the newest. Transactions are \
+matched by id and compared field by field (category, comment, checkmark, booked state, amount, \
+payee, purpose, dates); accounts by UUID for their balance; categories by UUID for their path \
+and budget; holdings by UUID for quantity and market value. A part is left out if either side \
+lacks that export.
+
+Transactions booked before `--from-date` are ignored on both sides. It defaults to the later \
+start of the two snapshots, so transactions that merely fell out of the older window aren't \
+reported as removed.",
+    after_help = "EXAMPLES:
+    moneymoney diff latest~1 latest --format table
+    moneymoney diff 20240601T120000Z latest
+    moneymoney diff old/transactions.json new/transactions.json --from-date 2024-05-01"
+)]
+struct DiffArgs {
+    /// Before: a file, snapshot id, `latest` or `latest~N`
+    #[clap(value_name = "OLD")]
+    old: String,
+    /// After: a file, snapshot id, `latest` or `latest~N`
+    #[clap(value_name = "NEW")]
+    new: String,
+    #[clap(flatten)]
+    store: SnapshotDirArgs,
+    /// Compare transactions booked on or after this date
+    #[clap(long = "from-date", value_name = "DATE", value_parser = parse_start_date)]
+    from_date: Option<NaiveDate>,
+    /// Output encoding: json or table
+    #[clap(long, value_enum, default_value = "json")]
+    format: OutputFormat,
+}
+
+impl DiffArgs {
+    /// The exports of one side, and the snapshot's transaction window start.
+    fn load(
+        &self,
+        side: &str,
+        profile: &Profile,
+    ) -> Result<(SavedExports, Option<NaiveDate>), Box<dyn std::error::Error + Send + Sync>> {
+        let path = Path::new(side);
+        if path.exists() {
+            let mut saved = SavedExports::new();
+            let info = source::read_input(path, &mut saved)?;
+            return Ok((saved, info.map(|info| info.from_date)));
+        }
+        let snapshot = self.store.store(profile)?.load(side)?;
+        let from_date = snapshot.info.from_date;
+        Ok((snapshot.into(), Some(from_date)))
+    }
+}
+
+#[derive(Args)]
+#[clap(
+    long_about = "Report new and changed transactions as they come in.
+
+Every `--interval`, the transactions of the last `--window` days are exported and compared with \
+a cursor of the previous poll (`--cursor`, default `~/.local/state/moneymoney/watch.json`). \
+Each difference becomes one JSON line on stdout: `new`, `changed` with the changed `fields` \
+(e.g. `booked`, `category`) or `disappeared`. A pending transaction the bank books under a new \
+id is one `changed` event with `previousId`. The first poll only fills the cursor unless \
+`--emit-existing` is passed.
+
+The cursor keeps ids and field hashes, not transaction data, and is saved after the events \
+are handled, so an interrupted watch repeats rather than loses events. Polls that fail (for \
+example while MoneyMoney is locked) are reported on stderr and retried at the next interval.
+
+With hooks, events are delivered there instead of stdout: the profile's `hooks` (local \
+commands or signed HTTP POSTs, with event and query filters, retries and a dead-letter file) \
+and `--hook-command`, which gets each event on stdin with `MONEYMONEY_EVENT` set to its kind. \
+An event whose dead letter can't be written is reported again at the next poll.",
+    after_help = "EXAMPLES:
+    moneymoney watch
+    moneymoney watch --interval 1m --window 7 | jq -c 'select(.event == \"new\")'
+    moneymoney watch --hook-command 'terminal-notifier -title MoneyMoney -message \"$(jq -r .transaction.name)\"'
+    moneymoney watch --once --cursor ~/watch.json   # e.g. from launchd or cron"
+)]
+struct WatchArgs {
+    /// Time between polls: `90s`, `5m`, `1h` or seconds
+    #[clap(long, value_name = "DURATION", default_value = "5m", value_parser = parse_interval)]
+    interval: Duration,
+    /// Watch transactions booked in the last N days
+    #[clap(long, value_name = "DAYS", default_value_t = 30)]
+    window: u64,
+    /// Cursor file (default: ~/.local/state/moneymoney/watch.json)
+    #[clap(long, value_name = "FILE")]
+    cursor: Option<PathBuf>,
+    /// Report the transactions of the first poll as new
+    #[clap(long)]
+    emit_existing: bool,
+    /// Poll once and exit
+    #[clap(long)]
+    once: bool,
+    /// Also pipe each event to this shell command (see the profile's `hooks`)
+    #[clap(long, value_name = "COMMAND")]
+    hook_command: Option<String>,
+}
+
+/// `90s`, `5m`, `1h` or plain seconds.
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        _ => return Err(format!("unknown unit {unit:?}, expected s, m or h")),
+    };
+    match number.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(Duration::from_secs(n * seconds)),
+        _ => Err(format!("expected a positive number like 90s, 5m or 1h, got {s:?}")),
+    }
+}
+
+/// Export the watch window from `inputs` (MoneyMoney if empty) and compare
+/// it with the cursor. Saved exports are read again on every poll.
+fn watch_poll(
+    inputs: &[PathBuf],
+    scope: source::Scope,
+    watcher: &mut Watcher,
+) -> Result<Vec<WatchEvent>, Box<dyn std::error::Error + Send + Sync>> {
+    let source = Source::new(inputs)?.with_scope(scope);
+    let from_date = watcher.from_date(chrono::Local::now().date_naive());
+    let response = source.transactions(ExportTransactionsParams::new(from_date))?;
+    Ok(watcher.observe(&response.transactions, from_date, chrono::Utc::now()))
+}
+
+#[derive(Subcommand)]
+enum ConfigTarget {
+    /// Print the selected profile's settings as TOML
+    Show,
+    /// Print the path of the config file
+    Path,
 }
 
 /// Output encoding for export subcommands.
-#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug, Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum OutputFormat {
     /// Pretty-printed JSON (default)
     Json,
+    /// OFX 2.2 statement (transactions only)
+    Ofx,
+    /// Quicken Interchange Format (transactions only)
+    Qif,
+    /// ISO 20022 camt.053 statement XML (transactions only)
+    Camt053,
+    /// Apache Parquet (transactions, accounts, portfolio)
+    Parquet,
+    /// Newline-delimited JSON, one compact object per item
+    Ndjson,
+    /// Aligned table with totals, for reading in a terminal
+    Table,
+    /// Comma-separated values, one row per item (see `--fields`)
+    Csv,
+}
+
+impl OutputFormat {
+    fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Ofx => "ofx",
+            OutputFormat::Qif => "qif",
+            OutputFormat::Camt053 => "camt053",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Table => "table",
+            OutputFormat::Csv => "csv",
+        }
+    }
+}
+
+/// Error for a `--format` value the given subcommand (e.g. `export accounts`) can't produce.
+fn unsupported_format(
+    format: OutputFormat,
+    command: &str,
+) -> Box<dyn std::error::Error + Send + Sync> {
+    format!("--format {} is not supported by `{command}`", format.name()).into()
+}
+
+/// `--from-date`: a date, or the first day of a range expression.
+fn parse_start_date(s: &str) -> Result<NaiveDate, moneymoney::date_range::ParseDateRangeError> {
+    DateRange::parse_local(s).map(|range| range.start)
+}
+
+/// `--to-date`: a date, or the last day of a range expression.
+fn parse_end_date(s: &str) -> Result<NaiveDate, moneymoney::date_range::ParseDateRangeError> {
+    DateRange::parse_local(s).map(|range| range.end)
+}
+
+/// `--from-date` / `--to-date`, or both ends from `--period`.
+fn date_bounds(
+    from_date: Option<NaiveDate>,
+    to_date: Option<NaiveDate>,
+    period: Option<DateRange>,
+) -> Option<(NaiveDate, Option<NaiveDate>)> {
+    match period {
+        Some(range) => Some((range.start, Some(range.end))),
+        None => from_date.map(|from| (from, to_date)),
+    }
+}
+
+/// Apply the client-side filters, then `--sort` and `--limit`.
+fn select_transactions(
+    transactions: &mut Vec<MoneymoneyTransaction>,
+    filter: &TransactionFilter,
+    sort: Option<SortOrder>,
+    limit: Option<usize>,
+) {
+    filter.apply(transactions);
+    if let Some(order) = sort {
+        sort_transactions(transactions, order);
+    }
+    if let Some(limit) = limit {
+        transactions.truncate(limit);
+    }
+}
+
+/// Fetch the accounts a transaction export refers to, plus the `--from-account`
+/// account if one was given. Statement formats need account metadata (type,
+/// IBAN, balance) that the transaction export itself doesn't carry.
+fn accounts_for_transactions(
+    source: &Source,
+    response: &TransactionsResponse,
+    from_account: Option<&str>,
+) -> Result<Vec<MoneymoneyAccount>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(source
+        .accounts()?
+        .into_iter()
+        .filter(|a| {
+            response
+                .transactions
+                .iter()
+                .any(|t| t.account_uuid == a.uuid)
+                || from_account.is_some_and(|ident| a.matches(ident))
+        })
+        .collect())
+}
+
+/// Write an exported, already filtered transaction list in `format`.
+///
+/// `range` is the export's date range, used for the camt.053 statement period.
+fn write_transactions(
+    source: &Source,
+    response: &TransactionsResponse,
+    format: OutputFormat,
+    output: Option<&Path>,
+    from_account: Option<&str>,
+    range: (NaiveDate, Option<NaiveDate>),
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if format == OutputFormat::Ndjson {
+        let mut sink = NdjsonSink::new(export_output(format, output)?, false);
+        let header = serde_json::json!({ "creator": response.creator });
+        if sink.push(&header).is_continue() {
+            for transaction in &response.transactions {
+                if sink.push(transaction).is_break() {
+                    break;
+                }
+            }
+        }
+        return sink.finish();
+    }
+    if format == OutputFormat::Table {
+        return write_table(&table::transactions_table(&response.transactions), output);
+    }
+    let out = export_output(format, output)?;
+    match format {
+        OutputFormat::Json => write_json_pretty(out, response)?,
+        OutputFormat::Ofx => {
+            let accounts = accounts_for_transactions(source, response, from_account)?;
+            moneymoney::formats::ofx::write_ofx(
+                out,
+                &accounts,
+                &response.transactions,
+                chrono::Utc::now(),
+            )?;
+        }
+        OutputFormat::Qif => {
+            let accounts = accounts_for_transactions(source, response, from_account)?;
+            moneymoney::formats::qif::write_qif(out, &accounts, &response.transactions)?;
+        }
+        OutputFormat::Camt053 => {
+            let accounts = accounts_for_transactions(source, response, from_account)?;
+            let now = chrono::Utc::now();
+            moneymoney::formats::camt053::write_camt053(
+                out,
+                &accounts,
+                &response.transactions,
+                range.0,
+                range.1.unwrap_or_else(|| now.date_naive()),
+                now,
+            )?;
+        }
+        OutputFormat::Parquet => {
+            let batch = moneymoney::formats::arrow::transactions_batch(&response.transactions)?;
+            moneymoney::formats::parquet::write_parquet(out, &batch)?;
+        }
+        OutputFormat::Ndjson | OutputFormat::Table | OutputFormat::Csv => {
+            unreachable!("handled above")
+        }
+    }
+    Ok(())
 }
 
 #[cfg(feature = "experimental")]
@@ -242,6 +1556,9 @@ enum CreateTarget {
     /// Create a SEPA bank transfer from JSON (stdin or file)
     #[clap(name = "bank-transfer")]
     BankTransfer(BankTransferArgs),
+    /// Create SEPA direct debits from JSON (stdin or file) or flags
+    #[clap(name = "direct-debit")]
+    DirectDebit(Box<DirectDebitArgs>),
 }
 
 #[cfg(feature = "experimental")]
@@ -249,17 +1566,210 @@ enum CreateTarget {
 struct BankTransferArgs {
     /// Path to JSON parameters, or `-` / omit for stdin
     file: Option<PathBuf>,
+    /// Write a SEPA pain.001 file to stdout instead of creating the transfer
+    #[clap(
+        long,
+        long_help = "Validate the transfer(s) and write a SEPA pain.001.001.09 credit transfer file \
+                     to stdout instead of handing them to MoneyMoney. The JSON input may be a single \
+                     object or an array; debtor name, IBAN and BIC are looked up from each transfer's \
+                     `fromAccount`."
+    )]
+    pain001: bool,
+}
+
+#[cfg(feature = "experimental")]
+#[derive(Args)]
+#[clap(
+    about = "Create SEPA direct debits from JSON (stdin or file) or flags",
+    long_about = "Create SEPA direct debits in MoneyMoney.
+
+Pass one debit with the flags below, or one or many as JSON (a single object or an array, \
+from a file or stdin) using the fields of the library's `CreateDirectDebitParams` \
+(`fromAccount`, `for`, `iban`, `amount`, `mandateReference`, `mandateDate`, \
+`sequenceCode`, ...). Every debit is validated before the first one is created: mandate \
+reference and date are required, the mandate must be signed on or before the collection \
+date, and sequence (RCUR, OOFF, FNAL; FRST only with --pain008) and instrument (CORE, B2B) \
+codes are checked. \
+MoneyMoney's results are printed as JSON.",
+    after_help = "EXAMPLES:
+    moneymoney create direct-debit debits.json
+    moneymoney create direct-debit --from-account Vereinskonto --for 'Max Mustermann' \\
+        --iban DE02120300000000202051 --amount 60 --purpose 'Beitrag 2024' \\
+        --mandate-reference M-0042 --mandate-date 2021-03-15 --sequence-code RCUR
+    moneymoney create direct-debit debits.json --pain008 --creditor-id DE98ZZZ09999999999 > debits.xml",
+    group(ArgGroup::new("flags").multiple(true).args([
+        "from_account", "for_debtor", "iban", "bic", "amount", "purpose",
+        "endtoend_reference", "purpose_code", "instrument_code", "sequence_code",
+        "mandate_reference", "mandate_date", "scheduled_date",
+    ]))
+)]
+struct DirectDebitArgs {
+    /// Path to JSON parameters, or `-` / omit for stdin (unless flags are given)
+    #[clap(conflicts_with = "flags")]
+    file: Option<PathBuf>,
+    /// Own account to collect into (UUID, name, IBAN or account number)
+    #[clap(
+        long = "from-account",
+        value_name = "ACCOUNT",
+        add = ArgValueCandidates::new(completion::account_names)
+    )]
+    from_account: Option<String>,
+    /// Debtor name
+    #[clap(long = "for", value_name = "NAME")]
+    for_debtor: Option<String>,
+    /// Debtor IBAN
+    #[clap(long)]
+    iban: Option<String>,
+    /// Debtor BIC
+    #[clap(long)]
+    bic: Option<String>,
+    /// Amount in EUR
+    #[clap(long)]
+    amount: Option<f64>,
+    /// Remittance information
+    #[clap(long)]
+    purpose: Option<String>,
+    /// End-to-end reference
+    #[clap(long = "endtoend-reference", value_name = "REF")]
+    endtoend_reference: Option<String>,
+    /// SEPA purpose code (e.g. `GDDS`)
+    #[clap(long = "purpose-code", value_name = "CODE")]
+    purpose_code: Option<String>,
+    /// Local instrument: CORE (default) or B2B
+    #[clap(long = "instrument-code", value_name = "CODE")]
+    instrument_code: Option<String>,
+    /// Sequence type: RCUR (default), OOFF or FNAL; FRST only with --pain008
+    #[clap(long = "sequence-code", value_name = "CODE")]
+    sequence_code: Option<String>,
+    /// Mandate reference
+    #[clap(long = "mandate-reference", value_name = "REF")]
+    mandate_reference: Option<String>,
+    /// Date the mandate was signed (YYYY-MM-DD)
+    #[clap(long = "mandate-date", value_name = "YYYY-MM-DD")]
+    mandate_date: Option<NaiveDate>,
+    /// Collection date (YYYY-MM-DD); earliest possible if omitted
+    #[clap(long = "scheduled-date", value_name = "YYYY-MM-DD")]
+    scheduled_date: Option<NaiveDate>,
+    /// Write a SEPA pain.008 file to stdout instead of creating the debits
+    #[clap(long, requires = "creditor_id")]
+    pain008: bool,
+    /// SEPA creditor identifier for `--pain008`
+    #[clap(long = "creditor-id", value_name = "ID", requires = "pain008")]
+    creditor_id: Option<String>,
+}
+
+#[cfg(feature = "experimental")]
+impl DirectDebitArgs {
+    /// The debits to create: from the flags if any were given, else from JSON.
+    fn debits(
+        &self,
+    ) -> Result<
+        Vec<moneymoney::create_direct_debit::CreateDirectDebitParams>,
+        Box<dyn std::error::Error + Send + Sync>,
+    > {
+        use moneymoney::create_direct_debit::CreateDirectDebitParams;
+
+        let date = |d: Option<NaiveDate>| d.map(|d| d.format("%Y-%m-%d").to_string());
+        let flags = CreateDirectDebitParams {
+            from_account: self.from_account.clone(),
+            for_debtor: self.for_debtor.clone(),
+            iban: self.iban.clone(),
+            bic: self.bic.clone(),
+            amount: self.amount,
+            purpose: self.purpose.clone(),
+            endtoend_reference: self.endtoend_reference.clone(),
+            purpose_code: self.purpose_code.clone(),
+            instrument_code: self.instrument_code.clone(),
+            sequence_code: self.sequence_code.clone(),
+            mandate_reference: self.mandate_reference.clone(),
+            mandate_date: date(self.mandate_date),
+            scheduled_date: date(self.scheduled_date),
+            into: None,
+        };
+        let from_flags = serde_json::to_value(&flags)?
+            .as_object()
+            .is_some_and(|fields| !fields.is_empty());
+        if from_flags {
+            return Ok(vec![flags]);
+        }
+        let raw = read_json_input(self.file.clone())?;
+        Ok(parse_one_or_many(raw.trim())
+            .map_err(|e| format!("invalid JSON for direct debit: {e}"))?)
+    }
 }
 
 fn write_json_pretty_stdout<T: Serialize>(
     value: &T,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut stdout = io::stdout().lock();
-    serde_json::to_writer_pretty(&mut stdout, value)?;
-    stdout.write_all(b"\n")?;
+    write_json_pretty(io::stdout().lock(), value)
+}
+
+fn write_json_pretty<W: Write, T: Serialize>(
+    mut writer: W,
+    value: &T,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    serde_json::to_writer_pretty(&mut writer, value)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
     Ok(())
 }
 
+/// Where an export goes: the `--output` file, or stdout.
+///
+/// Parquet is binary, so it is refused when stdout is a terminal.
+fn export_output(
+    format: OutputFormat,
+    path: Option<&Path>,
+) -> Result<Box<dyn Write + Send>, Box<dyn std::error::Error + Send + Sync>> {
+    match path {
+        Some(path) => Ok(Box::new(io::BufWriter::new(std::fs::File::create(path)?))),
+        None if format == OutputFormat::Parquet && io::stdout().is_terminal() => {
+            Err("--format parquet writes binary data; pass --output <FILE> or redirect stdout"
+                .into())
+        }
+        None => Ok(Box::new(io::BufWriter::new(io::stdout()))),
+    }
+}
+
+/// Render `table` to the `--output` file, or fitted and colored to a terminal.
+fn write_table(
+    table: &table::Table,
+    path: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let options = table::RenderOptions::detect(path.is_none() && io::stdout().is_terminal());
+    table.render(export_output(OutputFormat::Table, path)?, options)?;
+    Ok(())
+}
+
+/// The [`ShapedSink`] for `--fields`, `--template` or `--format csv`, or
+/// `None` for the other formats.
+fn shaped_sink(
+    args: &ShapeArgs,
+    format: OutputFormat,
+    output: Option<&Path>,
+    strip_icons: bool,
+) -> Result<Option<ShapedSink>, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(shape) = args.shape(format) else {
+        return Ok(None);
+    };
+    let render = table::RenderOptions::detect(output.is_none() && io::stdout().is_terminal());
+    let out = export_output(OutputFormat::Json, output)?;
+    Ok(Some(ShapedSink::new(shape, format, out, render, args.csv(), strip_icons)?))
+}
+
+/// Push every item into `sink`, stopping at the first error.
+fn push_all<T: Serialize>(
+    mut sink: ShapedSink,
+    items: &[T],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    for item in items {
+        if sink.push(item).is_break() {
+            break;
+        }
+    }
+    sink.finish()
+}
+
 /// Serialize export items for CLI JSON: drop `icon` unless `--include-icon-data` was passed.
 fn export_json_value_without_icons<T: Serialize>(
     items: &[T],
@@ -278,7 +1788,69 @@ fn export_json_value_without_icons<T: Serialize>(
     Ok(v)
 }
 
+/// Line-by-line NDJSON writer for the `*_each` export callbacks.
+///
+/// The callbacks can't return an error, so the first write error is kept and
+/// the export is stopped; [`NdjsonSink::finish`] reports it.
+struct NdjsonSink {
+    out: Box<dyn Write + Send>,
+    strip_icons: bool,
+    error: Option<Box<dyn std::error::Error + Send + Sync>>,
+}
+
+impl NdjsonSink {
+    fn new(out: Box<dyn Write + Send>, strip_icons: bool) -> Self {
+        Self {
+            out,
+            strip_icons,
+            error: None,
+        }
+    }
+
+    fn push<T: Serialize>(&mut self, item: &T) -> std::ops::ControlFlow<()> {
+        match self.write_line(item) {
+            Ok(()) => std::ops::ControlFlow::Continue(()),
+            Err(e) => {
+                self.error = Some(e);
+                std::ops::ControlFlow::Break(())
+            }
+        }
+    }
+
+    fn write_line<T: Serialize>(
+        &mut self,
+        item: &T,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.strip_icons {
+            let mut value = serde_json::to_value(item)?;
+            if let Some(obj) = value.as_object_mut() {
+                obj.remove("icon");
+            }
+            serde_json::to_writer(&mut self.out, &value)?;
+        } else {
+            serde_json::to_writer(&mut self.out, item)?;
+        }
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.out.flush()?),
+        }
+    }
+}
+
+/// Parse a JSON object or an array of objects into a list.
 #[cfg(feature = "experimental")]
+fn parse_one_or_many<T: serde::de::DeserializeOwned>(raw: &str) -> serde_json::Result<Vec<T>> {
+    match serde_json::from_str::<serde_json::Value>(raw)? {
+        serde_json::Value::Array(items) => items.into_iter().map(serde_json::from_value).collect(),
+        single => Ok(vec![serde_json::from_value(single)?]),
+    }
+}
+
 fn read_json_input(file: Option<PathBuf>) -> io::Result<String> {
     fn empty_stdin_error() -> io::Error {
         io::Error::new(
@@ -309,18 +1881,64 @@ fn read_json_input(file: Option<PathBuf>) -> io::Result<String> {
 }
 
 fn main() {
-    let cli = Cli::parse();
-    if let Err(e) = run(cli) {
+    CompleteEnv::with_factory(Cli::command)
+        .var(completion::COMPLETE_VAR)
+        .complete();
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let result = Profile::load(cli.config.as_deref(), cli.profile.as_deref())
+        .and_then(|profile| profile.apply(&mut cli, &matches).map(|()| profile))
+        .and_then(|profile| run(cli, &profile));
+    if let Err(e) = result {
         eprintln!("error: {e}");
-        std::process::exit(1);
+        let code = match e.downcast_ref::<batch::RowsFailed>() {
+            Some(_) => batch::RowsFailed::EXIT_CODE,
+            None => 1,
+        };
+        std::process::exit(code);
     }
 }
 
-fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+fn run(cli: Cli, profile: &Profile) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let source = Source::new(&cli.input)?;
+    // The profile's account scope and redaction shape what is read, never
+    // what is written.
+    let source = match cli.command {
+        Cmd::Export { .. } | Cmd::Query(_) | Cmd::Sync { .. } => source.with_scope(profile.scope()),
+        _ => source,
+    };
     match cli.command {
         Cmd::Export { target } => match target {
             ExportTarget::Accounts(args) => {
-                let accounts = moneymoney::export_accounts()?;
+                let strip_icons = !args.include_icon_data;
+                if let Some(mut sink) =
+                    shaped_sink(&args.shape, args.format, args.output.as_deref(), strip_icons)?
+                {
+                    source.accounts_each(|account| {
+                        if account.group && !args.include_group_accounts {
+                            return std::ops::ControlFlow::Continue(());
+                        }
+                        sink.push(&account)
+                    })?;
+                    return sink.finish();
+                }
+                if args.format == OutputFormat::Ndjson {
+                    let mut sink = NdjsonSink::new(
+                        export_output(args.format, args.output.as_deref())?,
+                        !args.include_icon_data,
+                    );
+                    source.accounts_each(|account| {
+                        if account.group && !args.include_group_accounts {
+                            return std::ops::ControlFlow::Continue(());
+                        }
+                        sink.push(&account)
+                    })?;
+                    return sink.finish();
+                }
+                let accounts = source.accounts()?;
+                if args.format == OutputFormat::Table {
+                    return write_table(&table::accounts_table(&accounts), args.output.as_deref());
+                }
                 let accounts = if args.include_group_accounts {
                     accounts
                 } else {
@@ -330,12 +1948,54 @@ fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                     OutputFormat::Json => {
                         let json =
                             export_json_value_without_icons(&accounts, args.include_icon_data)?;
-                        write_json_pretty_stdout(&json)?;
+                        write_json_pretty(
+                            export_output(args.format, args.output.as_deref())?,
+                            &json,
+                        )?;
+                    }
+                    OutputFormat::Parquet => {
+                        let batch = moneymoney::formats::arrow::accounts_batch(&accounts)?;
+                        moneymoney::formats::parquet::write_parquet(
+                            export_output(args.format, args.output.as_deref())?,
+                            &batch,
+                        )?;
                     }
+                    format => return Err(unsupported_format(format, "export accounts")),
                 }
             }
             ExportTarget::Categories(args) => {
-                let categories = moneymoney::export_categories()?;
+                let strip_icons = !args.include_icon_data;
+                if let Some(mut sink) =
+                    shaped_sink(&args.shape, args.format, args.output.as_deref(), strip_icons)?
+                {
+                    source.categories_each(|category| {
+                        if category.group && !args.include_group_categories {
+                            return std::ops::ControlFlow::Continue(());
+                        }
+                        sink.push(&category)
+                    })?;
+                    return sink.finish();
+                }
+                if args.format == OutputFormat::Ndjson {
+                    let mut sink = NdjsonSink::new(
+                        export_output(args.format, args.output.as_deref())?,
+                        !args.include_icon_data,
+                    );
+                    source.categories_each(|category| {
+                        if category.group && !args.include_group_categories {
+                            return std::ops::ControlFlow::Continue(());
+                        }
+                        sink.push(&category)
+                    })?;
+                    return sink.finish();
+                }
+                let categories = source.categories()?;
+                if args.format == OutputFormat::Table {
+                    return write_table(
+                        &table::categories_table(&categories),
+                        args.output.as_deref(),
+                    );
+                }
                 let categories = if args.include_group_categories {
                     categories
                 } else {
@@ -345,28 +2005,576 @@ fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                     OutputFormat::Json => {
                         let json =
                             export_json_value_without_icons(&categories, args.include_icon_data)?;
-                        write_json_pretty_stdout(&json)?;
+                        write_json_pretty(
+                            export_output(args.format, args.output.as_deref())?,
+                            &json,
+                        )?;
                     }
+                    format => return Err(unsupported_format(format, "export categories")),
                 }
             }
             ExportTarget::Transactions(args) => {
-                let mut params = ExportTransactionsParams::new(args.from_date);
-                params.to_date = args.to_date;
+                let (from_date, to_date) = date_bounds(args.from_date, args.to_date, args.period)
+                    .ok_or(
+                    "pass --from-date or --period, or set `period` in the config profile",
+                )?;
+                let mut params = ExportTransactionsParams::new(from_date);
+                params.to_date = to_date;
                 params.from_account = args.from_account;
                 params.from_category = args.from_category;
-                let response = moneymoney::export_transactions(params)?;
-                match args.format {
-                    OutputFormat::Json => write_json_pretty_stdout(&response)?,
+                let filter = args.filter.filter();
+                if args.format == OutputFormat::Camt053 {
+                    // Balances are reconstructed from the current balance, which needs
+                    // every booked transaction up to today.
+                    if params.from_category.is_some() || !filter.is_empty() || args.limit.is_some()
+                    {
+                        return Err(
+                            "--format camt053 cannot be combined with --from-category, \
+                                    filters or --limit (statement balances need all transactions)"
+                                .into(),
+                        );
+                    }
+                    params.to_date = None;
+                }
+                if let Some(sink) =
+                    shaped_sink(&args.shape, args.format, args.output.as_deref(), false)?
+                {
+                    let mut response = source.transactions(params)?;
+                    select_transactions(&mut response.transactions, &filter, args.sort, args.limit);
+                    return push_all(sink, &response.transactions);
+                }
+                // Sorting needs the whole export, so only unsorted NDJSON is streamed.
+                if args.format == OutputFormat::Ndjson && args.sort.is_none() {
+                    use moneymoney::export_transactions::TransactionsEvent;
+
+                    let mut sink =
+                        NdjsonSink::new(export_output(args.format, args.output.as_deref())?, false);
+                    let mut remaining = args.limit;
+                    source.transactions_each(params, |event| match event {
+                        TransactionsEvent::Creator(creator) => {
+                            sink.push(&serde_json::json!({ "creator": creator }))
+                        }
+                        TransactionsEvent::Transaction(transaction) => {
+                            if !filter.matches(&transaction) {
+                                return std::ops::ControlFlow::Continue(());
+                            }
+                            match &mut remaining {
+                                Some(0) => std::ops::ControlFlow::Break(()),
+                                Some(n) => {
+                                    *n -= 1;
+                                    sink.push(&transaction)
+                                }
+                                None => sink.push(&transaction),
+                            }
+                        }
+                    })?;
+                    return sink.finish();
                 }
+                let from_account = params.from_account.clone();
+                let mut response = source.transactions(params)?;
+                select_transactions(&mut response.transactions, &filter, args.sort, args.limit);
+                write_transactions(
+                    &source,
+                    &response,
+                    args.format,
+                    args.output.as_deref(),
+                    from_account.as_deref(),
+                    (from_date, to_date),
+                )?;
             }
             ExportTarget::Portfolio(args) => {
                 let mut params = ExportPortfolioParams::new();
                 params.from_account = args.from_account;
                 params.from_asset_class = args.from_asset_class;
-                let response = moneymoney::export_portfolio(params)?;
+                if let Some(mut sink) =
+                    shaped_sink(&args.shape, args.format, args.output.as_deref(), false)?
+                {
+                    source.portfolio_each(params, |security| sink.push(&security))?;
+                    return sink.finish();
+                }
+                if args.format == OutputFormat::Ndjson {
+                    let mut sink =
+                        NdjsonSink::new(export_output(args.format, args.output.as_deref())?, false);
+                    source.portfolio_each(params, |security| sink.push(&security))?;
+                    return sink.finish();
+                }
+                let response = source.portfolio(params)?;
+                match args.format {
+                    OutputFormat::Json => write_json_pretty(
+                        export_output(args.format, args.output.as_deref())?,
+                        &response,
+                    )?,
+                    OutputFormat::Parquet => {
+                        let batch =
+                            moneymoney::formats::arrow::securities_batch(&response.securities)?;
+                        moneymoney::formats::parquet::write_parquet(
+                            export_output(args.format, args.output.as_deref())?,
+                            &batch,
+                        )?;
+                    }
+                    OutputFormat::Table => write_table(
+                        &table::portfolio_table(&response.securities),
+                        args.output.as_deref(),
+                    )?,
+                    format => return Err(unsupported_format(format, "export portfolio")),
+                }
+            }
+        },
+        Cmd::Query(args) => {
+            let query = Query::parse(&args.query)?;
+            if args.format == OutputFormat::Camt053 {
+                return Err(unsupported_format(args.format, "query"));
+            }
+            let (from_date, to_date) = query.date_bounds();
+            let from_date = from_date.unwrap_or(FULL_HISTORY_START);
+            let mut params = ExportTransactionsParams::new(from_date);
+            params.to_date = to_date;
+            params.from_account = args.from_account;
+            let from_account = params.from_account.clone();
+            let mut response = source.transactions(params)?;
+            response.transactions.retain(|t| query.matches(t));
+            select_transactions(
+                &mut response.transactions,
+                &TransactionFilter::default(),
+                args.sort,
+                args.limit,
+            );
+            if let Some(sink) =
+                shaped_sink(&args.shape, args.format, args.output.as_deref(), false)?
+            {
+                return push_all(sink, &response.transactions);
+            }
+            write_transactions(
+                &source,
+                &response,
+                args.format,
+                args.output.as_deref(),
+                from_account.as_deref(),
+                (from_date, to_date),
+            )?;
+        }
+        Cmd::Add { target } => match target {
+            AddTarget::Transaction(args) => {
+                source.require_live("add transaction")?;
+                let rows = args.rows()?;
+                let accounts = source.accounts()?;
+                let categories = source.categories()?;
+                let mut report = batch::Report::new(io::stdout().lock());
+                for (i, params) in rows.into_iter().enumerate() {
+                    let result =
+                        batch::check_add_row(&params, &accounts, &categories).and_then(|()| {
+                            moneymoney::add_transaction(params).map_err(|e| e.to_string())
+                        });
+                    report.record(i + 1, None, batch::RowStatus::Added, result)?;
+                }
+                report.finish("added")?;
+            }
+        },
+        Cmd::Set { target } => match target {
+            SetTarget::Transaction(args) => {
+                if args.category.is_some() {
+                    let categories = source.categories()?;
+                    batch::check_category(args.category.as_deref(), &categories)?;
+                }
+                let bounds = date_bounds(args.from_date, args.to_date, args.period);
+                let ids = match (args.id, bounds) {
+                    (Some(id), _) => vec![id],
+                    (None, Some((from_date, to_date))) => {
+                        let mut params = ExportTransactionsParams::new(from_date);
+                        params.to_date = to_date;
+                        params.from_account = args.from_account.clone();
+                        params.from_category = args.from_category.clone();
+                        let mut transactions = source.transactions(params)?.transactions;
+                        let filter = TransactionFilter {
+                            payee: args.payee.clone(),
+                            ..TransactionFilter::default()
+                        };
+                        filter.apply(&mut transactions);
+                        if transactions.is_empty() {
+                            eprintln!("no transactions match the filters");
+                            return Ok(());
+                        }
+                        if !args.yes {
+                            write_table(
+                                &table::changes_table(&transactions, &args.params(0)),
+                                None,
+                            )?;
+                            eprintln!(
+                                "{} transaction(s) would change; pass --yes to apply",
+                                transactions.len()
+                            );
+                            return Ok(());
+                        }
+                        transactions.iter().map(|t| t.id).collect()
+                    }
+                    (None, None) => {
+                        return Err("pass a transaction id, --from-date or --period".into())
+                    }
+                };
+                source.require_live("set transaction")?;
+                let mut report = batch::Report::new(io::stdout().lock());
+                for (i, id) in ids.into_iter().enumerate() {
+                    let result =
+                        moneymoney::set_transaction(args.params(id)).map_err(|e| e.to_string());
+                    report.record(i + 1, Some(id), batch::RowStatus::Updated, result)?;
+                }
+                report.finish("updated")?;
+            }
+        },
+        Cmd::Rules { target } => match target {
+            RulesTarget::Apply(args) => {
+                let path = args
+                    .rules
+                    .or_else(|| profile.rules())
+                    .or_else(config::default_rules_path)
+                    .ok_or("pass --rules (neither XDG_CONFIG_HOME nor HOME is set)")?;
+                let rules = RuleSet::load(&path)?;
+                let categories = source.categories()?;
+                for rule in rules.rules() {
+                    let category = rule.set.category.as_deref().map(|c| profile.category(c));
+                    batch::check_category(category.as_deref(), &categories)
+                        .map_err(|e| format!("{}: {e}", rule.name))?;
+                }
+                let accounts = if rules.uses_accounts() {
+                    source.accounts()?
+                } else {
+                    Vec::new()
+                };
+                let (from_date, to_date) = date_bounds(args.from_date, args.to_date, args.period)
+                    .unwrap_or((chrono::Local::now().date_naive() - chrono::Days::new(90), None));
+                let mut params = ExportTransactionsParams::new(from_date);
+                params.to_date = to_date;
+                params.from_account = args.from_account;
+                let transactions = source.transactions(params)?.transactions;
+                let mut plan = rules.plan(&transactions, &accounts);
+                for assignment in &mut plan {
+                    if let Some(category) = &mut assignment.change.category_to {
+                        *category = profile.category(category);
+                    }
+                }
+                if plan.is_empty() {
+                    eprintln!("no rule matches an uncategorized transaction");
+                    return Ok(());
+                }
+                if !args.yes {
+                    write_table(&table::rules_plan_table(&plan), None)?;
+                    eprintln!("{} transaction(s) would change; pass --yes to apply", plan.len());
+                    return Ok(());
+                }
+                source.require_live("rules apply")?;
+                let mut report = batch::Report::new(io::stdout().lock());
+                for (i, assignment) in plan.into_iter().enumerate() {
+                    let id = assignment.transaction.id;
+                    let result =
+                        moneymoney::set_transaction(assignment.change).map_err(|e| e.to_string());
+                    report.record(i + 1, Some(id), batch::RowStatus::Updated, result)?;
+                }
+                report.finish("updated")?;
+            }
+        },
+        Cmd::Categorize { target } => match target {
+            CategorizeTarget::Train(args) => {
+                let path = args.model.path()?;
+                let params =
+                    ExportTransactionsParams::new(args.from_date.unwrap_or(FULL_HISTORY_START));
+                let transactions = source.transactions(params)?.transactions;
+                let model = Classifier::train(&transactions);
+                if model.transactions() == 0 {
+                    return Err("no categorized transactions to learn from".into());
+                }
+                model.save(&path)?;
+                eprintln!(
+                    "learned {} categories from {} transactions, saved to {}",
+                    model.categories().count(),
+                    model.transactions(),
+                    path.display()
+                );
+            }
+            CategorizeTarget::Suggest(args) => {
+                let path = args.model.path()?;
+                if !path.exists() {
+                    return Err(format!(
+                        "no model at {}; run `moneymoney categorize train` first",
+                        path.display()
+                    )
+                    .into());
+                }
+                let model = Classifier::load(&path)?;
+                if args.interactive {
+                    source.require_live("categorize suggest --interactive")?;
+                }
+                let (from_date, to_date) = date_bounds(args.from_date, args.to_date, args.period)
+                    .unwrap_or((chrono::Local::now().date_naive() - chrono::Days::new(90), None));
+                let mut params = ExportTransactionsParams::new(from_date);
+                params.to_date = to_date;
+                params.from_account = args.from_account;
+                let mut transactions = source.transactions(params)?.transactions;
+                TransactionFilter::new()
+                    .uncategorized()
+                    .apply(&mut transactions);
+                let suggestions: Vec<_> = transactions
+                    .iter()
+                    .filter_map(|t| Some((t, model.suggest(t)?)))
+                    .filter(|(_, s)| s.confidence >= args.min_confidence)
+                    .collect();
+                let rows: Vec<_> = suggestions
+                    .iter()
+                    .map(|(t, s)| categorize::SuggestionRow::new(t, s))
+                    .collect();
+                if !args.interactive {
+                    return match args.format {
+                        OutputFormat::Json => write_json_pretty_stdout(&rows),
+                        OutputFormat::Ndjson => {
+                            let mut out = io::stdout().lock();
+                            for row in &rows {
+                                serde_json::to_writer(&mut out, row)?;
+                                writeln!(out)?;
+                            }
+                            Ok(out.flush()?)
+                        }
+                        OutputFormat::Table => write_table(&table::suggestions_table(&rows), None),
+                        format => Err(unsupported_format(format, "categorize suggest")),
+                    };
+                }
+                if rows.is_empty() {
+                    eprintln!("no suggestions");
+                    return Ok(());
+                }
+                let accepted = categorize::review(&rows, io::stdin().lock(), io::stderr())?;
+                let mut report = batch::Report::new(io::stdout().lock());
+                for (i, index) in accepted.into_iter().enumerate() {
+                    let row = &rows[index];
+                    let params = SetTransactionParams::new(row.id).category(row.category);
+                    let result = moneymoney::set_transaction(params).map_err(|e| e.to_string());
+                    report.record(i + 1, Some(row.id), batch::RowStatus::Updated, result)?;
+                }
+                report.finish("updated")?;
+            }
+            CategorizeTarget::Evaluate(args) => {
+                if !matches!(args.format, OutputFormat::Json | OutputFormat::Table) {
+                    return Err(unsupported_format(args.format, "categorize evaluate"));
+                }
+                if args.holdout == 0.0 || args.holdout == 1.0 {
+                    return Err("--holdout must be between 0 and 1".into());
+                }
+                let params =
+                    ExportTransactionsParams::new(args.from_date.unwrap_or(FULL_HISTORY_START));
+                let transactions = source.transactions(params)?.transactions;
+                let evaluation = moneymoney::classify::evaluate(
+                    &transactions,
+                    args.holdout,
+                    args.min_confidence,
+                );
+                if evaluation.tested == 0 {
+                    return Err("no categorized transactions to evaluate on".into());
+                }
                 match args.format {
-                    OutputFormat::Json => write_json_pretty_stdout(&response)?,
+                    OutputFormat::Table => {
+                        write_table(&table::evaluation_table(&evaluation), None)?
+                    }
+                    _ => write_json_pretty_stdout(&evaluation)?,
+                }
+            }
+        },
+        Cmd::Snapshot(args) => match args.action {
+            None => {
+                let args = args.take;
+                let store = args.store.store(profile)?;
+                let today = chrono::Local::now().date_naive();
+                let from_date = match (args.from_date, args.period) {
+                    (Some(date), _) => date,
+                    (None, Some(range)) => range.start,
+                    (None, None) => today - chrono::Days::new(90),
+                };
+                let data = source.sync_data(from_date)?;
+                let saved =
+                    store.save(&data, chrono::SubsecRound::trunc_subsecs(chrono::Utc::now(), 0))?;
+                store.prune(&args.retention.retention())?;
+                write_json_pretty_stdout(&saved.info)?;
+                eprintln!("snapshot {}: {} of 4 payloads new", saved.info.id, saved.new_payloads);
+            }
+            Some(SnapshotAction::List(args)) => {
+                let snapshots = args.store.store(profile)?.list()?;
+                if args.format == OutputFormat::Json {
+                    return write_json_pretty_stdout(&snapshots);
+                }
+                let shape = Shape::Fields(SNAPSHOT_LIST_FIELDS.parse()?);
+                let render = table::RenderOptions::detect(io::stdout().is_terminal());
+                let out = export_output(args.format, None)?;
+                let csv = CsvOptions::default();
+                let sink = ShapedSink::new(shape, args.format, out, render, csv, false)?;
+                push_all(sink, &snapshots)?;
+            }
+            Some(SnapshotAction::Show(args)) => {
+                let snapshot = args.store.store(profile)?.load(&args.id)?;
+                let accounts: Vec<_> = snapshot.accounts.into_iter().filter(|a| !a.group).collect();
+                match args.format {
+                    OutputFormat::Json => {
+                        #[derive(Serialize)]
+                        #[serde(rename_all = "camelCase")]
+                        struct Shown<'a> {
+                            #[serde(flatten)]
+                            info: &'a moneymoney::snapshot::SnapshotInfo,
+                            balances: Vec<serde_json::Value>,
+                            securities: &'a [moneymoney::export_portfolio::Security],
+                        }
+                        let balances = accounts
+                            .iter()
+                            .map(|a| {
+                                serde_json::json!({
+                                    "accountUuid": a.uuid,
+                                    "name": a.name,
+                                    "balance": a.balance,
+                                })
+                            })
+                            .collect();
+                        write_json_pretty_stdout(&Shown {
+                            info: &snapshot.info,
+                            balances,
+                            securities: &snapshot.securities,
+                        })?;
+                    }
+                    OutputFormat::Table => {
+                        println!(
+                            "snapshot {} taken {}, transactions from {}",
+                            snapshot.info.id, snapshot.info.taken_at, snapshot.info.from_date
+                        );
+                        write_table(&table::accounts_table(&accounts), None)?;
+                        if !snapshot.securities.is_empty() {
+                            write_table(&table::portfolio_table(&snapshot.securities), None)?;
+                        }
+                    }
+                    format => return Err(unsupported_format(format, "snapshot show")),
+                }
+            }
+            Some(SnapshotAction::Prune(args)) => {
+                let store = args.store.store(profile)?;
+                let retention = args.retention.retention();
+                if retention.is_empty() {
+                    return Err("pass at least one of --keep-last, --keep-daily, --keep-weekly \
+                                or --keep-monthly"
+                        .into());
                 }
+                let deleted = if args.dry_run {
+                    retention
+                        .expired(&store.list()?)
+                        .into_iter()
+                        .cloned()
+                        .collect()
+                } else {
+                    store.prune(&retention)?
+                };
+                write_json_pretty_stdout(&deleted)?;
+            }
+        },
+        Cmd::Diff(args) => {
+            let (old, old_from) = args.load(&args.old, profile)?;
+            let (new, new_from) = args.load(&args.new, profile)?;
+            let from_date = args
+                .from_date
+                .or(old_from.max(new_from))
+                .unwrap_or(NaiveDate::MIN);
+            let diff = moneymoney::diff::Diff::between(&old, &new, from_date);
+            match args.format {
+                OutputFormat::Json => write_json_pretty_stdout(&diff)?,
+                OutputFormat::Table => {
+                    if diff.is_empty() {
+                        println!("no changes");
+                    }
+                    if let Some(d) = diff.transactions.as_ref().filter(|d| !d.is_empty()) {
+                        println!("Transactions");
+                        write_table(&table::transactions_diff_table(d), None)?;
+                    }
+                    if let Some(d) = diff.balances.as_ref().filter(|d| !d.is_empty()) {
+                        println!("Balances");
+                        write_table(&table::balances_diff_table(d), None)?;
+                    }
+                    if let Some(d) = diff.categories.as_ref().filter(|d| !d.is_empty()) {
+                        println!("Categories");
+                        write_table(&table::categories_diff_table(d), None)?;
+                    }
+                    if let Some(d) = diff.portfolio.as_ref().filter(|d| !d.is_empty()) {
+                        println!("Portfolio");
+                        write_table(&table::portfolio_diff_table(d), None)?;
+                    }
+                }
+                format => return Err(unsupported_format(format, "diff")),
+            }
+        }
+        Cmd::Watch(args) => {
+            let cursor = args
+                .cursor
+                .or_else(config::default_cursor_path)
+                .ok_or("pass --cursor (neither XDG_STATE_HOME nor HOME is set)")?;
+            let mut watcher = Watcher::new(Cursor::load(&cursor)?)
+                .window_days(args.window)
+                .emit_existing(args.emit_existing);
+            let hooks = hooks::Hooks::new(&profile.hooks, args.hook_command.as_deref())?;
+            loop {
+                let before = watcher.cursor().clone();
+                match watch_poll(&cli.input, profile.scope(), &mut watcher) {
+                    Ok(events) => {
+                        let mut out = io::stdout().lock();
+                        let mut failed = None;
+                        for event in &events {
+                            if hooks.is_empty() {
+                                serde_json::to_writer(&mut out, event)?;
+                                writeln!(out)?;
+                            } else if let Err(e) = hooks.dispatch(event) {
+                                // Neither delivered nor dead-lettered: report the
+                                // event again next time; only `--once` gives up.
+                                eprintln!(
+                                    "warning: {} event {} will be retried: {e}",
+                                    event.kind(),
+                                    event.id()
+                                );
+                                watcher.redeliver(event, &before);
+                                failed.get_or_insert(e);
+                            }
+                        }
+                        out.flush()?;
+                        watcher.cursor().save(&cursor)?;
+                        if let Some(e) = failed.filter(|_| args.once) {
+                            return Err(e);
+                        }
+                    }
+                    Err(e) if args.once => return Err(e),
+                    Err(e) => eprintln!("warning: poll failed: {e}"),
+                }
+                if args.once {
+                    break;
+                }
+                std::thread::sleep(args.interval);
+            }
+        }
+        Cmd::Config { target } => match target {
+            ConfigTarget::Show => print!("{}", toml::to_string(profile)?),
+            ConfigTarget::Path => match cli.config.or_else(config::default_path) {
+                Some(path) => println!("{}", path.display()),
+                None => return Err("neither XDG_CONFIG_HOME nor HOME is set".into()),
+            },
+        },
+        Cmd::Completions { shell } => {
+            completion::write_registration(shell, &mut io::stdout().lock())?
+        }
+        Cmd::Man { dir: Some(dir) } => {
+            std::fs::create_dir_all(&dir)?;
+            clap_mangen::generate_to(Cli::command(), &dir)?;
+        }
+        Cmd::Man { dir: None } => clap_mangen::Man::new(Cli::command()).render(&mut io::stdout())?,
+        Cmd::Sync { target } => match target {
+            SyncTarget::Sqlite(args) => {
+                use moneymoney::sync::sqlite::SqliteMirror;
+
+                let mut mirror = SqliteMirror::open(&args.path)?;
+                let now = chrono::Utc::now();
+                let from_date = match args.from_date {
+                    Some(date) => date,
+                    None => mirror.incremental_from_date(now.date_naive())?,
+                };
+                let data = source.sync_data(from_date)?;
+                let run = mirror.sync(&data, now)?;
+                write_json_pretty_stdout(&run)?;
             }
         },
         #[cfg(feature = "experimental")]
@@ -375,11 +2583,64 @@ fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 use moneymoney::create_bank_transfer::CreateBankTransferParams;
 
                 let raw = read_json_input(args.file)?;
+                if args.pain001 {
+                    let transfers: Vec<CreateBankTransferParams> = parse_one_or_many(raw.trim())
+                        .map_err(|e| format!("invalid JSON for bank transfer: {e}"))?;
+                    let accounts = source.accounts()?;
+                    moneymoney::formats::pain001::write_pain001(
+                        io::stdout().lock(),
+                        &accounts,
+                        &transfers,
+                        chrono::Utc::now(),
+                    )?;
+                    return Ok(());
+                }
+                source.require_live("create bank-transfer")?;
                 let params: CreateBankTransferParams = serde_json::from_str(raw.trim())
                     .map_err(|e| format!("invalid JSON for bank transfer: {e}"))?;
                 let result = moneymoney::create_bank_transfer(params)?;
                 write_json_pretty_stdout(&result)?;
             }
+            CreateTarget::DirectDebit(args) => {
+                use moneymoney::formats::pain008;
+
+                let debits = args.debits()?;
+                if args.pain008 {
+                    let creditor_id = args
+                        .creditor_id
+                        .as_deref()
+                        .ok_or("--pain008 requires --creditor-id")?;
+                    let accounts = source.accounts()?;
+                    pain008::write_pain008(
+                        io::stdout().lock(),
+                        &accounts,
+                        &debits,
+                        creditor_id,
+                        chrono::Utc::now(),
+                    )?;
+                    return Ok(());
+                }
+                source.require_live("create direct-debit")?;
+                if debits.is_empty() {
+                    return Err("no direct debits in the input".into());
+                }
+                for (index, debit) in debits.iter().enumerate() {
+                    pain008::validate_direct_debit(index, debit)?;
+                }
+                let total = debits.len();
+                let mut results = Vec::with_capacity(total);
+                for (index, debit) in debits.into_iter().enumerate() {
+                    let result = moneymoney::create_direct_debit(debit).map_err(|e| {
+                        format!("direct debit {index} failed after {index} of {total} were created: {e}")
+                    })?;
+                    results.push(result);
+                }
+                if results.len() == 1 {
+                    write_json_pretty_stdout(&results[0])?;
+                } else {
+                    write_json_pretty_stdout(&results)?;
+                }
+            }
         },
     }
     Ok(())
@@ -0,0 +1,486 @@
+//! `--fields` and `--template`: shape export items into exactly the columns
+//! or lines a script needs.
+//!
+//! Both work on the items as they are serialized for `--format json`
+//! (camelCase keys), so every export supports them the same way. So does
+//! `--format csv`, which writes the `--fields` (by default every field) as
+//! CSV rows.
+
+use std::io::Write;
+use std::ops::ControlFlow;
+
+use minijinja::{Environment, UndefinedBehavior};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use crate::table::{self, RenderOptions};
+use crate::OutputFormat;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Name of the single template in an [`OutputTemplate`]'s environment.
+const TEMPLATE_NAME: &str = "line";
+
+/// `--fields`: a comma-separated list of field paths like `balance.amount`.
+#[derive(Clone, Debug)]
+pub(crate) struct Fields(Vec<FieldPath>);
+
+#[derive(Clone, Debug)]
+struct FieldPath {
+    /// As given on the command line; used as the output key.
+    name: String,
+    segments: Vec<String>,
+}
+
+impl std::str::FromStr for Fields {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut paths = Vec::new();
+        for name in s.split(',').map(str::trim) {
+            let segments: Vec<String> = name.split('.').map(str::to_string).collect();
+            if segments.iter().any(String::is_empty) {
+                return Err(format!(
+                    "invalid field {name:?}: expected names like `balance.amount`"
+                ));
+            }
+            paths.push(FieldPath {
+                name: name.to_string(),
+                segments,
+            });
+        }
+        Ok(Self(paths))
+    }
+}
+
+/// One projected item: the values of [`Fields`], in their order.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Row(Vec<(String, Value)>);
+
+impl Row {
+    pub(crate) fn values(&self) -> impl Iterator<Item = &Value> {
+        self.0.iter().map(|(_, value)| value)
+    }
+}
+
+impl Serialize for Row {
+    /// An object with the keys in field order.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in &self.0 {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl Fields {
+    /// The field names, in the order given.
+    pub(crate) fn names(&self) -> Vec<&str> {
+        self.0.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    /// Every leaf of `item`, with nested objects flattened to dotted paths.
+    pub(crate) fn of(item: &Value) -> Self {
+        fn walk(value: &Value, prefix: &mut Vec<String>, paths: &mut Vec<FieldPath>) {
+            match value {
+                Value::Object(obj) if !obj.is_empty() => {
+                    for (key, value) in obj {
+                        prefix.push(key.clone());
+                        walk(value, prefix, paths);
+                        prefix.pop();
+                    }
+                }
+                _ if prefix.is_empty() => {}
+                _ => paths.push(FieldPath {
+                    name: prefix.join("."),
+                    segments: prefix.clone(),
+                }),
+            }
+        }
+        let mut paths = Vec::new();
+        walk(item, &mut Vec::new(), &mut paths);
+        Self(paths)
+    }
+
+    /// Pick the fields out of a serialized item, keyed by their names.
+    ///
+    /// Keys match exactly or ignoring case, `-` and `_`, so `booking_date`
+    /// finds `bookingDate`. A top-level field the item doesn't have is an
+    /// error; a nested one under a missing or `null` parent is `null`.
+    pub(crate) fn project(&self, item: &Value) -> Result<Row, BoxError> {
+        let mut projected = Vec::new();
+        for path in &self.0 {
+            let (first, rest) = path.segments.split_first().expect("paths are non-empty");
+            let Some(mut value) = lookup(item, first) else {
+                let known: Vec<&str> = item
+                    .as_object()
+                    .map(|obj| obj.keys().map(String::as_str).collect())
+                    .unwrap_or_default();
+                return Err(format!(
+                    "unknown field `{}`; available: {}",
+                    path.name,
+                    known.join(", ")
+                )
+                .into());
+            };
+            for segment in rest {
+                value = lookup(value, segment).unwrap_or(&Value::Null);
+            }
+            projected.push((path.name.clone(), value.clone()));
+        }
+        Ok(Row(projected))
+    }
+}
+
+/// `value[key]` for objects (see [`Fields::project`] for key matching) or
+/// `value[index]` for arrays.
+fn lookup<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(obj) => obj.get(key).or_else(|| {
+            let wanted = normalize(key);
+            obj.iter()
+                .find(|(k, _)| normalize(k) == wanted)
+                .map(|(_, v)| v)
+        }),
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    }
+}
+
+fn normalize(key: &str) -> String {
+    key.chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// `--template`: a MiniJinja template rendered once per item.
+#[derive(Clone)]
+pub(crate) struct OutputTemplate(Environment<'static>);
+
+impl std::fmt::Debug for OutputTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("OutputTemplate").finish_non_exhaustive()
+    }
+}
+
+impl std::str::FromStr for OutputTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut env = Environment::new();
+        // Optional fields are often missing or null; print them as nothing
+        // rather than failing or writing `none`.
+        env.set_undefined_behavior(UndefinedBehavior::Chainable);
+        env.set_formatter(|out, state, value| {
+            let value = if value.is_none() {
+                &minijinja::Value::UNDEFINED
+            } else {
+                value
+            };
+            minijinja::escape_formatter(out, state, value)
+        });
+        env.add_template_owned(TEMPLATE_NAME, s.to_string())
+            .map_err(|e| e.to_string())?;
+        Ok(Self(env))
+    }
+}
+
+impl OutputTemplate {
+    /// Render the template for one serialized item, without a trailing newline.
+    pub(crate) fn render(&self, item: &Value) -> Result<String, BoxError> {
+        let template = self.0.get_template(TEMPLATE_NAME)?;
+        Ok(template.render(item)?)
+    }
+}
+
+/// How `--fields`, `--template` or `--format csv` shape the output.
+pub(crate) enum Shape {
+    /// `--fields`.
+    Fields(Fields),
+    /// Every field of the first item, for `--format csv` without `--fields`.
+    AllFields,
+    /// `--template`.
+    Template(OutputTemplate),
+}
+
+/// The CSV dialect for `--format csv`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct CsvOptions {
+    pub(crate) delimiter: char,
+    /// Replaces the `.` in fractional numbers, e.g. `,` for German spreadsheets.
+    pub(crate) decimal_separator: char,
+    /// Whether the first line names the fields.
+    pub(crate) header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            decimal_separator: '.',
+            header: true,
+        }
+    }
+}
+
+impl CsvOptions {
+    fn write_record<'a>(
+        &self,
+        out: &mut dyn Write,
+        cells: impl Iterator<Item = std::borrow::Cow<'a, str>>,
+    ) -> std::io::Result<()> {
+        let mut line = String::new();
+        for (i, cell) in cells.enumerate() {
+            if i > 0 {
+                line.push(self.delimiter);
+            }
+            if cell.contains([self.delimiter, '"', '\n', '\r']) {
+                line.push('"');
+                line.push_str(&cell.replace('"', "\"\""));
+                line.push('"');
+            } else {
+                line.push_str(&cell);
+            }
+        }
+        line.push_str("\r\n");
+        out.write_all(line.as_bytes())
+    }
+
+    fn cell(&self, value: &Value) -> String {
+        match value {
+            Value::Null => String::new(),
+            Value::String(s) => s.clone(),
+            Value::Number(n) if n.is_f64() => n
+                .to_string()
+                .replace('.', &self.decimal_separator.to_string()),
+            Value::Number(n) => n.to_string(),
+            v => v.to_string(),
+        }
+    }
+}
+
+/// Writes shaped items as they are pushed, like `NdjsonSink`; JSON arrays and
+/// tables are buffered until [`ShapedSink::finish`].
+pub(crate) struct ShapedSink {
+    shape: Shape,
+    format: OutputFormat,
+    out: Box<dyn Write + Send>,
+    render: RenderOptions,
+    csv: CsvOptions,
+    strip_icons: bool,
+    /// The fields for [`Shape::AllFields`], from the first item.
+    all_fields: Option<Fields>,
+    /// Items pushed so far.
+    count: usize,
+    rows: Vec<Row>,
+    error: Option<BoxError>,
+}
+
+impl ShapedSink {
+    /// Fails if `--fields` is combined with a format other than json,
+    /// ndjson, table or csv.
+    pub(crate) fn new(
+        shape: Shape,
+        format: OutputFormat,
+        out: Box<dyn Write + Send>,
+        render: RenderOptions,
+        csv: CsvOptions,
+        strip_icons: bool,
+    ) -> Result<Self, BoxError> {
+        if matches!(shape, Shape::Fields(_))
+            && !matches!(
+                format,
+                OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Table | OutputFormat::Csv
+            )
+        {
+            return Err(format!(
+                "--fields works with --format json, ndjson, table or csv, not {}",
+                format.name()
+            )
+            .into());
+        }
+        Ok(Self {
+            shape,
+            format,
+            out,
+            render,
+            csv,
+            strip_icons,
+            all_fields: None,
+            count: 0,
+            rows: Vec::new(),
+            error: None,
+        })
+    }
+
+    pub(crate) fn push<T: Serialize>(&mut self, item: &T) -> ControlFlow<()> {
+        match self.write_item(item) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(e) => {
+                self.error = Some(e);
+                ControlFlow::Break(())
+            }
+        }
+    }
+
+    fn write_item<T: Serialize>(&mut self, item: &T) -> Result<(), BoxError> {
+        let mut value = serde_json::to_value(item)?;
+        if self.strip_icons {
+            if let Some(obj) = value.as_object_mut() {
+                obj.remove("icon");
+            }
+        }
+        let first = self.count == 0;
+        self.count += 1;
+        let fields = match &self.shape {
+            Shape::Template(template) => {
+                let line = template.render(&value)?;
+                writeln!(self.out, "{line}")?;
+                return Ok(());
+            }
+            Shape::Fields(fields) => fields,
+            Shape::AllFields => self.all_fields.get_or_insert_with(|| Fields::of(&value)),
+        };
+        let projected = fields.project(&value)?;
+        match self.format {
+            OutputFormat::Ndjson => {
+                serde_json::to_writer(&mut self.out, &projected)?;
+                self.out.write_all(b"\n")?;
+            }
+            OutputFormat::Csv => {
+                if first && self.csv.header {
+                    let names = fields.names().into_iter().map(Into::into);
+                    self.csv.write_record(&mut self.out, names)?;
+                }
+                let cells = projected.values().map(|v| self.csv.cell(v).into());
+                self.csv.write_record(&mut self.out, cells)?;
+            }
+            _ => self.rows.push(projected),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<(), BoxError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        let fields = match &self.shape {
+            Shape::Fields(fields) => Some(fields),
+            Shape::AllFields => self.all_fields.as_ref(),
+            Shape::Template(_) => None,
+        };
+        if let Some(fields) = fields {
+            match self.format {
+                OutputFormat::Json => {
+                    serde_json::to_writer_pretty(&mut self.out, &self.rows)?;
+                    self.out.write_all(b"\n")?;
+                }
+                OutputFormat::Table => {
+                    table::fields_table(&fields.names(), &self.rows)
+                        .render(&mut self.out, self.render)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(self.out.flush()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn projects_nested_and_normalized_fields() {
+        let fields: Fields = "name, balance.amount,account_number,tags.1,balance.missing"
+            .parse()
+            .unwrap();
+        let item = json!({
+            "name": "Girokonto",
+            "accountNumber": "0532013000",
+            "balance": { "amount": 12.5, "currency": "EUR" },
+            "tags": ["a", "b"],
+        });
+        let projected = fields.project(&item).unwrap();
+        assert_eq!(
+            serde_json::to_string(&projected).unwrap(),
+            r#"{"name":"Girokonto","balance.amount":12.5,"account_number":"0532013000","tags.1":"b","balance.missing":null}"#
+        );
+        assert_eq!(
+            serde_json::to_value(&projected).unwrap(),
+            json!({
+                "name": "Girokonto",
+                "balance.amount": 12.5,
+                "account_number": "0532013000",
+                "tags.1": "b",
+                "balance.missing": null,
+            })
+        );
+        assert_eq!(
+            fields.names(),
+            [
+                "name",
+                "balance.amount",
+                "account_number",
+                "tags.1",
+                "balance.missing"
+            ]
+        );
+
+        let null_balance = json!({ "name": "x", "accountNumber": "", "balance": null, "tags": [] });
+        let projected = fields.project(&null_balance).unwrap();
+        assert_eq!(projected.values().nth(1), Some(&Value::Null));
+
+        let err = "nmae"
+            .parse::<Fields>()
+            .unwrap()
+            .project(&item)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("unknown field `nmae`; available: "),
+            "{err}"
+        );
+        assert!("name,,amount".parse::<Fields>().is_err());
+        assert!("balance.".parse::<Fields>().is_err());
+    }
+
+    #[test]
+    fn renders_templates() {
+        let template: OutputTemplate = "{{ name }};{{ amount }};{{ comment }};{{ balance.amount }}"
+            .parse()
+            .unwrap();
+        let item = json!({ "name": "REWE", "amount": -12.5, "comment": null });
+        assert_eq!(template.render(&item).unwrap(), "REWE;-12.5;;");
+
+        let template: OutputTemplate = "{{ bookingDate[:10] }} {{ '%.2f'|format(amount) }}"
+            .parse()
+            .unwrap();
+        let item = json!({ "bookingDate": "2024-03-01T12:00:00Z", "amount": -12.5 });
+        assert_eq!(template.render(&item).unwrap(), "2024-03-01 -12.50");
+
+        assert!("{{ name ".parse::<OutputTemplate>().is_err());
+    }
+
+    #[test]
+    fn fields_need_a_structured_format() {
+        let shape = Shape::Fields("name".parse().unwrap());
+        let render = RenderOptions {
+            width: None,
+            color: false,
+        };
+        let out = Box::new(Vec::new());
+        let csv = CsvOptions::default();
+        let err = ShapedSink::new(shape, OutputFormat::Ofx, out, render, csv, true)
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "--fields works with --format json, ndjson, table or csv, not ofx"
+        );
+    }
+}
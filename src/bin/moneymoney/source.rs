@@ -0,0 +1,406 @@
+//! Where exports come from: MoneyMoney, or the saved files passed with
+//! `--input`.
+//!
+//! Every read goes through [`Source`] so filters, formats and conversions
+//! work the same on a snapshot as on the live app. Writes always need
+//! MoneyMoney; [`Source::require_live`] rejects them when `--input` is set.
+//!
+//! A [`Scope`] from the config profile limits reads to some accounts and
+//! masks sensitive fields.
+
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use moneymoney::export_accounts::MoneymoneyAccount;
+use moneymoney::export_categories::MoneymoneyCategory;
+use moneymoney::export_portfolio::{ExportPortfolioParams, ExportPortfolioResponse, Security};
+use moneymoney::export_transactions::{
+    ExportTransactionsParams, TransactionsEvent, TransactionsResponse,
+};
+use moneymoney::offline::{SavedExport, SavedExports};
+use moneymoney::snapshot::{Snapshot, SnapshotError, SnapshotInfo};
+use moneymoney::sync::SyncData;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+pub(crate) struct Source {
+    backend: Backend,
+    scope: Scope,
+}
+
+enum Backend {
+    /// Ask the running MoneyMoney app.
+    Live,
+    /// Answer from saved exports.
+    Saved(SavedExports),
+}
+
+/// Which accounts reads cover and which fields they mask.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Scope {
+    /// Only these accounts or groups (name, IBAN, account number or UUID);
+    /// all if empty.
+    pub(crate) include: Vec<String>,
+    /// Never these accounts or groups.
+    pub(crate) exclude: Vec<String>,
+    /// Keys of the JSON output whose values are masked, e.g. `iban`.
+    pub(crate) redact: Vec<String>,
+}
+
+impl Scope {
+    fn limits_accounts(&self) -> bool {
+        !self.include.is_empty() || !self.exclude.is_empty()
+    }
+
+    /// UUIDs of the accounts in scope, out of all `accounts`.
+    fn account_uuids(&self, accounts: &[MoneymoneyAccount]) -> HashSet<uuid::Uuid> {
+        let included = if self.include.is_empty() {
+            accounts.iter().map(|a| a.uuid).collect()
+        } else {
+            selected(accounts, &self.include)
+        };
+        let excluded = selected(accounts, &self.exclude);
+        included.difference(&excluded).copied().collect()
+    }
+
+    /// Mask the [`Scope::redact`] fields of `item`, at any depth: text keeps
+    /// its last four characters, numbers become 0.
+    fn redact<T: Serialize + DeserializeOwned>(&self, item: T) -> Result<T, BoxError> {
+        if self.redact.is_empty() {
+            return Ok(item);
+        }
+        let mut value = serde_json::to_value(item)?;
+        let keys: Vec<String> = self.redact.iter().map(|k| normalize(k)).collect();
+        redact_value(&mut value, &keys);
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+/// UUIDs of the accounts `idents` name, with every account below a named group.
+fn selected(accounts: &[MoneymoneyAccount], idents: &[String]) -> HashSet<uuid::Uuid> {
+    let mut uuids = HashSet::new();
+    for (i, account) in accounts.iter().enumerate() {
+        if !idents.iter().any(|ident| account.matches(ident)) {
+            continue;
+        }
+        uuids.insert(account.uuid);
+        if account.group {
+            uuids.extend(
+                accounts[i + 1..]
+                    .iter()
+                    .take_while(|a| a.indentation > account.indentation)
+                    .map(|a| a.uuid),
+            );
+        }
+    }
+    uuids
+}
+
+fn normalize(key: &str) -> String {
+    key.chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn redact_value(value: &mut serde_json::Value, keys: &[String]) {
+    use serde_json::Value;
+
+    match value {
+        Value::Object(obj) => {
+            for (key, value) in obj.iter_mut() {
+                if keys.contains(&normalize(key)) {
+                    match value {
+                        Value::String(s) => *s = mask(s),
+                        Value::Number(_) => *value = Value::from(0),
+                        _ => {}
+                    }
+                } else {
+                    redact_value(value, keys);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| redact_value(item, keys)),
+        _ => {}
+    }
+}
+
+/// `DE89370400440532013000` → `******************3000`.
+fn mask(s: &str) -> String {
+    let len = s.chars().count();
+    let keep = if len > 4 { 4 } else { 0 };
+    s.chars()
+        .enumerate()
+        .map(|(i, c)| if i < len - keep { '*' } else { c })
+        .collect()
+}
+
+/// Add the exports in `input`, an export file or snapshot manifest, to
+/// `saved`. Returns the snapshot's manifest if it is one.
+pub(crate) fn read_input(
+    input: &Path,
+    saved: &mut SavedExports,
+) -> Result<Option<SnapshotInfo>, BoxError> {
+    match Snapshot::load(input) {
+        Ok(snapshot) => {
+            let info = snapshot.info.clone();
+            for export in snapshot.into_exports() {
+                saved.insert(export)?;
+            }
+            Ok(Some(info))
+        }
+        Err(SnapshotError::NotASnapshot(_)) => {
+            saved.insert(SavedExport::read(input)?)?;
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl Source {
+    /// MoneyMoney if `inputs` is empty, else the exports saved in `inputs`:
+    /// export files or snapshot manifests.
+    pub(crate) fn new(inputs: &[PathBuf]) -> Result<Self, BoxError> {
+        let backend = if inputs.is_empty() {
+            Backend::Live
+        } else {
+            let mut saved = SavedExports::new();
+            for input in inputs {
+                read_input(input, &mut saved)?;
+            }
+            Backend::Saved(saved)
+        };
+        Ok(Source {
+            backend,
+            scope: Scope::default(),
+        })
+    }
+
+    /// Limit reads to `scope`.
+    pub(crate) fn with_scope(self, scope: Scope) -> Self {
+        Source { scope, ..self }
+    }
+
+    /// Fail if `command` would write to MoneyMoney while reading saved exports.
+    pub(crate) fn require_live(&self, command: &str) -> Result<(), BoxError> {
+        match self.backend {
+            Backend::Live => Ok(()),
+            Backend::Saved(_) => {
+                Err(format!("`{command}` writes to MoneyMoney and can't be used with --input")
+                    .into())
+            }
+        }
+    }
+
+    /// All accounts, regardless of the scope.
+    fn all_accounts(&self) -> Result<Vec<MoneymoneyAccount>, BoxError> {
+        match &self.backend {
+            Backend::Live => Ok(moneymoney::export_accounts()?),
+            Backend::Saved(saved) => Ok(saved.accounts()?.to_vec()),
+        }
+    }
+
+    /// UUIDs of the accounts in scope, or `None` if the scope has all.
+    fn scoped_uuids(&self) -> Result<Option<HashSet<uuid::Uuid>>, BoxError> {
+        if !self.scope.limits_accounts() {
+            return Ok(None);
+        }
+        Ok(Some(self.scope.account_uuids(&self.all_accounts()?)))
+    }
+
+    pub(crate) fn accounts(&self) -> Result<Vec<MoneymoneyAccount>, BoxError> {
+        let mut accounts = self.all_accounts()?;
+        if self.scope.limits_accounts() {
+            let uuids = self.scope.account_uuids(&accounts);
+            accounts.retain(|a| uuids.contains(&a.uuid));
+        }
+        accounts.into_iter().map(|a| self.scope.redact(a)).collect()
+    }
+
+    pub(crate) fn categories(&self) -> Result<Vec<MoneymoneyCategory>, BoxError> {
+        let categories = match &self.backend {
+            Backend::Live => moneymoney::export_categories()?,
+            Backend::Saved(saved) => saved.categories()?.to_vec(),
+        };
+        categories
+            .into_iter()
+            .map(|c| self.scope.redact(c))
+            .collect()
+    }
+
+    pub(crate) fn transactions(
+        &self,
+        params: ExportTransactionsParams,
+    ) -> Result<TransactionsResponse, BoxError> {
+        let uuids = self.scoped_uuids()?;
+        let mut response = match &self.backend {
+            Backend::Live => moneymoney::export_transactions(params)?,
+            Backend::Saved(saved) => saved.transactions(&params)?,
+        };
+        if let Some(uuids) = uuids {
+            response
+                .transactions
+                .retain(|t| uuids.contains(&t.account_uuid));
+        }
+        response.transactions = std::mem::take(&mut response.transactions)
+            .into_iter()
+            .map(|t| self.scope.redact(t))
+            .collect::<Result<_, _>>()?;
+        Ok(response)
+    }
+
+    pub(crate) fn portfolio(
+        &self,
+        params: ExportPortfolioParams,
+    ) -> Result<ExportPortfolioResponse, BoxError> {
+        let uuids = self.scoped_uuids()?;
+        let mut response = match &self.backend {
+            Backend::Live => moneymoney::export_portfolio(params)?,
+            Backend::Saved(saved) => saved.portfolio(&params)?,
+        };
+        if let Some(uuids) = uuids {
+            response
+                .securities
+                .retain(|s| uuids.contains(&s.account_uuid));
+        }
+        response.securities = std::mem::take(&mut response.securities)
+            .into_iter()
+            .map(|s| self.scope.redact(s))
+            .collect::<Result<_, _>>()?;
+        Ok(response)
+    }
+
+    /// Whether reads go straight to MoneyMoney, so they can be streamed.
+    fn streams(&self) -> bool {
+        matches!(self.backend, Backend::Live)
+            && !self.scope.limits_accounts()
+            && self.scope.redact.is_empty()
+    }
+
+    /// Like [`Source::accounts`], one at a time; streamed when live.
+    pub(crate) fn accounts_each<F>(&self, mut f: F) -> Result<(), BoxError>
+    where
+        F: FnMut(MoneymoneyAccount) -> ControlFlow<()>,
+    {
+        if self.streams() {
+            return Ok(moneymoney::export_accounts::export_accounts_each(f)?);
+        }
+        for account in self.accounts()? {
+            if f(account).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`Source::categories`], one at a time; streamed when live.
+    pub(crate) fn categories_each<F>(&self, mut f: F) -> Result<(), BoxError>
+    where
+        F: FnMut(MoneymoneyCategory) -> ControlFlow<()>,
+    {
+        if self.streams() {
+            return Ok(moneymoney::export_categories::export_categories_each(f)?);
+        }
+        for category in self.categories()? {
+            if f(category).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`Source::transactions`], one event at a time; streamed when live.
+    pub(crate) fn transactions_each<F>(
+        &self,
+        params: ExportTransactionsParams,
+        mut f: F,
+    ) -> Result<(), BoxError>
+    where
+        F: FnMut(TransactionsEvent) -> ControlFlow<()>,
+    {
+        if self.streams() {
+            return Ok(moneymoney::export_transactions::export_transactions_each(params, f)?);
+        }
+        let response = self.transactions(params)?;
+        if f(TransactionsEvent::Creator(response.creator)).is_break() {
+            return Ok(());
+        }
+        for transaction in response.transactions {
+            if f(TransactionsEvent::Transaction(Box::new(transaction))).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`Source::portfolio`], one security at a time; streamed when live.
+    pub(crate) fn portfolio_each<F>(
+        &self,
+        params: ExportPortfolioParams,
+        mut f: F,
+    ) -> Result<(), BoxError>
+    where
+        F: FnMut(Security) -> ControlFlow<()>,
+    {
+        if self.streams() {
+            return Ok(moneymoney::export_portfolio::export_portfolio_each(params, f)?);
+        }
+        for security in self.portfolio(params)?.securities {
+            if f(security).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Everything `sync` mirrors, see [`SyncData::fetch`].
+    pub(crate) fn sync_data(&self, from_date: NaiveDate) -> Result<SyncData, BoxError> {
+        if self.streams() {
+            return Ok(SyncData::fetch(from_date)?);
+        }
+        Ok(SyncData {
+            from_date,
+            accounts: self.accounts()?,
+            categories: self.categories()?,
+            transactions: self
+                .transactions(ExportTransactionsParams::new(from_date))?
+                .transactions,
+            securities: self.portfolio(ExportPortfolioParams::new())?.securities,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_all_but_the_last_four_characters() {
+        assert_eq!(mask("DE89370400440532013000"), "******************3000");
+        assert_eq!(mask("Müller"), "**ller");
+        assert_eq!(mask("1234"), "****");
+        assert_eq!(mask(""), "");
+
+        let mut value = serde_json::json!({
+            "name": "Girokonto",
+            "IBAN": "DE89370400440532013000",
+            "balance": { "amount": 12.5 },
+            "nested": [{ "account_number": "0532013000" }],
+        });
+        let keys = ["iban", "accountnumber", "amount"].map(String::from);
+        redact_value(&mut value, &keys);
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "name": "Girokonto",
+                "IBAN": "******************3000",
+                "balance": { "amount": 0 },
+                "nested": [{ "account_number": "******3000" }],
+            })
+        );
+    }
+}
@@ -0,0 +1,1080 @@
+//! `--format table`: aligned, colored tables for reading exports in a terminal.
+//!
+//! Tables are built from the typed export results and rendered in one go.
+//! Text columns shrink (with a trailing `…`) when the terminal is too narrow;
+//! amounts are never truncated. Colors are only used when writing to a
+//! terminal and `NO_COLOR` is unset.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use moneymoney::classify::Evaluation;
+use moneymoney::diff::{
+    BalanceChange, CategoriesDiff, FieldChange, PositionChange, TransactionsDiff,
+};
+use moneymoney::export_accounts::MoneymoneyAccount;
+use moneymoney::export_categories::MoneymoneyCategory;
+use moneymoney::export_portfolio::Security;
+use moneymoney::export_transactions::MoneymoneyTransaction;
+use moneymoney::rules::Assignment;
+use moneymoney::set_transaction::SetTransactionParams;
+use serde_json::Value;
+
+use crate::categorize::SuggestionRow;
+use crate::projection::Row;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Gap between columns.
+const SEPARATOR: &str = "  ";
+/// Text columns are not shrunk below this width.
+const MIN_FLEX_WIDTH: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Style {
+    Plain,
+    Bold,
+    Dim,
+    Positive,
+    Negative,
+}
+
+impl Style {
+    /// Green for positive, red for negative, plain for zero.
+    fn signed(value: f64) -> Self {
+        if value > 0.0 {
+            Style::Positive
+        } else if value < 0.0 {
+            Style::Negative
+        } else {
+            Style::Plain
+        }
+    }
+
+    fn ansi(self) -> Option<&'static str> {
+        match self {
+            Style::Plain => None,
+            Style::Bold => Some("\x1b[1m"),
+            Style::Dim => Some("\x1b[2m"),
+            Style::Positive => Some("\x1b[32m"),
+            Style::Negative => Some("\x1b[31m"),
+        }
+    }
+}
+
+struct Cell {
+    text: String,
+    style: Style,
+}
+
+impl Cell {
+    fn plain(text: impl Into<String>) -> Self {
+        Self::styled(text, Style::Plain)
+    }
+
+    fn styled(text: impl Into<String>, style: Style) -> Self {
+        Self {
+            text: text.into(),
+            style,
+        }
+    }
+
+    fn empty() -> Self {
+        Self::plain("")
+    }
+}
+
+struct Column {
+    header: Cow<'static, str>,
+    align: Align,
+    /// Whether the column may be truncated to fit the terminal.
+    flex: bool,
+}
+
+const fn text(header: &'static str) -> Column {
+    Column {
+        header: Cow::Borrowed(header),
+        align: Align::Left,
+        flex: true,
+    }
+}
+
+const fn fixed(header: &'static str) -> Column {
+    Column {
+        header: Cow::Borrowed(header),
+        align: Align::Left,
+        flex: false,
+    }
+}
+
+const fn number(header: &'static str) -> Column {
+    Column {
+        header: Cow::Borrowed(header),
+        align: Align::Right,
+        flex: false,
+    }
+}
+
+/// A table with a header, body rows and optional footer rows.
+pub(crate) struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<Cell>>,
+    footer: Vec<Vec<Cell>>,
+}
+
+/// How a table is rendered.
+#[derive(Clone, Copy)]
+pub(crate) struct RenderOptions {
+    /// Total width to fit into; `None` never truncates.
+    pub(crate) width: Option<usize>,
+    pub(crate) color: bool,
+}
+
+impl RenderOptions {
+    /// Fit and color when writing to the terminal, plain text otherwise.
+    pub(crate) fn detect(to_terminal: bool) -> Self {
+        if !to_terminal {
+            return Self {
+                width: None,
+                color: false,
+            };
+        }
+        Self {
+            width: terminal_size::terminal_size().map(|(w, _)| usize::from(w.0)),
+            color: std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        }
+    }
+}
+
+impl Table {
+    fn new(columns: Vec<Column>) -> Self {
+        Self {
+            columns,
+            rows: Vec::new(),
+            footer: Vec::new(),
+        }
+    }
+
+    /// Write the table, followed by a newline.
+    pub(crate) fn render<W: Write>(&self, mut w: W, options: RenderOptions) -> io::Result<()> {
+        let widths = self.widths(options.width);
+        let header: Vec<Cell> = self
+            .columns
+            .iter()
+            .map(|c| Cell::styled(c.header.clone(), Style::Bold))
+            .collect();
+        self.write_row(&mut w, &header, &widths, options.color)?;
+        self.write_rule(&mut w, &widths)?;
+        for row in &self.rows {
+            self.write_row(&mut w, row, &widths, options.color)?;
+        }
+        if !self.footer.is_empty() {
+            self.write_rule(&mut w, &widths)?;
+            for row in &self.footer {
+                self.write_row(&mut w, row, &widths, options.color)?;
+            }
+        }
+        w.flush()
+    }
+
+    /// Natural column widths, with text columns shrunk to fit `max_width`.
+    fn widths(&self, max_width: Option<usize>) -> Vec<usize> {
+        let mut widths: Vec<usize> = self.columns.iter().map(|c| c.header.width()).collect();
+        for row in self.rows.iter().chain(&self.footer) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.text.width());
+            }
+        }
+        let Some(max_width) = max_width else {
+            return widths;
+        };
+        let total = |widths: &[usize]| {
+            widths.iter().sum::<usize>() + SEPARATOR.len() * widths.len().saturating_sub(1)
+        };
+        while total(&widths) > max_width {
+            // Take from the widest shrinkable column first.
+            let Some((index, _)) = widths
+                .iter()
+                .enumerate()
+                .filter(|(i, w)| self.columns[*i].flex && **w > MIN_FLEX_WIDTH)
+                .max_by_key(|(_, w)| **w)
+            else {
+                break;
+            };
+            widths[index] -= 1;
+        }
+        widths
+    }
+
+    fn write_row<W: Write>(
+        &self,
+        w: &mut W,
+        row: &[Cell],
+        widths: &[usize],
+        color: bool,
+    ) -> io::Result<()> {
+        let mut line = String::new();
+        for (i, (column, width)) in self.columns.iter().zip(widths).enumerate() {
+            let cell = row.get(i);
+            let text = cell.map_or("", |c| c.text.as_str());
+            let text = truncate(text, *width);
+            let padding = " ".repeat(width - text.width());
+            let last = i + 1 == widths.len();
+            if i > 0 {
+                line.push_str(SEPARATOR);
+            }
+            if column.align == Align::Right {
+                line.push_str(&padding);
+            }
+            match cell.and_then(|c| c.style.ansi()).filter(|_| color) {
+                Some(code) => {
+                    line.push_str(code);
+                    line.push_str(&text);
+                    line.push_str("\x1b[0m");
+                }
+                None => line.push_str(&text),
+            }
+            if column.align == Align::Left && !last {
+                line.push_str(&padding);
+            }
+        }
+        writeln!(w, "{}", line.trim_end())
+    }
+
+    fn write_rule<W: Write>(&self, w: &mut W, widths: &[usize]) -> io::Result<()> {
+        let rule: Vec<String> = widths.iter().map(|width| "─".repeat(*width)).collect();
+        writeln!(w, "{}", rule.join(SEPARATOR))
+    }
+}
+
+/// Cut `text` to `width` display columns, ending in `…` if anything was cut.
+fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push('…');
+    out
+}
+
+/// Two decimals with thousands separators: `-1,234.50`.
+fn format_amount(amount: f64) -> String {
+    let formatted = format!("{:.2}", amount.abs());
+    let (int, frac) = formatted.split_once('.').unwrap_or((&formatted, "00"));
+    let mut grouped = String::new();
+    for (i, digit) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    let sign = if amount < 0.0 && formatted != "0.00" {
+        "-"
+    } else {
+        ""
+    };
+    format!("{sign}{grouped}.{frac}")
+}
+
+/// Indent a tree level by two spaces per step.
+fn indented(name: &str, indentation: u8) -> String {
+    format!("{}{name}", "  ".repeat(usize::from(indentation)))
+}
+
+/// Running sums keyed by currency code, in code order.
+#[derive(Default)]
+struct Totals(BTreeMap<String, f64>);
+
+impl Totals {
+    fn add(&mut self, currency: &str, amount: f64) {
+        *self.0.entry(currency.to_string()).or_default() += amount;
+    }
+}
+
+/// Accounts as an indented tree with right-aligned balances and totals per
+/// currency (group accounts are not counted twice).
+pub(crate) fn accounts_table(accounts: &[MoneymoneyAccount]) -> Table {
+    let mut table = Table::new(vec![
+        text("Account"),
+        text("Number"),
+        number("Balance"),
+        fixed("Currency"),
+    ]);
+    let mut totals = Totals::default();
+    for account in accounts {
+        let name_style = if account.group {
+            Style::Bold
+        } else {
+            Style::Plain
+        };
+        let number = account
+            .iban
+            .clone()
+            .filter(|iban| !iban.is_empty())
+            .unwrap_or_else(|| account.account_number.clone());
+        let (balance, currency) = match &account.balance {
+            Some(balance) => {
+                if !account.group {
+                    totals.add(balance.currency.code(), balance.amount);
+                }
+                (
+                    Cell::styled(format_amount(balance.amount), Style::signed(balance.amount)),
+                    Cell::plain(balance.currency.code()),
+                )
+            }
+            None => (Cell::empty(), Cell::plain(account.currency.as_str())),
+        };
+        table.rows.push(vec![
+            Cell::styled(indented(&account.name, account.indentation), name_style),
+            Cell::plain(number),
+            balance,
+            currency,
+        ]);
+    }
+    for (currency, amount) in totals.0 {
+        table.footer.push(vec![
+            Cell::styled("Total", Style::Bold),
+            Cell::empty(),
+            Cell::styled(format_amount(amount), Style::signed(amount)),
+            Cell::plain(currency),
+        ]);
+    }
+    table
+}
+
+/// Categories as an indented tree with budget and available columns, and
+/// totals per currency and budget period (group categories are not counted
+/// twice).
+pub(crate) fn categories_table(categories: &[MoneymoneyCategory]) -> Table {
+    let mut table = Table::new(vec![
+        text("Category"),
+        number("Budget"),
+        number("Available"),
+        fixed("Period"),
+        fixed("Currency"),
+    ]);
+    let mut totals: BTreeMap<(&str, &str), (f64, f64)> = BTreeMap::new();
+    for category in categories {
+        let name_style = if category.group {
+            Style::Bold
+        } else {
+            Style::Plain
+        };
+        let mut row = vec![Cell::styled(
+            indented(&category.name, category.indentation),
+            name_style,
+        )];
+        match &category.budget {
+            Some(budget) => {
+                let currency = category.currency.code();
+                if !category.group {
+                    let total = totals.entry((currency, &budget.period)).or_default();
+                    total.0 += budget.amount;
+                    total.1 += budget.available;
+                }
+                row.extend([
+                    Cell::plain(format_amount(budget.amount)),
+                    Cell::styled(format_amount(budget.available), Style::signed(budget.available)),
+                    Cell::plain(budget.period.clone()),
+                    Cell::plain(currency),
+                ]);
+            }
+            None => row.extend([Cell::empty(), Cell::empty(), Cell::empty(), Cell::empty()]),
+        }
+        table.rows.push(row);
+    }
+    for ((currency, period), (amount, left)) in totals {
+        table.footer.push(vec![
+            Cell::styled("Total", Style::Bold),
+            Cell::plain(format_amount(amount)),
+            Cell::styled(format_amount(left), Style::signed(left)),
+            Cell::plain(period),
+            Cell::plain(currency),
+        ]);
+    }
+    table
+}
+
+/// Transactions with amounts colored by sign; pending ones are dimmed.
+pub(crate) fn transactions_table(transactions: &[MoneymoneyTransaction]) -> Table {
+    let mut table = Table::new(vec![
+        fixed("Date"),
+        text("Payee"),
+        text("Category"),
+        number("Amount"),
+        fixed("Currency"),
+    ]);
+    let mut totals = Totals::default();
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for transaction in transactions {
+        totals.add(&transaction.currency, transaction.amount);
+        *counts.entry(transaction.currency.as_str()).or_default() += 1;
+        let text_style = if transaction.booked {
+            Style::Plain
+        } else {
+            Style::Dim
+        };
+        table.rows.push(vec![
+            Cell::styled(transaction.booking_date.date_naive().to_string(), text_style),
+            Cell::styled(transaction.name.clone(), text_style),
+            Cell::styled(transaction.category.clone().unwrap_or_default(), text_style),
+            Cell::styled(format_amount(transaction.amount), Style::signed(transaction.amount)),
+            Cell::plain(transaction.currency.clone()),
+        ]);
+    }
+    for (currency, amount) in totals.0 {
+        let count = counts.get(currency.as_str()).copied().unwrap_or_default();
+        table.footer.push(vec![
+            Cell::styled("Total", Style::Bold),
+            Cell::plain(format!("{count} transactions")),
+            Cell::empty(),
+            Cell::styled(format_amount(amount), Style::signed(amount)),
+            Cell::plain(currency),
+        ]);
+    }
+    table
+}
+
+/// Preview of a bulk `set transaction`: each transaction with the fields
+/// that change shown as `old → new`.
+pub(crate) fn changes_table(
+    transactions: &[MoneymoneyTransaction],
+    change: &SetTransactionParams,
+) -> Table {
+    let mut table = Table::new(vec![
+        number("Id"),
+        fixed("Date"),
+        text("Payee"),
+        number("Amount"),
+        text("Category"),
+        text("Comment"),
+        fixed("Checkmark"),
+    ]);
+    let changed = |old: &str, new: Option<&String>| match new {
+        Some(new) if new != old => Cell::styled(format!("{old} → {new}"), Style::Bold),
+        _ => Cell::plain(old),
+    };
+    for transaction in transactions {
+        let checkmark = if transaction.checkmark { "on" } else { "off" };
+        table.rows.push(vec![
+            Cell::plain(transaction.id.to_string()),
+            Cell::plain(transaction.booking_date.date_naive().to_string()),
+            Cell::plain(transaction.name.clone()),
+            Cell::styled(format_amount(transaction.amount), Style::signed(transaction.amount)),
+            changed(
+                transaction.category.as_deref().unwrap_or_default(),
+                change.category_to.as_ref(),
+            ),
+            changed(transaction.comment.as_deref().unwrap_or_default(), change.comment_to.as_ref()),
+            changed(checkmark, change.checkmark_to.as_ref()),
+        ]);
+    }
+    table.footer.push(vec![
+        Cell::empty(),
+        Cell::empty(),
+        Cell::styled(format!("{} transactions", transactions.len()), Style::Bold),
+    ]);
+    table
+}
+
+/// `rules apply` plan: each transaction with the rule that matched and what it gets.
+pub(crate) fn rules_plan_table(plan: &[Assignment<'_>]) -> Table {
+    let mut table = Table::new(vec![
+        number("Id"),
+        fixed("Date"),
+        text("Payee"),
+        number("Amount"),
+        text("Rule"),
+        text("Category"),
+        text("Comment"),
+        fixed("Checkmark"),
+    ]);
+    let new = |value: Option<&String>| match value {
+        Some(value) => Cell::styled(value.clone(), Style::Bold),
+        None => Cell::empty(),
+    };
+    for Assignment {
+        transaction,
+        rule,
+        change,
+    } in plan
+    {
+        table.rows.push(vec![
+            Cell::plain(transaction.id.to_string()),
+            Cell::plain(transaction.booking_date.date_naive().to_string()),
+            Cell::plain(transaction.name.clone()),
+            Cell::styled(format_amount(transaction.amount), Style::signed(transaction.amount)),
+            Cell::plain(rule.name.clone()),
+            new(change.category_to.as_ref()),
+            new(change.comment_to.as_ref()),
+            new(change.checkmark_to.as_ref()),
+        ]);
+    }
+    table.footer.push(vec![
+        Cell::empty(),
+        Cell::empty(),
+        Cell::styled(format!("{} transactions", plan.len()), Style::Bold),
+    ]);
+    table
+}
+
+/// `categorize suggest` output: each transaction with its suggested category.
+pub(crate) fn suggestions_table(rows: &[SuggestionRow<'_>]) -> Table {
+    let mut table = Table::new(vec![
+        number("Id"),
+        fixed("Date"),
+        text("Payee"),
+        number("Amount"),
+        text("Category"),
+        number("Confidence"),
+    ]);
+    for row in rows {
+        table.rows.push(vec![
+            Cell::plain(row.id.to_string()),
+            Cell::plain(row.booking_date.to_string()),
+            Cell::plain(row.name),
+            Cell::styled(format_amount(row.amount), Style::signed(row.amount)),
+            Cell::styled(row.category, Style::Bold),
+            Cell::plain(percent(row.confidence)),
+        ]);
+    }
+    table.footer.push(vec![
+        Cell::empty(),
+        Cell::empty(),
+        Cell::styled(format!("{} suggestions", rows.len()), Style::Bold),
+    ]);
+    table
+}
+
+/// `categorize evaluate` result as one row.
+pub(crate) fn evaluation_table(evaluation: &Evaluation) -> Table {
+    let mut table = Table::new(vec![
+        number("Trained"),
+        number("Tested"),
+        number("Accuracy"),
+        number("Confident"),
+        number("Confident accuracy"),
+    ]);
+    table.rows.push(vec![
+        Cell::plain(evaluation.trained.to_string()),
+        Cell::plain(evaluation.tested.to_string()),
+        Cell::styled(percent(evaluation.accuracy), Style::Bold),
+        Cell::plain(format!("{} (≥ {})", evaluation.confident, percent(evaluation.min_confidence))),
+        Cell::styled(percent(evaluation.confident_accuracy), Style::Bold),
+    ]);
+    table
+}
+
+/// `0.934` as `93%`.
+fn percent(share: f64) -> String {
+    format!("{:.0}%", share * 100.0)
+}
+
+/// Securities with market value and profit colored by sign.
+pub(crate) fn portfolio_table(securities: &[Security]) -> Table {
+    let mut table = Table::new(vec![
+        text("Security"),
+        text("Account"),
+        number("Quantity"),
+        number("Price"),
+        number("Value"),
+        number("Profit"),
+        number("%"),
+        fixed("Currency"),
+    ]);
+    let mut values = Totals::default();
+    let mut profits = Totals::default();
+    let mut purchases = Totals::default();
+    for security in securities {
+        values.add(&security.currency, security.market_value);
+        profits.add(&security.currency, security.profit);
+        purchases.add(&security.currency, security.purchase_value);
+        let profit_style = Style::signed(security.profit);
+        table.rows.push(vec![
+            Cell::plain(security.name.clone()),
+            Cell::plain(security.account_name.clone()),
+            Cell::plain(format!("{}", security.quantity)),
+            Cell::plain(format_amount(security.market_price)),
+            Cell::plain(format_amount(security.market_value)),
+            Cell::styled(format_amount(security.profit), profit_style),
+            Cell::styled(format!("{:.2}", security.profit_percent), profit_style),
+            Cell::plain(security.currency.clone()),
+        ]);
+    }
+    for (currency, value) in values.0 {
+        let profit = profits.0.get(&currency).copied().unwrap_or_default();
+        let purchase = purchases.0.get(&currency).copied().unwrap_or_default();
+        let percent = if purchase == 0.0 {
+            String::new()
+        } else {
+            format!("{:.2}", profit / purchase * 100.0)
+        };
+        table.footer.push(vec![
+            Cell::styled("Total", Style::Bold),
+            Cell::empty(),
+            Cell::empty(),
+            Cell::empty(),
+            Cell::plain(format_amount(value)),
+            Cell::styled(format_amount(profit), Style::signed(profit)),
+            Cell::styled(percent, Style::signed(profit)),
+            Cell::plain(currency),
+        ]);
+    }
+    table
+}
+
+/// `field: old → new` for each changed field, separated by `; `.
+fn field_changes(changes: &[FieldChange]) -> String {
+    changes
+        .iter()
+        .map(|c| format!("{}: {} → {}", c.field, c.old, c.new))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// `+` for added, `-` for removed and `~` for changed rows.
+fn marker(marker: &'static str) -> Cell {
+    let style = match marker {
+        "+" => Style::Positive,
+        "-" => Style::Negative,
+        _ => Style::Bold,
+    };
+    Cell::styled(marker, style)
+}
+
+/// `moneymoney diff` transactions: added, removed, then changed ones with
+/// their fields as `old → new`.
+pub(crate) fn transactions_diff_table(diff: &TransactionsDiff) -> Table {
+    let mut table = Table::new(vec![
+        fixed(""),
+        number("Id"),
+        fixed("Date"),
+        text("Payee"),
+        number("Amount"),
+        text("Changes"),
+    ]);
+    let sides = [("+", &diff.added), ("-", &diff.removed)];
+    for (sign, transactions) in sides {
+        for transaction in transactions {
+            table.rows.push(vec![
+                marker(sign),
+                Cell::plain(transaction.id.to_string()),
+                Cell::plain(transaction.booking_date.date_naive().to_string()),
+                Cell::plain(transaction.name.clone()),
+                Cell::styled(format_amount(transaction.amount), Style::signed(transaction.amount)),
+                Cell::plain(transaction.category.clone().unwrap_or_default()),
+            ]);
+        }
+    }
+    for change in &diff.changed {
+        table.rows.push(vec![
+            marker("~"),
+            Cell::plain(change.id.to_string()),
+            Cell::plain(change.booking_date.date_naive().to_string()),
+            Cell::plain(change.name.clone()),
+            Cell::styled(format_amount(change.amount), Style::signed(change.amount)),
+            Cell::styled(field_changes(&change.changes), Style::Bold),
+        ]);
+    }
+    table.footer.push(vec![
+        Cell::empty(),
+        Cell::empty(),
+        Cell::empty(),
+        Cell::styled(
+            format!(
+                "{} added, {} removed, {} changed",
+                diff.added.len(),
+                diff.removed.len(),
+                diff.changed.len()
+            ),
+            Style::Bold,
+        ),
+    ]);
+    table
+}
+
+/// `moneymoney diff` balances: before, after and the difference per account.
+pub(crate) fn balances_diff_table(changes: &[BalanceChange]) -> Table {
+    let mut table = Table::new(vec![
+        text("Account"),
+        number("Before"),
+        number("After"),
+        number("Change"),
+        fixed("Currency"),
+    ]);
+    let mut totals = Totals::default();
+    let amount =
+        |amount: Option<f64>| amount.map_or_else(Cell::empty, |a| Cell::plain(format_amount(a)));
+    for change in changes {
+        let delta = change.new.unwrap_or_default() - change.old.unwrap_or_default();
+        totals.add(&change.currency, delta);
+        table.rows.push(vec![
+            Cell::plain(change.name.clone()),
+            amount(change.old),
+            amount(change.new),
+            Cell::styled(format_amount(delta), Style::signed(delta)),
+            Cell::plain(change.currency.clone()),
+        ]);
+    }
+    for (currency, delta) in totals.0 {
+        table.footer.push(vec![
+            Cell::styled("Total", Style::Bold),
+            Cell::empty(),
+            Cell::empty(),
+            Cell::styled(format_amount(delta), Style::signed(delta)),
+            Cell::plain(currency),
+        ]);
+    }
+    table
+}
+
+/// `moneymoney diff` categories: added, removed, then renamed or re-budgeted.
+pub(crate) fn categories_diff_table(diff: &CategoriesDiff) -> Table {
+    let mut table = Table::new(vec![fixed(""), text("Category"), text("Changes")]);
+    let sides = [("+", &diff.added), ("-", &diff.removed)];
+    for (sign, categories) in sides {
+        for category in categories {
+            table.rows.push(vec![
+                marker(sign),
+                Cell::plain(category.path.clone()),
+                Cell::empty(),
+            ]);
+        }
+    }
+    for change in &diff.changed {
+        table.rows.push(vec![
+            marker("~"),
+            Cell::plain(change.category.path.clone()),
+            Cell::styled(field_changes(&change.changes), Style::Bold),
+        ]);
+    }
+    table
+}
+
+/// `moneymoney diff` portfolio: quantity and market value before and after.
+pub(crate) fn portfolio_diff_table(changes: &[PositionChange]) -> Table {
+    let mut table = Table::new(vec![
+        text("Security"),
+        text("Account"),
+        number("Quantity"),
+        number("Before"),
+        number("After"),
+        number("Change"),
+        fixed("Currency"),
+    ]);
+    let mut totals = Totals::default();
+    let value =
+        |value: Option<f64>| value.map_or_else(Cell::empty, |v| Cell::plain(format_amount(v)));
+    for change in changes {
+        let delta = change.new_market_value.unwrap_or_default()
+            - change.old_market_value.unwrap_or_default();
+        totals.add(&change.currency, delta);
+        let quantity = |q: Option<f64>| q.map_or_else(|| "0".to_string(), |q| q.to_string());
+        let quantity = if change.old_quantity == change.new_quantity {
+            Cell::plain(quantity(change.new_quantity))
+        } else {
+            Cell::styled(
+                format!("{} → {}", quantity(change.old_quantity), quantity(change.new_quantity)),
+                Style::Bold,
+            )
+        };
+        table.rows.push(vec![
+            Cell::plain(change.name.clone()),
+            Cell::plain(change.account_name.clone()),
+            quantity,
+            value(change.old_market_value),
+            value(change.new_market_value),
+            Cell::styled(format_amount(delta), Style::signed(delta)),
+            Cell::plain(change.currency.clone()),
+        ]);
+    }
+    for (currency, delta) in totals.0 {
+        table.footer.push(vec![
+            Cell::styled("Total", Style::Bold),
+            Cell::empty(),
+            Cell::empty(),
+            Cell::empty(),
+            Cell::empty(),
+            Cell::styled(format_amount(delta), Style::signed(delta)),
+            Cell::plain(currency),
+        ]);
+    }
+    table
+}
+
+/// `--fields` rows with one column per field; columns holding only numbers
+/// are right-aligned.
+pub(crate) fn fields_table(names: &[&str], rows: &[Row]) -> Table {
+    let columns = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let numeric = rows
+                .iter()
+                .filter_map(|row| row.values().nth(i))
+                .all(|v| v.is_number() || v.is_null());
+            Column {
+                header: Cow::Owned((*name).to_string()),
+                align: if numeric { Align::Right } else { Align::Left },
+                flex: !numeric,
+            }
+        })
+        .collect();
+    let mut table = Table::new(columns);
+    for row in rows {
+        table.rows.push(
+            row.values()
+                .map(|value| match value {
+                    Value::Null => Cell::empty(),
+                    Value::String(s) => Cell::plain(s.clone()),
+                    v => Cell::plain(v.to_string()),
+                })
+                .collect(),
+        );
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: RenderOptions = RenderOptions {
+        width: None,
+        color: false,
+    };
+
+    fn render(table: &Table, options: RenderOptions) -> String {
+        let mut out = Vec::new();
+        table.render(&mut out, options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn sample() -> Table {
+        let mut table = Table::new(vec![text("Payee"), number("Amount"), fixed("Currency")]);
+        table.rows.push(vec![
+            Cell::plain("Bäckerei Müller"),
+            Cell::styled(format_amount(-3.5), Style::Negative),
+            Cell::plain("EUR"),
+        ]);
+        table.rows.push(vec![
+            Cell::plain("Gehalt"),
+            Cell::styled(format_amount(2500.0), Style::Positive),
+            Cell::plain("EUR"),
+        ]);
+        table.footer.push(vec![
+            Cell::styled("Total", Style::Bold),
+            Cell::plain(format_amount(2496.5)),
+            Cell::plain("EUR"),
+        ]);
+        table
+    }
+
+    #[test]
+    fn test_render_aligns_columns() {
+        assert_eq!(
+            render(&sample(), PLAIN),
+            "\
+Payee              Amount  Currency
+───────────────  ────────  ────────
+Bäckerei Müller     -3.50  EUR
+Gehalt           2,500.00  EUR
+───────────────  ────────  ────────
+Total            2,496.50  EUR
+"
+        );
+    }
+
+    #[test]
+    fn test_render_truncates_text_columns_only() {
+        let out = render(
+            &sample(),
+            RenderOptions {
+                width: Some(28),
+                ..PLAIN
+            },
+        );
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[2], "Bäckere…     -3.50  EUR");
+        assert_eq!(lines[3], "Gehalt    2,500.00  EUR");
+        assert!(lines.iter().all(|l| l.width() <= 28));
+    }
+
+    #[test]
+    fn test_render_colors_cells() {
+        let out = render(
+            &sample(),
+            RenderOptions {
+                color: true,
+                ..PLAIN
+            },
+        );
+        assert!(out.contains("\x1b[31m-3.50\x1b[0m"));
+        assert!(out.contains("\x1b[32m2,500.00\x1b[0m"));
+        assert!(out.starts_with("\x1b[1mPayee\x1b[0m"));
+    }
+
+    fn transaction(name: &str, amount: f64, currency: &str, booked: bool) -> MoneymoneyTransaction {
+        serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "bookingDate": "2024-03-01T12:00:00Z",
+            "valueDate": "2024-03-01T12:00:00Z",
+            "name": name,
+            "amount": amount,
+            "currency": currency,
+            "accountUuid": "adcf1f45-4add-4e74-9958-a9907e0f8060",
+            "booked": booked,
+            "categoryUuid": "adcf1f45-4add-4e74-9958-a9907e0f8060",
+            "checkmark": false,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_transactions_table_totals_per_currency() {
+        let table = transactions_table(&[
+            transaction("REWE", -45.67, "EUR", true),
+            transaction("Gehalt", 2500.0, "EUR", true),
+            transaction("Coop", -12.0, "CHF", false),
+        ]);
+        let footer: Vec<Vec<&str>> = table
+            .footer
+            .iter()
+            .map(|row| row.iter().map(|c| c.text.as_str()).collect())
+            .collect();
+        assert_eq!(
+            footer,
+            [
+                ["Total", "1 transactions", "", "-12.00", "CHF"],
+                ["Total", "2 transactions", "", "2,454.33", "EUR"],
+            ]
+        );
+        assert_eq!(table.rows[2][1].style, Style::Dim);
+        assert_eq!(table.rows[2][3].style, Style::Negative);
+    }
+
+    #[test]
+    fn test_categories_table_totals_per_period() {
+        let category =
+            |name: &str, group: bool, budget: Option<(f64, f64, &str)>| MoneymoneyCategory {
+                uuid: uuid::Uuid::nil(),
+                name: name.to_string(),
+                budget: budget.map(|(amount, available, period)| {
+                    moneymoney::export_categories::MoneymoneyCategoryBudget {
+                        amount,
+                        available,
+                        period: period.to_string(),
+                    }
+                }),
+                currency: iso_currency::Currency::EUR,
+                default: false,
+                group,
+                icon: plist::Data::new(Vec::new()),
+                indentation: u8::from(!group),
+            };
+        let table = categories_table(&[
+            category("Haushalt", true, Some((1000.0, 300.0, "monthly"))),
+            category("Lebensmittel", false, Some((400.0, 120.5, "monthly"))),
+            category("Drogerie", false, Some((50.0, -5.0, "monthly"))),
+            category("Versicherungen", false, Some((1200.0, 1200.0, "yearly"))),
+            category("Sonstiges", false, None),
+        ]);
+        let footer: Vec<Vec<&str>> = table
+            .footer
+            .iter()
+            .map(|row| row.iter().map(|c| c.text.as_str()).collect())
+            .collect();
+        assert_eq!(
+            footer,
+            [
+                ["Total", "450.00", "115.50", "monthly", "EUR"],
+                ["Total", "1,200.00", "1,200.00", "yearly", "EUR"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_changes_table_marks_changed_fields() {
+        let mut netflix = transaction("Netflix", -12.99, "EUR", true);
+        netflix.category = Some("Freizeit".to_string());
+        let change = SetTransactionParams::new(0)
+            .category("Abos")
+            .checkmark("off");
+        let table = changes_table(&[netflix], &change);
+        let row: Vec<&str> = table.rows[0].iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(
+            row,
+            [
+                "1",
+                "2024-03-01",
+                "Netflix",
+                "-12.99",
+                "Freizeit → Abos",
+                "",
+                "off"
+            ]
+        );
+        assert_eq!(table.rows[0][4].style, Style::Bold);
+        assert_eq!(table.rows[0][6].style, Style::Plain);
+    }
+
+    #[test]
+    fn test_transactions_diff_table_marks_rows() {
+        let pending = transaction("Amazon", -19.99, "EUR", false);
+        let mut booked = pending.clone();
+        booked.booked = true;
+        booked.category = Some("Shopping".to_string());
+        let mut gone = transaction("Storno", 19.99, "EUR", true);
+        gone.id = 2;
+        let diff = moneymoney::diff::diff_transactions(&[pending, gone], &[booked]);
+        let table = transactions_diff_table(&diff);
+        let markers: Vec<&str> = table.rows.iter().map(|r| r[0].text.as_str()).collect();
+        assert_eq!(markers, ["-", "~"]);
+        assert_eq!(table.rows[1][5].text, "category: (none) → \"Shopping\"; booked: false → true");
+        assert_eq!(table.footer[0][3].text, "0 added, 1 removed, 1 changed");
+    }
+
+    #[test]
+    fn test_fields_table_aligns_numeric_columns() {
+        let fields: crate::projection::Fields = "name,balance.amount".parse().unwrap();
+        let rows: Vec<Row> = [
+            serde_json::json!({ "name": "Girokonto", "balance": { "amount": 1234.5 } }),
+            serde_json::json!({ "name": "Bargeld", "balance": null }),
+        ]
+        .iter()
+        .map(|item| fields.project(item).unwrap())
+        .collect();
+        assert_eq!(
+            render(&fields_table(&["name", "balance.amount"], &rows), PLAIN),
+            "\
+name       balance.amount
+─────────  ──────────────
+Girokonto          1234.5
+Bargeld
+"
+        );
+    }
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(0.0), "0.00");
+        assert_eq!(format_amount(-0.001), "0.00");
+        assert_eq!(format_amount(999.999), "1,000.00");
+        assert_eq!(format_amount(-1234567.891), "-1,234,567.89");
+    }
+}
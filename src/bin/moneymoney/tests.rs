@@ -5,13 +5,19 @@
 //! sibling `moneymoney.rs`. Reachable here through `mod tests;` declared
 //! at the bottom of that file.
 
+use std::time::Duration;
+
 use chrono::NaiveDate;
 use clap::Parser;
 
-use super::{export_json_value_without_icons, Cli, Cmd, ExportTarget};
+use super::{
+    config::Profile, date_bounds, export_json_value_without_icons, parse_fraction, parse_interval,
+    run, unsupported_format, AddTarget, Checkmark, Cli, Cmd, ExportTarget, OutputFormat, SetTarget,
+    Source, SyncTarget,
+};
 
 #[cfg(feature = "experimental")]
-use super::CreateTarget;
+use super::{parse_one_or_many, CreateTarget};
 
 #[test]
 fn export_transactions_rejects_invalid_date_at_parse_time() {
@@ -101,23 +107,146 @@ fn parses_export_transactions_all_filters() {
     else {
         panic!("expected Export::Transactions");
     };
-    assert_eq!(args.from_date, NaiveDate::from_ymd_opt(2026, 1, 1).unwrap());
+    assert_eq!(args.from_date, NaiveDate::from_ymd_opt(2026, 1, 1));
     assert_eq!(args.to_date, NaiveDate::from_ymd_opt(2026, 12, 31));
     assert_eq!(args.from_account.as_deref(), Some("DE89370400440532013000"));
     assert_eq!(args.from_category.as_deref(), Some("Groceries"));
 }
 
 #[test]
-fn export_transactions_requires_from_date() {
-    let result = Cli::try_parse_from(["moneymoney", "export", "transactions"]);
-    let err = match result {
-        Ok(_) => panic!("expected parse error for missing --from-date"),
-        Err(e) => e,
+fn parses_export_transactions_format_ofx() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "export",
+        "transactions",
+        "--from-date",
+        "2026-01-01",
+        "--format",
+        "ofx",
+    ])
+    .unwrap();
+    let Cmd::Export {
+        target: ExportTarget::Transactions(args),
+    } = cli.command
+    else {
+        panic!("expected Export::Transactions");
     };
-    let msg = err.to_string();
+    assert!(args.format == OutputFormat::Ofx);
+}
+
+#[test]
+fn parses_export_transactions_format_camt053() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "export",
+        "transactions",
+        "--from-date",
+        "2026-01-01",
+        "--to-date",
+        "2026-03-31",
+        "--format",
+        "camt053",
+    ])
+    .unwrap();
+    let Cmd::Export {
+        target: ExportTarget::Transactions(args),
+    } = cli.command
+    else {
+        panic!("expected Export::Transactions");
+    };
+    assert!(args.format == OutputFormat::Camt053);
+}
+
+#[test]
+fn parses_export_format_parquet_with_output() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "export",
+        "portfolio",
+        "--format",
+        "parquet",
+        "-o",
+        "portfolio.parquet",
+    ])
+    .unwrap();
+    let Cmd::Export {
+        target: ExportTarget::Portfolio(args),
+    } = cli.command
+    else {
+        panic!("expected Export::Portfolio");
+    };
+    assert!(args.format == OutputFormat::Parquet);
+    assert_eq!(args.output.as_deref(), Some(std::path::Path::new("portfolio.parquet")));
+}
+
+#[test]
+fn parses_export_format_ndjson() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "export",
+        "transactions",
+        "--from-date",
+        "2024-01-01",
+        "--format",
+        "ndjson",
+    ])
+    .unwrap();
+    let Cmd::Export {
+        target: ExportTarget::Transactions(args),
+    } = cli.command
+    else {
+        panic!("expected Export::Transactions");
+    };
+    assert!(args.format == OutputFormat::Ndjson);
+    assert!(args.output.is_none());
+}
+
+#[test]
+fn unsupported_format_names_command() {
+    let msg = unsupported_format(OutputFormat::Ofx, "export accounts").to_string();
+    assert_eq!(msg, "--format ofx is not supported by `export accounts`");
+}
+
+#[test]
+fn export_transactions_requires_from_date() {
+    // A profile may supply the period, so the check happens in `run`.
+    let cli = Cli::try_parse_from(["moneymoney", "export", "transactions"]).unwrap();
+    let msg = run(cli, &Profile::default()).unwrap_err().to_string();
     assert!(msg.contains("--from-date"), "got: {msg}");
 }
 
+#[test]
+fn parses_completions_and_man() {
+    let cli = Cli::try_parse_from(["moneymoney", "completions", "zsh"]).unwrap();
+    let Cmd::Completions { shell } = cli.command else {
+        panic!("expected Completions");
+    };
+    assert_eq!(shell, crate::completion::Shell::Zsh);
+    assert!(Cli::try_parse_from(["moneymoney", "completions", "tcsh"]).is_err());
+
+    let cli = Cli::try_parse_from(["moneymoney", "man", "--dir", "man1"]).unwrap();
+    let Cmd::Man { dir } = cli.command else {
+        panic!("expected Man");
+    };
+    assert_eq!(dir.as_deref(), Some(std::path::Path::new("man1")));
+}
+
+#[test]
+fn man_page_includes_long_help() {
+    let mut page = Vec::new();
+    let cmd = <Cli as clap::CommandFactory>::command();
+    let transactions = cmd
+        .find_subcommand("export")
+        .and_then(|export| export.find_subcommand("transactions"))
+        .unwrap()
+        .clone();
+    clap_mangen::Man::new(transactions)
+        .render(&mut page)
+        .unwrap();
+    let page = String::from_utf8(page).unwrap();
+    assert!(page.contains("Inclusive start date of the export range"));
+}
+
 #[test]
 fn parses_export_portfolio_no_filters() {
     let cli = Cli::try_parse_from(["moneymoney", "export", "portfolio"]).unwrap();
@@ -166,6 +295,36 @@ fn unknown_subcommand_is_rejected() {
     assert!(msg.contains("expert"), "got: {msg}");
 }
 
+#[test]
+fn parses_sync_sqlite() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "sync",
+        "sqlite",
+        "mirror.sqlite",
+        "--from-date",
+        "2024-01-01",
+    ])
+    .unwrap();
+    let Cmd::Sync {
+        target: SyncTarget::Sqlite(args),
+    } = cli.command
+    else {
+        panic!("expected Sync::Sqlite");
+    };
+    assert_eq!(args.path.to_str(), Some("mirror.sqlite"));
+    assert_eq!(args.from_date, NaiveDate::from_ymd_opt(2024, 1, 1));
+
+    let cli = Cli::try_parse_from(["moneymoney", "sync", "sqlite", "mirror.sqlite"]).unwrap();
+    let Cmd::Sync {
+        target: SyncTarget::Sqlite(args),
+    } = cli.command
+    else {
+        panic!("expected Sync::Sqlite");
+    };
+    assert!(args.from_date.is_none());
+}
+
 #[cfg(feature = "experimental")]
 #[test]
 fn parses_create_bank_transfer_with_file() {
@@ -192,3 +351,1089 @@ fn parses_create_bank_transfer_with_stdin_marker() {
     };
     assert_eq!(args.file.as_deref(), Some(std::path::Path::new("-")));
 }
+
+#[cfg(feature = "experimental")]
+#[test]
+fn parses_create_bank_transfer_pain001_flag() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "create",
+        "bank-transfer",
+        "--pain001",
+        "x.json",
+    ])
+    .unwrap();
+    let Cmd::Create {
+        target: CreateTarget::BankTransfer(args),
+    } = cli.command
+    else {
+        panic!("expected Create::BankTransfer");
+    };
+    assert!(args.pain001);
+}
+
+#[cfg(feature = "experimental")]
+#[test]
+fn parses_create_direct_debit_flags() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "create",
+        "direct-debit",
+        "--from-account",
+        "Vereinskonto",
+        "--for",
+        "Max Mustermann",
+        "--iban",
+        "DE02120300000000202051",
+        "--amount",
+        "60",
+        "--mandate-reference",
+        "M-0042",
+        "--mandate-date",
+        "2021-03-15",
+        "--sequence-code",
+        "OOFF",
+    ])
+    .unwrap();
+    let Cmd::Create {
+        target: CreateTarget::DirectDebit(args),
+    } = cli.command
+    else {
+        panic!("expected Create::DirectDebit");
+    };
+    let debits = args.debits().unwrap();
+    assert_eq!(debits.len(), 1);
+    assert_eq!(debits[0].for_debtor.as_deref(), Some("Max Mustermann"));
+    assert_eq!(debits[0].mandate_date.as_deref(), Some("2021-03-15"));
+    assert_eq!(debits[0].sequence_code.as_deref(), Some("OOFF"));
+    assert!(debits[0].scheduled_date.is_none());
+    assert!(moneymoney::formats::pain008::validate_direct_debit(0, &debits[0]).is_ok());
+}
+
+#[cfg(feature = "experimental")]
+#[test]
+fn create_direct_debit_rejects_mixed_input() {
+    assert!(Cli::try_parse_from([
+        "moneymoney",
+        "create",
+        "direct-debit",
+        "debits.json",
+        "--amount",
+        "1",
+    ])
+    .is_err());
+    // --pain008 needs a creditor id.
+    assert!(Cli::try_parse_from([
+        "moneymoney",
+        "create",
+        "direct-debit",
+        "debits.json",
+        "--pain008"
+    ])
+    .is_err());
+    // ... and a creditor id is only used with --pain008.
+    assert!(Cli::try_parse_from([
+        "moneymoney",
+        "create",
+        "direct-debit",
+        "debits.json",
+        "--creditor-id",
+        "DE98ZZZ09999999999"
+    ])
+    .is_err());
+}
+
+#[cfg(feature = "experimental")]
+#[test]
+fn one_or_many_accepts_object_and_array() {
+    let one: Vec<serde_json::Value> = parse_one_or_many(r#"{"amount": 1}"#).unwrap();
+    assert_eq!(one.len(), 1);
+    let many: Vec<serde_json::Value> =
+        parse_one_or_many(r#"[{"amount": 1}, {"amount": 2}]"#).unwrap();
+    assert_eq!(many.len(), 2);
+    assert!(parse_one_or_many::<serde_json::Value>("not json").is_err());
+}
+
+#[test]
+fn parses_add_transaction_flags() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "add",
+        "transaction",
+        "--to-account",
+        "Bargeld",
+        "--date",
+        "2024-03-01",
+        "--to",
+        "Bäckerei",
+        "--amount",
+        "-3.50",
+        "--category",
+        "Lebensmittel",
+    ])
+    .unwrap();
+    let Cmd::Add {
+        target: AddTarget::Transaction(args),
+    } = cli.command
+    else {
+        panic!("expected Add::Transaction");
+    };
+    let rows = args.rows().unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].to_account, "Bargeld");
+    assert_eq!(rows[0].on_date, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+    assert_eq!(rows[0].amount, -3.5);
+    assert_eq!(rows[0].category.as_deref(), Some("Lebensmittel"));
+    assert!(rows[0].purpose.is_none());
+}
+
+#[test]
+fn add_transaction_requires_flags_or_file() {
+    assert!(Cli::try_parse_from(["moneymoney", "add", "transaction", "--to", "X"]).is_err());
+    assert!(
+        Cli::try_parse_from(["moneymoney", "add", "transaction", "--file", "rows.ndjson"]).is_ok()
+    );
+    assert!(Cli::try_parse_from([
+        "moneymoney",
+        "add",
+        "transaction",
+        "--file",
+        "rows.ndjson",
+        "--amount",
+        "1",
+    ])
+    .is_err());
+}
+
+#[test]
+fn parses_set_transaction_by_id() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "set",
+        "transaction",
+        "4711",
+        "--checkmark",
+        "on",
+        "--comment",
+        "",
+    ])
+    .unwrap();
+    let Cmd::Set {
+        target: SetTarget::Transaction(args),
+    } = cli.command
+    else {
+        panic!("expected Set::Transaction");
+    };
+    assert_eq!(args.id, Some(4711));
+    assert_eq!(args.checkmark, Some(Checkmark::On));
+    let params = args.params(4711);
+    assert_eq!(params.checkmark_to.as_deref(), Some("on"));
+    assert_eq!(params.comment_to.as_deref(), Some(""));
+    assert!(params.category_to.is_none());
+}
+
+#[test]
+fn parses_set_transaction_bulk() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "set",
+        "transaction",
+        "--from-date",
+        "2020-01-01",
+        "--payee",
+        "(?i)^netflix",
+        "--category",
+        "Abos",
+        "--yes",
+    ])
+    .unwrap();
+    let Cmd::Set {
+        target: SetTarget::Transaction(args),
+    } = cli.command
+    else {
+        panic!("expected Set::Transaction");
+    };
+    assert!(args.id.is_none());
+    assert!(args.yes);
+    assert!(args.payee.unwrap().is_match("NETFLIX.COM"));
+}
+
+#[test]
+fn set_transaction_rejects_incomplete_arguments() {
+    // No change given.
+    assert!(Cli::try_parse_from(["moneymoney", "set", "transaction", "4711"]).is_err());
+    // Neither id nor filters.
+    assert!(Cli::try_parse_from(["moneymoney", "set", "transaction", "--checkmark", "on"]).is_err());
+    // Filters don't combine with an id.
+    assert!(Cli::try_parse_from([
+        "moneymoney",
+        "set",
+        "transaction",
+        "4711",
+        "--checkmark",
+        "on",
+        "--from-date",
+        "2024-01-01",
+    ])
+    .is_err());
+    // Invalid regex is rejected at parse time.
+    assert!(Cli::try_parse_from([
+        "moneymoney",
+        "set",
+        "transaction",
+        "--from-date",
+        "2024-01-01",
+        "--payee",
+        "(",
+        "--checkmark",
+        "on",
+    ])
+    .is_err());
+}
+
+#[test]
+fn parses_global_input() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "export",
+        "transactions",
+        "--input",
+        "accounts.json",
+        "--from-date",
+        "2024-01-01",
+        "--input",
+        "transactions.plist",
+    ])
+    .unwrap();
+    assert_eq!(
+        cli.input,
+        [
+            std::path::PathBuf::from("accounts.json"),
+            std::path::PathBuf::from("transactions.plist")
+        ]
+    );
+    let cli =
+        Cli::try_parse_from(["moneymoney", "--input", "a.json", "export", "accounts"]).unwrap();
+    assert_eq!(cli.input, [std::path::PathBuf::from("a.json")]);
+    let cli = Cli::try_parse_from(["moneymoney", "export", "accounts"]).unwrap();
+    assert!(cli.input.is_empty());
+}
+
+#[test]
+fn saved_source_answers_reads_and_refuses_writes() {
+    let path = std::env::temp_dir().join(format!("moneymoney-input-{}.ndjson", std::process::id()));
+    std::fs::write(
+        &path,
+        "{\"creator\":\"MoneyMoney\"}\n\
+         {\"id\":1,\"bookingDate\":\"2024-03-01T12:00:00Z\",\"valueDate\":\"2024-03-01T12:00:00Z\",\
+         \"name\":\"REWE\",\"amount\":-12.5,\"currency\":\"EUR\",\
+         \"accountUuid\":\"adcf1f45-4add-4e74-9958-a9907e0f8060\",\"booked\":true,\
+         \"categoryUuid\":\"00000000-0000-0000-0000-000000000000\",\"checkmark\":false}\n",
+    )
+    .unwrap();
+    let source = Source::new(std::slice::from_ref(&path));
+    std::fs::remove_file(&path).unwrap();
+    let source = source.unwrap();
+
+    let march = moneymoney::export_transactions::ExportTransactionsParams::new(
+        NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+    );
+    assert_eq!(source.transactions(march).unwrap().transactions.len(), 1);
+    let april = moneymoney::export_transactions::ExportTransactionsParams::new(
+        NaiveDate::from_ymd_opt(2024, 4, 1).unwrap(),
+    );
+    assert!(source.transactions(april).unwrap().transactions.is_empty());
+
+    let err = source.accounts().unwrap_err().to_string();
+    assert!(err.contains("no saved accounts export"), "got: {err}");
+    let err = source
+        .require_live("set transaction")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("can't be used with --input"), "got: {err}");
+}
+
+#[test]
+fn parses_export_transactions_period_and_range_dates() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "export",
+        "transactions",
+        "--period",
+        "2024-Q3",
+    ])
+    .unwrap();
+    let Cmd::Export {
+        target: ExportTarget::Transactions(args),
+    } = cli.command
+    else {
+        panic!("expected Export::Transactions");
+    };
+    assert_eq!(
+        date_bounds(args.from_date, args.to_date, args.period),
+        Some((
+            NaiveDate::from_ymd_opt(2024, 7, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 9, 30)
+        ))
+    );
+
+    // Range expressions: `--from-date` takes the start, `--to-date` the end.
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "export",
+        "transactions",
+        "--from-date",
+        "2024-05",
+        "--to-date",
+        "2024-Q3",
+    ])
+    .unwrap();
+    let Cmd::Export {
+        target: ExportTarget::Transactions(args),
+    } = cli.command
+    else {
+        panic!("expected Export::Transactions");
+    };
+    assert_eq!(args.from_date, NaiveDate::from_ymd_opt(2024, 5, 1));
+    assert_eq!(args.to_date, NaiveDate::from_ymd_opt(2024, 9, 30));
+}
+
+#[test]
+fn period_conflicts_with_explicit_dates() {
+    for argv in [
+        &[
+            "moneymoney",
+            "export",
+            "transactions",
+            "--period",
+            "ytd",
+            "--from-date",
+            "2024-01-01",
+        ][..],
+        &[
+            "moneymoney",
+            "set",
+            "transaction",
+            "4711",
+            "--period",
+            "ytd",
+            "--checkmark",
+            "on",
+        ][..],
+        &[
+            "moneymoney",
+            "export",
+            "transactions",
+            "--period",
+            "next-week",
+        ][..],
+    ] {
+        assert!(Cli::try_parse_from(argv).is_err(), "{argv:?}");
+    }
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "set",
+        "transaction",
+        "--period",
+        "last-month",
+        "--checkmark",
+        "on",
+    ])
+    .unwrap();
+    let Cmd::Set {
+        target: SetTarget::Transaction(args),
+    } = cli.command
+    else {
+        panic!("expected Set::Transaction");
+    };
+    assert!(args.period.is_some() && args.from_date.is_none());
+}
+
+#[test]
+fn parses_export_transactions_client_filters() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "export",
+        "transactions",
+        "--from-date",
+        "2024-01-01",
+        "--min-amount",
+        "-500",
+        "--max-amount",
+        "-10",
+        "--sign",
+        "expense",
+        "--booked-only",
+        "--checkmark",
+        "off",
+        "--payee",
+        "(?i)^rewe",
+        "--purpose",
+        "Einkauf",
+        "--uncategorized",
+        "--currency",
+        "EUR",
+        "--counterpart-iban",
+        "DE02 1203 0000 0000 2020 51",
+        "--sort",
+        "-amount",
+        "--limit",
+        "10",
+    ])
+    .unwrap();
+    let Cmd::Export {
+        target: ExportTarget::Transactions(args),
+    } = cli.command
+    else {
+        panic!("expected Export::Transactions");
+    };
+    let filter = args.filter.filter();
+    assert_eq!(filter.min_amount, Some(-500.0));
+    assert_eq!(filter.max_amount, Some(-10.0));
+    assert_eq!(filter.sign, Some(moneymoney::filter::Sign::Expense));
+    assert_eq!(filter.booked, Some(true));
+    assert_eq!(filter.checkmark, Some(false));
+    assert!(filter.payee.unwrap().is_match("REWE Markt"));
+    assert!(filter.uncategorized);
+    assert_eq!(filter.currency.as_deref(), Some("EUR"));
+    let sort = args.sort.unwrap();
+    assert_eq!(sort.key, moneymoney::filter::SortKey::Amount);
+    assert!(sort.descending);
+    assert_eq!(args.limit, Some(10));
+}
+
+#[test]
+fn export_transactions_filter_conflicts_and_invalid_values() {
+    let base = [
+        "moneymoney",
+        "export",
+        "transactions",
+        "--from-date",
+        "2024-01-01",
+    ];
+    for extra in [
+        &["--booked-only", "--pending-only"][..],
+        &["--sign", "both"][..],
+        &["--sort", "size"][..],
+        &["--purpose", "("][..],
+        &["--limit", "-1"][..],
+    ] {
+        let argv: Vec<&str> = base.iter().chain(extra).copied().collect();
+        assert!(Cli::try_parse_from(&argv).is_err(), "{argv:?}");
+    }
+    let cli = Cli::try_parse_from(base).unwrap();
+    let Cmd::Export {
+        target: ExportTarget::Transactions(args),
+    } = cli.command
+    else {
+        panic!("expected Export::Transactions");
+    };
+    assert!(args.filter.filter().is_empty());
+}
+
+#[test]
+fn parses_query_args() {
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "query",
+        r#"amount < -100 and category ~ "Food\\*""#,
+        "--sort=-amount",
+        "--limit",
+        "5",
+        "--format",
+        "table",
+    ])
+    .unwrap();
+    let Cmd::Query(args) = cli.command else {
+        panic!("expected Query");
+    };
+    assert_eq!(args.query, r#"amount < -100 and category ~ "Food\\*""#);
+    assert_eq!(args.limit, Some(5));
+    assert!(args.format == OutputFormat::Table);
+    assert!(args.sort.is_some());
+
+    assert!(Cli::try_parse_from(["moneymoney", "query"]).is_err());
+}
+
+#[test]
+fn query_filters_saved_transactions() {
+    let dir = std::env::temp_dir();
+    let input = dir.join(format!("moneymoney-query-in-{}.ndjson", std::process::id()));
+    let output = dir.join(format!("moneymoney-query-out-{}.ndjson", std::process::id()));
+    let transaction = |id: u32, date: &str, name: &str, amount: f64| {
+        format!(
+            "{{\"id\":{id},\"bookingDate\":\"{date}T12:00:00Z\",\"valueDate\":\"{date}T12:00:00Z\",\
+             \"name\":\"{name}\",\"amount\":{amount},\"currency\":\"EUR\",\
+             \"accountUuid\":\"adcf1f45-4add-4e74-9958-a9907e0f8060\",\"booked\":true,\
+             \"categoryUuid\":\"00000000-0000-0000-0000-000000000000\",\"checkmark\":false}}\n"
+        )
+    };
+    let content = [
+        "{\"creator\":\"MoneyMoney\"}\n".to_string(),
+        transaction(1, "2023-12-30", "REWE", -150.0),
+        transaction(2, "2024-01-05", "REWE Markt", -120.0),
+        transaction(3, "2024-01-06", "REWE", -12.5),
+        transaction(4, "2024-01-07", "ALDI", -130.0),
+        transaction(5, "2024-01-08", "rewe", -101.0),
+    ]
+    .concat();
+    std::fs::write(&input, content).unwrap();
+
+    let cli = Cli::try_parse_from([
+        "moneymoney".as_ref(),
+        "--input".as_ref(),
+        input.as_os_str(),
+        "query".as_ref(),
+        "payee:rewe and amount < -100 and date >= 2024-01-01".as_ref(),
+        "--sort=amount".as_ref(),
+        "--format".as_ref(),
+        "ndjson".as_ref(),
+        "--output".as_ref(),
+        output.as_os_str(),
+    ] as [&std::ffi::OsStr; 10])
+    .unwrap();
+    let result = run(cli, &Profile::default());
+    let written = std::fs::read_to_string(&output);
+    std::fs::remove_file(&input).unwrap();
+    let _ = std::fs::remove_file(&output);
+    result.unwrap();
+
+    let ids: Vec<u64> = written
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|line| {
+            serde_json::from_str::<serde_json::Value>(line).unwrap()["id"]
+                .as_u64()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(ids, [2, 5]);
+
+    let cli = Cli::try_parse_from(["moneymoney", "query", "payee < 3"]).unwrap();
+    let err = run(cli, &Profile::default()).unwrap_err().to_string();
+    assert!(err.contains("`<` doesn't work on text field `payee`"), "got: {err}");
+    assert!(err.ends_with("        ^"), "got: {err}");
+}
+
+#[test]
+fn parses_fields_and_template_on_every_export() {
+    for target in ["accounts", "categories", "portfolio"] {
+        let cli = Cli::try_parse_from(["moneymoney", "export", target, "--fields", "name,uuid"]);
+        assert!(cli.is_ok(), "export {target} --fields");
+        let cli = Cli::try_parse_from(["moneymoney", "export", target, "--template", "{{ name }}"]);
+        assert!(cli.is_ok(), "export {target} --template");
+    }
+    let cli = Cli::try_parse_from([
+        "moneymoney",
+        "export",
+        "accounts",
+        "--fields",
+        "name,balance.amount",
+        "--format",
+        "table",
+    ])
+    .unwrap();
+    let Cmd::Export {
+        target: ExportTarget::Accounts(args),
+    } = cli.command
+    else {
+        panic!("expected Export::Accounts");
+    };
+    assert_eq!(args.shape.fields.unwrap().names(), ["name", "balance.amount"]);
+
+    for conflicting in [
+        &["--template", "{{ name }}", "--format", "ndjson"][..],
+        &["--template", "{{ name }}", "--fields", "name"][..],
+        &["--template", "{{ name "][..],
+        &["--fields", "name,,amount"][..],
+    ] {
+        let argv = ["moneymoney", "query", "booked"]
+            .into_iter()
+            .chain(conflicting.iter().copied());
+        assert!(Cli::try_parse_from(argv).is_err(), "{conflicting:?}");
+    }
+}
+
+#[test]
+fn fields_and_template_shape_saved_transactions() {
+    let dir = std::env::temp_dir();
+    let input = dir.join(format!("moneymoney-shape-in-{}.ndjson", std::process::id()));
+    std::fs::write(
+        &input,
+        "{\"creator\":\"MoneyMoney\"}\n\
+         {\"id\":1,\"bookingDate\":\"2024-03-01T12:00:00Z\",\"valueDate\":\"2024-03-01T12:00:00Z\",\
+         \"name\":\"REWE\",\"amount\":-12.5,\"currency\":\"EUR\",\
+         \"accountUuid\":\"adcf1f45-4add-4e74-9958-a9907e0f8060\",\"booked\":true,\
+         \"categoryUuid\":\"00000000-0000-0000-0000-000000000000\",\"checkmark\":false}\n",
+    )
+    .unwrap();
+    let run_with = |shape: &[&str], format: &str| {
+        let output = dir.join(format!("moneymoney-shape-out-{}-{format}", std::process::id()));
+        let mut argv: Vec<std::ffi::OsString> = [
+            "moneymoney",
+            "export",
+            "transactions",
+            "--from-date",
+            "2024-01-01",
+        ]
+        .iter()
+        .map(Into::into)
+        .collect();
+        argv.extend(["--input".into(), input.clone().into_os_string()]);
+        argv.extend(shape.iter().map(Into::into));
+        if !format.is_empty() {
+            argv.extend(["--format".into(), format.into()]);
+        }
+        argv.extend(["--output".into(), output.clone().into_os_string()]);
+        let result = run(Cli::try_parse_from(argv).unwrap(), &Profile::default());
+        let written = std::fs::read_to_string(&output);
+        let _ = std::fs::remove_file(&output);
+        result.map(|()| written.unwrap())
+    };
+
+    let json = run_with(&["--fields", "booking_date,name,amount"], "json");
+    let ndjson = run_with(&["--fields", "name,amount"], "ndjson");
+    let template = run_with(
+        &[
+            "--template",
+            "{{ bookingDate[:10] }} {{ name }} {{ comment }}|",
+        ],
+        "",
+    );
+    let csv = run_with(&[], "csv");
+    let dialect = run_with(
+        &[
+            "--fields",
+            "name,amount",
+            "--csv-delimiter",
+            ";",
+            "--csv-decimal-separator",
+            ",",
+            "--csv-no-header",
+        ],
+        "csv",
+    );
+    let unknown = run_with(&["--fields", "nmae"], "json");
+    let ofx = run_with(&["--fields", "name"], "ofx");
+    std::fs::remove_file(&input).unwrap();
+
+    let json: serde_json::Value = serde_json::from_str(&json.unwrap()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!([{ "booking_date": "2024-03-01T12:00:00Z", "name": "REWE", "amount": -12.5 }])
+    );
+    assert_eq!(ndjson.unwrap(), "{\"name\":\"REWE\",\"amount\":-12.5}\n");
+    assert_eq!(template.unwrap(), "2024-03-01 REWE |\n");
+    let csv = csv.unwrap();
+    let mut lines = csv.lines();
+    let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+    let row: Vec<&str> = lines.next().unwrap().split(',').collect();
+    let cell = |name| row[header.iter().position(|h| *h == name).unwrap()];
+    assert_eq!((cell("name"), cell("amount"), cell("id")), ("REWE", "-12.5", "1"));
+    assert_eq!(dialect.unwrap(), "REWE;-12,5\r\n");
+    assert!(unknown
+        .unwrap_err()
+        .to_string()
+        .contains("unknown field `nmae`"));
+    assert!(ofx.unwrap_err().to_string().contains("--fields works with"));
+}
+
+#[test]
+fn snapshot_round_trips_through_input() {
+    let dir = std::env::temp_dir().join(format!("moneymoney-cli-snapshot-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("old/objects")).unwrap();
+    std::fs::create_dir_all(dir.join("old/snapshots")).unwrap();
+    std::fs::write(dir.join("old/objects/empty.json"), "[]").unwrap();
+    let payload = serde_json::json!({ "sha256": "empty", "items": 0 });
+    let manifest = dir.join("old/snapshots/20240301T120000Z.json");
+    std::fs::write(
+        &manifest,
+        serde_json::json!({
+            "takenAt": "2024-03-01T12:00:00Z",
+            "fromDate": "2024-01-01",
+            "accounts": payload,
+            "categories": payload,
+            "transactions": payload,
+            "securities": payload,
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let run_args = |args: &[&str]| {
+        let argv = ["moneymoney"].iter().chain(args);
+        run(Cli::try_parse_from(argv).unwrap(), &Profile::default())
+    };
+    let new = dir.join("new");
+    let manifest = manifest.to_str().unwrap();
+    let new = new.to_str().unwrap();
+
+    run_args(&["snapshot", "--input", manifest, "--dir", new]).unwrap();
+    run_args(&["snapshot", "list", "--dir", new, "--format", "table"]).unwrap();
+    run_args(&["snapshot", "show", "--dir", new]).unwrap();
+    let store = moneymoney::snapshot::SnapshotStore::new(new);
+    let latest = store.load("latest").unwrap();
+    assert!(latest.accounts.is_empty());
+    assert_eq!(store.list().unwrap().len(), 1);
+
+    let err = run_args(&["snapshot", "show", "nope", "--dir", new]).unwrap_err();
+    assert_eq!(err.to_string(), "no snapshot \"nope\"");
+    let err = run_args(&["snapshot", "prune", "--dir", new]).unwrap_err();
+    assert!(err.to_string().contains("--keep-last"));
+    run_args(&["snapshot", "prune", "--dir", new, "--keep-last", "1"]).unwrap();
+    assert!(Cli::try_parse_from(["moneymoney", "snapshot", "list", "--from-date", "30d"]).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn diff_compares_files_and_snapshots() {
+    let dir = std::env::temp_dir().join(format!("moneymoney-cli-diff-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let transaction = |id: u64, category: &str| {
+        serde_json::json!({
+            "id": id,
+            "bookingDate": "2024-03-01T12:00:00Z",
+            "valueDate": "2024-03-01T12:00:00Z",
+            "name": "REWE",
+            "amount": -12.5,
+            "currency": "EUR",
+            "accountUuid": "adcf1f45-4add-4e74-9958-a9907e0f8060",
+            "booked": true,
+            "category": category,
+            "categoryUuid": "00000000-0000-0000-0000-000000000000",
+            "checkmark": false,
+        })
+    };
+    let old = dir.join("old.json");
+    let new = dir.join("new.json");
+    let export = |items: serde_json::Value| {
+        serde_json::json!({ "creator": "MoneyMoney", "transactions": items }).to_string()
+    };
+    std::fs::write(&old, export(serde_json::json!([transaction(1, "Essen"), transaction(2, "")])))
+        .unwrap();
+    std::fs::write(&new, export(serde_json::json!([transaction(1, "Lebensmittel")]))).unwrap();
+    let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+
+    let cli = Cli::try_parse_from(["moneymoney", "diff", old, new]).unwrap();
+    let Cmd::Diff(args) = cli.command else {
+        panic!("expected diff");
+    };
+    let (before, from) = args.load(&args.old, &Profile::default()).unwrap();
+    let (after, _) = args.load(&args.new, &Profile::default()).unwrap();
+    assert_eq!(from, None);
+    let diff = moneymoney::diff::Diff::between(&before, &after, NaiveDate::MIN);
+    assert!(diff.balances.is_none());
+    let transactions = diff.transactions.unwrap();
+    assert_eq!(transactions.removed[0].id, 2);
+    assert_eq!(transactions.changed[0].changes[0].field, "category");
+
+    let run_args = |args: &[&str]| {
+        let argv = ["moneymoney"].iter().chain(args);
+        run(Cli::try_parse_from(argv).unwrap(), &Profile::default())
+    };
+    run_args(&["diff", old, new, "--format", "table"]).unwrap();
+    let store = dir.join("store");
+    let store = store.to_str().unwrap();
+    let err = run_args(&["diff", "latest~1", "latest", "--dir", store]).unwrap_err();
+    assert_eq!(err.to_string(), "no snapshot \"latest~1\"");
+    let err = run_args(&["diff", old, new, "--format", "csv"]).unwrap_err();
+    assert!(err.to_string().contains("diff"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn parses_watch_intervals() {
+    assert_eq!(parse_interval("90s"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_interval("5m"), Ok(Duration::from_secs(300)));
+    assert_eq!(parse_interval("2h"), Ok(Duration::from_secs(7200)));
+    assert_eq!(parse_interval("45"), Ok(Duration::from_secs(45)));
+    assert!(parse_interval("0s").is_err());
+    assert!(parse_interval("1d").is_err());
+    assert!(parse_interval("m").is_err());
+}
+
+#[test]
+fn watch_once_pipes_events_to_the_hook() {
+    let dir = std::env::temp_dir().join(format!("moneymoney-cli-watch-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("transactions.json");
+    let cursor = dir.join("state/watch.json");
+    let events = dir.join("events.ndjson");
+    let today = chrono::Local::now().date_naive();
+    let write_input = |transactions: &[(u64, bool)]| {
+        let transactions: Vec<_> = transactions
+            .iter()
+            .map(|(id, booked)| {
+                serde_json::json!({
+                    "id": id,
+                    "bookingDate": format!("{today}T12:00:00Z"),
+                    "valueDate": format!("{today}T12:00:00Z"),
+                    "name": "AMAZON",
+                    "amount": -19.99,
+                    "currency": "EUR",
+                    "accountUuid": "adcf1f45-4add-4e74-9958-a9907e0f8060",
+                    "booked": booked,
+                    "categoryUuid": "00000000-0000-0000-0000-000000000000",
+                    "checkmark": false,
+                })
+            })
+            .collect();
+        let export = serde_json::json!({ "creator": "MoneyMoney", "transactions": transactions });
+        std::fs::write(&input, export.to_string()).unwrap();
+    };
+    let hook = format!("echo \"$MONEYMONEY_EVENT\" >> '{}'; cat >> '{0}'", events.display());
+    let watch = || {
+        let argv = [
+            "moneymoney".as_ref(),
+            "watch".as_ref(),
+            "--once".as_ref(),
+            "--input".as_ref(),
+            input.as_os_str(),
+            "--cursor".as_ref(),
+            cursor.as_os_str(),
+            "--hook-command".as_ref(),
+            hook.as_ref(),
+        ];
+        run(Cli::try_parse_from(argv).unwrap(), &Profile::default()).unwrap();
+    };
+
+    write_input(&[(1, false)]);
+    watch();
+    assert!(!events.exists());
+    assert!(cursor.exists());
+    write_input(&[(2, true), (3, true)]);
+    watch();
+    let lines: Vec<String> = std::fs::read_to_string(&events)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "changed");
+    let changed: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+    assert_eq!(changed["previousId"], 1);
+    assert_eq!(changed["fields"], serde_json::json!(["booked"]));
+    assert_eq!(lines[2], "new");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn watch_redelivers_events_it_could_not_dead_letter() {
+    let dir =
+        std::env::temp_dir().join(format!("moneymoney-cli-deadletter-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("transactions.json");
+    let cursor = dir.join("watch.json");
+    let today = chrono::Local::now().date_naive();
+    let write_input = |ids: &[u64]| {
+        let transactions: Vec<_> = ids
+            .iter()
+            .map(|id| {
+                serde_json::json!({
+                    "id": id,
+                    "bookingDate": format!("{today}T12:00:00Z"),
+                    "valueDate": format!("{today}T12:00:00Z"),
+                    "name": "AMAZON",
+                    "amount": -19.99,
+                    "currency": "EUR",
+                    "accountUuid": "adcf1f45-4add-4e74-9958-a9907e0f8060",
+                    "booked": true,
+                    "categoryUuid": "00000000-0000-0000-0000-000000000000",
+                    "checkmark": false,
+                })
+            })
+            .collect();
+        let export = serde_json::json!({ "creator": "MoneyMoney", "transactions": transactions });
+        std::fs::write(&input, export.to_string()).unwrap();
+    };
+    // The first hook fails and its dead letter can't be written while a
+    // directory is in the way; the second one records what it gets.
+    let dead_letter = dir.join("dead-letter.ndjson");
+    let events = dir.join("events.ndjson");
+    std::fs::create_dir_all(&dead_letter).unwrap();
+    let profile = Profile {
+        hooks: vec![
+            toml::from_str(&format!(
+                "command = \"cat > /dev/null; exit 1\"\nretries = 0\ndead-letter = '{}'",
+                dead_letter.display()
+            ))
+            .unwrap(),
+            toml::from_str(&format!("command = \"cat >> '{}'\"", events.display())).unwrap(),
+        ],
+        ..Profile::default()
+    };
+    let received = || {
+        std::fs::read_to_string(&events)
+            .unwrap_or_default()
+            .lines()
+            .count()
+    };
+    let watch = || {
+        let argv = [
+            "moneymoney".as_ref(),
+            "watch".as_ref(),
+            "--once".as_ref(),
+            "--input".as_ref(),
+            input.as_os_str(),
+            "--cursor".as_ref(),
+            cursor.as_os_str(),
+        ];
+        run(Cli::try_parse_from(argv).unwrap(), &profile)
+    };
+
+    write_input(&[1]);
+    watch().unwrap();
+    write_input(&[1, 2]);
+    assert!(watch().is_err());
+    assert_eq!(received(), 1);
+    // Neither delivered everywhere nor dead-lettered, so it comes again.
+    assert!(watch().is_err());
+    assert_eq!(received(), 2);
+    std::fs::remove_dir(&dead_letter).unwrap();
+    watch().unwrap();
+    assert_eq!(received(), 3);
+    assert!(std::fs::read_to_string(&dead_letter)
+        .unwrap()
+        .contains("\"id\":2"));
+    watch().unwrap();
+    assert_eq!(received(), 3);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rules_apply_plans_from_saved_exports() {
+    let dir = std::env::temp_dir().join(format!("moneymoney-cli-rules-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let transactions = dir.join("transactions.json");
+    let categories = dir.join("categories.json");
+    let rules = dir.join("rules.yaml");
+    let transaction = |id: u64, name: &str| {
+        serde_json::json!({
+            "id": id,
+            "bookingDate": "2024-03-01T12:00:00Z",
+            "valueDate": "2024-03-01T12:00:00Z",
+            "name": name,
+            "amount": -12.99,
+            "currency": "EUR",
+            "accountUuid": "adcf1f45-4add-4e74-9958-a9907e0f8060",
+            "booked": true,
+            "categoryUuid": "00000000-0000-0000-0000-000000000000",
+            "checkmark": false,
+        })
+    };
+    let export = serde_json::json!({
+        "creator": "MoneyMoney",
+        "transactions": [transaction(1, "NETFLIX.COM"), transaction(2, "REWE")],
+    });
+    std::fs::write(&transactions, export.to_string()).unwrap();
+    let category = serde_json::json!([{
+        "uuid": "7d1e8c5a-2b8e-4f0a-9c1d-3e5f7a9b1c2d",
+        "name": "Abos",
+        "currency": "EUR",
+        "default": false,
+        "group": false,
+        "indentation": 0,
+    }]);
+    std::fs::write(&categories, category.to_string()).unwrap();
+    let run_args = |rule_file: &str, extra: &[&str]| {
+        std::fs::write(&rules, rule_file).unwrap();
+        let mut argv = vec!["moneymoney", "rules", "apply", "--from-date", "2024-01-01"];
+        argv.extend(["--rules", rules.to_str().unwrap()]);
+        argv.extend(["--input", transactions.to_str().unwrap()]);
+        argv.extend(["--input", categories.to_str().unwrap()]);
+        argv.extend(extra);
+        run(Cli::try_parse_from(argv).unwrap(), &Profile::default())
+    };
+
+    let streaming = "rules:\n  - name: Streaming\n    when: { name: '^netflix' }\n    set: { category: Abos }\n";
+    run_args(streaming, &[]).unwrap();
+    let err = run_args(streaming, &["--yes"]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "`rules apply` writes to MoneyMoney and can't be used with --input"
+    );
+    let err = run_args(&streaming.replace("Abos", "Kino"), &[]).unwrap_err();
+    assert_eq!(err.to_string(), "Streaming: unknown category `Kino`");
+    let err = run_args("rules:\n  - when: {}\n    set: { category: Abos }\n", &[]).unwrap_err();
+    assert_eq!(err.to_string(), "rule 1: `when` needs at least one condition");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn categorize_trains_suggests_and_evaluates() {
+    let dir =
+        std::env::temp_dir().join(format!("moneymoney-cli-categorize-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("transactions.json");
+    let model = dir.join("model/category-model.json");
+    let today = chrono::Local::now().date_naive();
+    let transactions: Vec<_> = (0..30u64)
+        .map(|id| {
+            let (name, category) = match id % 3 {
+                _ if id >= 27 => ("REWE City", ""),
+                0 => ("REWE Markt", "Lebensmittel"),
+                1 => ("NETFLIX.COM", "Abos"),
+                _ => ("Stadtwerke", "Wohnen\\Strom"),
+            };
+            serde_json::json!({
+                "id": id,
+                "bookingDate": format!("{today}T12:00:00Z"),
+                "valueDate": format!("{today}T12:00:00Z"),
+                "name": name,
+                "amount": -10.0 - id as f64,
+                "currency": "EUR",
+                "accountUuid": "adcf1f45-4add-4e74-9958-a9907e0f8060",
+                "booked": true,
+                "category": category,
+                "categoryUuid": if category.is_empty() {
+                    "00000000-0000-0000-0000-000000000000"
+                } else {
+                    "7d1e8c5a-2b8e-4f0a-9c1d-3e5f7a9b1c2d"
+                },
+                "checkmark": false,
+            })
+        })
+        .collect();
+    let export = serde_json::json!({ "creator": "MoneyMoney", "transactions": transactions });
+    std::fs::write(&input, export.to_string()).unwrap();
+    let (input, model) = (input.to_str().unwrap(), model.to_str().unwrap());
+    let run_args = |args: &[&str]| {
+        let mut argv = vec!["moneymoney", "categorize", "--input", input];
+        argv.extend(args);
+        run(Cli::try_parse_from(argv).unwrap(), &Profile::default())
+    };
+
+    let err = run_args(&["suggest", "--model", model]).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("run `moneymoney categorize train` first"),
+        "{err}"
+    );
+    run_args(&["train", "--model", model]).unwrap();
+    let trained = moneymoney::classify::Classifier::load(model).unwrap();
+    assert_eq!(trained.transactions(), 27);
+    run_args(&["suggest", "--model", model, "--format", "table"]).unwrap();
+    let err = run_args(&["suggest", "--model", model, "--interactive"]).unwrap_err();
+    assert!(err.to_string().contains("can't be used with --input"), "{err}");
+    let err = run_args(&["suggest", "--model", model, "--format", "csv"]).unwrap_err();
+    assert!(err.to_string().contains("categorize suggest"), "{err}");
+    run_args(&["evaluate", "--holdout", "0.3", "--format", "table"]).unwrap();
+    let err = run_args(&["evaluate", "--holdout", "0"]).unwrap_err();
+    assert_eq!(err.to_string(), "--holdout must be between 0 and 1");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn parses_fractions() {
+    assert_eq!(parse_fraction("0.75"), Ok(0.75));
+    assert_eq!(parse_fraction("1"), Ok(1.0));
+    assert!(parse_fraction("1.5").is_err());
+    assert!(parse_fraction("-0.1").is_err());
+    assert!(parse_fraction("most").is_err());
+}
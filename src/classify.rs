@@ -0,0 +1,440 @@
+//! Suggest categories from the categorization history.
+//!
+//! A [`Classifier`] is a multinomial naive Bayes model trained on already
+//! categorized transactions. Each transaction is described by a few
+//! [`features`]: the words of its payee and purpose, its amount (exact and
+//! as an order-of-magnitude bucket) and the counterparty IBAN. For an
+//! uncategorized transaction the model ranks the known categories and
+//! reports how confident it is in each, as a probability between 0 and 1.
+//!
+//! Training only counts features, so it is fast enough to redo on every
+//! run; [`Classifier::save`] keeps a model for later anyway, e.g. to suggest
+//! from saved exports that have no history. [`evaluate`] trains on part of
+//! the history and reports the accuracy on the rest.
+//!
+//! Requires the `classify` feature (enabled by `cli`).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use chrono::NaiveDate;
+//! use moneymoney::classify::Classifier;
+//! use moneymoney::export_transactions::ExportTransactionsParams;
+//! use moneymoney::filter::TransactionFilter;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let params = ExportTransactionsParams::new(NaiveDate::from_ymd_opt(2020, 1, 1).unwrap());
+//! let transactions = moneymoney::export_transactions(params)?.transactions;
+//! let model = Classifier::train(&transactions);
+//! let uncategorized = TransactionFilter::new().uncategorized();
+//! for t in transactions.iter().filter(|t| uncategorized.matches(t)) {
+//!     if let Some(s) = model.suggest(t).filter(|s| s.confidence >= 0.8) {
+//!         println!("{}: {} ({:.0}%)", t.name, s.category, s.confidence * 100.0);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::export_transactions::MoneymoneyTransaction;
+use crate::filter::is_uncategorized;
+use crate::fs::write_atomic;
+
+/// Version of the saved model format; older models have to be retrained.
+pub const MODEL_VERSION: u32 = 1;
+
+/// Errors while loading or saving a model.
+#[derive(Debug, Error)]
+pub enum ClassifyError {
+    /// The model file could not be read or written.
+    #[error("failed to access {path}: {source}")]
+    Io {
+        /// The model file.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+
+    /// The model file is not a valid model.
+    #[error("{path}: {source}")]
+    Json {
+        /// The model file.
+        path: PathBuf,
+        /// The underlying error.
+        source: serde_json::Error,
+    },
+
+    /// The model was saved in another format version.
+    #[error("{path}: model version {version} is not supported, train it again")]
+    Version {
+        /// The model file.
+        path: PathBuf,
+        /// Its format version.
+        version: u32,
+    },
+}
+
+/// Naive Bayes category model.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Classifier {
+    version: u32,
+    /// Categorized transactions the model was trained on.
+    transactions: usize,
+    /// Distinct features seen in training.
+    vocabulary: usize,
+    categories: BTreeMap<String, CategoryCounts>,
+}
+
+/// Feature counts of one category.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct CategoryCounts {
+    transactions: usize,
+    features: u64,
+    counts: BTreeMap<String, u32>,
+}
+
+/// A category for a transaction and the model's confidence in it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    /// Category as MoneyMoney exports it (backslash-separated path).
+    pub category: String,
+    /// Probability between 0 and 1.
+    pub confidence: f64,
+}
+
+/// Result of [`evaluate`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Evaluation {
+    /// Categorized transactions trained on.
+    pub trained: usize,
+    /// Categorized transactions held out for testing.
+    pub tested: usize,
+    /// Held-out transactions whose category was the top suggestion.
+    pub correct: usize,
+    /// `correct / tested`; transactions without a suggestion count as wrong.
+    pub accuracy: f64,
+    /// Threshold for the `confident` numbers.
+    pub min_confidence: f64,
+    /// Held-out transactions with a suggestion of at least `min_confidence`.
+    pub confident: usize,
+    /// Of those, how many were right.
+    pub confident_correct: usize,
+    /// `confident_correct / confident`.
+    pub confident_accuracy: f64,
+}
+
+impl Classifier {
+    /// Count the features of the categorized `transactions`; uncategorized
+    /// ones are skipped.
+    pub fn train<'a>(transactions: impl IntoIterator<Item = &'a MoneymoneyTransaction>) -> Self {
+        let mut model = Self {
+            version: MODEL_VERSION,
+            ..Self::default()
+        };
+        let mut vocabulary = BTreeSet::new();
+        for t in transactions {
+            let Some(category) = t.category.as_deref().filter(|_| !is_uncategorized(t)) else {
+                continue;
+            };
+            let counts = model.categories.entry(category.to_string()).or_default();
+            counts.transactions += 1;
+            for feature in features(t) {
+                counts.features += 1;
+                *counts.counts.entry(feature.clone()).or_default() += 1;
+                vocabulary.insert(feature);
+            }
+            model.transactions += 1;
+        }
+        model.vocabulary = vocabulary.len();
+        model
+    }
+
+    /// Read a model written by [`Classifier::save`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClassifyError`] if the file can't be read, isn't a model or
+    /// has another format version.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ClassifyError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|source| io_error(path, source))?;
+        let model: Self = serde_json::from_slice(&bytes).map_err(|source| ClassifyError::Json {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        if model.version != MODEL_VERSION {
+            return Err(ClassifyError::Version {
+                path: path.to_path_buf(),
+                version: model.version,
+            });
+        }
+        Ok(model)
+    }
+
+    /// Write the model through a temporary file, creating the directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClassifyError::Io`] if the file can't be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ClassifyError> {
+        let path = path.as_ref();
+        let json = serde_json::to_vec(self).map_err(|source| ClassifyError::Json {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        write_atomic(path, &json, io_error)
+    }
+
+    /// Categorized transactions the model was trained on.
+    pub fn transactions(&self) -> usize {
+        self.transactions
+    }
+
+    /// The categories the model knows, by name.
+    pub fn categories(&self) -> impl Iterator<Item = &str> {
+        self.categories.keys().map(String::as_str)
+    }
+
+    /// Every known category for `t`, most likely first. Empty if none of
+    /// `t`'s features occurred in training.
+    pub fn rank(&self, t: &MoneymoneyTransaction) -> Vec<Suggestion> {
+        let known: Vec<String> = features(t)
+            .into_iter()
+            .filter(|f| self.categories.values().any(|c| c.counts.contains_key(f)))
+            .collect();
+        if known.is_empty() {
+            return Vec::new();
+        }
+        // Log-probabilities with add-one smoothing over the vocabulary.
+        let scores: Vec<(&String, f64)> = self
+            .categories
+            .iter()
+            .map(|(category, counts)| {
+                let prior = (counts.transactions as f64 / self.transactions as f64).ln();
+                let denominator = (counts.features + self.vocabulary as u64) as f64;
+                let likelihood: f64 = known
+                    .iter()
+                    .map(|f| {
+                        let count = counts.counts.get(f).copied().unwrap_or(0);
+                        ((f64::from(count) + 1.0) / denominator).ln()
+                    })
+                    .sum();
+                (category, prior + likelihood)
+            })
+            .collect();
+        let max = scores
+            .iter()
+            .map(|(_, score)| *score)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let total: f64 = scores.iter().map(|(_, score)| (score - max).exp()).sum();
+        let mut ranked: Vec<Suggestion> = scores
+            .into_iter()
+            .map(|(category, score)| Suggestion {
+                category: category.clone(),
+                confidence: (score - max).exp() / total,
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        ranked
+    }
+
+    /// The most likely category for `t`, if any feature of it is known.
+    pub fn suggest(&self, t: &MoneymoneyTransaction) -> Option<Suggestion> {
+        self.rank(t).into_iter().next()
+    }
+}
+
+fn io_error(path: &Path, source: std::io::Error) -> ClassifyError {
+    ClassifyError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// The features of `t`: `payee:` and `purpose:` words (lowercase, at
+/// least two characters with a letter, so reference numbers are left out),
+/// `amount:` with the exact amount and `amount~` with its sign and power of
+/// two, and `iban:` with the counterparty account number.
+pub fn features(t: &MoneymoneyTransaction) -> Vec<String> {
+    let mut features = BTreeSet::new();
+    let words = |text: &str| {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| w.chars().count() >= 2 && w.chars().any(char::is_alphabetic))
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>()
+    };
+    for word in words(&t.name) {
+        features.insert(format!("payee:{word}"));
+    }
+    for word in words(t.purpose.as_deref().unwrap_or_default()) {
+        features.insert(format!("purpose:{word}"));
+    }
+    let sign = if t.amount < 0.0 { '-' } else { '+' };
+    let magnitude = t.amount.abs().max(1.0).log2().floor() as i32;
+    features.insert(format!("amount:{:.2}", t.amount));
+    features.insert(format!("amount~{sign}{magnitude}"));
+    if let Some(number) = t.account_number.as_deref() {
+        let number = number.replace(' ', "").to_uppercase();
+        if !number.is_empty() {
+            features.insert(format!("iban:{number}"));
+        }
+    }
+    features.into_iter().collect()
+}
+
+/// Train on the categorized `transactions` except a `holdout` fraction
+/// (0 to 1) and test on those. The split is by a hash of the transaction
+/// id, so it is the same on every run.
+pub fn evaluate(
+    transactions: &[MoneymoneyTransaction],
+    holdout: f64,
+    min_confidence: f64,
+) -> Evaluation {
+    let categorized = transactions.iter().filter(|t| !is_uncategorized(t));
+    let (tested, trained): (Vec<_>, Vec<_>) = categorized.partition(|t| held_out(t.id, holdout));
+    let model = Classifier::train(trained.iter().copied());
+    let mut evaluation = Evaluation {
+        trained: trained.len(),
+        tested: tested.len(),
+        correct: 0,
+        accuracy: 0.0,
+        min_confidence,
+        confident: 0,
+        confident_correct: 0,
+        confident_accuracy: 0.0,
+    };
+    for t in &tested {
+        let Some(suggestion) = model.suggest(t) else {
+            continue;
+        };
+        let right = t.category.as_deref() == Some(suggestion.category.as_str());
+        evaluation.correct += usize::from(right);
+        if suggestion.confidence >= min_confidence {
+            evaluation.confident += 1;
+            evaluation.confident_correct += usize::from(right);
+        }
+    }
+    let ratio = |part: usize, whole: usize| {
+        if whole == 0 {
+            0.0
+        } else {
+            part as f64 / whole as f64
+        }
+    };
+    evaluation.accuracy = ratio(evaluation.correct, evaluation.tested);
+    evaluation.confident_accuracy = ratio(evaluation.confident_correct, evaluation.confident);
+    evaluation
+}
+
+/// Whether transaction `id` is in the held-out `fraction`, by a SplitMix64
+/// hash of the id.
+fn held_out(id: u64, fraction: f64) -> bool {
+    let mut z = id.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z as f64 / u64::MAX as f64) < fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{date, transaction, GIRO_UUID};
+
+    fn categorized(id: u64, name: &str, amount: f64, category: &str) -> MoneymoneyTransaction {
+        let mut t = transaction(id, GIRO_UUID, date(2024, 3, 1), name, amount);
+        t.category = Some(category.to_string());
+        t.category_uuid = uuid::Uuid::new_v4();
+        t
+    }
+
+    fn history() -> Vec<MoneymoneyTransaction> {
+        let mut history = Vec::new();
+        for i in 0..20 {
+            let amount = -20.0 - i as f64;
+            history.push(categorized(i * 3, "REWE Markt GmbH", amount, "Lebensmittel"));
+            history.push(categorized(i * 3 + 1, "NETFLIX.COM", -12.99, "Abos\\Streaming"));
+            let mut rent = categorized(i * 3 + 2, "Hausverwaltung", -1200.0, "Wohnen\\Miete");
+            rent.purpose = Some(format!("Miete {i:02}/2024 Whg 12"));
+            rent.account_number = Some("DE02 1203 0000 0000 2020 51".to_string());
+            history.push(rent);
+        }
+        history
+    }
+
+    #[test]
+    fn test_features() {
+        let mut t = transaction(1, GIRO_UUID, date(2024, 3, 1), "REWE Markt 0815", -23.5);
+        t.purpose = Some("EC 4711 Kartenzahlung".to_string());
+        t.account_number = Some("de02 1203 0000 0000 2020 51".to_string());
+        assert_eq!(
+            features(&t),
+            [
+                "amount:-23.50",
+                "amount~-4",
+                "iban:DE02120300000000202051",
+                "payee:markt",
+                "payee:rewe",
+                "purpose:ec",
+                "purpose:kartenzahlung",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_suggests_from_history() {
+        let model = Classifier::train(&history());
+        assert_eq!(model.transactions(), 60);
+        assert_eq!(model.categories().count(), 3);
+
+        let shop = transaction(100, GIRO_UUID, date(2024, 4, 2), "REWE City", -31.2);
+        let suggestion = model.suggest(&shop).unwrap();
+        assert_eq!(suggestion.category, "Lebensmittel");
+        assert!(suggestion.confidence > 0.9, "{suggestion:?}");
+
+        let mut rent = transaction(101, GIRO_UUID, date(2024, 4, 1), "HV Schmidt", -1250.0);
+        rent.account_number = Some("DE02120300000000202051".to_string());
+        assert_eq!(model.suggest(&rent).unwrap().category, "Wohnen\\Miete");
+
+        let ranked = model.rank(&shop);
+        assert_eq!(ranked.len(), 3);
+        let total: f64 = ranked.iter().map(|s| s.confidence).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        let unknown = transaction(102, GIRO_UUID, date(2024, 4, 3), "Kiosk", 0.5);
+        assert!(model.suggest(&unknown).is_none());
+        assert!(Classifier::default().suggest(&shop).is_none());
+    }
+
+    #[test]
+    fn test_saves_and_evaluates() {
+        let dir = std::env::temp_dir().join(format!("moneymoney-classify-{}", std::process::id()));
+        let path = dir.join("model.json");
+        let model = Classifier::train(&history());
+        model.save(&path).unwrap();
+        assert_eq!(Classifier::load(&path).unwrap(), model);
+        std::fs::write(&path, r#"{"version":0,"transactions":0,"vocabulary":0,"categories":{}}"#)
+            .unwrap();
+        let err = Classifier::load(&path).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("model version 0 is not supported, train it again"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let evaluation = evaluate(&history(), 0.25, 0.5);
+        assert_eq!(evaluation.trained + evaluation.tested, 60);
+        assert!(evaluation.tested > 5, "{evaluation:?}");
+        assert_eq!(evaluation.correct, evaluation.tested);
+        assert_eq!(evaluation.accuracy, 1.0);
+        assert_eq!(evaluate(&history(), 0.0, 0.5).tested, 0);
+    }
+}
@@ -0,0 +1,285 @@
+//! Relative and named date ranges such as `30d`, `last-month` or `2024-Q3`.
+//!
+//! A [`DateRange`] is an inclusive span of calendar days. It is parsed from
+//! the expressions below, relative to a given "today", and converts into
+//! [`ExportTransactionsParams`] so scripts don't have to compute dates
+//! themselves.
+//!
+//! | Expression | Range |
+//! |---|---|
+//! | `today`, `yesterday` | that day |
+//! | `30d`, `2w`, `6m`, `1y` | that many days / weeks / months / years ago until today |
+//! | `this-week`, `last-week` | Monday to Sunday |
+//! | `this-month`, `last-month` | first to last day of the month |
+//! | `this-quarter`, `last-quarter` | first to last day of the quarter |
+//! | `this-year`, `last-year` | January 1 to December 31 |
+//! | `mtd`, `qtd`, `ytd` | start of the month / quarter / year until today |
+//! | `2024`, `2024-Q3`, `2024-05`, `2024-05-17` | that year, quarter, month or day |
+//! | `<from>..<to>` | from the start of `<from>` to the end of `<to>` |
+//!
+//! # Example
+//!
+//! ```rust
+//! use chrono::NaiveDate;
+//! use moneymoney::date_range::DateRange;
+//! use moneymoney::export_transactions::ExportTransactionsParams;
+//!
+//! let today = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+//! let range = DateRange::parse("last-month", today).unwrap();
+//! assert_eq!(range.start, NaiveDate::from_ymd_opt(2024, 2, 1).unwrap());
+//! assert_eq!(range.end, NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+//!
+//! let params: ExportTransactionsParams = range.into();
+//! assert_eq!(params.to_date, Some(range.end));
+//! ```
+
+use chrono::{Datelike, Days, Months, NaiveDate};
+use thiserror::Error;
+
+use crate::export_transactions::ExportTransactionsParams;
+
+/// An inclusive range of calendar days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DateRange {
+    /// First day of the range.
+    pub start: NaiveDate,
+    /// Last day of the range.
+    pub end: NaiveDate,
+}
+
+/// Error returned when an expression is not a date range.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error(
+    "invalid date range {input:?}: expected YYYY-MM-DD, YYYY-MM, YYYY-Qn, YYYY, today, \
+     yesterday, <n>d/w/m/y, this-/last-week|month|quarter|year, mtd, qtd, ytd or <from>..<to>"
+)]
+pub struct ParseDateRangeError {
+    input: String,
+}
+
+impl DateRange {
+    /// A range from `start` to `end`, both inclusive.
+    pub fn new(start: NaiveDate, end: NaiveDate) -> Self {
+        Self { start, end }
+    }
+
+    /// Parse `expr` relative to `today`; see the [module docs](self) for the
+    /// accepted expressions.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ParseDateRangeError`] if `expr` isn't one of them, or a
+    /// `<from>..<to>` range ends before it starts.
+    pub fn parse(expr: &str, today: NaiveDate) -> Result<Self, ParseDateRangeError> {
+        let expr = expr.trim();
+        let error = || ParseDateRangeError {
+            input: expr.to_string(),
+        };
+        if let Some((from, to)) = expr.split_once("..") {
+            let start = Self::parse_single(from, today).ok_or_else(error)?.start;
+            let end = Self::parse_single(to, today).ok_or_else(error)?.end;
+            if end < start {
+                return Err(error());
+            }
+            return Ok(Self::new(start, end));
+        }
+        Self::parse_single(expr, today).ok_or_else(error)
+    }
+
+    /// Parse `expr` relative to the local date.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`DateRange::parse`].
+    pub fn parse_local(expr: &str) -> Result<Self, ParseDateRangeError> {
+        Self::parse(expr, chrono::Local::now().date_naive())
+    }
+
+    /// Whether `date` lies within the range.
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+
+    fn parse_single(expr: &str, today: NaiveDate) -> Option<Self> {
+        let expr = expr.trim().to_ascii_lowercase();
+        let day = |d: NaiveDate| Self::new(d, d);
+        let range = match expr.as_str() {
+            "today" => day(today),
+            "yesterday" => day(today.pred_opt()?),
+            "this-week" => week(today),
+            "last-week" => week(today.checked_sub_days(Days::new(7))?),
+            "this-month" => month(today.year(), today.month())?,
+            "last-month" => {
+                let d = today.checked_sub_months(Months::new(1))?;
+                month(d.year(), d.month())?
+            }
+            "this-quarter" => quarter(today.year(), quarter_of(today))?,
+            "last-quarter" => {
+                let d = today.checked_sub_months(Months::new(3))?;
+                quarter(d.year(), quarter_of(d))?
+            }
+            "this-year" => year(today.year())?,
+            "last-year" => year(today.year() - 1)?,
+            "mtd" => Self::new(month(today.year(), today.month())?.start, today),
+            "qtd" => Self::new(quarter(today.year(), quarter_of(today))?.start, today),
+            "ytd" => Self::new(year(today.year())?.start, today),
+            _ => return relative(&expr, today).or_else(|| absolute(&expr)),
+        };
+        Some(range)
+    }
+}
+
+impl std::str::FromStr for DateRange {
+    type Err = ParseDateRangeError;
+
+    /// Parse relative to the local date, see [`DateRange::parse_local`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_local(s)
+    }
+}
+
+impl From<DateRange> for ExportTransactionsParams {
+    fn from(range: DateRange) -> Self {
+        ExportTransactionsParams::new(range.start).to_date(range.end)
+    }
+}
+
+/// `<n>d`, `<n>w`, `<n>m` or `<n>y`: from that long ago until today.
+fn relative(expr: &str, today: NaiveDate) -> Option<DateRange> {
+    let unit = expr.chars().last()?;
+    let n: u32 = expr[..expr.len() - unit.len_utf8()].parse().ok()?;
+    let start = match unit {
+        'd' => today.checked_sub_days(Days::new(n.into()))?,
+        'w' => today.checked_sub_days(Days::new(u64::from(n) * 7))?,
+        'm' => today.checked_sub_months(Months::new(n))?,
+        'y' => today.checked_sub_months(Months::new(n.checked_mul(12)?))?,
+        _ => return None,
+    };
+    Some(DateRange::new(start, today))
+}
+
+/// `YYYY`, `YYYY-Qn`, `YYYY-MM` or `YYYY-MM-DD`.
+fn absolute(expr: &str) -> Option<DateRange> {
+    if let Ok(d) = NaiveDate::parse_from_str(expr, "%Y-%m-%d") {
+        return Some(DateRange::new(d, d));
+    }
+    let (y, rest) = match expr.split_once('-') {
+        Some((y, rest)) => (y, Some(rest)),
+        None => (expr, None),
+    };
+    if y.len() != 4 {
+        return None;
+    }
+    let y: i32 = y.parse().ok()?;
+    match rest {
+        None => year(y),
+        Some(q) if q.starts_with('q') => quarter(y, q[1..].parse().ok()?),
+        Some(m) if m.len() == 2 => month(y, m.parse().ok()?),
+        Some(_) => None,
+    }
+}
+
+fn week(day: NaiveDate) -> DateRange {
+    let week = day.week(chrono::Weekday::Mon);
+    DateRange::new(week.first_day(), week.last_day())
+}
+
+fn month(y: i32, m: u32) -> Option<DateRange> {
+    let start = NaiveDate::from_ymd_opt(y, m, 1)?;
+    let end = start.checked_add_months(Months::new(1))?.pred_opt()?;
+    Some(DateRange::new(start, end))
+}
+
+fn quarter_of(day: NaiveDate) -> u32 {
+    (day.month() - 1) / 3 + 1
+}
+
+fn quarter(y: i32, q: u32) -> Option<DateRange> {
+    if !(1..=4).contains(&q) {
+        return None;
+    }
+    let first = month(y, (q - 1) * 3 + 1)?;
+    let last = month(y, q * 3)?;
+    Some(DateRange::new(first.start, last.end))
+}
+
+fn year(y: i32) -> Option<DateRange> {
+    Some(DateRange::new(
+        NaiveDate::from_ymd_opt(y, 1, 1)?,
+        NaiveDate::from_ymd_opt(y, 12, 31)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::date;
+
+    fn parse(expr: &str) -> (NaiveDate, NaiveDate) {
+        // A Thursday in Q1 of a leap year.
+        let range = DateRange::parse(expr, date(2024, 3, 14)).unwrap();
+        (range.start, range.end)
+    }
+
+    #[test]
+    fn test_named_ranges() {
+        assert_eq!(parse("today"), (date(2024, 3, 14), date(2024, 3, 14)));
+        assert_eq!(parse("Yesterday"), (date(2024, 3, 13), date(2024, 3, 13)));
+        assert_eq!(parse("this-week"), (date(2024, 3, 11), date(2024, 3, 17)));
+        assert_eq!(parse("last-week"), (date(2024, 3, 4), date(2024, 3, 10)));
+        assert_eq!(parse("this-month"), (date(2024, 3, 1), date(2024, 3, 31)));
+        assert_eq!(parse("last-month"), (date(2024, 2, 1), date(2024, 2, 29)));
+        assert_eq!(parse("this-quarter"), (date(2024, 1, 1), date(2024, 3, 31)));
+        assert_eq!(parse("last-quarter"), (date(2023, 10, 1), date(2023, 12, 31)));
+        assert_eq!(parse("last-year"), (date(2023, 1, 1), date(2023, 12, 31)));
+        assert_eq!(parse("mtd"), (date(2024, 3, 1), date(2024, 3, 14)));
+        assert_eq!(parse("ytd"), (date(2024, 1, 1), date(2024, 3, 14)));
+    }
+
+    #[test]
+    fn test_relative_ranges() {
+        assert_eq!(parse("30d"), (date(2024, 2, 13), date(2024, 3, 14)));
+        assert_eq!(parse("2w"), (date(2024, 2, 29), date(2024, 3, 14)));
+        assert_eq!(parse("1m"), (date(2024, 2, 14), date(2024, 3, 14)));
+        assert_eq!(parse("1y"), (date(2023, 3, 14), date(2024, 3, 14)));
+    }
+
+    #[test]
+    fn test_absolute_ranges() {
+        assert_eq!(parse("2024-Q3"), (date(2024, 7, 1), date(2024, 9, 30)));
+        assert_eq!(parse("2023-q4"), (date(2023, 10, 1), date(2023, 12, 31)));
+        assert_eq!(parse("2024-05"), (date(2024, 5, 1), date(2024, 5, 31)));
+        assert_eq!(parse("2024-05-17"), (date(2024, 5, 17), date(2024, 5, 17)));
+        assert_eq!(parse("2022"), (date(2022, 1, 1), date(2022, 12, 31)));
+        assert_eq!(parse("2024-01..last-month"), (date(2024, 1, 1), date(2024, 2, 29)));
+    }
+
+    #[test]
+    fn test_invalid_ranges() {
+        let today = date(2024, 3, 14);
+        for expr in [
+            "",
+            "soon",
+            "2024-Q5",
+            "2024-13",
+            "24-05",
+            "d",
+            "-3d",
+            "2024-5",
+            "today..2020",
+        ] {
+            let err = DateRange::parse(expr, today).unwrap_err();
+            assert!(err.to_string().contains("invalid date range"), "{expr}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_into_params() {
+        let range = DateRange::new(date(2024, 1, 1), date(2024, 1, 31));
+        let params = ExportTransactionsParams::from(range);
+        assert_eq!(params.from_date, date(2024, 1, 1));
+        assert_eq!(params.to_date, Some(date(2024, 1, 31)));
+        assert!(range.contains(date(2024, 1, 31)));
+        assert!(!range.contains(date(2024, 2, 1)));
+    }
+}
@@ -0,0 +1,539 @@
+//! Compare two exports: what a bank refresh or someone else's
+//! recategorization actually changed.
+//!
+//! Each part is compared on its own, keyed the way MoneyMoney identifies
+//! things: transactions by `id`, accounts, categories and holdings by UUID.
+//! [`Diff::between`] compares every kind two sets of saved exports (or
+//! snapshots) have in common.
+//!
+//! # Example
+//!
+//! ```rust
+//! use moneymoney::diff::diff_transactions;
+//!
+//! let before = Vec::new();
+//! let after = Vec::new();
+//! let diff = diff_transactions(&before, &after);
+//! for change in &diff.changed {
+//!     for field in &change.changes {
+//!         println!("{} {}: {:?} -> {:?}", change.id, field.field, field.old, field.new);
+//!     }
+//! }
+//! # assert!(diff.is_empty());
+//! ```
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::export_accounts::MoneymoneyAccount;
+use crate::export_categories::{category_paths, MoneymoneyCategory};
+use crate::export_portfolio::Security;
+use crate::export_transactions::MoneymoneyTransaction;
+
+/// Everything that changed between two sets of exports. A part is `None`
+/// if it wasn't compared because one side lacks that export.
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Diff {
+    /// Added, removed and changed transactions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transactions: Option<TransactionsDiff>,
+    /// Accounts whose balance changed, appeared or disappeared.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balances: Option<Vec<BalanceChange>>,
+    /// Added, removed and changed categories.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub categories: Option<CategoriesDiff>,
+    /// Holdings whose quantity or market value changed, appeared or disappeared.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub portfolio: Option<Vec<PositionChange>>,
+}
+
+impl Diff {
+    /// Whether nothing changed in the compared parts.
+    pub fn is_empty(&self) -> bool {
+        self.transactions
+            .as_ref()
+            .is_none_or(TransactionsDiff::is_empty)
+            && self.balances.as_ref().is_none_or(Vec::is_empty)
+            && self
+                .categories
+                .as_ref()
+                .is_none_or(CategoriesDiff::is_empty)
+            && self.portfolio.as_ref().is_none_or(Vec::is_empty)
+    }
+
+    /// Compare every kind of export both `old` and `new` hold. Transactions
+    /// booked before `from_date` are ignored on both sides, so exports of
+    /// different windows can be compared on their overlap.
+    #[cfg(feature = "offline")]
+    pub fn between(
+        old: &crate::offline::SavedExports,
+        new: &crate::offline::SavedExports,
+        from_date: chrono::NaiveDate,
+    ) -> Self {
+        use crate::export_portfolio::ExportPortfolioParams;
+        use crate::export_transactions::ExportTransactionsParams;
+
+        let params = ExportTransactionsParams::new(from_date);
+        let transactions = match (old.transactions(&params), new.transactions(&params)) {
+            (Ok(old), Ok(new)) => Some(diff_transactions(&old.transactions, &new.transactions)),
+            _ => None,
+        };
+        let balances = match (old.accounts(), new.accounts()) {
+            (Ok(old), Ok(new)) => Some(diff_balances(old, new)),
+            _ => None,
+        };
+        let categories = match (old.categories(), new.categories()) {
+            (Ok(old), Ok(new)) => Some(diff_categories(old, new)),
+            _ => None,
+        };
+        let params = ExportPortfolioParams::new();
+        let portfolio = match (old.portfolio(&params), new.portfolio(&params)) {
+            (Ok(old), Ok(new)) => Some(diff_portfolio(&old.securities, &new.securities)),
+            _ => None,
+        };
+        Diff {
+            transactions,
+            balances,
+            categories,
+            portfolio,
+        }
+    }
+}
+
+/// A field's value on one side of a change; `null` in JSON if missing.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum FieldValue {
+    /// The field is empty or absent.
+    Missing,
+    /// A flag such as `booked` or `checkmark`.
+    Bool(bool),
+    /// An amount.
+    Number(f64),
+    /// Text such as a category path or comment.
+    Text(String),
+    /// A booking or value date.
+    Date(DateTime<Utc>),
+}
+
+impl std::fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldValue::Missing => f.write_str("(none)"),
+            FieldValue::Bool(b) => write!(f, "{b}"),
+            FieldValue::Number(n) => write!(f, "{n:.2}"),
+            FieldValue::Text(s) => write!(f, "{s:?}"),
+            FieldValue::Date(d) => write!(f, "{}", d.date_naive()),
+        }
+    }
+}
+
+impl From<Option<&str>> for FieldValue {
+    fn from(text: Option<&str>) -> Self {
+        match text.filter(|s| !s.is_empty()) {
+            Some(s) => FieldValue::Text(s.to_string()),
+            None => FieldValue::Missing,
+        }
+    }
+}
+
+/// One field that differs.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    /// The field's name in the JSON export, e.g. `category` or `bookingDate`.
+    pub field: &'static str,
+    /// Its value before.
+    pub old: FieldValue,
+    /// Its value after.
+    pub new: FieldValue,
+}
+
+/// Collects the fields that differ between two versions of an item.
+struct Changes(Vec<FieldChange>);
+
+impl Changes {
+    fn compare(&mut self, field: &'static str, old: FieldValue, new: FieldValue) {
+        let same = match (&old, &new) {
+            (FieldValue::Number(a), FieldValue::Number(b)) => same_amount(*a, *b),
+            (a, b) => a == b,
+        };
+        if !same {
+            self.0.push(FieldChange { field, old, new });
+        }
+    }
+}
+
+/// Amounts are compared to the cent.
+fn same_amount(a: f64, b: f64) -> bool {
+    (a - b).abs() < 0.005
+}
+
+/// Transactions that appeared, disappeared or changed.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct TransactionsDiff {
+    /// Only in the new export, by booking date.
+    pub added: Vec<MoneymoneyTransaction>,
+    /// Only in the old export, by booking date.
+    pub removed: Vec<MoneymoneyTransaction>,
+    /// In both with different fields, by booking date.
+    pub changed: Vec<TransactionChange>,
+}
+
+impl TransactionsDiff {
+    /// Whether no transaction was added, removed or changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// A transaction that is in both exports with different fields.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionChange {
+    /// The transaction id.
+    pub id: u64,
+    /// Its booking date in the new export.
+    pub booking_date: DateTime<Utc>,
+    /// Its payee in the new export.
+    pub name: String,
+    /// Its amount in the new export.
+    pub amount: f64,
+    /// Its account.
+    pub account_uuid: Uuid,
+    /// The fields that differ.
+    pub changes: Vec<FieldChange>,
+}
+
+/// Compare transactions by `id`: category, comment, checkmark, booked state,
+/// amount, payee, purpose and dates.
+pub fn diff_transactions(
+    old: &[MoneymoneyTransaction],
+    new: &[MoneymoneyTransaction],
+) -> TransactionsDiff {
+    let old_by_id: HashMap<u64, &MoneymoneyTransaction> = old.iter().map(|t| (t.id, t)).collect();
+    let new_by_id: HashMap<u64, &MoneymoneyTransaction> = new.iter().map(|t| (t.id, t)).collect();
+    let mut diff = TransactionsDiff::default();
+    for transaction in new {
+        let Some(before) = old_by_id.get(&transaction.id) else {
+            diff.added.push(transaction.clone());
+            continue;
+        };
+        let changes = transaction_changes(before, transaction);
+        if !changes.is_empty() {
+            diff.changed.push(TransactionChange {
+                id: transaction.id,
+                booking_date: transaction.booking_date,
+                name: transaction.name.clone(),
+                amount: transaction.amount,
+                account_uuid: transaction.account_uuid,
+                changes,
+            });
+        }
+    }
+    diff.removed = old
+        .iter()
+        .filter(|t| !new_by_id.contains_key(&t.id))
+        .cloned()
+        .collect();
+    diff.added.sort_by_key(|t| (t.booking_date, t.id));
+    diff.removed.sort_by_key(|t| (t.booking_date, t.id));
+    diff.changed.sort_by_key(|c| (c.booking_date, c.id));
+    diff
+}
+
+/// The fields [`diff_transactions`] compares, by their JSON names.
+pub(crate) fn transaction_fields(t: &MoneymoneyTransaction) -> [(&'static str, FieldValue); 9] {
+    [
+        ("category", t.category.as_deref().into()),
+        ("comment", t.comment.as_deref().into()),
+        ("checkmark", FieldValue::Bool(t.checkmark)),
+        ("booked", FieldValue::Bool(t.booked)),
+        ("amount", FieldValue::Number(t.amount)),
+        ("name", Some(t.name.as_str()).into()),
+        ("purpose", t.purpose.as_deref().into()),
+        ("bookingDate", FieldValue::Date(t.booking_date)),
+        ("valueDate", FieldValue::Date(t.value_date)),
+    ]
+}
+
+fn transaction_changes(
+    old: &MoneymoneyTransaction,
+    new: &MoneymoneyTransaction,
+) -> Vec<FieldChange> {
+    let mut changes = Changes(Vec::new());
+    for ((field, old), (_, new)) in transaction_fields(old)
+        .into_iter()
+        .zip(transaction_fields(new))
+    {
+        changes.compare(field, old, new);
+    }
+    changes.0
+}
+
+/// An account whose balance differs; a missing side means the account
+/// appeared or disappeared.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceChange {
+    /// The account.
+    pub account_uuid: Uuid,
+    /// Its name (in the new export, if it's there).
+    pub name: String,
+    /// Currency of the balance.
+    pub currency: String,
+    /// Balance before, if the account existed.
+    pub old: Option<f64>,
+    /// Balance after, if the account still exists.
+    pub new: Option<f64>,
+}
+
+/// Compare the balances of accounts (not groups) by UUID, in the new
+/// export's order followed by the accounts that disappeared.
+pub fn diff_balances(old: &[MoneymoneyAccount], new: &[MoneymoneyAccount]) -> Vec<BalanceChange> {
+    let balance = |a: &MoneymoneyAccount| a.balance.as_ref().map(|b| b.amount);
+    let change = |a: &MoneymoneyAccount, old, new| BalanceChange {
+        account_uuid: a.uuid,
+        name: a.name.clone(),
+        currency: a
+            .balance
+            .as_ref()
+            .map_or_else(|| a.currency.clone(), |b| b.currency.code().to_string()),
+        old,
+        new,
+    };
+    let old_by_uuid: HashMap<Uuid, &MoneymoneyAccount> = old
+        .iter()
+        .filter(|a| !a.group)
+        .map(|a| (a.uuid, a))
+        .collect();
+    let mut changes = Vec::new();
+    for account in new.iter().filter(|a| !a.group) {
+        match old_by_uuid.get(&account.uuid) {
+            Some(before) => {
+                let (old, new) = (balance(before), balance(account));
+                let same = match (old, new) {
+                    (Some(a), Some(b)) => same_amount(a, b),
+                    (a, b) => a == b,
+                };
+                if !same {
+                    changes.push(change(account, old, new));
+                }
+            }
+            None => changes.push(change(account, None, balance(account))),
+        }
+    }
+    for account in old.iter().filter(|a| !a.group) {
+        if !new.iter().any(|a| a.uuid == account.uuid) {
+            changes.push(change(account, balance(account), None));
+        }
+    }
+    changes
+}
+
+/// Categories that appeared, disappeared or changed.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct CategoriesDiff {
+    /// Only in the new export.
+    pub added: Vec<CategoryRef>,
+    /// Only in the old export.
+    pub removed: Vec<CategoryRef>,
+    /// In both with a different path or budget.
+    pub changed: Vec<CategoryChange>,
+}
+
+impl CategoriesDiff {
+    /// Whether no category was added, removed or changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// A category by UUID and backslash-separated path.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CategoryRef {
+    /// The category.
+    pub uuid: Uuid,
+    /// Its path, e.g. `Wohnen\Miete`.
+    pub path: String,
+}
+
+/// A category in both exports with a different path or budget.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CategoryChange {
+    /// The category, with its new path.
+    #[serde(flatten)]
+    pub category: CategoryRef,
+    /// `path`, `budget` (the budgeted amount) and `budgetPeriod` changes.
+    pub changes: Vec<FieldChange>,
+}
+
+/// Compare categories by UUID: new, removed, moved or renamed, and budget
+/// amount or period changes. What is still available of a budget changes
+/// with every booking and is not compared.
+pub fn diff_categories(old: &[MoneymoneyCategory], new: &[MoneymoneyCategory]) -> CategoriesDiff {
+    fn refs(categories: &[MoneymoneyCategory]) -> Vec<(CategoryRef, &MoneymoneyCategory)> {
+        category_paths(categories)
+            .into_iter()
+            .zip(categories)
+            .map(|(path, c)| (CategoryRef { uuid: c.uuid, path }, c))
+            .collect()
+    }
+    let (old, new) = (refs(old), refs(new));
+    let old_by_uuid: HashMap<Uuid, &(CategoryRef, &MoneymoneyCategory)> =
+        old.iter().map(|entry| (entry.0.uuid, entry)).collect();
+    let mut diff = CategoriesDiff::default();
+    for (category, after) in &new {
+        let Some((before_ref, before)) = old_by_uuid.get(&category.uuid) else {
+            diff.added.push(category.clone());
+            continue;
+        };
+        let mut changes = Changes(Vec::new());
+        changes.compare(
+            "path",
+            FieldValue::Text(before_ref.path.clone()),
+            FieldValue::Text(category.path.clone()),
+        );
+        let amount = |c: &MoneymoneyCategory| match &c.budget {
+            Some(budget) => FieldValue::Number(budget.amount),
+            None => FieldValue::Missing,
+        };
+        changes.compare("budget", amount(before), amount(after));
+        changes.compare(
+            "budgetPeriod",
+            before.budget.as_ref().map(|b| b.period.as_str()).into(),
+            after.budget.as_ref().map(|b| b.period.as_str()).into(),
+        );
+        if !changes.0.is_empty() {
+            diff.changed.push(CategoryChange {
+                category: category.clone(),
+                changes: changes.0,
+            });
+        }
+    }
+    diff.removed = old
+        .iter()
+        .filter(|(category, _)| !new.iter().any(|(c, _)| c.uuid == category.uuid))
+        .map(|(category, _)| category.clone())
+        .collect();
+    diff
+}
+
+/// A holding whose quantity or market value differs; a missing side means
+/// the position was opened or closed.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionChange {
+    /// The holding.
+    pub uuid: Uuid,
+    /// The security's name.
+    pub name: String,
+    /// The portfolio account it's held in.
+    pub account_name: String,
+    /// Currency of the market values.
+    pub currency: String,
+    /// Quantity before, if held.
+    pub old_quantity: Option<f64>,
+    /// Quantity after, if still held.
+    pub new_quantity: Option<f64>,
+    /// Market value before, if held.
+    pub old_market_value: Option<f64>,
+    /// Market value after, if still held.
+    pub new_market_value: Option<f64>,
+}
+
+/// Compare holdings by UUID, in the new export's order followed by the
+/// positions that were closed.
+pub fn diff_portfolio(old: &[Security], new: &[Security]) -> Vec<PositionChange> {
+    let change =
+        |s: &Security, before: Option<&Security>, after: Option<&Security>| PositionChange {
+            uuid: s.uuid,
+            name: s.name.clone(),
+            account_name: s.account_name.clone(),
+            currency: s.currency.clone(),
+            old_quantity: before.map(|s| s.quantity),
+            new_quantity: after.map(|s| s.quantity),
+            old_market_value: before.map(|s| s.market_value),
+            new_market_value: after.map(|s| s.market_value),
+        };
+    let old_by_uuid: HashMap<Uuid, &Security> = old.iter().map(|s| (s.uuid, s)).collect();
+    let mut changes = Vec::new();
+    for security in new {
+        match old_by_uuid.get(&security.uuid) {
+            Some(before) => {
+                // Quantities can be fractional, so they get more precision than amounts.
+                if (before.quantity - security.quantity).abs() > 1e-9
+                    || !same_amount(before.market_value, security.market_value)
+                {
+                    changes.push(change(security, Some(before), Some(security)));
+                }
+            }
+            None => changes.push(change(security, None, Some(security))),
+        }
+    }
+    for security in old {
+        if !new.iter().any(|s| s.uuid == security.uuid) {
+            changes.push(change(security, Some(security), None));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{cash, date, giro, transaction, GIRO_UUID};
+
+    #[test]
+    fn test_diff_transactions_by_id() {
+        let mut pending = transaction(2, GIRO_UUID, date(2024, 3, 2), "Amazon", -19.99);
+        pending.booked = false;
+        let old = vec![
+            transaction(1, GIRO_UUID, date(2024, 3, 1), "REWE", -42.0),
+            pending.clone(),
+            transaction(3, GIRO_UUID, date(2024, 3, 3), "Gone", -1.0),
+        ];
+        let mut booked = pending;
+        booked.booked = true;
+        booked.category = Some("Shopping\\Online".to_string());
+        let new = vec![
+            transaction(4, GIRO_UUID, date(2024, 3, 4), "Salary", 2500.0),
+            booked,
+            old[0].clone(),
+        ];
+
+        let diff = diff_transactions(&old, &new);
+        assert_eq!(diff.added.iter().map(|t| t.id).collect::<Vec<_>>(), [4]);
+        assert_eq!(diff.removed.iter().map(|t| t.id).collect::<Vec<_>>(), [3]);
+        assert_eq!(diff.changed.len(), 1);
+        let fields: Vec<_> = diff.changed[0].changes.iter().map(|c| c.field).collect();
+        assert_eq!(fields, ["category", "booked"]);
+        assert_eq!(diff.changed[0].changes[1].new, FieldValue::Bool(true));
+        assert!(diff_transactions(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_diff_balances_and_json() {
+        let old = vec![giro(), cash()];
+        let mut after = giro();
+        after.balance.as_mut().unwrap().amount -= 42.0;
+        let new = vec![after];
+        let changes = diff_balances(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert_eq!((changes[0].old, changes[0].new), (Some(1234.56), Some(1192.56)));
+        assert_eq!((changes[1].name.as_str(), changes[1].new), ("Bargeld", None));
+
+        let diff = Diff {
+            balances: Some(changes),
+            ..Diff::default()
+        };
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(json["balances"][1]["accountUuid"], cash().uuid.to_string());
+        assert!(json.get("transactions").is_none());
+        assert!(!diff.is_empty());
+    }
+}
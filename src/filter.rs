@@ -0,0 +1,381 @@
+//! Client-side transaction filters and sorting.
+//!
+//! MoneyMoney itself only filters exports by date, account and category.
+//! [`TransactionFilter`] narrows an exported list further (amount, sign,
+//! booking state, checkmark, payee and purpose patterns, category,
+//! currency, counterpart IBAN) and [`SortOrder`] orders it, with the same
+//! semantics as the `moneymoney export transactions` flags.
+//!
+//! Requires the `filter` feature (enabled by `cli`).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use chrono::NaiveDate;
+//! use moneymoney::export_transactions::ExportTransactionsParams;
+//! use moneymoney::filter::{sort_transactions, Sign, SortOrder, TransactionFilter};
+//! use regex::Regex;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let params = ExportTransactionsParams::new(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+//! let mut transactions = moneymoney::export_transactions(params)?.transactions;
+//!
+//! let filter = TransactionFilter::new()
+//!     .sign(Sign::Expense)
+//!     .max_amount(-100.0)
+//!     .payee(Regex::new("(?i)amazon")?)
+//!     .booked_only();
+//! filter.apply(&mut transactions);
+//! sort_transactions(&mut transactions, "-amount".parse()?);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cmp::Ordering;
+
+use regex::Regex;
+use thiserror::Error;
+
+use crate::export_transactions::MoneymoneyTransaction;
+
+/// Direction of money flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sign {
+    /// Positive amounts.
+    Income,
+    /// Negative amounts.
+    Expense,
+}
+
+/// Conditions a transaction has to meet; unset fields match everything.
+///
+/// All set conditions must hold. Build one with the chainable setters, or
+/// fill in the public fields directly.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionFilter {
+    /// Smallest amount to keep (inclusive, signed).
+    pub min_amount: Option<f64>,
+    /// Largest amount to keep (inclusive, signed).
+    pub max_amount: Option<f64>,
+    /// Keep only income or only expenses.
+    pub sign: Option<Sign>,
+    /// `Some(true)` keeps booked, `Some(false)` pending transactions.
+    pub booked: Option<bool>,
+    /// Keep only checked (`true`) or unchecked (`false`) transactions.
+    pub checkmark: Option<bool>,
+    /// Pattern the payee (`name`) must match.
+    pub payee: Option<Regex>,
+    /// Pattern the purpose must match; transactions without one don't.
+    pub purpose: Option<Regex>,
+    /// Keep only transactions without a category.
+    pub uncategorized: bool,
+    /// Currency code to keep (case-insensitive).
+    pub currency: Option<String>,
+    /// Counterpart IBAN or account number to keep (spaces and case ignored).
+    pub counterpart_iban: Option<String>,
+}
+
+impl TransactionFilter {
+    /// A filter that keeps every transaction.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep amounts of at least `amount`.
+    pub fn min_amount(mut self, amount: f64) -> Self {
+        self.min_amount = Some(amount);
+        self
+    }
+
+    /// Keep amounts of at most `amount`.
+    pub fn max_amount(mut self, amount: f64) -> Self {
+        self.max_amount = Some(amount);
+        self
+    }
+
+    /// Keep only income or only expenses.
+    pub fn sign(mut self, sign: Sign) -> Self {
+        self.sign = Some(sign);
+        self
+    }
+
+    /// Keep only booked transactions.
+    pub fn booked_only(mut self) -> Self {
+        self.booked = Some(true);
+        self
+    }
+
+    /// Keep only pending (not yet booked) transactions.
+    pub fn pending_only(mut self) -> Self {
+        self.booked = Some(false);
+        self
+    }
+
+    /// Keep only checked (`true`) or unchecked (`false`) transactions.
+    pub fn checkmark(mut self, checked: bool) -> Self {
+        self.checkmark = Some(checked);
+        self
+    }
+
+    /// Keep payees matching `pattern`.
+    pub fn payee(mut self, pattern: Regex) -> Self {
+        self.payee = Some(pattern);
+        self
+    }
+
+    /// Keep purposes matching `pattern`.
+    pub fn purpose(mut self, pattern: Regex) -> Self {
+        self.purpose = Some(pattern);
+        self
+    }
+
+    /// Keep only uncategorized transactions.
+    pub fn uncategorized(mut self) -> Self {
+        self.uncategorized = true;
+        self
+    }
+
+    /// Keep transactions in `currency`.
+    pub fn currency(mut self, currency: impl Into<String>) -> Self {
+        self.currency = Some(currency.into());
+        self
+    }
+
+    /// Keep transactions with this counterpart IBAN or account number.
+    pub fn counterpart_iban(mut self, iban: impl Into<String>) -> Self {
+        self.counterpart_iban = Some(iban.into());
+        self
+    }
+
+    /// Whether no condition is set, i.e. every transaction matches.
+    pub fn is_empty(&self) -> bool {
+        let Self {
+            min_amount,
+            max_amount,
+            sign,
+            booked,
+            checkmark,
+            payee,
+            purpose,
+            uncategorized,
+            currency,
+            counterpart_iban,
+        } = self;
+        min_amount.is_none()
+            && max_amount.is_none()
+            && sign.is_none()
+            && booked.is_none()
+            && checkmark.is_none()
+            && payee.is_none()
+            && purpose.is_none()
+            && !uncategorized
+            && currency.is_none()
+            && counterpart_iban.is_none()
+    }
+
+    /// Whether `t` meets every condition.
+    pub fn matches(&self, t: &MoneymoneyTransaction) -> bool {
+        let compact = |s: &str| s.replace(' ', "").to_ascii_uppercase();
+        self.min_amount.is_none_or(|min| t.amount >= min)
+            && self.max_amount.is_none_or(|max| t.amount <= max)
+            && self.sign.is_none_or(|sign| match sign {
+                Sign::Income => t.amount > 0.0,
+                Sign::Expense => t.amount < 0.0,
+            })
+            && self.booked.is_none_or(|booked| t.booked == booked)
+            && self.checkmark.is_none_or(|checked| t.checkmark == checked)
+            && self.payee.as_ref().is_none_or(|re| re.is_match(&t.name))
+            && self
+                .purpose
+                .as_ref()
+                .is_none_or(|re| t.purpose.as_deref().is_some_and(|p| re.is_match(p)))
+            && (!self.uncategorized || is_uncategorized(t))
+            && self
+                .currency
+                .as_deref()
+                .is_none_or(|c| t.currency.eq_ignore_ascii_case(c.trim()))
+            && self.counterpart_iban.as_deref().is_none_or(|iban| {
+                t.account_number
+                    .as_deref()
+                    .is_some_and(|n| compact(n) == compact(iban))
+            })
+    }
+
+    /// Remove the transactions that don't match.
+    pub fn apply(&self, transactions: &mut Vec<MoneymoneyTransaction>) {
+        transactions.retain(|t| self.matches(t));
+    }
+}
+
+/// No category assigned: MoneyMoney exports an empty category name or a nil
+/// category UUID for these.
+pub(crate) fn is_uncategorized(t: &MoneymoneyTransaction) -> bool {
+    t.category.as_deref().is_none_or(str::is_empty) || t.category_uuid.is_nil()
+}
+
+/// Field to sort transactions by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Booking date (`date`).
+    Date,
+    /// Value date (`value-date`).
+    ValueDate,
+    /// Signed amount (`amount`).
+    Amount,
+    /// Payee, case-insensitive (`payee`).
+    Payee,
+    /// Category path (`category`).
+    Category,
+    /// Transaction id (`id`).
+    Id,
+}
+
+/// A sort key and direction, parsed from e.g. `date` or `-amount`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortOrder {
+    /// Field to compare.
+    pub key: SortKey,
+    /// Largest first.
+    pub descending: bool,
+}
+
+/// Error returned for an unknown sort key.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("unknown sort key {0:?}: expected date, value-date, amount, payee, category or id, optionally prefixed with `-` for descending")]
+pub struct ParseSortOrderError(String);
+
+impl std::str::FromStr for SortOrder {
+    type Err = ParseSortOrderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let (descending, name) = match trimmed.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, trimmed),
+        };
+        let key = match name {
+            "date" => SortKey::Date,
+            "value-date" => SortKey::ValueDate,
+            "amount" => SortKey::Amount,
+            "payee" => SortKey::Payee,
+            "category" => SortKey::Category,
+            "id" => SortKey::Id,
+            _ => return Err(ParseSortOrderError(s.to_string())),
+        };
+        Ok(Self { key, descending })
+    }
+}
+
+impl SortOrder {
+    /// Compare two transactions in this order.
+    pub fn compare(&self, a: &MoneymoneyTransaction, b: &MoneymoneyTransaction) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Date => a.booking_date.cmp(&b.booking_date),
+            SortKey::ValueDate => a.value_date.cmp(&b.value_date),
+            SortKey::Amount => a.amount.total_cmp(&b.amount),
+            SortKey::Payee => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortKey::Category => a.category.cmp(&b.category),
+            SortKey::Id => a.id.cmp(&b.id),
+        };
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+/// Sort `transactions` by `order`; ties keep their export order.
+pub fn sort_transactions(transactions: &mut [MoneymoneyTransaction], order: SortOrder) {
+    transactions.sort_by(|a, b| order.compare(a, b));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{date, transaction, GIRO_UUID};
+    use uuid::Uuid;
+
+    fn sample() -> Vec<MoneymoneyTransaction> {
+        let mut salary = transaction(1, GIRO_UUID, date(2024, 1, 31), "Arbeitgeber GmbH", 3000.0);
+        salary.purpose = Some("Gehalt Januar".to_string());
+        salary.category = Some("Einkommen\\Gehalt".to_string());
+        salary.category_uuid = Uuid::new_v4();
+        salary.checkmark = true;
+        let mut rent = transaction(2, GIRO_UUID, date(2024, 2, 1), "Hausverwaltung", -900.0);
+        rent.account_number = Some("DE02 1203 0000 0000 2020 51".to_string());
+        rent.category = Some("Wohnen\\Miete".to_string());
+        rent.category_uuid = Uuid::new_v4();
+        let mut pending = transaction(3, GIRO_UUID, date(2024, 2, 3), "REWE Markt", -42.5);
+        pending.booked = false;
+        let mut usd = transaction(4, GIRO_UUID, date(2024, 1, 15), "amazon.com", -19.99);
+        usd.currency = "USD".to_string();
+        vec![salary, rent, pending, usd]
+    }
+
+    fn ids(filter: &TransactionFilter) -> Vec<u64> {
+        let mut transactions = sample();
+        filter.apply(&mut transactions);
+        transactions.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn test_filters() {
+        assert!(TransactionFilter::new().is_empty());
+        assert_eq!(ids(&TransactionFilter::new()), [1, 2, 3, 4]);
+        assert_eq!(ids(&TransactionFilter::new().sign(Sign::Income)), [1]);
+        assert_eq!(ids(&TransactionFilter::new().sign(Sign::Expense)), [2, 3, 4]);
+        assert_eq!(ids(&TransactionFilter::new().min_amount(-100.0).max_amount(0.0)), [3, 4]);
+        assert_eq!(ids(&TransactionFilter::new().booked_only()), [1, 2, 4]);
+        assert_eq!(ids(&TransactionFilter::new().pending_only()), [3]);
+        assert_eq!(ids(&TransactionFilter::new().checkmark(true)), [1]);
+        assert_eq!(
+            ids(&TransactionFilter::new().payee(Regex::new("(?i)^rewe|amazon").unwrap())),
+            [3, 4]
+        );
+        assert_eq!(ids(&TransactionFilter::new().purpose(Regex::new("Gehalt").unwrap())), [1]);
+        assert_eq!(ids(&TransactionFilter::new().uncategorized()), [3, 4]);
+        assert_eq!(ids(&TransactionFilter::new().currency("usd")), [4]);
+        assert_eq!(ids(&TransactionFilter::new().counterpart_iban("de02120300000000202051")), [2]);
+        let combined = TransactionFilter::new()
+            .sign(Sign::Expense)
+            .booked_only()
+            .currency("EUR");
+        assert!(!combined.is_empty());
+        assert_eq!(ids(&combined), [2]);
+    }
+
+    #[test]
+    fn test_sort_order() {
+        let order: SortOrder = "-amount".parse().unwrap();
+        assert_eq!(
+            order,
+            SortOrder {
+                key: SortKey::Amount,
+                descending: true
+            }
+        );
+        assert!("size".parse::<SortOrder>().is_err());
+
+        let mut transactions = sample();
+        sort_transactions(&mut transactions, order);
+        let ids: Vec<u64> = transactions.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [1, 4, 3, 2]);
+
+        sort_transactions(&mut transactions, "date".parse().unwrap());
+        let ids: Vec<u64> = transactions.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [4, 1, 2, 3]);
+
+        sort_transactions(&mut transactions, "payee".parse().unwrap());
+        let names: Vec<&str> = transactions.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "amazon.com",
+                "Arbeitgeber GmbH",
+                "Hausverwaltung",
+                "REWE Markt"
+            ]
+        );
+    }
+}
@@ -0,0 +1,392 @@
+//! Apache Arrow `RecordBatch` conversion for analytics tools.
+//!
+//! Converts transactions, accounts and securities into typed columnar batches
+//! that DuckDB, Polars or pandas can load without guessing types:
+//!
+//! - Timestamps are `Timestamp(Millisecond, "UTC")`.
+//! - Money amounts are `Decimal128(18, 2)`; quantities and unit prices of
+//!   securities are `Decimal128(24, 6)`.
+//! - Low-cardinality strings (category, currency, account type, asset class)
+//!   are dictionary-encoded (`Dictionary(Int32, Utf8)`).
+//! - UUIDs are their hyphenated string form.
+//!
+//! Column names are the snake_case field names. See [`super::parquet`] to
+//! write a batch to a file.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use chrono::NaiveDate;
+//! use moneymoney::export_transactions::ExportTransactionsParams;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let params = ExportTransactionsParams::new(NaiveDate::from_ymd_opt(2020, 1, 1).unwrap());
+//! let response = moneymoney::export_transactions(params)?;
+//! let batch = moneymoney::formats::arrow::transactions_batch(&response.transactions)?;
+//! println!("{} rows, schema: {:?}", batch.num_rows(), batch.schema());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use arrow_array::builder::StringDictionaryBuilder;
+use arrow_array::types::Int32Type;
+use arrow_array::{
+    ArrayRef, BooleanArray, Decimal128Array, Float64Array, RecordBatch, StringArray,
+    TimestampMillisecondArray, UInt64Array, UInt8Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, Utc};
+
+use crate::export_accounts::MoneymoneyAccount;
+use crate::export_portfolio::Security;
+use crate::export_transactions::MoneymoneyTransaction;
+
+/// Precision and scale of money amounts.
+pub const AMOUNT_DECIMAL: (u8, i8) = (18, 2);
+
+/// Precision and scale of security quantities and unit prices.
+pub const QUANTITY_DECIMAL: (u8, i8) = (24, 6);
+
+fn timestamp() -> DataType {
+    DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))
+}
+
+fn dictionary() -> DataType {
+    DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8))
+}
+
+fn amount() -> DataType {
+    DataType::Decimal128(AMOUNT_DECIMAL.0, AMOUNT_DECIMAL.1)
+}
+
+fn quantity() -> DataType {
+    DataType::Decimal128(QUANTITY_DECIMAL.0, QUANTITY_DECIMAL.1)
+}
+
+fn utf8(name: &str, nullable: bool) -> Field {
+    Field::new(name, DataType::Utf8, nullable)
+}
+
+/// Schema of [`transactions_batch`].
+pub fn transactions_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::UInt64, false),
+        utf8("account_uuid", false),
+        Field::new("booking_date", timestamp(), false),
+        Field::new("value_date", timestamp(), false),
+        utf8("name", false),
+        Field::new("amount", amount(), true),
+        Field::new("currency", dictionary(), false),
+        Field::new("booked", DataType::Boolean, false),
+        Field::new("checkmark", DataType::Boolean, false),
+        Field::new("category", dictionary(), true),
+        utf8("category_uuid", false),
+        utf8("purpose", true),
+        utf8("comment", true),
+        utf8("booking_text", true),
+        utf8("account_number", true),
+        utf8("bank_code", true),
+        Field::new("transaction_code", DataType::UInt64, true),
+        Field::new("text_key_extension", DataType::UInt64, true),
+        utf8("purpose_code", true),
+        utf8("booking_key", true),
+        utf8("primanota_number", true),
+        utf8("batch_reference", true),
+        utf8("end_to_end_reference", true),
+        utf8("mandate_reference", true),
+        utf8("creditor_id", true),
+        utf8("return_reason", true),
+    ]))
+}
+
+/// Schema of [`accounts_batch`].
+pub fn accounts_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        utf8("uuid", false),
+        utf8("name", false),
+        Field::new("type", dictionary(), false),
+        utf8("account_number", false),
+        utf8("bank_code", false),
+        utf8("sub_account", true),
+        utf8("iban", true),
+        utf8("bic", true),
+        utf8("owner", false),
+        Field::new("currency", dictionary(), false),
+        Field::new("group", DataType::Boolean, false),
+        Field::new("portfolio", DataType::Boolean, false),
+        Field::new("indentation", DataType::UInt8, false),
+        utf8("comment", true),
+        Field::new("balance", amount(), true),
+        Field::new("balance_currency", dictionary(), true),
+        Field::new("balance_date", timestamp(), true),
+        Field::new("refresh_timestamp", timestamp(), false),
+    ]))
+}
+
+/// Schema of [`securities_batch`].
+pub fn securities_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        utf8("uuid", false),
+        utf8("account_uuid", false),
+        utf8("account_name", false),
+        utf8("name", false),
+        utf8("isin", false),
+        utf8("wkn", false),
+        utf8("symbol", false),
+        Field::new("asset_class", dictionary(), false),
+        Field::new("quantity", quantity(), true),
+        Field::new("currency", dictionary(), false),
+        Field::new("market_price", quantity(), true),
+        Field::new("market_value", amount(), true),
+        Field::new("purchase_price", quantity(), true),
+        Field::new("purchase_value", amount(), true),
+        Field::new("profit", amount(), true),
+        Field::new("profit_percent", DataType::Float64, false),
+    ]))
+}
+
+/// Convert transactions into a batch with [`transactions_schema`].
+///
+/// # Errors
+///
+/// Returns [`ArrowError`] if the columns don't match the schema, which would
+/// be a bug in this module.
+pub fn transactions_batch(
+    transactions: &[MoneymoneyTransaction],
+) -> Result<RecordBatch, ArrowError> {
+    let strings = |f: fn(&MoneymoneyTransaction) -> Option<&str>| -> ArrayRef {
+        Arc::new(transactions.iter().map(f).collect::<StringArray>())
+    };
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(
+            transactions
+                .iter()
+                .map(|t| Some(t.id))
+                .collect::<UInt64Array>(),
+        ),
+        uuids(transactions.iter().map(|t| &t.account_uuid)),
+        timestamps(transactions.iter().map(|t| Some(&t.booking_date))),
+        timestamps(transactions.iter().map(|t| Some(&t.value_date))),
+        strings(|t| Some(&t.name)),
+        decimals(transactions.iter().map(|t| Some(t.amount)), AMOUNT_DECIMAL)?,
+        dictionary_column(transactions.iter().map(|t| Some(t.currency.as_str()))),
+        booleans(transactions.iter().map(|t| t.booked)),
+        booleans(transactions.iter().map(|t| t.checkmark)),
+        dictionary_column(transactions.iter().map(|t| t.category.as_deref())),
+        uuids(transactions.iter().map(|t| &t.category_uuid)),
+        strings(|t| t.purpose.as_deref()),
+        strings(|t| t.comment.as_deref()),
+        strings(|t| t.booking_text.as_deref()),
+        strings(|t| t.account_number.as_deref()),
+        strings(|t| t.bank_code.as_deref()),
+        Arc::new(
+            transactions
+                .iter()
+                .map(|t| t.transaction_code)
+                .collect::<UInt64Array>(),
+        ),
+        Arc::new(
+            transactions
+                .iter()
+                .map(|t| t.text_key_extension)
+                .collect::<UInt64Array>(),
+        ),
+        strings(|t| t.purpose_code.as_deref()),
+        strings(|t| t.booking_key.as_deref()),
+        strings(|t| t.primanota_number.as_deref()),
+        strings(|t| t.batch_reference.as_deref()),
+        strings(|t| t.end_to_end_reference.as_deref()),
+        strings(|t| t.mandate_reference.as_deref()),
+        strings(|t| t.creditor_id.as_deref()),
+        strings(|t| t.return_reason.as_deref()),
+    ];
+    RecordBatch::try_new(transactions_schema(), columns)
+}
+
+/// Convert accounts into a batch with [`accounts_schema`].
+///
+/// # Errors
+///
+/// Returns [`ArrowError`] if the columns don't match the schema, which would
+/// be a bug in this module.
+pub fn accounts_batch(accounts: &[MoneymoneyAccount]) -> Result<RecordBatch, ArrowError> {
+    let strings = |f: fn(&MoneymoneyAccount) -> Option<&str>| -> ArrayRef {
+        Arc::new(accounts.iter().map(f).collect::<StringArray>())
+    };
+    let columns: Vec<ArrayRef> = vec![
+        uuids(accounts.iter().map(|a| &a.uuid)),
+        strings(|a| Some(&a.name)),
+        dictionary_column(accounts.iter().map(|a| Some(a.r#type.as_str()))),
+        strings(|a| Some(&a.account_number)),
+        strings(|a| Some(&a.bank_code)),
+        strings(|a| a.sub_account.as_deref()),
+        strings(|a| a.iban.as_deref()),
+        strings(|a| a.bic.as_deref()),
+        strings(|a| Some(&a.owner)),
+        dictionary_column(accounts.iter().map(|a| Some(a.currency.as_str()))),
+        booleans(accounts.iter().map(|a| a.group)),
+        booleans(accounts.iter().map(|a| a.portfolio)),
+        Arc::new(
+            accounts
+                .iter()
+                .map(|a| Some(a.indentation))
+                .collect::<UInt8Array>(),
+        ),
+        strings(|a| a.comment.as_deref()),
+        decimals(
+            accounts
+                .iter()
+                .map(|a| a.balance.as_ref().map(|b| b.amount)),
+            AMOUNT_DECIMAL,
+        )?,
+        dictionary_column(
+            accounts
+                .iter()
+                .map(|a| a.balance.as_ref().map(|b| b.currency.code())),
+        ),
+        timestamps(accounts.iter().map(|a| a.balance_date.as_ref())),
+        timestamps(accounts.iter().map(|a| Some(&a.refresh_timestamp))),
+    ];
+    RecordBatch::try_new(accounts_schema(), columns)
+}
+
+/// Convert securities into a batch with [`securities_schema`].
+///
+/// # Errors
+///
+/// Returns [`ArrowError`] if the columns don't match the schema, which would
+/// be a bug in this module.
+pub fn securities_batch(securities: &[Security]) -> Result<RecordBatch, ArrowError> {
+    let strings = |f: fn(&Security) -> &str| -> ArrayRef {
+        Arc::new(
+            securities
+                .iter()
+                .map(|s| Some(f(s)))
+                .collect::<StringArray>(),
+        )
+    };
+    let columns: Vec<ArrayRef> = vec![
+        uuids(securities.iter().map(|s| &s.uuid)),
+        uuids(securities.iter().map(|s| &s.account_uuid)),
+        strings(|s| &s.account_name),
+        strings(|s| &s.name),
+        strings(|s| &s.isin),
+        strings(|s| &s.wkn),
+        strings(|s| &s.symbol),
+        dictionary_column(securities.iter().map(|s| Some(s.asset_class.as_str()))),
+        decimals(securities.iter().map(|s| Some(s.quantity)), QUANTITY_DECIMAL)?,
+        dictionary_column(securities.iter().map(|s| Some(s.currency.as_str()))),
+        decimals(securities.iter().map(|s| Some(s.market_price)), QUANTITY_DECIMAL)?,
+        decimals(securities.iter().map(|s| Some(s.market_value)), AMOUNT_DECIMAL)?,
+        decimals(securities.iter().map(|s| Some(s.purchase_price)), QUANTITY_DECIMAL)?,
+        decimals(securities.iter().map(|s| Some(s.purchase_value)), AMOUNT_DECIMAL)?,
+        decimals(securities.iter().map(|s| Some(s.profit)), AMOUNT_DECIMAL)?,
+        Arc::new(
+            securities
+                .iter()
+                .map(|s| Some(s.profit_percent))
+                .collect::<Float64Array>(),
+        ),
+    ];
+    RecordBatch::try_new(securities_schema(), columns)
+}
+
+fn uuids<'a>(values: impl Iterator<Item = &'a uuid::Uuid>) -> ArrayRef {
+    Arc::new(values.map(|u| Some(u.to_string())).collect::<StringArray>())
+}
+
+fn timestamps<'a>(values: impl Iterator<Item = Option<&'a DateTime<Utc>>>) -> ArrayRef {
+    Arc::new(
+        values
+            .map(|v| v.map(|at| at.timestamp_millis()))
+            .collect::<TimestampMillisecondArray>()
+            .with_timezone("UTC"),
+    )
+}
+
+fn booleans(values: impl Iterator<Item = bool>) -> ArrayRef {
+    Arc::new(values.map(Some).collect::<BooleanArray>())
+}
+
+fn dictionary_column<'a>(values: impl Iterator<Item = Option<&'a str>>) -> ArrayRef {
+    let mut builder = StringDictionaryBuilder::<Int32Type>::new();
+    for value in values {
+        builder.append_option(value);
+    }
+    Arc::new(builder.finish())
+}
+
+/// Round `values` to `scale` decimal places; non-finite values become null.
+fn decimals(
+    values: impl Iterator<Item = Option<f64>>,
+    (precision, scale): (u8, i8),
+) -> Result<ArrayRef, ArrowError> {
+    let factor = 10f64.powi(scale.into());
+    let array = values
+        .map(|v| {
+            v.filter(|v| v.is_finite())
+                .map(|v| (v * factor).round() as i128)
+        })
+        .collect::<Decimal128Array>()
+        .with_precision_and_scale(precision, scale)?;
+    Ok(Arc::new(array))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, date, transaction, GIRO_UUID};
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Decimal128Type, TimestampMillisecondType};
+    use arrow_array::Array;
+
+    #[test]
+    fn test_transactions_batch_types() {
+        let mut rent = transaction(1, GIRO_UUID, date(2024, 3, 1), "Miete", -900.0);
+        rent.category = Some("Wohnen".to_string());
+        rent.transaction_code = Some(5);
+        let mut groceries = transaction(2, GIRO_UUID, date(2024, 3, 2), "REWE", -45.675);
+        groceries.category = Some("Wohnen".to_string());
+        let salary = transaction(3, GIRO_UUID, date(2024, 3, 3), "Gehalt", 2500.1);
+
+        let batch = transactions_batch(&[rent, groceries, salary]).unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.schema(), transactions_schema());
+
+        let amounts = batch.column_by_name("amount").unwrap();
+        let amounts = amounts.as_primitive::<Decimal128Type>();
+        assert_eq!(amounts.value_as_string(0), "-900.00");
+        assert_eq!(amounts.value_as_string(1), "-45.68");
+        assert_eq!(amounts.value_as_string(2), "2500.10");
+
+        let categories = batch.column_by_name("category").unwrap();
+        let categories = categories.as_dictionary::<Int32Type>();
+        assert_eq!(categories.values().len(), 1);
+        assert!(categories.is_null(2));
+
+        let booked = batch.column_by_name("booking_date").unwrap();
+        let booked = booked.as_primitive::<TimestampMillisecondType>();
+        assert_eq!(booked.value(0), 1_709_294_400_000);
+
+        let codes = batch.column_by_name("transaction_code").unwrap();
+        assert!(codes.is_valid(0));
+        assert!(codes.is_null(1));
+    }
+
+    #[test]
+    fn test_accounts_and_securities_batches() {
+        let accounts = accounts_batch(&[test_fixtures::giro(), test_fixtures::cash()]).unwrap();
+        assert_eq!(accounts.num_rows(), 2);
+        let balance = accounts.column_by_name("balance").unwrap();
+        assert_eq!(balance.as_primitive::<Decimal128Type>().value_as_string(0), "1234.56");
+        let kinds = accounts.column_by_name("type").unwrap();
+        let kinds = kinds.as_dictionary::<Int32Type>();
+        assert_eq!(kinds.values().len(), 2);
+
+        let empty = securities_batch(&[]).unwrap();
+        assert_eq!(empty.num_rows(), 0);
+        assert_eq!(empty.schema(), securities_schema());
+    }
+}
@@ -0,0 +1,599 @@
+//! ISO 20022 camt.053 bank-to-customer statement export.
+//!
+//! Writes one `camt.053.001.08` document with one `Stmt` per account, the
+//! format most German tax and accounting software imports.
+//!
+//! MoneyMoney only reports the *current* balance, so the statement balances
+//! are reconstructed from it:
+//!
+//! - closing balance (`CLBD`) = current balance − booked transactions after the period
+//! - opening balance (`OPBD`) = closing balance − booked transactions within the period
+//!
+//! For that to be correct, `transactions` must include every booked
+//! transaction from the start of the period up to today, not just the ones
+//! inside the period. Pending transactions are never part of an end-of-day
+//! statement and are skipped.
+//!
+//! SEPA fields map to their camt elements: `end_to_end_reference` →
+//! `Refs/EndToEndId`, `mandate_reference` → `Refs/MndtId`, `creditor_id` →
+//! the creditor's scheme identification, `purpose_code` → `Purp/Cd`,
+//! `return_reason` → `RtrInf/Rsn/Cd` and the counterparty `bank_code` →
+//! `BICFI` (or a German `DEBLZ` clearing member id when it's a BLZ).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use chrono::{NaiveDate, Utc};
+//! use moneymoney::export_transactions::ExportTransactionsParams;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let from = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+//! let to = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+//! let accounts = moneymoney::export_accounts()?;
+//! // No upper bound: later transactions are needed to reconstruct the balances.
+//! let response = moneymoney::export_transactions(ExportTransactionsParams::new(from))?;
+//!
+//! let file = std::fs::File::create("statement.xml")?;
+//! moneymoney::formats::camt053::write_camt053(
+//!     file,
+//!     &accounts,
+//!     &response.transactions,
+//!     from,
+//!     to,
+//!     Utc::now(),
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, Write};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::xml::XmlWriter;
+use super::{is_bic, non_empty};
+use crate::export_accounts::{MoneymoneyAccount, MoneymoneyAccountType};
+use crate::export_transactions::MoneymoneyTransaction;
+
+/// XML namespace of the generated documents.
+pub const NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:camt.053.001.08";
+
+/// Maximum length of a single `Ustrd` remittance line.
+const USTRD_MAX: usize = 140;
+
+/// Opening and closing balance of an account for a statement period.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatementBalances {
+    /// Balance at the start of `from` (`OPBD`).
+    pub opening: f64,
+    /// Balance at the end of `to` (`CLBD`).
+    pub closing: f64,
+}
+
+/// Reconstruct opening and closing balance from the current balance.
+///
+/// `transactions` must contain every booked transaction of `account` from
+/// `from` up to today; other accounts' and pending transactions are ignored.
+pub fn statement_balances(
+    account: &MoneymoneyAccount,
+    transactions: &[MoneymoneyTransaction],
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Option<StatementBalances> {
+    let current = account.balance.as_ref()?.amount;
+    let booked = || {
+        transactions
+            .iter()
+            .filter(|t| t.account_uuid == account.uuid && t.booked)
+    };
+    let after: f64 = booked()
+        .filter(|t| t.booking_date.date_naive() > to)
+        .map(|t| t.amount)
+        .sum();
+    let within: f64 = booked()
+        .filter(|t| in_period(t, from, to))
+        .map(|t| t.amount)
+        .sum();
+    let closing = round_cents(current - after);
+    Some(StatementBalances {
+        opening: round_cents(closing - within),
+        closing,
+    })
+}
+
+/// Write a camt.053 document covering `from..=to` for each account.
+///
+/// Account groups and portfolio accounts are skipped.
+///
+/// # Errors
+///
+/// Returns [`io::ErrorKind::InvalidInput`] if a statement account has no
+/// balance, since camt.053 requires opening and closing balances.
+pub fn write_camt053<W: Write>(
+    writer: W,
+    accounts: &[MoneymoneyAccount],
+    transactions: &[MoneymoneyTransaction],
+    from: NaiveDate,
+    to: NaiveDate,
+    created_at: DateTime<Utc>,
+) -> io::Result<()> {
+    let created = created_at.format("%Y-%m-%dT%H:%M:%S").to_string();
+
+    let mut w = XmlWriter::new(writer);
+    w.raw(r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    w.open_with_attrs("Document", &[("xmlns", NAMESPACE)])?;
+    w.open("BkToCstmrStmt")?;
+
+    w.open("GrpHdr")?;
+    w.leaf("MsgId", &format!("MM{}", created_at.format("%Y%m%d%H%M%S")))?;
+    w.leaf("CreDtTm", &created)?;
+    w.close()?;
+
+    let statement_accounts = accounts
+        .iter()
+        .filter(|a| !a.group && !a.portfolio)
+        .filter(|a| !matches!(a.r#type, MoneymoneyAccountType::Portfolio));
+    for (i, account) in statement_accounts.enumerate() {
+        let balances = statement_balances(account, transactions, from, to).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("account {:?} has no balance", account.name),
+            )
+        })?;
+        let currency = account
+            .balance
+            .as_ref()
+            .map(|b| b.currency.code().to_string())
+            .unwrap_or_else(|| account.currency.clone());
+        let entries: Vec<&MoneymoneyTransaction> = transactions
+            .iter()
+            .filter(|t| t.account_uuid == account.uuid && t.booked && in_period(t, from, to))
+            .collect();
+
+        w.open("Stmt")?;
+        w.leaf("Id", &format!("{}-{}-{}", from.format("%Y%m%d"), to.format("%Y%m%d"), i + 1))?;
+        w.leaf("CreDtTm", &created)?;
+        w.open("FrToDt")?;
+        w.leaf("FrDtTm", &format!("{}T00:00:00", from.format("%Y-%m-%d")))?;
+        w.leaf("ToDtTm", &format!("{}T23:59:59", to.format("%Y-%m-%d")))?;
+        w.close()?;
+        write_account(&mut w, account, &currency)?;
+        write_balance(&mut w, "OPBD", balances.opening, &currency, from)?;
+        write_balance(&mut w, "CLBD", balances.closing, &currency, to)?;
+        write_summary(&mut w, &entries)?;
+        for t in entries {
+            write_entry(&mut w, account, t)?;
+        }
+        w.close()?;
+    }
+
+    w.finish()?;
+    Ok(())
+}
+
+fn write_account<W: Write>(
+    w: &mut XmlWriter<W>,
+    account: &MoneymoneyAccount,
+    currency: &str,
+) -> io::Result<()> {
+    w.open("Acct")?;
+    w.open("Id")?;
+    match non_empty(account.iban.as_deref()) {
+        Some(iban) => w.leaf("IBAN", &iban.replace(' ', ""))?,
+        None => {
+            w.open("Othr")?;
+            let id = non_empty(Some(&account.account_number)).unwrap_or(&account.name);
+            w.leaf("Id", id)?;
+            w.close()?;
+        }
+    }
+    w.close()?;
+    w.leaf("Ccy", currency)?;
+    w.leaf("Nm", &account.name)?;
+    if let Some(owner) = non_empty(Some(&account.owner)) {
+        w.open("Ownr")?;
+        w.leaf("Nm", owner)?;
+        w.close()?;
+    }
+    let bic = non_empty(account.bic.as_deref());
+    let blz = non_empty(Some(&account.bank_code));
+    if bic.is_some() || blz.is_some() {
+        w.open("Svcr")?;
+        write_institution(w, bic.or(blz).unwrap_or_default())?;
+        w.close()?;
+    }
+    w.close()
+}
+
+fn write_balance<W: Write>(
+    w: &mut XmlWriter<W>,
+    code: &str,
+    amount: f64,
+    currency: &str,
+    date: NaiveDate,
+) -> io::Result<()> {
+    w.open("Bal")?;
+    w.open("Tp")?;
+    w.open("CdOrPrtry")?;
+    w.leaf("Cd", code)?;
+    w.close()?;
+    w.close()?;
+    w.leaf_with_attrs("Amt", &[("Ccy", currency)], &format!("{:.2}", amount.abs()))?;
+    w.leaf("CdtDbtInd", credit_debit(amount))?;
+    w.open("Dt")?;
+    w.leaf("Dt", &date.format("%Y-%m-%d").to_string())?;
+    w.close()?;
+    w.close()
+}
+
+fn write_summary<W: Write>(
+    w: &mut XmlWriter<W>,
+    entries: &[&MoneymoneyTransaction],
+) -> io::Result<()> {
+    let (credits, debits): (Vec<f64>, Vec<f64>) = (
+        entries
+            .iter()
+            .map(|t| t.amount)
+            .filter(|a| *a >= 0.0)
+            .collect(),
+        entries
+            .iter()
+            .map(|t| t.amount)
+            .filter(|a| *a < 0.0)
+            .collect(),
+    );
+    let net: f64 = entries.iter().map(|t| t.amount).sum();
+
+    w.open("TxsSummry")?;
+    w.open("TtlNtries")?;
+    w.leaf("NbOfNtries", &entries.len().to_string())?;
+    w.leaf("Sum", &format!("{:.2}", entries.iter().map(|t| t.amount.abs()).sum::<f64>()))?;
+    w.open("TtlNetNtry")?;
+    w.leaf("Amt", &format!("{:.2}", net.abs()))?;
+    w.leaf("CdtDbtInd", credit_debit(net))?;
+    w.close()?;
+    w.close()?;
+    for (tag, amounts) in [("TtlCdtNtries", credits), ("TtlDbtNtries", debits)] {
+        w.open(tag)?;
+        w.leaf("NbOfNtries", &amounts.len().to_string())?;
+        w.leaf("Sum", &format!("{:.2}", amounts.iter().map(|a| a.abs()).sum::<f64>()))?;
+        w.close()?;
+    }
+    w.close()
+}
+
+fn write_entry<W: Write>(
+    w: &mut XmlWriter<W>,
+    account: &MoneymoneyAccount,
+    t: &MoneymoneyTransaction,
+) -> io::Result<()> {
+    let credit = t.amount >= 0.0;
+
+    w.open("Ntry")?;
+    w.leaf("NtryRef", &t.id.to_string())?;
+    w.leaf_with_attrs("Amt", &[("Ccy", &t.currency)], &format!("{:.2}", t.amount.abs()))?;
+    w.leaf("CdtDbtInd", credit_debit(t.amount))?;
+    if non_empty(t.return_reason.as_deref()).is_some() {
+        w.leaf("RvslInd", "true")?;
+    }
+    w.open("Sts")?;
+    w.leaf("Cd", "BOOK")?;
+    w.close()?;
+    w.open("BookgDt")?;
+    w.leaf("Dt", &t.booking_date.format("%Y-%m-%d").to_string())?;
+    w.close()?;
+    w.open("ValDt")?;
+    w.leaf("Dt", &t.value_date.format("%Y-%m-%d").to_string())?;
+    w.close()?;
+    w.opt_leaf("AcctSvcrRef", t.batch_reference.as_deref())?;
+    w.open("BkTxCd")?;
+    w.open("Prtry")?;
+    w.leaf("Cd", &bank_transaction_code(t))?;
+    w.leaf("Issr", "DK")?;
+    w.close()?;
+    w.close()?;
+
+    w.open("NtryDtls")?;
+    w.open("TxDtls")?;
+
+    let end_to_end = non_empty(t.end_to_end_reference.as_deref());
+    let mandate = non_empty(t.mandate_reference.as_deref());
+    if end_to_end.is_some() || mandate.is_some() {
+        w.open("Refs")?;
+        w.opt_leaf("EndToEndId", end_to_end)?;
+        w.opt_leaf("MndtId", mandate)?;
+        w.close()?;
+    }
+
+    // Counterparty is the debtor of incoming and the creditor of outgoing payments.
+    let counterparty = non_empty(Some(&t.name));
+    let counterparty_account = non_empty(t.account_number.as_deref());
+    let creditor_id = non_empty(t.creditor_id.as_deref());
+    w.open("RltdPties")?;
+    if credit {
+        write_party(w, "Dbtr", counterparty, None)?;
+        write_party_account(w, "DbtrAcct", counterparty_account)?;
+        write_party(w, "Cdtr", non_empty(Some(&account.owner)), creditor_id)?;
+    } else {
+        write_party(w, "Cdtr", counterparty, creditor_id)?;
+        write_party_account(w, "CdtrAcct", counterparty_account)?;
+    }
+    w.close()?;
+
+    if let Some(bank_code) = non_empty(t.bank_code.as_deref()) {
+        w.open("RltdAgts")?;
+        w.open(if credit { "DbtrAgt" } else { "CdtrAgt" })?;
+        write_institution(w, bank_code)?;
+        w.close()?;
+        w.close()?;
+    }
+
+    if let Some(code) = non_empty(t.purpose_code.as_deref()) {
+        w.open("Purp")?;
+        w.leaf("Cd", code)?;
+        w.close()?;
+    }
+
+    if let Some(purpose) = non_empty(t.purpose.as_deref()) {
+        w.open("RmtInf")?;
+        for line in ustrd_lines(purpose) {
+            w.leaf("Ustrd", &line)?;
+        }
+        w.close()?;
+    }
+
+    if let Some(reason) = non_empty(t.return_reason.as_deref()) {
+        w.open("RtrInf")?;
+        w.open("Rsn")?;
+        w.leaf("Cd", reason)?;
+        w.close()?;
+        w.close()?;
+    }
+
+    w.close()?;
+    w.close()?;
+    w.opt_leaf("AddtlNtryInf", t.booking_text.as_deref())?;
+    w.close()
+}
+
+/// `Party40Choice` with a name and, for creditors, the SEPA creditor identifier.
+fn write_party<W: Write>(
+    w: &mut XmlWriter<W>,
+    tag: &'static str,
+    name: Option<&str>,
+    creditor_id: Option<&str>,
+) -> io::Result<()> {
+    if name.is_none() && creditor_id.is_none() {
+        return Ok(());
+    }
+    w.open(tag)?;
+    w.open("Pty")?;
+    w.opt_leaf("Nm", name)?;
+    if let Some(id) = creditor_id {
+        w.open("Id")?;
+        w.open("PrvtId")?;
+        w.open("Othr")?;
+        w.leaf("Id", id)?;
+        w.open("SchmeNm")?;
+        w.leaf("Prtry", "SEPA")?;
+        w.close()?;
+        w.close()?;
+        w.close()?;
+        w.close()?;
+    }
+    w.close()?;
+    w.close()
+}
+
+fn write_party_account<W: Write>(
+    w: &mut XmlWriter<W>,
+    tag: &'static str,
+    account_number: Option<&str>,
+) -> io::Result<()> {
+    let Some(number) = account_number else {
+        return Ok(());
+    };
+    let number = number.replace(' ', "");
+    w.open(tag)?;
+    w.open("Id")?;
+    if looks_like_iban(&number) {
+        w.leaf("IBAN", &number)?;
+    } else {
+        w.open("Othr")?;
+        w.leaf("Id", &number)?;
+        w.close()?;
+    }
+    w.close()?;
+    w.close()
+}
+
+/// `FinInstnId` from either a BIC or a German bank code (BLZ).
+fn write_institution<W: Write>(w: &mut XmlWriter<W>, bank_code: &str) -> io::Result<()> {
+    w.open("FinInstnId")?;
+    if is_bic(bank_code) {
+        w.leaf("BICFI", bank_code)?;
+    } else {
+        w.open("ClrSysMmbId")?;
+        w.open("ClrSysId")?;
+        w.leaf("Cd", "DEBLZ")?;
+        w.close()?;
+        w.leaf("MmbId", bank_code)?;
+        w.close()?;
+    }
+    w.close()
+}
+
+/// Proprietary DK bank transaction code: `N<SWIFT key>+<GVC>[+<primanota>]`.
+fn bank_transaction_code(t: &MoneymoneyTransaction) -> String {
+    let swift_key = non_empty(t.booking_key.as_deref())
+        .filter(|k| k.len() == 3 && k.chars().all(|c| c.is_ascii_alphabetic()))
+        .map(str::to_ascii_uppercase)
+        .unwrap_or_else(|| "MSC".to_string());
+    let mut code = format!("N{swift_key}+{:03}", t.transaction_code.unwrap_or(0));
+    if let Some(primanota) = non_empty(t.primanota_number.as_deref()) {
+        code.push('+');
+        code.push_str(primanota);
+    }
+    code
+}
+
+fn ustrd_lines(purpose: &str) -> Vec<String> {
+    let flat = purpose.split_whitespace().collect::<Vec<_>>().join(" ");
+    let chars: Vec<char> = flat.chars().collect();
+    chars
+        .chunks(USTRD_MAX)
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+fn looks_like_iban(number: &str) -> bool {
+    number.len() >= 15
+        && number[..2].chars().all(|c| c.is_ascii_uppercase())
+        && number[2..4].chars().all(|c| c.is_ascii_digit())
+        && number.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn in_period(t: &MoneymoneyTransaction, from: NaiveDate, to: NaiveDate) -> bool {
+    let day = t.booking_date.date_naive();
+    day >= from && day <= to
+}
+
+fn credit_debit(amount: f64) -> &'static str {
+    if amount < 0.0 {
+        "DBIT"
+    } else {
+        "CRDT"
+    }
+}
+
+fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, date, transaction, GIRO_UUID};
+    use chrono::TimeZone;
+
+    fn sample_transactions() -> Vec<MoneymoneyTransaction> {
+        let mut salary = transaction(1, GIRO_UUID, date(2024, 3, 1), "ACME GmbH", 3500.0);
+        salary.purpose_code = Some("SALA".to_string());
+        salary.end_to_end_reference = Some("EREF-1".to_string());
+        salary.account_number = Some("DE02120300000000202051".to_string());
+        salary.bank_code = Some("BYLADEM1001".to_string());
+        salary.transaction_code = Some(153);
+        salary.booking_key = Some("trf".to_string());
+
+        let mut insurance = transaction(2, GIRO_UUID, date(2024, 3, 15), "Versicherung", -120.0);
+        insurance.mandate_reference = Some("MREF-9".to_string());
+        insurance.creditor_id = Some("DE98ZZZ09999999999".to_string());
+        insurance.bank_code = Some("37040044".to_string());
+        insurance.purpose = Some("Beitrag <März>".to_string());
+        insurance.booking_text = Some("LASTSCHRIFT".to_string());
+
+        let mut pending = transaction(3, GIRO_UUID, date(2024, 3, 20), "Pending", -10.0);
+        pending.booked = false;
+        let after = transaction(4, GIRO_UUID, date(2024, 4, 2), "Later", -34.56);
+        vec![salary, insurance, pending, after]
+    }
+
+    #[test]
+    fn test_statement_balances() {
+        let balances = statement_balances(
+            &test_fixtures::giro(),
+            &sample_transactions(),
+            date(2024, 3, 1),
+            date(2024, 3, 31),
+        )
+        .unwrap();
+        // 1234.56 current + 34.56 booked after the period.
+        assert_eq!(balances.closing, 1269.12);
+        // closing - (3500 - 120) within the period.
+        assert_eq!(balances.opening, -2110.88);
+    }
+
+    #[test]
+    fn test_write_statement() {
+        let mut out = Vec::new();
+        write_camt053(
+            &mut out,
+            &[test_fixtures::giro()],
+            &sample_transactions(),
+            date(2024, 3, 1),
+            date(2024, 3, 31),
+            Utc.with_ymd_and_hms(2024, 4, 5, 9, 30, 0).unwrap(),
+        )
+        .unwrap();
+        let xml = String::from_utf8(out).unwrap();
+
+        assert!(xml.contains(&format!("<Document xmlns=\"{NAMESPACE}\">")));
+        assert!(xml.contains("<MsgId>MM20240405093000</MsgId>"));
+        assert!(xml.contains("<IBAN>DE89370400440532013000</IBAN>"));
+        assert!(xml.contains("<BICFI>COBADEFFXXX</BICFI>"));
+        assert!(xml.contains("<Cd>OPBD</Cd>"));
+        let opening = &xml[xml.find("<Cd>OPBD</Cd>").unwrap()..];
+        assert!(opening.contains("<Amt Ccy=\"EUR\">2110.88</Amt>"));
+        assert!(opening[..opening.find("</Bal>").unwrap()].contains("<CdtDbtInd>DBIT</CdtDbtInd>"));
+        assert!(xml.contains("<Amt Ccy=\"EUR\">1269.12</Amt>"));
+        assert!(xml.contains("<NbOfNtries>2</NbOfNtries>"));
+        assert!(xml.contains("<NtryRef>1</NtryRef>"));
+        assert!(!xml.contains("<NtryRef>3</NtryRef>"), "pending entries are skipped");
+        assert!(!xml.contains("<NtryRef>4</NtryRef>"), "entries after the period are skipped");
+        assert!(xml.contains("<EndToEndId>EREF-1</EndToEndId>"));
+        assert!(xml.contains("<MndtId>MREF-9</MndtId>"));
+        assert!(xml.contains("<Id>DE98ZZZ09999999999</Id>"));
+        assert!(xml.contains("<Cd>SALA</Cd>"));
+        assert!(xml.contains("<Cd>NTRF+153</Cd>"));
+        assert!(xml.contains("<Cd>NMSC+000</Cd>"));
+        assert!(xml.contains("<BICFI>BYLADEM1001</BICFI>"));
+        assert!(xml.contains("<MmbId>37040044</MmbId>"));
+        assert!(xml.contains("<IBAN>DE02120300000000202051</IBAN>"));
+        assert!(xml.contains("<Ustrd>Beitrag &lt;März&gt;</Ustrd>"));
+        assert!(xml.contains("<AddtlNtryInf>LASTSCHRIFT</AddtlNtryInf>"));
+    }
+
+    #[test]
+    fn test_return_reason() {
+        let mut t = transaction(5, GIRO_UUID, date(2024, 3, 10), "Rückgabe", 50.0);
+        t.return_reason = Some("AC04".to_string());
+        let mut out = Vec::new();
+        write_camt053(
+            &mut out,
+            &[test_fixtures::giro()],
+            &[t],
+            date(2024, 3, 1),
+            date(2024, 3, 31),
+            Utc.with_ymd_and_hms(2024, 4, 5, 9, 30, 0).unwrap(),
+        )
+        .unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert!(xml.contains("<RvslInd>true</RvslInd>"));
+        assert!(xml.contains("<Rsn>\n"));
+        assert!(xml.contains("<Cd>AC04</Cd>"));
+    }
+
+    #[test]
+    fn test_missing_balance_is_an_error() {
+        let mut account = test_fixtures::giro();
+        account.balance = None;
+        let err = write_camt053(
+            Vec::new(),
+            &[account],
+            &[],
+            date(2024, 3, 1),
+            date(2024, 3, 31),
+            Utc::now(),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_ustrd_lines_split() {
+        let long = "x".repeat(300);
+        let lines = ustrd_lines(&long);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), USTRD_MAX);
+    }
+}
@@ -0,0 +1,77 @@
+//! File formats for handing MoneyMoney data to other tools.
+//!
+//! Each submodule writes exported accounts and transactions in a format some
+//! other software imports:
+//!
+//! - [`ofx`] - OFX 2.x bank and credit card statements
+//! - [`qif`] - QIF for legacy finance tools
+//! - [`camt053`] - ISO 20022 camt.053 bank-to-customer statements
+//!
+//! For analytics, the `arrow` feature converts transactions, accounts and
+//! securities into Arrow record batches (`arrow`) and the `parquet` feature
+//! writes those as Parquet files (`parquet`); both are enabled by `cli`.
+//!
+//! With the `experimental` feature, payment parameters can also be written as
+//! SEPA payment files instead of being sent to MoneyMoney:
+//!
+//! - `pain001` - SEPA credit transfer initiation from `CreateBankTransferParams`
+//! - `pain008` - SEPA direct debit initiation from `CreateDirectDebitParams`
+//!
+//! Writers take any [`std::io::Write`] and only work on data already exported
+//! from MoneyMoney, so they don't need the app to be running.
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod camt053;
+pub mod ofx;
+pub mod qif;
+
+#[cfg(feature = "experimental")]
+pub mod pain001;
+#[cfg(feature = "experimental")]
+pub mod pain008;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "experimental")]
+pub mod sepa;
+
+mod xml;
+
+use crate::export_transactions::MoneymoneyTransaction;
+
+/// Free-text memo of a transaction: purpose and comment, joined by ` / `.
+pub(crate) fn transaction_memo(t: &MoneymoneyTransaction) -> Option<String> {
+    let parts: Vec<&str> = [t.purpose.as_deref(), t.comment.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(" / "))
+}
+
+/// Trimmed text, or `None` when absent or blank.
+pub(crate) fn non_empty(text: Option<&str>) -> Option<&str> {
+    text.map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// Whether `code` has the shape of a BIC (8 or 11 characters, letters for
+/// the bank and country code) rather than a national bank code like a BLZ.
+pub(crate) fn is_bic(code: &str) -> bool {
+    (code.len() == 8 || code.len() == 11)
+        && code.chars().all(|c| c.is_ascii_alphanumeric())
+        && code[..6].chars().all(|c| c.is_ascii_alphabetic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_bic() {
+        assert!(is_bic("COBADEFFXXX"));
+        assert!(is_bic("BYLADEM1"));
+        assert!(!is_bic("37040044"));
+        assert!(!is_bic("COBADE"));
+    }
+}
@@ -0,0 +1,360 @@
+//! OFX 2.x statement export.
+//!
+//! Writes accounts and their transactions as an OFX 2.2 XML document that
+//! GnuCash, most accounting importers and many personal finance tools accept.
+//!
+//! - Giro, savings, fixed term, loan and cash accounts become `BANKACCTFROM`
+//!   statements; credit cards become `CCACCTFROM` statements.
+//! - `FITID` is the stable MoneyMoney transaction `id`, so re-importing an
+//!   overlapping range does not duplicate transactions.
+//! - `TRNTYPE` is derived from the German business transaction code
+//!   (`transaction_code`) where it is known, otherwise from the sign.
+//! - `BANKID` is the bank code (BLZ) or the BIC. Cash and other offline
+//!   accounts have neither, so they get the [`NO_BANK_ID`] placeholder because
+//!   OFX requires a non-empty `BANKID`.
+//! - `ACCTID` is the IBAN, the account number or, failing both, the MoneyMoney
+//!   account UUID.
+//! - `LEDGERBAL` is the account balance as reported by MoneyMoney. Accounts
+//!   without a balance report `0.00` as of their last refresh, since OFX
+//!   requires the element.
+//!
+//! Account groups and portfolio accounts are skipped; OFX models securities
+//! in a separate investment statement that MoneyMoney's data doesn't fill.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use chrono::{NaiveDate, Utc};
+//! use moneymoney::export_transactions::ExportTransactionsParams;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let accounts = moneymoney::export_accounts()?;
+//! let params = ExportTransactionsParams::new(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+//! let response = moneymoney::export_transactions(params)?;
+//!
+//! let file = std::fs::File::create("statement.ofx")?;
+//! moneymoney::formats::ofx::write_ofx(file, &accounts, &response.transactions, Utc::now())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, Write};
+
+use chrono::{DateTime, Utc};
+
+use super::transaction_memo;
+use super::xml::XmlWriter;
+use crate::export_accounts::{MoneymoneyAccount, MoneymoneyAccountType};
+use crate::export_transactions::MoneymoneyTransaction;
+
+/// `BANKID` written for bank statements of accounts that have neither a bank
+/// code nor a BIC, such as cash accounts.
+pub const NO_BANK_ID: &str = "NONE";
+
+/// OFX bank account type (`ACCTTYPE`) of a MoneyMoney account.
+///
+/// Returns `None` for accounts that don't map to a bank statement, i.e. credit
+/// cards (which use `CCACCTFROM`), portfolios and account groups.
+pub fn bank_account_type(account_type: &MoneymoneyAccountType) -> Option<&'static str> {
+    match account_type {
+        MoneymoneyAccountType::Giro | MoneymoneyAccountType::Cash => Some("CHECKING"),
+        MoneymoneyAccountType::Savings | MoneymoneyAccountType::Tagesgeld => Some("SAVINGS"),
+        MoneymoneyAccountType::FixedTermDeposit => Some("CD"),
+        MoneymoneyAccountType::Loan => Some("CREDITLINE"),
+        MoneymoneyAccountType::Other | MoneymoneyAccountType::Custom(_) => Some("CHECKING"),
+        MoneymoneyAccountType::CreditCard
+        | MoneymoneyAccountType::Portfolio
+        | MoneymoneyAccountType::Group => None,
+    }
+}
+
+/// OFX transaction type (`TRNTYPE`) of a transaction.
+///
+/// Known German business transaction codes (Geschäftsvorfallcodes) win over
+/// the sign: direct debits, standing orders, card payments, cash, fees and
+/// interest. Anything else is `DEBIT` for negative and `CREDIT` for positive
+/// amounts.
+pub fn transaction_type(transaction: &MoneymoneyTransaction) -> &'static str {
+    let negative = transaction.amount < 0.0;
+    match transaction.transaction_code {
+        Some(4 | 5 | 104 | 105 | 107 | 108 | 109) if negative => "DIRECTDEBIT",
+        Some(52 | 152) if negative => "REPEATPMT",
+        Some(6 | 106) if negative => "POS",
+        Some(83) if negative => "ATM",
+        Some(82) if !negative => "DEP",
+        Some(53 | 153) if !negative => "DIRECTDEP",
+        Some(805 | 806 | 808) => "SRVCHG",
+        Some(804 | 814) => {
+            if negative {
+                "FEE"
+            } else {
+                "INT"
+            }
+        }
+        _ if negative => "DEBIT",
+        _ => "CREDIT",
+    }
+}
+
+/// Write an OFX 2.2 document with one statement per account.
+///
+/// Every non-group, non-portfolio account in `accounts` gets a statement,
+/// including accounts without transactions in `transactions` (their balance
+/// is still useful). Transactions whose `account_uuid` isn't in `accounts`
+/// are ignored.
+///
+/// `generated_at` becomes `DTSERVER` and the fallback statement period for
+/// accounts without transactions; pass `Utc::now()` outside of tests.
+pub fn write_ofx<W: Write>(
+    writer: W,
+    accounts: &[MoneymoneyAccount],
+    transactions: &[MoneymoneyTransaction],
+    generated_at: DateTime<Utc>,
+) -> io::Result<()> {
+    let statement_accounts: Vec<&MoneymoneyAccount> = accounts
+        .iter()
+        .filter(|a| !a.group && !a.portfolio)
+        .filter(|a| !matches!(a.r#type, MoneymoneyAccountType::Portfolio))
+        .collect();
+    let (cards, banks): (Vec<_>, Vec<_>) = statement_accounts
+        .into_iter()
+        .partition(|a| matches!(a.r#type, MoneymoneyAccountType::CreditCard));
+
+    let mut w = XmlWriter::new(writer);
+    w.raw(r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>"#)?;
+    w.raw(
+        r#"<?OFX OFXHEADER="200" VERSION="220" SECURITY="NONE" OLDFILEUID="NONE" NEWFILEUID="NONE"?>"#,
+    )?;
+    w.open("OFX")?;
+
+    w.open("SIGNONMSGSRSV1")?;
+    w.open("SONRS")?;
+    write_status(&mut w)?;
+    w.leaf("DTSERVER", &ofx_datetime(&generated_at))?;
+    w.leaf("LANGUAGE", "ENG")?;
+    w.close()?;
+    w.close()?;
+
+    if !banks.is_empty() {
+        w.open("BANKMSGSRSV1")?;
+        for (i, account) in banks.iter().enumerate() {
+            w.open("STMTTRNRS")?;
+            w.leaf("TRNUID", &(i + 1).to_string())?;
+            write_status(&mut w)?;
+            w.open("STMTRS")?;
+            w.leaf("CURDEF", &account.currency)?;
+            w.open("BANKACCTFROM")?;
+            w.leaf("BANKID", bank_id(account))?;
+            w.leaf("ACCTID", &account_id(account))?;
+            w.leaf("ACCTTYPE", bank_account_type(&account.r#type).unwrap_or("CHECKING"))?;
+            w.close()?;
+            write_statement_body(&mut w, account, transactions, &generated_at)?;
+            w.close()?;
+            w.close()?;
+        }
+        w.close()?;
+    }
+
+    if !cards.is_empty() {
+        w.open("CREDITCARDMSGSRSV1")?;
+        for (i, account) in cards.iter().enumerate() {
+            w.open("CCSTMTTRNRS")?;
+            w.leaf("TRNUID", &(i + 1).to_string())?;
+            write_status(&mut w)?;
+            w.open("CCSTMTRS")?;
+            w.leaf("CURDEF", &account.currency)?;
+            w.open("CCACCTFROM")?;
+            w.leaf("ACCTID", &account_id(account))?;
+            w.close()?;
+            write_statement_body(&mut w, account, transactions, &generated_at)?;
+            w.close()?;
+            w.close()?;
+        }
+        w.close()?;
+    }
+
+    w.finish()?;
+    Ok(())
+}
+
+fn write_status<W: Write>(w: &mut XmlWriter<W>) -> io::Result<()> {
+    w.open("STATUS")?;
+    w.leaf("CODE", "0")?;
+    w.leaf("SEVERITY", "INFO")?;
+    w.close()
+}
+
+fn write_statement_body<W: Write>(
+    w: &mut XmlWriter<W>,
+    account: &MoneymoneyAccount,
+    transactions: &[MoneymoneyTransaction],
+    generated_at: &DateTime<Utc>,
+) -> io::Result<()> {
+    let own: Vec<&MoneymoneyTransaction> = transactions
+        .iter()
+        .filter(|t| t.account_uuid == account.uuid)
+        .collect();
+    let start = own
+        .iter()
+        .map(|t| t.booking_date)
+        .min()
+        .unwrap_or(*generated_at);
+    let end = own
+        .iter()
+        .map(|t| t.booking_date)
+        .max()
+        .unwrap_or(*generated_at);
+
+    w.open("BANKTRANLIST")?;
+    w.leaf("DTSTART", &ofx_datetime(&start))?;
+    w.leaf("DTEND", &ofx_datetime(&end))?;
+    for t in own {
+        w.open("STMTTRN")?;
+        w.leaf("TRNTYPE", transaction_type(t))?;
+        w.leaf("DTPOSTED", &ofx_datetime(&t.booking_date))?;
+        w.leaf("DTUSER", &ofx_datetime(&t.value_date))?;
+        w.leaf("TRNAMT", &format!("{:.2}", t.amount))?;
+        w.leaf("FITID", &t.id.to_string())?;
+        if !t.name.trim().is_empty() {
+            // OFX 2.x limits NAME to 32 characters.
+            w.leaf("NAME", &t.name.trim().chars().take(32).collect::<String>())?;
+        }
+        w.opt_leaf("MEMO", transaction_memo(t).as_deref())?;
+        w.close()?;
+    }
+    w.close()?;
+
+    // LEDGERBAL is mandatory; without a balance report zero at the last refresh.
+    let (amount, as_of) = match &account.balance {
+        Some(balance) => {
+            (balance.amount, account.balance_date.unwrap_or(account.refresh_timestamp))
+        }
+        None => (0.0, account.refresh_timestamp),
+    };
+    w.open("LEDGERBAL")?;
+    w.leaf("BALAMT", &format!("{amount:.2}"))?;
+    w.leaf("DTASOF", &ofx_datetime(&as_of))?;
+    w.close()
+}
+
+/// Prefer the bank code (BLZ), fall back to the BIC, then [`NO_BANK_ID`].
+fn bank_id(account: &MoneymoneyAccount) -> &str {
+    if !account.bank_code.trim().is_empty() {
+        return account.bank_code.trim();
+    }
+    match account.bic.as_deref().map(str::trim) {
+        Some(bic) if !bic.is_empty() => bic,
+        _ => NO_BANK_ID,
+    }
+}
+
+/// Prefer the IBAN, fall back to the account number, then the MoneyMoney UUID.
+fn account_id(account: &MoneymoneyAccount) -> String {
+    match account.iban.as_deref().map(str::trim) {
+        Some(iban) if !iban.is_empty() => iban.to_string(),
+        _ if !account.account_number.trim().is_empty() => account.account_number.trim().to_string(),
+        _ => account.uuid.to_string(),
+    }
+}
+
+fn ofx_datetime(at: &DateTime<Utc>) -> String {
+    at.format("%Y%m%d%H%M%S.000[0:GMT]").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, date, transaction, CARD_UUID, CASH_UUID, GIRO_UUID};
+    use chrono::TimeZone;
+
+    fn render(accounts: &[MoneymoneyAccount], transactions: &[MoneymoneyTransaction]) -> String {
+        let mut out = Vec::new();
+        let at = Utc.with_ymd_and_hms(2024, 4, 1, 8, 0, 0).unwrap();
+        write_ofx(&mut out, accounts, transactions, at).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_bank_statement() {
+        let mut salary = transaction(4711, GIRO_UUID, date(2024, 3, 1), "ACME GmbH", 3500.0);
+        salary.transaction_code = Some(153);
+        salary.purpose = Some("Gehalt März".to_string());
+        let rent = transaction(4712, GIRO_UUID, date(2024, 3, 3), "Vermieter & Co", -900.0);
+
+        let ofx = render(&[test_fixtures::giro()], &[salary, rent]);
+        assert!(ofx.starts_with("<?xml"));
+        assert!(ofx.contains(r#"<?OFX OFXHEADER="200" VERSION="220""#));
+        assert!(ofx.contains("<DTSERVER>20240401080000.000[0:GMT]</DTSERVER>"));
+        assert!(ofx.contains("<BANKID>37040044</BANKID>"));
+        assert!(ofx.contains("<ACCTID>DE89370400440532013000</ACCTID>"));
+        assert!(ofx.contains("<ACCTTYPE>CHECKING</ACCTTYPE>"));
+        assert!(ofx.contains("<DTSTART>20240301120000.000[0:GMT]</DTSTART>"));
+        assert!(ofx.contains("<DTEND>20240303120000.000[0:GMT]</DTEND>"));
+        assert!(ofx.contains("<TRNTYPE>DIRECTDEP</TRNTYPE>"));
+        assert!(ofx.contains("<FITID>4711</FITID>"));
+        assert!(ofx.contains("<MEMO>Gehalt März</MEMO>"));
+        assert!(ofx.contains("<TRNAMT>-900.00</TRNAMT>"));
+        assert!(ofx.contains("<NAME>Vermieter &amp; Co</NAME>"));
+        assert!(ofx.contains("<BALAMT>1234.56</BALAMT>"));
+        assert!(!ofx.contains("CREDITCARDMSGSRSV1"));
+    }
+
+    #[test]
+    fn test_credit_card_statement() {
+        let t = transaction(1, CARD_UUID, date(2024, 3, 5), "Hotel", -120.5);
+        let ofx = render(&[test_fixtures::credit_card()], &[t]);
+        assert!(ofx.contains("<CREDITCARDMSGSRSV1>"));
+        assert!(ofx.contains("<CCACCTFROM>"));
+        assert!(ofx.contains("<ACCTID>4111111111111111</ACCTID>"));
+        assert!(ofx.contains("<TRNTYPE>DEBIT</TRNTYPE>"));
+        assert!(ofx.contains("<BALAMT>-250.00</BALAMT>"));
+        assert!(!ofx.contains("BANKMSGSRSV1"));
+    }
+
+    #[test]
+    fn test_account_without_bank_details() {
+        let mut cash = test_fixtures::cash();
+        cash.balance = None;
+        let t = transaction(1, CASH_UUID, date(2024, 3, 5), "Bäcker", -4.2);
+        let ofx = render(&[cash], &[t]);
+        assert!(ofx.contains("<BANKID>NONE</BANKID>"));
+        assert!(ofx.contains(&format!("<ACCTID>{CASH_UUID}</ACCTID>")));
+        assert!(ofx.contains("<BALAMT>0.00</BALAMT>"));
+        assert!(ofx.contains("<DTASOF>20240331180000.000[0:GMT]</DTASOF>"));
+    }
+
+    #[test]
+    fn test_foreign_transactions_and_groups_skipped() {
+        let mut group = test_fixtures::giro();
+        group.group = true;
+        let t = transaction(1, CARD_UUID, date(2024, 3, 5), "Hotel", -120.5);
+        let ofx = render(&[group], &[t]);
+        assert!(!ofx.contains("STMTTRN"));
+        assert!(!ofx.contains("BANKMSGSRSV1"));
+    }
+
+    #[test]
+    fn test_transaction_type_mapping() {
+        let mut t = transaction(1, GIRO_UUID, date(2024, 1, 1), "x", -10.0);
+        assert_eq!(transaction_type(&t), "DEBIT");
+        t.transaction_code = Some(105);
+        assert_eq!(transaction_type(&t), "DIRECTDEBIT");
+        t.transaction_code = Some(83);
+        assert_eq!(transaction_type(&t), "ATM");
+        t.transaction_code = Some(808);
+        assert_eq!(transaction_type(&t), "SRVCHG");
+        t.amount = 10.0;
+        t.transaction_code = Some(105);
+        assert_eq!(transaction_type(&t), "CREDIT");
+        t.transaction_code = Some(814);
+        assert_eq!(transaction_type(&t), "INT");
+    }
+
+    #[test]
+    fn test_bank_account_type_mapping() {
+        assert_eq!(bank_account_type(&MoneymoneyAccountType::Savings), Some("SAVINGS"));
+        assert_eq!(bank_account_type(&MoneymoneyAccountType::FixedTermDeposit), Some("CD"));
+        assert_eq!(bank_account_type(&MoneymoneyAccountType::Loan), Some("CREDITLINE"));
+        assert_eq!(bank_account_type(&MoneymoneyAccountType::CreditCard), None);
+    }
+}
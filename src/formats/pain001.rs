@@ -0,0 +1,369 @@
+//! SEPA credit transfer initiation (pain.001.001.09) generation (experimental).
+//!
+//! Turns [`CreateBankTransferParams`] into a payment file for banks and
+//! accountants that want a file instead of MoneyMoney's outbox, or for
+//! reviewing a batch before submitting it. Nothing is sent to MoneyMoney.
+//!
+//! Debtor details (name, IBAN, BIC) come from the account each transfer's
+//! `from_account` names, so pass the result of [`crate::export_accounts()`].
+//! Transfers are grouped into one `PmtInf` block per debtor account,
+//! requested execution date (`scheduled_date`) and instrument (`TRF` or
+//! `INST`); unscheduled transfers use the "as soon as possible" date
+//! `1999-01-01`.
+//!
+//! Every transfer is validated before anything is written: IBAN checksums,
+//! BIC shape, amounts, reference characters and lengths, purpose codes and
+//! dates. The first problem is returned as a [`SepaError`].
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use chrono::Utc;
+//! use moneymoney::create_bank_transfer::CreateBankTransferParams;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let accounts = moneymoney::export_accounts()?;
+//! let transfers = vec![CreateBankTransferParams {
+//!     from_account: Some("Girokonto".to_string()),
+//!     to: Some("Jane Doe".to_string()),
+//!     iban: Some("DE02120300000000202051".to_string()),
+//!     amount: Some(250.0),
+//!     purpose: Some("Invoice 2024-17".to_string()),
+//!     ..Default::default()
+//! }];
+//!
+//! let file = std::fs::File::create("transfers.xml")?;
+//! moneymoney::formats::pain001::write_pain001(file, &accounts, &transfers, Utc::now())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::non_empty;
+use super::sepa::{self, OwnAccount, SepaError};
+use super::xml::XmlWriter;
+use crate::create_bank_transfer::CreateBankTransferParams;
+use crate::export_accounts::MoneymoneyAccount;
+
+/// XML namespace of the generated documents.
+pub const NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:pain.001.001.09";
+
+/// A transfer after validation, ready to be written.
+struct Transfer {
+    end_to_end: String,
+    cents: i64,
+    creditor_name: String,
+    creditor_iban: String,
+    creditor_bic: Option<String>,
+    purpose_code: Option<String>,
+    remittance: Option<String>,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct GroupKey {
+    debtor: OwnAccount,
+    execution_date: Option<NaiveDate>,
+    instant: bool,
+}
+
+/// Validate `transfers` and write them as a pain.001.001.09 document.
+///
+/// `created_at` becomes the creation timestamp and, with millisecond
+/// precision, the message id (`MsgId`), so two files generated at different
+/// times never share an id.
+///
+/// # Errors
+///
+/// Returns [`SepaError`] if `transfers` is empty, a transfer is missing a
+/// required field (`from_account`, `to`, `iban`, `amount`), a field is
+/// invalid, or `from_account` doesn't match any of `accounts`.
+pub fn write_pain001<W: Write>(
+    writer: W,
+    accounts: &[MoneymoneyAccount],
+    transfers: &[CreateBankTransferParams],
+    created_at: DateTime<Utc>,
+) -> Result<(), SepaError> {
+    if transfers.is_empty() {
+        return Err(SepaError::Empty);
+    }
+    let mut groups: BTreeMap<GroupKey, Vec<Transfer>> = BTreeMap::new();
+    for (index, params) in transfers.iter().enumerate() {
+        let (key, transfer) = validate(index, accounts, params)?;
+        groups.entry(key).or_default().push(transfer);
+    }
+
+    let message_id = format!("MM{}", created_at.format("%Y%m%d%H%M%S%3f"));
+    let total_cents: i64 = groups.values().flatten().map(|t| t.cents).sum();
+    let initiator = groups
+        .keys()
+        .next()
+        .map(|k| k.debtor.name.clone())
+        .unwrap_or_default();
+
+    let mut w = XmlWriter::new(writer);
+    w.raw(r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    w.open_with_attrs("Document", &[("xmlns", NAMESPACE)])?;
+    w.open("CstmrCdtTrfInitn")?;
+
+    w.open("GrpHdr")?;
+    w.leaf("MsgId", &message_id)?;
+    w.leaf("CreDtTm", &created_at.format("%Y-%m-%dT%H:%M:%S").to_string())?;
+    w.leaf("NbOfTxs", &transfers.len().to_string())?;
+    w.leaf("CtrlSum", &sepa::format_cents(total_cents))?;
+    w.open("InitgPty")?;
+    w.leaf("Nm", &initiator)?;
+    w.close()?;
+    w.close()?;
+
+    for (n, (key, group)) in groups.iter().enumerate() {
+        w.open("PmtInf")?;
+        w.leaf("PmtInfId", &format!("{message_id}-{}", n + 1))?;
+        w.leaf("PmtMtd", "TRF")?;
+        w.leaf("NbOfTxs", &group.len().to_string())?;
+        w.leaf("CtrlSum", &sepa::format_cents(group.iter().map(|t| t.cents).sum()))?;
+        w.open("PmtTpInf")?;
+        w.open("SvcLvl")?;
+        w.leaf("Cd", "SEPA")?;
+        w.close()?;
+        if key.instant {
+            w.open("LclInstrm")?;
+            w.leaf("Cd", "INST")?;
+            w.close()?;
+        }
+        w.close()?;
+        w.open("ReqdExctnDt")?;
+        w.leaf(
+            "Dt",
+            &key.execution_date
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| sepa::ASAP_DATE.to_string()),
+        )?;
+        w.close()?;
+        w.open("Dbtr")?;
+        w.leaf("Nm", &key.debtor.name)?;
+        w.close()?;
+        w.open("DbtrAcct")?;
+        w.open("Id")?;
+        w.leaf("IBAN", &key.debtor.iban)?;
+        w.close()?;
+        w.close()?;
+        w.open("DbtrAgt")?;
+        sepa::write_agent(&mut w, key.debtor.bic.as_deref())?;
+        w.close()?;
+        w.leaf("ChrgBr", "SLEV")?;
+
+        for t in group {
+            w.open("CdtTrfTxInf")?;
+            w.open("PmtId")?;
+            w.leaf("EndToEndId", &t.end_to_end)?;
+            w.close()?;
+            w.open("Amt")?;
+            w.leaf_with_attrs("InstdAmt", &[("Ccy", "EUR")], &sepa::format_cents(t.cents))?;
+            w.close()?;
+            if let Some(bic) = &t.creditor_bic {
+                w.open("CdtrAgt")?;
+                sepa::write_agent(&mut w, Some(bic))?;
+                w.close()?;
+            }
+            w.open("Cdtr")?;
+            w.leaf("Nm", &t.creditor_name)?;
+            w.close()?;
+            w.open("CdtrAcct")?;
+            w.open("Id")?;
+            w.leaf("IBAN", &t.creditor_iban)?;
+            w.close()?;
+            w.close()?;
+            if let Some(code) = &t.purpose_code {
+                w.open("Purp")?;
+                w.leaf("Cd", code)?;
+                w.close()?;
+            }
+            if let Some(text) = &t.remittance {
+                w.open("RmtInf")?;
+                w.leaf("Ustrd", text)?;
+                w.close()?;
+            }
+            w.close()?;
+        }
+        w.close()?;
+    }
+
+    w.finish()?;
+    Ok(())
+}
+
+fn validate(
+    index: usize,
+    accounts: &[MoneymoneyAccount],
+    params: &CreateBankTransferParams,
+) -> Result<(GroupKey, Transfer), SepaError> {
+    let debtor = OwnAccount::resolve(index, accounts, params.from_account.as_deref())?;
+    let creditor_name = non_empty(params.to.as_deref())
+        .map(|name| sepa::clean_text(name, 70))
+        .ok_or(SepaError::MissingField { index, field: "to" })?;
+    let creditor_iban = non_empty(params.iban.as_deref())
+        .ok_or(SepaError::MissingField {
+            index,
+            field: "iban",
+        })
+        .and_then(|iban| sepa::validate_iban(index, "iban", iban))?;
+    let creditor_bic = non_empty(params.bic.as_deref())
+        .map(|bic| sepa::validate_bic(index, "bic", bic))
+        .transpose()?;
+    let cents = sepa::amount_cents(index, params.amount)?;
+    let end_to_end = non_empty(params.endtoend_reference.as_deref())
+        .map(|r| sepa::validate_reference(index, "endtoend_reference", r))
+        .transpose()?
+        .unwrap_or_else(|| "NOTPROVIDED".to_string());
+    let purpose_code = non_empty(params.purpose_code.as_deref())
+        .map(|c| sepa::validate_purpose_code(index, c))
+        .transpose()?;
+    let instant = match non_empty(params.instrument_code.as_deref()).map(str::to_ascii_uppercase) {
+        None => false,
+        Some(code) if code == "TRF" => false,
+        Some(code) if code == "INST" => true,
+        Some(code) => {
+            return Err(SepaError::InvalidField {
+                index,
+                field: "instrument_code",
+                reason: format!("{code:?} is neither TRF nor INST"),
+            })
+        }
+    };
+    let execution_date =
+        sepa::parse_date(index, "scheduled_date", params.scheduled_date.as_deref())?;
+    let remittance = non_empty(params.purpose.as_deref())
+        .map(|p| sepa::clean_text(p, 140))
+        .filter(|p| !p.is_empty());
+
+    Ok((
+        GroupKey {
+            debtor,
+            execution_date,
+            instant,
+        },
+        Transfer {
+            end_to_end,
+            cents,
+            creditor_name,
+            creditor_iban,
+            creditor_bic,
+            purpose_code,
+            remittance,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+    use chrono::TimeZone;
+
+    fn transfer(amount: f64) -> CreateBankTransferParams {
+        CreateBankTransferParams {
+            from_account: Some("Girokonto".to_string()),
+            to: Some("Jane Doe".to_string()),
+            iban: Some("DE02 1203 0000 0000 2020 51".to_string()),
+            amount: Some(amount),
+            purpose: Some("Rechnung Nr. 17 für März".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn render(transfers: &[CreateBankTransferParams]) -> Result<String, SepaError> {
+        let mut out = Vec::new();
+        let at = Utc.with_ymd_and_hms(2024, 5, 2, 10, 0, 0).unwrap();
+        write_pain001(&mut out, &[test_fixtures::giro()], transfers, at)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_single_transfer() {
+        let xml = render(&[transfer(250.0)]).unwrap();
+        assert!(xml.contains(&format!("<Document xmlns=\"{NAMESPACE}\">")));
+        assert!(xml.contains("<MsgId>MM20240502100000000</MsgId>"));
+        assert!(xml.contains("<NbOfTxs>1</NbOfTxs>"));
+        assert!(xml.contains("<CtrlSum>250.00</CtrlSum>"));
+        assert!(xml.contains("<Nm>Erika Mustermann</Nm>"));
+        assert!(xml.contains("<IBAN>DE89370400440532013000</IBAN>"));
+        assert!(xml.contains("<BICFI>COBADEFFXXX</BICFI>"));
+        assert!(xml.contains("<Dt>1999-01-01</Dt>"));
+        assert!(xml.contains("<EndToEndId>NOTPROVIDED</EndToEndId>"));
+        assert!(xml.contains("<InstdAmt Ccy=\"EUR\">250.00</InstdAmt>"));
+        assert!(xml.contains("<IBAN>DE02120300000000202051</IBAN>"));
+        assert!(xml.contains("<Ustrd>Rechnung Nr. 17 fuer Maerz</Ustrd>"));
+        assert!(!xml.contains("LclInstrm"));
+        assert!(!xml.contains("CdtrAgt"));
+    }
+
+    #[test]
+    fn test_grouping_by_date_and_instrument() {
+        let mut scheduled = transfer(10.0);
+        scheduled.scheduled_date = Some("2024-06-01".to_string());
+        scheduled.endtoend_reference = Some("E2E-1".to_string());
+        scheduled.purpose_code = Some("rent".to_string());
+        let mut instant = transfer(20.0);
+        instant.instrument_code = Some("INST".to_string());
+        instant.bic = Some("bylademm".to_string());
+        let xml = render(&[transfer(0.1), scheduled, instant, transfer(0.2)]).unwrap();
+
+        assert_eq!(xml.matches("<PmtInf>").count(), 3);
+        assert!(xml.contains("<NbOfTxs>4</NbOfTxs>"));
+        assert!(xml.contains("<CtrlSum>30.30</CtrlSum>"));
+        assert!(xml.contains("<CtrlSum>0.30</CtrlSum>"));
+        assert!(xml.contains("<Dt>2024-06-01</Dt>"));
+        assert!(xml.contains("<Cd>INST</Cd>"));
+        assert!(xml.contains("<Cd>RENT</Cd>"));
+        assert!(xml.contains("<EndToEndId>E2E-1</EndToEndId>"));
+        assert!(xml.contains("<BICFI>BYLADEMM</BICFI>"));
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        assert!(matches!(render(&[]), Err(SepaError::Empty)));
+
+        let mut t = transfer(1.0);
+        t.iban = Some("DE02120300000000202052".to_string());
+        assert!(matches!(
+            render(&[transfer(1.0), t]),
+            Err(SepaError::InvalidField {
+                index: 1,
+                field: "iban",
+                ..
+            })
+        ));
+
+        let mut t = transfer(1.0);
+        t.from_account = Some("Unknown".to_string());
+        assert!(matches!(render(&[t]), Err(SepaError::UnknownAccount { index: 0, .. })));
+
+        let mut t = transfer(1.0);
+        t.to = None;
+        assert!(matches!(render(&[t]), Err(SepaError::MissingField { field: "to", .. })));
+
+        let mut t = transfer(1.0);
+        t.instrument_code = Some("SWIFT".to_string());
+        assert!(matches!(
+            render(&[t]),
+            Err(SepaError::InvalidField {
+                field: "instrument_code",
+                ..
+            })
+        ));
+
+        let mut t = transfer(1.0);
+        t.scheduled_date = Some("tomorrow".to_string());
+        assert!(matches!(
+            render(&[t]),
+            Err(SepaError::InvalidField {
+                field: "scheduled_date",
+                ..
+            })
+        ));
+    }
+}
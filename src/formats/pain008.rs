@@ -0,0 +1,585 @@
+//! SEPA direct debit initiation (pain.008.001.08) generation (experimental).
+//!
+//! The collection counterpart of [`super::pain001`]: turns
+//! [`CreateDirectDebitParams`] into a payment file for the bank's upload
+//! portal. Nothing is sent to MoneyMoney.
+//!
+//! Creditor details (name, IBAN, BIC) come from the account each debit's
+//! `from_account` names, so pass the result of [`crate::export_accounts()`].
+//! The SEPA creditor identifier isn't part of [`CreateDirectDebitParams`] and
+//! is passed separately; it is written as `CdtrSchmeId` of every batch.
+//!
+//! Debits are grouped into one `PmtInf` block per creditor account,
+//! instrument (`CORE` or `B2B`), sequence type (`FRST`, `RCUR`, `OOFF`,
+//! `FNAL`) and collection date (`scheduled_date`), since the scheme requires
+//! these to be uniform within a batch. Unlike transfers, direct debits have
+//! no "as soon as possible" date, so `scheduled_date` is required.
+//!
+//! Elements are written in the order of the official pain.008.001.08 schema
+//! and every field is checked against the schema's length and pattern
+//! restrictions (and the SEPA rulebook's) before anything is written; the
+//! first problem is returned as a [`SepaError`].
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use chrono::Utc;
+//! use moneymoney::create_direct_debit::CreateDirectDebitParams;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let accounts = moneymoney::export_accounts()?;
+//! let debits = vec![CreateDirectDebitParams {
+//!     from_account: Some("Vereinskonto".to_string()),
+//!     for_debtor: Some("Max Mustermann".to_string()),
+//!     iban: Some("DE02120300000000202051".to_string()),
+//!     amount: Some(60.0),
+//!     purpose: Some("Mitgliedsbeitrag 2024".to_string()),
+//!     sequence_code: Some("RCUR".to_string()),
+//!     mandate_reference: Some("M-0042".to_string()),
+//!     mandate_date: Some("2021-03-15".to_string()),
+//!     scheduled_date: Some("2024-06-03".to_string()),
+//!     ..Default::default()
+//! }];
+//!
+//! let file = std::fs::File::create("debits.xml")?;
+//! moneymoney::formats::pain008::write_pain008(
+//!     file,
+//!     &accounts,
+//!     &debits,
+//!     "DE98ZZZ09999999999",
+//!     Utc::now(),
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::non_empty;
+use super::sepa::{self, OwnAccount, SepaError};
+use super::xml::XmlWriter;
+use crate::create_direct_debit::CreateDirectDebitParams;
+use crate::export_accounts::MoneymoneyAccount;
+
+/// XML namespace of the generated documents.
+pub const NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:pain.008.001.08";
+
+/// Sequence types accepted in `sequence_code`.
+pub const SEQUENCE_CODES: [&str; 4] = ["FRST", "RCUR", "OOFF", "FNAL"];
+
+/// Sequence types MoneyMoney accepts when it creates the debits; `RCUR`
+/// covers first debits there, so there is no `FRST`.
+pub const MONEYMONEY_SEQUENCE_CODES: [&str; 3] = ["RCUR", "OOFF", "FNAL"];
+
+/// Local instruments accepted in `instrument_code`.
+pub const INSTRUMENT_CODES: [&str; 2] = ["CORE", "B2B"];
+
+/// A debit after validation, ready to be written.
+struct Debit {
+    end_to_end: String,
+    cents: i64,
+    mandate_reference: String,
+    mandate_date: NaiveDate,
+    debtor_name: String,
+    debtor_iban: String,
+    debtor_bic: Option<String>,
+    purpose_code: Option<String>,
+    remittance: Option<String>,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct GroupKey {
+    creditor: OwnAccount,
+    instrument: &'static str,
+    sequence: &'static str,
+    collection_date: NaiveDate,
+}
+
+/// Validate `debits` and write them as a pain.008.001.08 document.
+///
+/// `creditor_id` is the SEPA creditor identifier (e.g. `DE98ZZZ09999999999`).
+/// `created_at` becomes the creation timestamp and, with millisecond
+/// precision, the message id (`MsgId`).
+///
+/// # Errors
+///
+/// Returns [`SepaError`] if `debits` is empty, `creditor_id` is invalid, a
+/// debit is missing a required field (`from_account`, `for`, `iban`,
+/// `amount`, `mandate_reference`, `mandate_date`, `scheduled_date`), a field
+/// is invalid, or `from_account` doesn't match any of `accounts`. Errors
+/// about `creditor_id` are reported with index 0.
+pub fn write_pain008<W: Write>(
+    writer: W,
+    accounts: &[MoneymoneyAccount],
+    debits: &[CreateDirectDebitParams],
+    creditor_id: &str,
+    created_at: DateTime<Utc>,
+) -> Result<(), SepaError> {
+    if debits.is_empty() {
+        return Err(SepaError::Empty);
+    }
+    let creditor_id = sepa::validate_creditor_id(0, creditor_id)?;
+    let mut groups: BTreeMap<GroupKey, Vec<Debit>> = BTreeMap::new();
+    for (index, params) in debits.iter().enumerate() {
+        let (key, debit) = validate(index, accounts, params)?;
+        groups.entry(key).or_default().push(debit);
+    }
+
+    let message_id = format!("MM{}", created_at.format("%Y%m%d%H%M%S%3f"));
+    let total_cents: i64 = groups.values().flatten().map(|d| d.cents).sum();
+    let initiator = groups
+        .keys()
+        .next()
+        .map(|k| k.creditor.name.clone())
+        .unwrap_or_default();
+
+    let mut w = XmlWriter::new(writer);
+    w.raw(r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    w.open_with_attrs("Document", &[("xmlns", NAMESPACE)])?;
+    w.open("CstmrDrctDbtInitn")?;
+
+    w.open("GrpHdr")?;
+    w.leaf("MsgId", &message_id)?;
+    w.leaf("CreDtTm", &created_at.format("%Y-%m-%dT%H:%M:%S").to_string())?;
+    w.leaf("NbOfTxs", &debits.len().to_string())?;
+    w.leaf("CtrlSum", &sepa::format_cents(total_cents))?;
+    w.open("InitgPty")?;
+    w.leaf("Nm", &initiator)?;
+    w.close()?;
+    w.close()?;
+
+    for (n, (key, group)) in groups.iter().enumerate() {
+        w.open("PmtInf")?;
+        w.leaf("PmtInfId", &format!("{message_id}-{}", n + 1))?;
+        w.leaf("PmtMtd", "DD")?;
+        w.leaf("NbOfTxs", &group.len().to_string())?;
+        w.leaf("CtrlSum", &sepa::format_cents(group.iter().map(|d| d.cents).sum()))?;
+        w.open("PmtTpInf")?;
+        w.open("SvcLvl")?;
+        w.leaf("Cd", "SEPA")?;
+        w.close()?;
+        w.open("LclInstrm")?;
+        w.leaf("Cd", key.instrument)?;
+        w.close()?;
+        w.leaf("SeqTp", key.sequence)?;
+        w.close()?;
+        w.leaf("ReqdColltnDt", &key.collection_date.format("%Y-%m-%d").to_string())?;
+        w.open("Cdtr")?;
+        w.leaf("Nm", &key.creditor.name)?;
+        w.close()?;
+        w.open("CdtrAcct")?;
+        w.open("Id")?;
+        w.leaf("IBAN", &key.creditor.iban)?;
+        w.close()?;
+        w.close()?;
+        w.open("CdtrAgt")?;
+        sepa::write_agent(&mut w, key.creditor.bic.as_deref())?;
+        w.close()?;
+        w.leaf("ChrgBr", "SLEV")?;
+        w.open("CdtrSchmeId")?;
+        w.open("Id")?;
+        w.open("PrvtId")?;
+        w.open("Othr")?;
+        w.leaf("Id", &creditor_id)?;
+        w.open("SchmeNm")?;
+        w.leaf("Prtry", "SEPA")?;
+        w.close()?;
+        w.close()?;
+        w.close()?;
+        w.close()?;
+        w.close()?;
+
+        for d in group {
+            w.open("DrctDbtTxInf")?;
+            w.open("PmtId")?;
+            w.leaf("EndToEndId", &d.end_to_end)?;
+            w.close()?;
+            w.leaf_with_attrs("InstdAmt", &[("Ccy", "EUR")], &sepa::format_cents(d.cents))?;
+            w.open("DrctDbtTx")?;
+            w.open("MndtRltdInf")?;
+            w.leaf("MndtId", &d.mandate_reference)?;
+            w.leaf("DtOfSgntr", &d.mandate_date.format("%Y-%m-%d").to_string())?;
+            w.close()?;
+            w.close()?;
+            w.open("DbtrAgt")?;
+            sepa::write_agent(&mut w, d.debtor_bic.as_deref())?;
+            w.close()?;
+            w.open("Dbtr")?;
+            w.leaf("Nm", &d.debtor_name)?;
+            w.close()?;
+            w.open("DbtrAcct")?;
+            w.open("Id")?;
+            w.leaf("IBAN", &d.debtor_iban)?;
+            w.close()?;
+            w.close()?;
+            if let Some(code) = &d.purpose_code {
+                w.open("Purp")?;
+                w.leaf("Cd", code)?;
+                w.close()?;
+            }
+            if let Some(text) = &d.remittance {
+                w.open("RmtInf")?;
+                w.leaf("Ustrd", text)?;
+                w.close()?;
+            }
+            w.close()?;
+        }
+        w.close()?;
+    }
+
+    w.finish()?;
+    Ok(())
+}
+
+/// Validate a `sequence_code`, defaulting to `RCUR` like MoneyMoney does.
+pub(crate) fn sequence_code(index: usize, code: Option<&str>) -> Result<&'static str, SepaError> {
+    one_of(index, "sequence_code", code, &SEQUENCE_CODES, "RCUR")
+}
+
+/// Validate an `instrument_code`, defaulting to `CORE` like MoneyMoney does.
+pub(crate) fn instrument_code(index: usize, code: Option<&str>) -> Result<&'static str, SepaError> {
+    one_of(index, "instrument_code", code, &INSTRUMENT_CODES, "CORE")
+}
+
+fn one_of(
+    index: usize,
+    field: &'static str,
+    code: Option<&str>,
+    allowed: &[&'static str],
+    default: &'static str,
+) -> Result<&'static str, SepaError> {
+    let Some(code) = non_empty(code) else {
+        return Ok(default);
+    };
+    allowed
+        .iter()
+        .find(|c| c.eq_ignore_ascii_case(code))
+        .copied()
+        .ok_or_else(|| SepaError::InvalidField {
+            index,
+            field,
+            reason: format!("{code:?} is not one of {}", allowed.join(", ")),
+        })
+}
+
+/// Check one direct debit before handing it to MoneyMoney's
+/// [`crate::create_direct_debit()`].
+///
+/// Applies the same checks as [`write_pain008`] (mandate reference and date,
+/// sequence and instrument codes, IBAN, BIC, amount, references), except
+/// that `from_account` is only required, not resolved, `scheduled_date`
+/// may be missing (MoneyMoney then picks the earliest possible date) and
+/// `sequence_code` must be one of [`MONEYMONEY_SEQUENCE_CODES`].
+///
+/// # Errors
+///
+/// Returns the first problem as [`SepaError`], reported with `index`.
+pub fn validate_direct_debit(
+    index: usize,
+    params: &CreateDirectDebitParams,
+) -> Result<(), SepaError> {
+    if non_empty(params.from_account.as_deref()).is_none() {
+        return Err(SepaError::MissingField {
+            index,
+            field: "from_account",
+        });
+    }
+    one_of(
+        index,
+        "sequence_code",
+        params.sequence_code.as_deref(),
+        &MONEYMONEY_SEQUENCE_CODES,
+        "RCUR",
+    )?;
+    debit_fields(index, params).map(|_| ())
+}
+
+/// A debit's fields, checked independently of the creditor account.
+struct DebitFields {
+    instrument: &'static str,
+    sequence: &'static str,
+    collection_date: Option<NaiveDate>,
+    debit: Debit,
+}
+
+fn validate(
+    index: usize,
+    accounts: &[MoneymoneyAccount],
+    params: &CreateDirectDebitParams,
+) -> Result<(GroupKey, Debit), SepaError> {
+    let creditor = OwnAccount::resolve(index, accounts, params.from_account.as_deref())?;
+    let fields = debit_fields(index, params)?;
+    let collection_date = fields.collection_date.ok_or(SepaError::MissingField {
+        index,
+        field: "scheduled_date",
+    })?;
+    Ok((
+        GroupKey {
+            creditor,
+            instrument: fields.instrument,
+            sequence: fields.sequence,
+            collection_date,
+        },
+        fields.debit,
+    ))
+}
+
+fn debit_fields(index: usize, params: &CreateDirectDebitParams) -> Result<DebitFields, SepaError> {
+    let debtor_name = non_empty(params.for_debtor.as_deref())
+        .map(|name| sepa::clean_text(name, 70))
+        .ok_or(SepaError::MissingField {
+            index,
+            field: "for",
+        })?;
+    let debtor_iban = non_empty(params.iban.as_deref())
+        .ok_or(SepaError::MissingField {
+            index,
+            field: "iban",
+        })
+        .and_then(|iban| sepa::validate_iban(index, "iban", iban))?;
+    let debtor_bic = non_empty(params.bic.as_deref())
+        .map(|bic| sepa::validate_bic(index, "bic", bic))
+        .transpose()?;
+    let cents = sepa::amount_cents(index, params.amount)?;
+    let end_to_end = non_empty(params.endtoend_reference.as_deref())
+        .map(|r| sepa::validate_reference(index, "endtoend_reference", r))
+        .transpose()?
+        .unwrap_or_else(|| "NOTPROVIDED".to_string());
+    let purpose_code = non_empty(params.purpose_code.as_deref())
+        .map(|c| sepa::validate_purpose_code(index, c))
+        .transpose()?;
+    let instrument = instrument_code(index, params.instrument_code.as_deref())?;
+    let sequence = sequence_code(index, params.sequence_code.as_deref())?;
+    let mandate_reference = non_empty(params.mandate_reference.as_deref())
+        .ok_or(SepaError::MissingField {
+            index,
+            field: "mandate_reference",
+        })
+        .and_then(|r| sepa::validate_reference(index, "mandate_reference", r))?;
+    let mandate_date = sepa::parse_date(index, "mandate_date", params.mandate_date.as_deref())?
+        .ok_or(SepaError::MissingField {
+            index,
+            field: "mandate_date",
+        })?;
+    let collection_date =
+        sepa::parse_date(index, "scheduled_date", params.scheduled_date.as_deref())?;
+    if let Some(collection_date) = collection_date.filter(|d| mandate_date > *d) {
+        return Err(SepaError::InvalidField {
+            index,
+            field: "mandate_date",
+            reason: format!("{mandate_date} is after the collection date {collection_date}"),
+        });
+    }
+    let remittance = non_empty(params.purpose.as_deref())
+        .map(|p| sepa::clean_text(p, 140))
+        .filter(|p| !p.is_empty());
+
+    Ok(DebitFields {
+        instrument,
+        sequence,
+        collection_date,
+        debit: Debit {
+            end_to_end,
+            cents,
+            mandate_reference,
+            mandate_date,
+            debtor_name,
+            debtor_iban,
+            debtor_bic,
+            purpose_code,
+            remittance,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+    use chrono::TimeZone;
+
+    const CREDITOR_ID: &str = "DE98ZZZ09999999999";
+
+    fn debit(amount: f64) -> CreateDirectDebitParams {
+        CreateDirectDebitParams {
+            from_account: Some("Girokonto".to_string()),
+            for_debtor: Some("Max Müller".to_string()),
+            iban: Some("DE02 1203 0000 0000 2020 51".to_string()),
+            amount: Some(amount),
+            purpose: Some("Mitgliedsbeitrag 2024".to_string()),
+            mandate_reference: Some("M-0042".to_string()),
+            mandate_date: Some("2021-03-15".to_string()),
+            scheduled_date: Some("2024-06-03".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn render(debits: &[CreateDirectDebitParams]) -> Result<String, SepaError> {
+        let mut out = Vec::new();
+        let at = Utc.with_ymd_and_hms(2024, 5, 2, 10, 0, 0).unwrap();
+        write_pain008(&mut out, &[test_fixtures::giro()], debits, CREDITOR_ID, at)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_single_debit() {
+        let xml = render(&[debit(60.0)]).unwrap();
+        assert!(xml.contains(&format!("<Document xmlns=\"{NAMESPACE}\">")));
+        assert!(xml.contains("<CstmrDrctDbtInitn>"));
+        assert!(xml.contains("<PmtMtd>DD</PmtMtd>"));
+        assert!(xml.contains("<CtrlSum>60.00</CtrlSum>"));
+        assert!(xml.contains("<Cd>CORE</Cd>"));
+        assert!(xml.contains("<SeqTp>RCUR</SeqTp>"));
+        assert!(xml.contains("<ReqdColltnDt>2024-06-03</ReqdColltnDt>"));
+        assert!(xml.contains("<IBAN>DE89370400440532013000</IBAN>"));
+        assert!(xml.contains(&format!("<Id>{CREDITOR_ID}</Id>")));
+        assert!(xml.contains("<Prtry>SEPA</Prtry>"));
+        assert!(xml.contains("<MndtId>M-0042</MndtId>"));
+        assert!(xml.contains("<DtOfSgntr>2021-03-15</DtOfSgntr>"));
+        assert!(xml.contains("<Nm>Max Mueller</Nm>"));
+        assert!(xml.contains("<IBAN>DE02120300000000202051</IBAN>"));
+        assert!(xml.contains("<Id>NOTPROVIDED</Id>"));
+        assert!(xml.contains("<Ustrd>Mitgliedsbeitrag 2024</Ustrd>"));
+
+        // Schema order within a transaction.
+        let order = [
+            "<PmtId>",
+            "<InstdAmt",
+            "<DrctDbtTx>",
+            "<DbtrAgt>",
+            "<Dbtr>",
+            "<DbtrAcct>",
+        ];
+        let positions: Vec<usize> = order.iter().map(|tag| xml.find(tag).unwrap()).collect();
+        assert!(positions.windows(2).all(|p| p[0] < p[1]));
+    }
+
+    #[test]
+    fn test_grouping_by_sequence_and_date() {
+        let mut first = debit(10.0);
+        first.sequence_code = Some("frst".to_string());
+        let mut later = debit(20.0);
+        later.scheduled_date = Some("2024-07-01".to_string());
+        let mut b2b = debit(30.0);
+        b2b.instrument_code = Some("B2B".to_string());
+        b2b.sequence_code = Some("OOFF".to_string());
+        let xml = render(&[debit(1.0), first, later, b2b, debit(2.0)]).unwrap();
+
+        assert_eq!(xml.matches("<PmtInf>").count(), 4);
+        assert!(xml.contains("<NbOfTxs>5</NbOfTxs>"));
+        assert!(xml.contains("<CtrlSum>63.00</CtrlSum>"));
+        assert!(xml.contains("<CtrlSum>3.00</CtrlSum>"));
+        assert!(xml.contains("<SeqTp>FRST</SeqTp>"));
+        assert!(xml.contains("<SeqTp>OOFF</SeqTp>"));
+        assert!(xml.contains("<Cd>B2B</Cd>"));
+        assert!(xml.contains("<ReqdColltnDt>2024-07-01</ReqdColltnDt>"));
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        assert!(matches!(render(&[]), Err(SepaError::Empty)));
+
+        let mut out = Vec::new();
+        let result = write_pain008(
+            &mut out,
+            &[test_fixtures::giro()],
+            &[debit(1.0)],
+            "DE00ZZZ09999999999",
+            Utc::now(),
+        );
+        assert!(matches!(
+            result,
+            Err(SepaError::InvalidField {
+                field: "creditor_id",
+                ..
+            })
+        ));
+
+        for field in ["mandate_reference", "mandate_date", "scheduled_date"] {
+            let mut d = debit(1.0);
+            match field {
+                "mandate_reference" => d.mandate_reference = None,
+                "mandate_date" => d.mandate_date = None,
+                _ => d.scheduled_date = None,
+            }
+            assert!(
+                matches!(render(&[d]), Err(SepaError::MissingField { field: f, .. }) if f == field),
+                "{field}"
+            );
+        }
+
+        let mut d = debit(1.0);
+        d.sequence_code = Some("LAST".to_string());
+        assert!(matches!(
+            render(&[d]),
+            Err(SepaError::InvalidField {
+                field: "sequence_code",
+                ..
+            })
+        ));
+
+        let mut d = debit(1.0);
+        d.mandate_date = Some("2024-06-04".to_string());
+        assert!(matches!(
+            render(&[debit(1.0), d]),
+            Err(SepaError::InvalidField {
+                index: 1,
+                field: "mandate_date",
+                ..
+            })
+        ));
+
+        let mut d = debit(1.0);
+        d.for_debtor = None;
+        assert!(matches!(render(&[d]), Err(SepaError::MissingField { field: "for", .. })));
+    }
+
+    #[test]
+    fn test_validate_direct_debit() {
+        assert!(validate_direct_debit(0, &debit(1.0)).is_ok());
+
+        // No collection date is fine for MoneyMoney, but not for a file.
+        let mut d = debit(1.0);
+        d.scheduled_date = None;
+        assert!(validate_direct_debit(0, &d).is_ok());
+
+        let mut d = debit(1.0);
+        d.from_account = None;
+        assert!(matches!(
+            validate_direct_debit(3, &d),
+            Err(SepaError::MissingField {
+                index: 3,
+                field: "from_account"
+            })
+        ));
+
+        // FRST is for files only; MoneyMoney uses RCUR for first debits.
+        for code in ["LAST", "FRST"] {
+            let mut d = debit(1.0);
+            d.sequence_code = Some(code.to_string());
+            assert!(
+                matches!(
+                    validate_direct_debit(0, &d),
+                    Err(SepaError::InvalidField {
+                        field: "sequence_code",
+                        ..
+                    })
+                ),
+                "{code}"
+            );
+        }
+
+        let mut d = debit(1.0);
+        d.mandate_reference = Some("M/0042 ü".to_string());
+        assert!(matches!(
+            validate_direct_debit(0, &d),
+            Err(SepaError::InvalidField {
+                field: "mandate_reference",
+                ..
+            })
+        ));
+    }
+}
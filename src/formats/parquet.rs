@@ -0,0 +1,85 @@
+//! Apache Parquet files from Arrow batches.
+//!
+//! Writes a batch from [`super::arrow`] as a Snappy-compressed Parquet file.
+//! Column types carry over: timestamps stay UTC timestamps, amounts stay
+//! decimals, and dictionary-encoded columns are stored dictionary-encoded, so
+//! DuckDB and Polars load the file with the right types and no parsing.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use chrono::NaiveDate;
+//! use moneymoney::export_transactions::ExportTransactionsParams;
+//! use moneymoney::formats::{arrow, parquet};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let params = ExportTransactionsParams::new(NaiveDate::from_ymd_opt(2020, 1, 1).unwrap());
+//! let response = moneymoney::export_transactions(params)?;
+//!
+//! let batch = arrow::transactions_batch(&response.transactions)?;
+//! let file = std::fs::File::create("transactions.parquet")?;
+//! parquet::write_parquet(file, &batch)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::Write;
+
+use arrow_array::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+
+/// Write `batch` as a complete Parquet file.
+///
+/// # Errors
+///
+/// Returns [`ParquetError`] if encoding or writing fails.
+pub fn write_parquet<W: Write + Send>(writer: W, batch: &RecordBatch) -> Result<(), ParquetError> {
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_created_by(format!("moneymoney {}", env!("CARGO_PKG_VERSION")))
+        .build();
+    let mut writer = ArrowWriter::try_new(writer, batch.schema(), Some(properties))?;
+    writer.write(batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::arrow::transactions_batch;
+    use crate::test_fixtures::{date, transaction, GIRO_UUID};
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn test_round_trip_keeps_types() {
+        let mut rent = transaction(1, GIRO_UUID, date(2024, 3, 1), "Miete", -900.0);
+        rent.category = Some("Wohnen\\Miete".to_string());
+        let batch = transactions_batch(&[
+            rent,
+            transaction(2, GIRO_UUID, date(2024, 3, 2), "REWE", -45.67),
+        ])
+        .unwrap();
+
+        let path = std::env::temp_dir()
+            .join(format!("moneymoney-parquet-{}.parquet", uuid::Uuid::new_v4()));
+        write_parquet(std::fs::File::create(&path).unwrap(), &batch).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(batches.len(), 1);
+        let read = &batches[0];
+        assert_eq!(read.num_rows(), 2);
+        assert_eq!(read.schema().fields(), batch.schema().fields());
+        let category = read.column_by_name("category").unwrap();
+        assert!(category.is_null(1));
+        std::fs::remove_file(path).unwrap();
+    }
+}
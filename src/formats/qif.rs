@@ -0,0 +1,205 @@
+//! QIF (Quicken Interchange Format) transaction export.
+//!
+//! QIF is the lowest common denominator older finance tools still import.
+//! Transactions are written as one `!Account` / `!Type:…` section per account:
+//!
+//! - Credit cards become `!Type:CCard`, cash accounts `!Type:Cash`, loans
+//!   `!Type:Oth L` and everything else `!Type:Bank`.
+//! - Category paths use QIF's colon form (`Food:Groceries`) instead of
+//!   MoneyMoney's backslash form (`Food\Groceries`).
+//! - Checked-off transactions are marked cleared (`C*`).
+//! - The memo is the purpose, followed by the comment if there is one.
+//!
+//! Dates use Quicken's `MM/DD/YYYY` form, which is what QIF importers expect
+//! unless told otherwise.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use chrono::NaiveDate;
+//! use moneymoney::export_transactions::ExportTransactionsParams;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let accounts = moneymoney::export_accounts()?;
+//! let params = ExportTransactionsParams::new(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+//! let response = moneymoney::export_transactions(params)?;
+//!
+//! let file = std::fs::File::create("transactions.qif")?;
+//! moneymoney::formats::qif::write_qif(file, &accounts, &response.transactions)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, Write};
+
+use super::transaction_memo;
+use crate::export_accounts::{MoneymoneyAccount, MoneymoneyAccountType};
+use crate::export_transactions::MoneymoneyTransaction;
+
+/// QIF section type (the part after `!Type:`) for a MoneyMoney account type.
+pub fn section_type(account_type: &MoneymoneyAccountType) -> &'static str {
+    match account_type {
+        MoneymoneyAccountType::CreditCard => "CCard",
+        MoneymoneyAccountType::Cash => "Cash",
+        MoneymoneyAccountType::Loan => "Oth L",
+        _ => "Bank",
+    }
+}
+
+/// Convert a MoneyMoney category path (`Food\Groceries`) to QIF form (`Food:Groceries`).
+///
+/// QIF reserves `:` for subcategories and `/` for classes, so those characters
+/// inside a single category name are replaced with `-`.
+pub fn category_path(category: &str) -> String {
+    category
+        .split('\\')
+        .map(|segment| segment.trim().replace([':', '/'], "-"))
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Write transactions as QIF, one section per account.
+///
+/// Accounts are written in the order of `accounts`; account groups, portfolio
+/// accounts and accounts without transactions are skipped. Transactions whose
+/// `account_uuid` isn't in `accounts` are ignored.
+pub fn write_qif<W: Write>(
+    mut writer: W,
+    accounts: &[MoneymoneyAccount],
+    transactions: &[MoneymoneyTransaction],
+) -> io::Result<()> {
+    for account in accounts.iter().filter(|a| !a.group && !a.portfolio) {
+        let own: Vec<&MoneymoneyTransaction> = transactions
+            .iter()
+            .filter(|t| t.account_uuid == account.uuid)
+            .collect();
+        if own.is_empty() {
+            continue;
+        }
+        let section = section_type(&account.r#type);
+
+        writeln!(writer, "!Account")?;
+        writeln!(writer, "N{}", single_line(&account.name))?;
+        writeln!(writer, "T{section}")?;
+        writeln!(writer, "^")?;
+        writeln!(writer, "!Type:{section}")?;
+        for t in own {
+            write_transaction(&mut writer, t)?;
+        }
+    }
+    writer.flush()
+}
+
+fn write_transaction<W: Write>(writer: &mut W, t: &MoneymoneyTransaction) -> io::Result<()> {
+    writeln!(writer, "D{}", t.booking_date.format("%m/%d/%Y"))?;
+    writeln!(writer, "T{:.2}", t.amount)?;
+    if t.checkmark {
+        writeln!(writer, "C*")?;
+    }
+    if !t.name.trim().is_empty() {
+        writeln!(writer, "P{}", single_line(&t.name))?;
+    }
+    if let Some(memo) = transaction_memo(t) {
+        writeln!(writer, "M{}", single_line(&memo))?;
+    }
+    if let Some(category) = t.category.as_deref().map(category_path) {
+        if !category.is_empty() {
+            writeln!(writer, "L{category}")?;
+        }
+    }
+    writeln!(writer, "^")
+}
+
+/// QIF is line-based; embedded line breaks would start a new field.
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, date, transaction, CARD_UUID, CASH_UUID, GIRO_UUID};
+
+    fn render(accounts: &[MoneymoneyAccount], transactions: &[MoneymoneyTransaction]) -> String {
+        let mut out = Vec::new();
+        write_qif(&mut out, accounts, transactions).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_category_path() {
+        assert_eq!(category_path("Food\\Groceries"), "Food:Groceries");
+        assert_eq!(category_path("Haus\\Strom: Abschlag"), "Haus:Strom- Abschlag");
+        assert_eq!(category_path("In/Out"), "In-Out");
+        assert_eq!(category_path(""), "");
+    }
+
+    #[test]
+    fn test_section_type() {
+        assert_eq!(section_type(&MoneymoneyAccountType::Giro), "Bank");
+        assert_eq!(section_type(&MoneymoneyAccountType::Savings), "Bank");
+        assert_eq!(section_type(&MoneymoneyAccountType::CreditCard), "CCard");
+        assert_eq!(section_type(&MoneymoneyAccountType::Cash), "Cash");
+        assert_eq!(section_type(&MoneymoneyAccountType::Loan), "Oth L");
+    }
+
+    #[test]
+    fn test_write_sections() {
+        let mut groceries = transaction(1, GIRO_UUID, date(2024, 3, 2), "REWE", -45.67);
+        groceries.category = Some("Lebensmittel\\Supermarkt".to_string());
+        groceries.purpose = Some("Einkauf\nWoche 9".to_string());
+        groceries.comment = Some("mit Pfand".to_string());
+        groceries.checkmark = true;
+        let hotel = transaction(2, CARD_UUID, date(2024, 3, 5), "Hotel", -120.5);
+        let coffee = transaction(3, CASH_UUID, date(2024, 3, 6), "Café", -3.2);
+
+        let qif = render(
+            &[
+                test_fixtures::giro(),
+                test_fixtures::credit_card(),
+                test_fixtures::cash(),
+            ],
+            &[groceries, hotel, coffee],
+        );
+        let expected = "\
+!Account
+NGirokonto
+TBank
+^
+!Type:Bank
+D03/02/2024
+T-45.67
+C*
+PREWE
+MEinkauf Woche 9 / mit Pfand
+LLebensmittel:Supermarkt
+^
+!Account
+NKreditkarte
+TCCard
+^
+!Type:CCard
+D03/05/2024
+T-120.50
+PHotel
+^
+!Account
+NBargeld
+TCash
+^
+!Type:Cash
+D03/06/2024
+T-3.20
+PCafé
+^
+";
+        assert_eq!(qif, expected);
+    }
+
+    #[test]
+    fn test_accounts_without_transactions_skipped() {
+        let qif = render(&[test_fixtures::giro()], &[]);
+        assert!(qif.is_empty());
+    }
+}
@@ -0,0 +1,395 @@
+//! Shared pieces of the SEPA payment file generators ([`super::pain001`],
+//! [`super::pain008`]): validation, the SEPA character set and the error type.
+
+use std::io::{self, Write};
+
+use chrono::NaiveDate;
+use thiserror::Error;
+
+use super::is_bic;
+use super::xml::XmlWriter;
+use crate::export_accounts::MoneymoneyAccount;
+
+/// Execution date banks read as "as soon as possible" when none is requested.
+pub(crate) const ASAP_DATE: &str = "1999-01-01";
+
+/// Largest amount a single SEPA transaction may carry, in cents.
+const MAX_AMOUNT_CENTS: i64 = 99_999_999_999;
+
+/// Errors while generating a SEPA payment file.
+///
+/// `index` is the position of the offending payment in the input slice.
+#[derive(Debug, Error)]
+pub enum SepaError {
+    /// A field required for the file is not set.
+    #[error("payment {index}: missing {field}")]
+    MissingField {
+        /// Position of the payment in the input.
+        index: usize,
+        /// Name of the missing field.
+        field: &'static str,
+    },
+
+    /// A field is set but not acceptable in a SEPA file.
+    #[error("payment {index}: invalid {field}: {reason}")]
+    InvalidField {
+        /// Position of the payment in the input.
+        index: usize,
+        /// Name of the invalid field.
+        field: &'static str,
+        /// Why the value was rejected.
+        reason: String,
+    },
+
+    /// `from_account` doesn't name any of the given accounts.
+    #[error("payment {index}: no account matches {account:?}")]
+    UnknownAccount {
+        /// Position of the payment in the input.
+        index: usize,
+        /// The unresolved `from_account` value.
+        account: String,
+    },
+
+    /// The input contained no payments.
+    #[error("no payments to write")]
+    Empty,
+
+    /// Writing the file failed.
+    #[error("failed to write SEPA file: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Own side of a payment file (debtor of a transfer, creditor of a direct debit).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct OwnAccount {
+    pub(crate) name: String,
+    pub(crate) iban: String,
+    pub(crate) bic: Option<String>,
+}
+
+impl OwnAccount {
+    pub(crate) fn resolve(
+        index: usize,
+        accounts: &[MoneymoneyAccount],
+        from_account: Option<&str>,
+    ) -> Result<Self, SepaError> {
+        let ident =
+            from_account
+                .filter(|s| !s.trim().is_empty())
+                .ok_or(SepaError::MissingField {
+                    index,
+                    field: "from_account",
+                })?;
+        let account = accounts
+            .iter()
+            .filter(|a| !a.group)
+            .find(|a| a.matches(ident))
+            .ok_or_else(|| SepaError::UnknownAccount {
+                index,
+                account: ident.to_string(),
+            })?;
+        let iban = super::non_empty(account.iban.as_deref()).ok_or(SepaError::InvalidField {
+            index,
+            field: "from_account",
+            reason: format!("account {:?} has no IBAN", account.name),
+        })?;
+        let owner = super::non_empty(Some(&account.owner)).unwrap_or(&account.name);
+        Ok(Self {
+            name: clean_text(owner, 70),
+            iban: validate_iban(index, "from_account", iban)?,
+            bic: super::non_empty(account.bic.as_deref())
+                .filter(|b| is_bic(b))
+                .map(str::to_string),
+        })
+    }
+}
+
+/// `FinInstnId` with the BIC, or `NOTPROVIDED` (allowed for IBAN-only SEPA payments).
+pub(crate) fn write_agent<W: Write>(w: &mut XmlWriter<W>, bic: Option<&str>) -> io::Result<()> {
+    w.open("FinInstnId")?;
+    match bic {
+        Some(bic) => w.leaf("BICFI", bic)?,
+        None => {
+            w.open("Othr")?;
+            w.leaf("Id", "NOTPROVIDED")?;
+            w.close()?;
+        }
+    }
+    w.close()
+}
+
+/// Normalise and checksum-validate an IBAN (ISO 13616 mod-97).
+pub(crate) fn validate_iban(
+    index: usize,
+    field: &'static str,
+    iban: &str,
+) -> Result<String, SepaError> {
+    let iban: String = iban
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_uppercase();
+    let invalid = |reason: &str| SepaError::InvalidField {
+        index,
+        field,
+        reason: format!("{iban:?} {reason}"),
+    };
+    if iban.len() < 15 || iban.len() > 34 || !iban.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(invalid("is not an IBAN"));
+    }
+    let (head, tail) = iban.split_at(4);
+    let remainder =
+        mod97(tail.chars().chain(head.chars())).ok_or_else(|| invalid("is not an IBAN"))?;
+    if remainder != 1 {
+        return Err(invalid("has a wrong checksum"));
+    }
+    Ok(iban)
+}
+
+/// Normalise and checksum-validate a SEPA creditor identifier.
+///
+/// Layout: country code, two check digits, a three-character business code
+/// (not part of the checksum) and the national identifier. The check digits
+/// are computed like an IBAN's over national identifier + country code.
+pub(crate) fn validate_creditor_id(index: usize, creditor_id: &str) -> Result<String, SepaError> {
+    let id: String = creditor_id
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_uppercase();
+    let invalid = |reason: &str| SepaError::InvalidField {
+        index,
+        field: "creditor_id",
+        reason: format!("{id:?} {reason}"),
+    };
+    if id.len() < 8
+        || id.len() > 35
+        || !id.chars().all(|c| c.is_ascii_alphanumeric())
+        || !id[..2].chars().all(|c| c.is_ascii_alphabetic())
+        || !id[2..4].chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid("is not a SEPA creditor identifier"));
+    }
+    let national = &id[7..];
+    let remainder = mod97(national.chars().chain(id[..4].chars()))
+        .ok_or_else(|| invalid("is not a SEPA creditor identifier"))?;
+    if remainder != 1 {
+        return Err(invalid("has a wrong checksum"));
+    }
+    Ok(id)
+}
+
+/// ISO 7064 mod-97 over alphanumerics, letters counting as 10..=35.
+fn mod97(chars: impl Iterator<Item = char>) -> Option<u32> {
+    chars.into_iter().try_fold(0u32, |acc, c| {
+        let value = c.to_digit(36)?;
+        Some(if value >= 10 {
+            (acc * 100 + value) % 97
+        } else {
+            (acc * 10 + value) % 97
+        })
+    })
+}
+
+pub(crate) fn validate_bic(
+    index: usize,
+    field: &'static str,
+    bic: &str,
+) -> Result<String, SepaError> {
+    let bic = bic.trim().to_ascii_uppercase();
+    if !is_bic(&bic) {
+        return Err(SepaError::InvalidField {
+            index,
+            field,
+            reason: format!("{bic:?} is not a BIC"),
+        });
+    }
+    Ok(bic)
+}
+
+/// Amount in cents; must be positive, at most two decimals and within SEPA limits.
+pub(crate) fn amount_cents(index: usize, amount: Option<f64>) -> Result<i64, SepaError> {
+    let amount = amount.ok_or(SepaError::MissingField {
+        index,
+        field: "amount",
+    })?;
+    let cents = (amount * 100.0).round();
+    let invalid = |reason: &str| SepaError::InvalidField {
+        index,
+        field: "amount",
+        reason: reason.to_string(),
+    };
+    if !amount.is_finite() || cents < 1.0 {
+        return Err(invalid("must be at least 0.01"));
+    }
+    if (amount * 100.0 - cents).abs() > 1e-6 {
+        return Err(invalid("must not have more than two decimals"));
+    }
+    let cents = cents as i64;
+    if cents > MAX_AMOUNT_CENTS {
+        return Err(invalid("exceeds 999999999.99"));
+    }
+    Ok(cents)
+}
+
+pub(crate) fn format_cents(cents: i64) -> String {
+    format!("{}.{:02}", cents / 100, cents % 100)
+}
+
+/// Parse an optional `YYYY-MM-DD` date field.
+pub(crate) fn parse_date(
+    index: usize,
+    field: &'static str,
+    date: Option<&str>,
+) -> Result<Option<NaiveDate>, SepaError> {
+    match super::non_empty(date) {
+        None => Ok(None),
+        Some(d) => NaiveDate::parse_from_str(d, "%Y-%m-%d")
+            .map(Some)
+            .map_err(|_| SepaError::InvalidField {
+                index,
+                field,
+                reason: format!("{d:?} is not a YYYY-MM-DD date"),
+            }),
+    }
+}
+
+/// Validate a reference (end-to-end id, mandate reference): 1–35 characters
+/// of the restricted SEPA reference character set.
+pub(crate) fn validate_reference(
+    index: usize,
+    field: &'static str,
+    reference: &str,
+) -> Result<String, SepaError> {
+    let reference = reference.trim();
+    let allowed = |c: char| c.is_ascii_alphanumeric() || "/-?:().,'+ ".contains(c);
+    if reference.is_empty()
+        || reference.chars().count() > 35
+        || !reference.chars().all(allowed)
+        || reference.starts_with('/')
+        || reference.contains("//")
+    {
+        return Err(SepaError::InvalidField {
+            index,
+            field,
+            reason: format!(
+                "{reference:?} must be 1-35 SEPA characters without leading or double '/'"
+            ),
+        });
+    }
+    Ok(reference.to_string())
+}
+
+/// Validate an ISO 20022 external purpose code (four uppercase letters).
+pub(crate) fn validate_purpose_code(index: usize, code: &str) -> Result<String, SepaError> {
+    let code = code.trim().to_ascii_uppercase();
+    if code.len() != 4 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(SepaError::InvalidField {
+            index,
+            field: "purpose_code",
+            reason: format!("{code:?} is not a four-letter purpose code"),
+        });
+    }
+    Ok(code)
+}
+
+/// Convert free text to the SEPA Latin character set and cut it to `max` characters.
+///
+/// German umlauts and `ß` are transliterated, `&` becomes `+`, anything else
+/// outside the set becomes a space.
+pub(crate) fn clean_text(text: &str, max: usize) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            'ä' => out.push_str("ae"),
+            'ö' => out.push_str("oe"),
+            'ü' => out.push_str("ue"),
+            'Ä' => out.push_str("Ae"),
+            'Ö' => out.push_str("Oe"),
+            'Ü' => out.push_str("Ue"),
+            'ß' => out.push_str("ss"),
+            '&' => out.push('+'),
+            c if c.is_ascii_alphanumeric() || "/-?:().,'+ ".contains(c) => out.push(c),
+            _ => out.push(' '),
+        }
+    }
+    let collapsed = out.split_whitespace().collect::<Vec<_>>().join(" ");
+    collapsed.chars().take(max).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_iban() {
+        assert_eq!(
+            validate_iban(0, "iban", "de89 3704 0044 0532 0130 00").unwrap(),
+            "DE89370400440532013000"
+        );
+        assert!(matches!(
+            validate_iban(3, "iban", "DE89370400440532013001"),
+            Err(SepaError::InvalidField {
+                index: 3,
+                field: "iban",
+                ..
+            })
+        ));
+        assert!(validate_iban(0, "iban", "not an iban").is_err());
+    }
+
+    #[test]
+    fn test_validate_creditor_id() {
+        assert_eq!(
+            validate_creditor_id(0, "de98 zzz0 9999 9999 99").unwrap(),
+            "DE98ZZZ09999999999"
+        );
+        assert!(validate_creditor_id(0, "DE97ZZZ09999999999").is_err());
+        assert!(validate_creditor_id(0, "ZZZ").is_err());
+    }
+
+    #[test]
+    fn test_amount_cents() {
+        assert_eq!(amount_cents(0, Some(100.5)).unwrap(), 10050);
+        assert_eq!(amount_cents(0, Some(0.1 + 0.2)).unwrap(), 30);
+        assert!(amount_cents(0, Some(0.0)).is_err());
+        assert!(amount_cents(0, Some(-5.0)).is_err());
+        assert!(amount_cents(0, Some(1.005)).is_err());
+        assert!(amount_cents(0, Some(1_000_000_000.0)).is_err());
+        assert!(matches!(
+            amount_cents(0, None),
+            Err(SepaError::MissingField {
+                field: "amount",
+                ..
+            })
+        ));
+        assert_eq!(format_cents(10050), "100.50");
+        assert_eq!(format_cents(7), "0.07");
+    }
+
+    #[test]
+    fn test_clean_text() {
+        assert_eq!(clean_text("Müller & Söhne GmbH", 70), "Mueller + Soehne GmbH");
+        assert_eq!(clean_text("Rechnung #42\n€ 10", 70), "Rechnung 42 10");
+        assert_eq!(clean_text("abcdef", 3), "abc");
+    }
+
+    #[test]
+    fn test_validate_reference() {
+        assert_eq!(validate_reference(0, "ref", " INV-2024/01 ").unwrap(), "INV-2024/01");
+        assert!(validate_reference(0, "ref", "/leading").is_err());
+        assert!(validate_reference(0, "ref", "a//b").is_err());
+        assert!(validate_reference(0, "ref", &"x".repeat(36)).is_err());
+        assert!(validate_reference(0, "ref", "Ümlaut").is_err());
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(
+            parse_date(0, "scheduled_date", Some("2024-05-01")).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 5, 1)
+        );
+        assert_eq!(parse_date(0, "scheduled_date", None).unwrap(), None);
+        assert!(parse_date(0, "scheduled_date", Some("01.05.2024")).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! Minimal indenting XML writer shared by the XML-based formats.
+//!
+//! Only what the statement and payment writers need: nested elements, text
+//! leaves, attributes and escaping. No namespaces handling beyond writing
+//! `xmlns` as a plain attribute.
+
+use std::io::{self, Write};
+
+/// Escape `&`, `<`, `>`, `"` and `'` for use in text nodes and attribute values.
+pub(crate) fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+pub(crate) struct XmlWriter<W: Write> {
+    inner: W,
+    stack: Vec<&'static str>,
+}
+
+impl<W: Write> XmlWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self {
+            inner,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Write a line verbatim at the current depth (declarations, processing instructions).
+    pub(crate) fn raw(&mut self, line: &str) -> io::Result<()> {
+        self.indent()?;
+        writeln!(self.inner, "{line}")
+    }
+
+    pub(crate) fn open(&mut self, tag: &'static str) -> io::Result<()> {
+        self.open_with_attrs(tag, &[])
+    }
+
+    pub(crate) fn open_with_attrs(
+        &mut self,
+        tag: &'static str,
+        attrs: &[(&str, &str)],
+    ) -> io::Result<()> {
+        self.indent()?;
+        writeln!(self.inner, "<{tag}{}>", format_attrs(attrs))?;
+        self.stack.push(tag);
+        Ok(())
+    }
+
+    pub(crate) fn close(&mut self) -> io::Result<()> {
+        let tag = self
+            .stack
+            .pop()
+            .ok_or_else(|| io::Error::other("XmlWriter::close without open element"))?;
+        self.indent()?;
+        writeln!(self.inner, "</{tag}>")
+    }
+
+    pub(crate) fn leaf(&mut self, tag: &str, text: &str) -> io::Result<()> {
+        self.leaf_with_attrs(tag, &[], text)
+    }
+
+    pub(crate) fn leaf_with_attrs(
+        &mut self,
+        tag: &str,
+        attrs: &[(&str, &str)],
+        text: &str,
+    ) -> io::Result<()> {
+        self.indent()?;
+        writeln!(self.inner, "<{tag}{}>{}</{tag}>", format_attrs(attrs), escape(text))
+    }
+
+    /// Write a leaf only when `text` is present and non-empty.
+    pub(crate) fn opt_leaf(&mut self, tag: &str, text: Option<&str>) -> io::Result<()> {
+        match text {
+            Some(t) if !t.trim().is_empty() => self.leaf(tag, t.trim()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Close any still-open elements and return the underlying writer.
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        while !self.stack.is_empty() {
+            self.close()?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn indent(&mut self) -> io::Result<()> {
+        for _ in 0..self.stack.len() {
+            self.inner.write_all(b"  ")?;
+        }
+        Ok(())
+    }
+}
+
+fn format_attrs(attrs: &[(&str, &str)]) -> String {
+    attrs
+        .iter()
+        .map(|(k, v)| format!(" {k}=\"{}\"", escape(v)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("Tom & Jerry <\"x\">"), "Tom &amp; Jerry &lt;&quot;x&quot;&gt;");
+        assert_eq!(escape("Bäckerei"), "Bäckerei");
+    }
+
+    #[test]
+    fn test_nested_output() {
+        let mut w = XmlWriter::new(Vec::new());
+        w.open_with_attrs("Doc", &[("xmlns", "urn:x")]).unwrap();
+        w.leaf("A", "1").unwrap();
+        w.opt_leaf("B", None).unwrap();
+        w.opt_leaf("C", Some("  ")).unwrap();
+        let out = String::from_utf8(w.finish().unwrap()).unwrap();
+        assert_eq!(out, "<Doc xmlns=\"urn:x\">\n  <A>1</A>\n</Doc>\n");
+    }
+}
@@ -0,0 +1,18 @@
+//! File helpers shared by the modules that persist state.
+
+use std::path::Path;
+
+/// Write `bytes` through a temporary file, creating the directory, so
+/// readers never see half a file. `io_error` turns a failure and the path
+/// it concerns into the caller's error type.
+pub(crate) fn write_atomic<E>(
+    path: &Path,
+    bytes: &[u8],
+    io_error: impl Fn(&Path, std::io::Error) -> E,
+) -> Result<(), E> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir).map_err(|source| io_error(dir, source))?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, bytes).map_err(|source| io_error(&tmp, source))?;
+    std::fs::rename(&tmp, path).map_err(|source| io_error(path, source))
+}
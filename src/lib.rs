@@ -52,17 +52,62 @@
 //! - [`export_categories()`] - Export all categories with budgets
 //! - [`export_transactions()`] - Export transactions with flexible filtering
 //! - [`export_portfolio()`] - Export securities and portfolio holdings
+//! - `export_*_each()` - Hand out exported items one at a time, e.g.
+//!   [`export_transactions::export_transactions_each()`]
+//!
+//! ### Date Ranges
+//! - [`date_range::DateRange`] - Parse `30d`, `last-month`, `2024-Q3` etc. into export parameters
+//!
+//! ### Filtering and Sorting
+//! - `filter::TransactionFilter` - Filter exported transactions by amount, sign, state, payee, purpose, currency and more (requires `filter` feature)
+//! - `filter::sort_transactions` - Sort by date, amount, payee, category or id (requires `filter` feature)
+//!
+//! ### Queries
+//! - [`query::Query`] - Parse and evaluate queries like `amount < -100 and payee:"REWE"` against transactions
 //!
 //! ### Transaction Management
 //! - [`add_transaction()`] - Add transactions to offline accounts
 //! - [`set_transaction()`] - Modify existing transaction properties (checkmark, category, comment)
+//! - `rules::RuleSet` - Categorize transactions by payee, purpose, amount range, creditor ID and more from a TOML or YAML rule file (requires `rules` feature)
+//! - `classify::Classifier` - Suggest categories with confidence from a model trained on categorized transactions (requires `classify` feature)
 //!
 //! ### Payment Operations (Experimental)
 //! - `create_bank_transfer()` - Create SEPA bank transfers (requires `experimental` feature)
 //! - `create_direct_debit()` - Create SEPA direct debit orders (requires `experimental` feature)
 //!
+//! ### File Formats
+//! - [`formats::ofx`] - Write accounts and transactions as OFX statements
+//! - [`formats::qif`] - Write transactions as QIF for legacy finance tools
+//! - [`formats::camt053`] - Write ISO 20022 camt.053 account statements
+//! - `formats::arrow` - Convert transactions, accounts and securities to Arrow record batches (requires `arrow` feature)
+//! - `formats::parquet` - Write record batches as Parquet files (requires `parquet` feature)
+//! - `formats::pain001` - Write SEPA credit transfer files (requires `experimental` feature)
+//! - `formats::pain008` - Write SEPA direct debit files (requires `experimental` feature)
+//!
+//! ### Local Mirror
+//! - [`sync::SyncData`] - Export everything a mirror needs in one go
+//! - `sync::sqlite` - Upsert it into a SQLite database with change tracking (requires `sqlite` feature)
+//! - `snapshot::SnapshotStore` - Keep deduplicated, timestamped snapshots for balance and portfolio history (requires `snapshot` feature)
+//!
+//! ### Comparing Exports
+//! - [`diff::Diff`] - What changed between two exports or snapshots: transactions, balances, categories and holdings
+//!
+//! ### Watching
+//! - `watch::Watcher` - Poll a sliding window and report new, changed and disappeared transactions (requires `watch` feature)
+//!
+//! ### Saved Exports
+//! - `offline::SavedExports` - Answer exports from saved plist or CLI JSON files, on any platform (requires `offline` feature)
+//!
 //! ## Feature Flags
 //!
+//! - `filter` - Enables `filter` for client-side transaction filters (on by default via `cli`)
+//! - `offline` - Enables `offline` for working from saved exports (on by default via `cli`)
+//! - `sqlite` - Enables the SQLite mirror in `sync::sqlite` (on by default via `cli`)
+//! - `snapshot` - Enables the `snapshot` store (on by default via `cli`)
+//! - `watch` - Enables `watch` for following new and changed transactions (on by default via `cli`)
+//! - `rules` - Enables `rules` for rule-based categorization (on by default via `cli`)
+//! - `classify` - Enables `classify` for category suggestions (on by default via `cli`)
+//! - `arrow` / `parquet` - Enable Arrow and Parquet output in `formats` (on by default via `cli`)
 //! - `experimental` - Enables experimental APIs like `create_bank_transfer` that may change
 //! - `test-utils` - Enables test utilities for integration testing (see the `test_utils` module)
 //!
@@ -74,12 +119,35 @@
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 
+#[cfg(feature = "classify")]
+pub mod classify;
+pub mod date_range;
+pub mod diff;
+#[cfg(feature = "filter")]
+pub mod filter;
+pub mod formats;
+#[cfg(any(feature = "classify", feature = "snapshot", feature = "watch"))]
+mod fs;
 mod methods;
+mod stream;
 pub use methods::*;
+#[cfg(feature = "offline")]
+pub mod offline;
+pub mod query;
+#[cfg(feature = "rules")]
+pub mod rules;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+pub mod sync;
+#[cfg(feature = "watch")]
+pub mod watch;
 
 #[cfg(feature = "test-utils")]
 pub mod test_utils;
 
+#[cfg(test)]
+mod test_fixtures;
+
 #[derive(Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum MoneymoneyActions {
@@ -17,9 +17,11 @@
 //! # }
 //! ```
 
+use crate::stream::{call_action_plist_each, Streamed};
 use crate::{call_action_plist, MoneymoneyActions};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::ControlFlow;
 use uuid::Uuid;
 
 /// The type of a MoneyMoney account.
@@ -36,7 +38,7 @@ use uuid::Uuid;
 /// Both English and German strings are supported (e.g., "Cash account"/"Bargeld",
 /// "Giro account"/"Girokonto"). Unknown account type strings are captured
 /// as [`MoneymoneyAccountType::Custom`].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[non_exhaustive]
 pub enum MoneymoneyAccountType {
     /// Account group (container for organizing other accounts).
@@ -66,12 +68,10 @@ pub enum MoneymoneyAccountType {
     Custom(String),
 }
 
-impl Serialize for MoneymoneyAccountType {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let s = match self {
+impl MoneymoneyAccountType {
+    /// The English label MoneyMoney uses for this type (e.g. `Giro account`).
+    pub fn as_str(&self) -> &str {
+        match self {
             MoneymoneyAccountType::Group => "Account group", // Kontengruppe
             MoneymoneyAccountType::Giro => "Giro account",
             MoneymoneyAccountType::Savings => "Savings account",
@@ -83,8 +83,16 @@ impl Serialize for MoneymoneyAccountType {
             MoneymoneyAccountType::Tagesgeld => "Tagesgeld",
             MoneymoneyAccountType::Other => "Other",
             MoneymoneyAccountType::Custom(value) => value,
-        };
-        serializer.serialize_str(s)
+        }
+    }
+}
+
+impl Serialize for MoneymoneyAccountType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
     }
 }
 
@@ -122,8 +130,8 @@ impl<'de> Deserialize<'de> for MoneymoneyAccountType {
 /// # Errors
 ///
 /// Deserialization fails if the currency code is invalid, returning [`crate::Error::InvalidCurrency`].
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(try_from = "Vec<BalanceTuple>")]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(try_from = "BalanceRepr")]
 pub struct AccountBalance {
     /// The balance amount.
     pub amount: f64,
@@ -134,6 +142,16 @@ pub struct AccountBalance {
 #[derive(Debug, Deserialize)]
 struct BalanceTuple(f64, String);
 
+/// The shapes a balance is read from.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum BalanceRepr {
+    /// MoneyMoney's `[[amount, currency]]`.
+    Tuples(Vec<BalanceTuple>),
+    /// `{"amount": .., "currency": ..}`, as [`AccountBalance`] serializes.
+    Object { amount: f64, currency: String },
+}
+
 impl TryFrom<Vec<BalanceTuple>> for AccountBalance {
     type Error = crate::Error;
 
@@ -150,23 +168,39 @@ impl TryFrom<Vec<BalanceTuple>> for AccountBalance {
     }
 }
 
+impl TryFrom<BalanceRepr> for AccountBalance {
+    type Error = crate::Error;
+
+    fn try_from(repr: BalanceRepr) -> Result<Self, Self::Error> {
+        match repr {
+            BalanceRepr::Tuples(tuples) => Self::try_from(tuples),
+            BalanceRepr::Object { amount, currency } => Ok(AccountBalance {
+                amount,
+                currency: iso_currency::Currency::from_code(&currency)
+                    .ok_or(crate::Error::InvalidCurrency(currency))?,
+            }),
+        }
+    }
+}
+
 /// Deserialize an optional [`AccountBalance`] from MoneyMoney's balance array.
 ///
 /// MoneyMoney returns balance as an array of `[amount, currency]` tuples. Some accounts
 /// (notably account groups with no aggregated balance, or hidden/disabled children) emit
 /// an empty array. We map that to `None` rather than treating it as a deserialization
-/// error.
+/// error. `null` and the serialized `{"amount", "currency"}` object are accepted too, so
+/// saved JSON exports read back.
 fn deserialize_optional_balance<'de, D>(deserializer: D) -> Result<Option<AccountBalance>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let tuples = Vec::<BalanceTuple>::deserialize(deserializer)?;
-    if tuples.is_empty() {
-        return Ok(None);
+    match Option::<BalanceRepr>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(BalanceRepr::Tuples(tuples)) if tuples.is_empty() => Ok(None),
+        Some(repr) => AccountBalance::try_from(repr)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
     }
-    AccountBalance::try_from(tuples)
-        .map(Some)
-        .map_err(serde::de::Error::custom)
 }
 
 /// A MoneyMoney account with all its metadata.
@@ -191,7 +225,7 @@ where
 /// * `refresh_timestamp` - Last synchronization timestamp
 /// * `type` - The account type (giro, savings, credit card, etc.)
 /// * `uuid` - Unique identifier for the account
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct MoneymoneyAccount {
     /// The account number.
@@ -209,6 +243,10 @@ pub struct MoneymoneyAccount {
     /// Whether this is an account group.
     pub group: bool,
     /// Account icon as binary data.
+    #[serde(
+        default = "super::empty_icon",
+        deserialize_with = "super::deserialize_icon"
+    )]
     pub icon: plist::Data,
     /// Display indentation level.
     pub indentation: u8,
@@ -236,6 +274,36 @@ pub struct MoneymoneyAccount {
     pub balance_date: Option<DateTime<Utc>>,
 }
 
+impl MoneymoneyAccount {
+    /// Whether `ident` refers to this account the way MoneyMoney resolves
+    /// `fromAccount`/`toAccount`: UUID, IBAN (spaces ignored), account number or
+    /// account name.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # fn main() -> Result<(), moneymoney::Error> {
+    /// let accounts = moneymoney::export_accounts()?;
+    /// let giro = accounts.iter().find(|a| a.matches("DE89 3704 0044 0532 0130 00"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn matches(&self, ident: &str) -> bool {
+        let ident = ident.trim();
+        if ident.is_empty() {
+            return false;
+        }
+        let compact = |s: &str| s.replace(' ', "");
+        self.uuid.to_string().eq_ignore_ascii_case(ident)
+            || self
+                .iban
+                .as_deref()
+                .is_some_and(|iban| compact(iban).eq_ignore_ascii_case(&compact(ident)))
+            || (!self.account_number.is_empty() && self.account_number == ident)
+            || self.name == ident
+    }
+}
+
 /// Export all accounts from MoneyMoney.
 ///
 /// Retrieves all accounts including account groups and their current balances.
@@ -269,6 +337,24 @@ pub fn export_accounts() -> Result<Vec<MoneymoneyAccount>, crate::Error> {
     call_action_plist(MoneymoneyActions::ExportAccounts)
 }
 
+/// Export accounts one by one, without collecting them into a `Vec`.
+///
+/// `f` is called for every account as soon as it is converted; return
+/// [`ControlFlow::Break`] to stop early.
+///
+/// # Errors
+///
+/// Same as [`export_accounts()`].
+pub fn export_accounts_each<F>(mut f: F) -> Result<(), crate::Error>
+where
+    F: FnMut(MoneymoneyAccount) -> ControlFlow<()>,
+{
+    call_action_plist_each(MoneymoneyActions::ExportAccounts, None, |event| match event {
+        Streamed::Item(account) => f(account),
+        Streamed::Creator(_) => ControlFlow::Continue(()),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -445,6 +531,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_account_matches() {
+        let account = crate::test_fixtures::giro();
+        assert!(account.matches(crate::test_fixtures::GIRO_UUID));
+        assert!(account.matches(&crate::test_fixtures::GIRO_UUID.to_uppercase()));
+        assert!(account.matches("DE89 3704 0044 0532 0130 00"));
+        assert!(account.matches("0532013000"));
+        assert!(account.matches("Girokonto"));
+        assert!(!account.matches("Sparkonto"));
+        assert!(!account.matches(""));
+    }
+
     /// Regression test for https://github.com/gronke/rust-moneymoney/issues/14:
     /// MoneyMoney returns an empty `<balance>` array for some accounts (e.g., account
     /// groups with no aggregated balance). Previously this caused
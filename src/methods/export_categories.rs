@@ -22,9 +22,11 @@
 //! # }
 //! ```
 
+use crate::stream::{call_action_plist_each, Streamed};
 use crate::{call_action_plist, Error, MoneymoneyActions};
 use iso_currency::Currency;
 use serde::{Deserialize, Serialize};
+use std::ops::ControlFlow;
 use uuid::Uuid;
 
 /// Budget period type.
@@ -46,7 +48,7 @@ pub enum Period {
 /// Budget information for a category.
 ///
 /// Contains the budgeted amount, available remaining amount, and the budget period.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct MoneymoneyCategoryBudget {
     /// Total budgeted amount.
     pub amount: f64,
@@ -71,17 +73,17 @@ fn untagged_to_option<'de, D>(deserializer: D) -> Result<Option<MoneymoneyCatego
 where
     D: serde::de::Deserializer<'de>,
 {
-    match MaybeBudget::deserialize(deserializer) {
-        Ok(MaybeBudget::Full {
+    match Option::<MaybeBudget>::deserialize(deserializer) {
+        Ok(Some(MaybeBudget::Full {
             amount,
             available,
             period,
-        }) => Ok(Some(MoneymoneyCategoryBudget {
+        })) => Ok(Some(MoneymoneyCategoryBudget {
             amount,
             available,
             period,
         })),
-        Ok(MaybeBudget::Empty {}) => Ok(None),
+        Ok(Some(MaybeBudget::Empty {}) | None) => Ok(None),
         Err(e) => {
             eprintln!("Warning: failed to parse budget data: {}", e);
             Ok(None)
@@ -93,14 +95,14 @@ where
 ///
 /// Categories are used to organize and classify transactions. Each category
 /// can have an associated budget that tracks spending limits.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MoneymoneyCategory {
     /// Unique category identifier.
     pub uuid: Uuid,
     /// Category display name.
     pub name: String,
     /// Optional budget information for this category.
-    #[serde(deserialize_with = "untagged_to_option")]
+    #[serde(default, deserialize_with = "untagged_to_option")]
     pub budget: Option<MoneymoneyCategoryBudget>,
     /// Category currency.
     pub currency: Currency,
@@ -109,6 +111,10 @@ pub struct MoneymoneyCategory {
     /// Whether this is a category group.
     pub group: bool,
     /// Category icon as binary data.
+    #[serde(
+        default = "super::empty_icon",
+        deserialize_with = "super::deserialize_icon"
+    )]
     pub icon: plist::Data,
     /// Display indentation level.
     pub indentation: u8,
@@ -151,6 +157,68 @@ pub fn export_categories() -> Result<Vec<MoneymoneyCategory>, Error> {
     call_action_plist(MoneymoneyActions::ExportCategories)
 }
 
+/// Backslash-separated paths (`"Wohnen\\Miete"`) of `categories`, in the
+/// same order.
+///
+/// The tree is rebuilt from the export order and each category's
+/// `indentation`, the way MoneyMoney lists them. These are the strings
+/// MoneyMoney uses for [`crate::export_transactions::MoneymoneyTransaction::category`].
+pub fn category_paths(categories: &[MoneymoneyCategory]) -> Vec<String> {
+    let mut ancestors: Vec<&str> = Vec::new();
+    categories
+        .iter()
+        .map(|category| {
+            ancestors.truncate(usize::from(category.indentation));
+            ancestors.push(&category.name);
+            ancestors.join("\\")
+        })
+        .collect()
+}
+
+/// Find the category `ident` refers to: its UUID, its full path (see
+/// [`category_paths`]) or, if no path matches, its name.
+///
+/// This mirrors what MoneyMoney accepts for the `category` parameter of
+/// [`crate::add_transaction()`] and [`crate::set_transaction()`].
+pub fn find_category<'a>(
+    categories: &'a [MoneymoneyCategory],
+    ident: &str,
+) -> Option<&'a MoneymoneyCategory> {
+    let ident = ident.trim();
+    if ident.is_empty() {
+        return None;
+    }
+    let paths = category_paths(categories);
+    categories
+        .iter()
+        .find(|c| c.uuid.to_string().eq_ignore_ascii_case(ident))
+        .or_else(|| {
+            paths
+                .iter()
+                .position(|path| path == ident)
+                .map(|i| &categories[i])
+        })
+        .or_else(|| categories.iter().find(|c| c.name == ident))
+}
+
+/// Export categories one by one, without collecting them into a `Vec`.
+///
+/// `f` is called for every category as soon as it is converted; return
+/// [`ControlFlow::Break`] to stop early.
+///
+/// # Errors
+///
+/// Same as [`export_categories()`].
+pub fn export_categories_each<F>(mut f: F) -> Result<(), Error>
+where
+    F: FnMut(MoneymoneyCategory) -> ControlFlow<()>,
+{
+    call_action_plist_each(MoneymoneyActions::ExportCategories, None, |event| match event {
+        Streamed::Item(category) => f(category),
+        Streamed::Creator(_) => ControlFlow::Continue(()),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -245,6 +313,46 @@ mod tests {
         assert!(category.budget.is_none());
     }
 
+    fn tree() -> Vec<MoneymoneyCategory> {
+        [
+            ("Wohnen", 0, true),
+            ("Miete", 1, false),
+            ("Strom", 1, false),
+            ("Freizeit", 0, true),
+        ]
+        .into_iter()
+        .map(|(name, indentation, group)| MoneymoneyCategory {
+            uuid: Uuid::new_v4(),
+            name: name.to_string(),
+            budget: None,
+            currency: Currency::EUR,
+            default: false,
+            group,
+            icon: plist::Data::new(Vec::new()),
+            indentation,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_category_paths() {
+        assert_eq!(
+            category_paths(&tree()),
+            ["Wohnen", "Wohnen\\Miete", "Wohnen\\Strom", "Freizeit"]
+        );
+    }
+
+    #[test]
+    fn test_find_category() {
+        let categories = tree();
+        let uuid = categories[2].uuid.to_string().to_uppercase();
+        assert_eq!(find_category(&categories, &uuid).unwrap().name, "Strom");
+        assert_eq!(find_category(&categories, "Wohnen\\Miete").unwrap().name, "Miete");
+        assert_eq!(find_category(&categories, "Freizeit").unwrap().name, "Freizeit");
+        assert!(find_category(&categories, "Freizeit\\Miete").is_none());
+        assert!(find_category(&categories, " ").is_none());
+    }
+
     #[test]
     fn test_budget_serialization() {
         let budget = MoneymoneyCategoryBudget {
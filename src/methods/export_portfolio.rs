@@ -23,8 +23,10 @@
 //! # }
 //! ```
 
+use crate::stream::{call_action_plist_each, Streamed};
 use crate::{call_action_plist, MoneymoneyActions};
 use serde::{Deserialize, Serialize};
+use std::ops::ControlFlow;
 use uuid::Uuid;
 
 /// Parameters for exporting portfolio data.
@@ -210,6 +212,28 @@ pub fn export_portfolio(
     call_action_plist(MoneymoneyActions::ExportPortfolio(params))
 }
 
+/// Export securities one by one, without collecting them into a `Vec`.
+///
+/// `f` is called for every security as soon as it is converted; return
+/// [`ControlFlow::Break`] to stop early.
+///
+/// # Errors
+///
+/// Same as [`export_portfolio()`].
+pub fn export_portfolio_each<F>(params: ExportPortfolioParams, mut f: F) -> Result<(), crate::Error>
+where
+    F: FnMut(Security) -> ControlFlow<()>,
+{
+    call_action_plist_each(
+        MoneymoneyActions::ExportPortfolio(params),
+        Some("securities"),
+        |event| match event {
+            Streamed::Item(security) => f(security),
+            Streamed::Creator(_) => ControlFlow::Continue(()),
+        },
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -22,9 +22,11 @@
 //! # }
 //! ```
 
+use crate::stream::{call_action_plist_each, Streamed};
 use crate::{call_action_plist, Error, MoneymoneyActions};
 use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::ops::ControlFlow;
 use uuid::Uuid;
 
 /// Parameters for filtering exported transactions.
@@ -44,7 +46,7 @@ use uuid::Uuid;
 /// .to_date(NaiveDate::from_ymd_opt(2024, 12, 31).expect("valid date"))
 /// .from_account("DE89370400440532013000");
 /// ```
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ExportTransactionsParams {
     /// Start date for transaction filtering (inclusive, required).
@@ -100,7 +102,7 @@ impl ExportTransactionsParams {
 ///
 /// `deny_unknown_fields` makes deserialisation fail if MoneyMoney emits a key this
 /// struct doesn't model — the tripwire that backs `tests/transaction_plist_schema.rs`.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct MoneymoneyTransaction {
     /// Unique transaction identifier.
@@ -160,7 +162,7 @@ pub struct MoneymoneyTransaction {
 /// Response from the export transactions operation.
 ///
 /// Contains metadata about the export and the list of transactions.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TransactionsResponse {
     /// Creator/exporter information.
     pub creator: String,
@@ -209,6 +211,61 @@ pub fn export_transactions(
     call_action_plist(MoneymoneyActions::ExportTransactions(params))
 }
 
+/// An item of a streamed transaction export, see [`export_transactions_each`].
+#[derive(Debug)]
+pub enum TransactionsEvent {
+    /// The response's `creator` (MoneyMoney sends it before the transactions).
+    Creator(String),
+    /// One transaction.
+    Transaction(Box<MoneymoneyTransaction>),
+}
+
+/// Export transactions one by one, without collecting them into a `Vec`.
+///
+/// `f` is called with the response's `creator` and then with every
+/// transaction as soon as it is converted; return [`ControlFlow::Break`] to
+/// stop early.
+///
+/// # Errors
+///
+/// Same as [`export_transactions()`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::ops::ControlFlow;
+///
+/// use chrono::NaiveDate;
+/// use moneymoney::export_transactions::{
+///     export_transactions_each, ExportTransactionsParams, TransactionsEvent,
+/// };
+///
+/// # fn main() -> Result<(), moneymoney::Error> {
+/// let params = ExportTransactionsParams::new(NaiveDate::from_ymd_opt(2015, 1, 1).unwrap());
+/// let mut total = 0.0;
+/// export_transactions_each(params, |event| {
+///     if let TransactionsEvent::Transaction(t) = event {
+///         total += t.amount;
+///     }
+///     ControlFlow::Continue(())
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn export_transactions_each<F>(params: ExportTransactionsParams, mut f: F) -> Result<(), Error>
+where
+    F: FnMut(TransactionsEvent) -> ControlFlow<()>,
+{
+    call_action_plist_each(
+        MoneymoneyActions::ExportTransactions(params),
+        Some("transactions"),
+        |event| match event {
+            Streamed::Creator(creator) => f(TransactionsEvent::Creator(creator)),
+            Streamed::Item(transaction) => f(TransactionsEvent::Transaction(Box::new(transaction))),
+        },
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
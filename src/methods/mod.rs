@@ -45,3 +45,28 @@ pub use create_bank_transfer::create_bank_transfer;
 
 #[cfg(feature = "experimental")]
 pub use create_direct_debit::create_direct_debit;
+
+/// Deserialize an `icon` from plist data or from the byte list it becomes in
+/// JSON; a missing icon (e.g. stripped by the CLI) or a string is empty.
+pub(crate) fn deserialize_icon<'de, D>(deserializer: D) -> Result<plist::Data, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Icon {
+        Data(plist::Data),
+        Bytes(Vec<u8>),
+        Other(serde::de::IgnoredAny),
+    }
+
+    Ok(match serde::Deserialize::deserialize(deserializer)? {
+        Icon::Data(data) => data,
+        Icon::Bytes(bytes) => plist::Data::new(bytes),
+        Icon::Other(_) => empty_icon(),
+    })
+}
+
+pub(crate) fn empty_icon() -> plist::Data {
+    plist::Data::new(Vec::new())
+}
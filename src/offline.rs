@@ -0,0 +1,650 @@
+//! Saved exports instead of a running MoneyMoney.
+//!
+//! Reads exports saved earlier, either the plist MoneyMoney returns or the
+//! JSON / NDJSON the `moneymoney` CLI writes, and answers the export calls
+//! from them. This works on any platform, so reports and conversions can run
+//! on Linux against a nightly export.
+//!
+//! Each file holds one export. Its kind (accounts, categories, transactions,
+//! portfolio) is detected from the content, so a set of files can be loaded
+//! without saying which is which. [`SavedExports::transactions`] and
+//! [`SavedExports::portfolio`] apply the export parameters the way
+//! MoneyMoney does, so a saved full history can be queried by date range,
+//! account and category.
+//!
+//! Requires the `offline` feature (enabled by `cli`).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use chrono::NaiveDate;
+//! use moneymoney::export_transactions::ExportTransactionsParams;
+//! use moneymoney::offline::SavedExports;
+//!
+//! # fn main() -> Result<(), moneymoney::offline::OfflineError> {
+//! let saved = SavedExports::load(["accounts.json", "transactions.plist"])?;
+//! let mut params = ExportTransactionsParams::new(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+//! params.from_account = Some("Girokonto".to_string());
+//! for t in saved.transactions(&params)?.transactions {
+//!     println!("{} {} {:.2}", t.booking_date.date_naive(), t.name, t.amount);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::export_accounts::MoneymoneyAccount;
+use crate::export_categories::MoneymoneyCategory;
+use crate::export_portfolio::{ExportPortfolioParams, ExportPortfolioResponse, Security};
+use crate::export_transactions::{
+    ExportTransactionsParams, MoneymoneyTransaction, TransactionsResponse,
+};
+
+/// Errors while reading or querying saved exports.
+#[derive(Debug, Error)]
+pub enum OfflineError {
+    /// The file could not be read.
+    #[error("failed to read {path}: {source}")]
+    Io {
+        /// The file that failed.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+
+    /// The content is neither a MoneyMoney plist nor CLI JSON / NDJSON of a
+    /// known export. [`SavedExport::read`] prefixes the message with the path.
+    #[error("{0}")]
+    Format(String),
+
+    /// Two files hold the same kind of export.
+    #[error("more than one saved {0} export")]
+    Duplicate(ExportKind),
+
+    /// A query needs an export that wasn't loaded.
+    #[error("no saved {0} export; pass one as input")]
+    Missing(ExportKind),
+
+    /// An account filter names no saved account.
+    #[error("no saved account matches {0:?}")]
+    UnknownAccount(String),
+}
+
+/// The kinds of export a file can hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    /// [`crate::export_accounts()`]
+    Accounts,
+    /// [`crate::export_categories()`]
+    Categories,
+    /// [`crate::export_transactions()`]
+    Transactions,
+    /// [`crate::export_portfolio()`]
+    Portfolio,
+}
+
+impl std::fmt::Display for ExportKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ExportKind::Accounts => "accounts",
+            ExportKind::Categories => "categories",
+            ExportKind::Transactions => "transactions",
+            ExportKind::Portfolio => "portfolio",
+        })
+    }
+}
+
+/// One saved export.
+#[derive(Debug, Clone)]
+pub enum SavedExport {
+    /// Saved [`crate::export_accounts()`] result.
+    Accounts(Vec<MoneymoneyAccount>),
+    /// Saved [`crate::export_categories()`] result.
+    Categories(Vec<MoneymoneyCategory>),
+    /// Saved [`crate::export_transactions()`] result.
+    Transactions(TransactionsResponse),
+    /// Saved [`crate::export_portfolio()`] result.
+    Portfolio(ExportPortfolioResponse),
+}
+
+impl SavedExport {
+    /// Read one export from a file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OfflineError::Io`] if the file can't be read and
+    /// [`OfflineError::Format`] if its content isn't a known export.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, OfflineError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|source| OfflineError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_bytes(&bytes).map_err(|e| match e {
+            OfflineError::Format(reason) => {
+                OfflineError::Format(format!("{}: {reason}", path.display()))
+            }
+            other => other,
+        })
+    }
+
+    /// Parse one export from MoneyMoney plist (XML or binary), JSON or NDJSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OfflineError::Format`] if the content isn't a known export.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OfflineError> {
+        let start = bytes.trim_ascii_start();
+        if start.starts_with(b"<") || start.starts_with(b"bplist") {
+            let value: plist::Value = plist::from_bytes(bytes).map_err(format_error)?;
+            return Self::from_plist(&value);
+        }
+        match serde_json::from_slice::<serde_json::Value>(bytes) {
+            Ok(value) => Self::from_json(value),
+            Err(_) => Self::from_ndjson(bytes),
+        }
+    }
+
+    /// The kind of export this is.
+    pub fn kind(&self) -> ExportKind {
+        match self {
+            SavedExport::Accounts(_) => ExportKind::Accounts,
+            SavedExport::Categories(_) => ExportKind::Categories,
+            SavedExport::Transactions(_) => ExportKind::Transactions,
+            SavedExport::Portfolio(_) => ExportKind::Portfolio,
+        }
+    }
+
+    fn from_plist(value: &plist::Value) -> Result<Self, OfflineError> {
+        let kind = match value {
+            plist::Value::Dictionary(dict) => response_kind(dict.keys().map(String::as_str)),
+            plist::Value::Array(items) => match items.first() {
+                Some(plist::Value::Dictionary(item)) => item_kind(item.keys().map(String::as_str)),
+                _ => None,
+            },
+            _ => None,
+        };
+        fn decode<T: DeserializeOwned>(value: &plist::Value) -> Result<T, OfflineError> {
+            plist::from_value(value).map_err(format_error)
+        }
+        Ok(match kind.ok_or_else(unknown_shape)? {
+            ExportKind::Accounts => SavedExport::Accounts(decode(value)?),
+            ExportKind::Categories => SavedExport::Categories(decode(value)?),
+            ExportKind::Transactions => SavedExport::Transactions(decode(value)?),
+            ExportKind::Portfolio => SavedExport::Portfolio(decode(value)?),
+        })
+    }
+
+    /// CLI JSON: the response object or array `--format json` writes, or a
+    /// bare array of transactions / securities (from NDJSON).
+    fn from_json(value: serde_json::Value) -> Result<Self, OfflineError> {
+        let kind = match &value {
+            serde_json::Value::Object(map) => response_kind(map.keys().map(String::as_str)),
+            serde_json::Value::Array(items) => match items.first() {
+                Some(serde_json::Value::Object(item)) => item_kind(item.keys().map(String::as_str)),
+                _ => None,
+            },
+            _ => None,
+        };
+        fn decode<T: DeserializeOwned>(value: serde_json::Value) -> Result<T, OfflineError> {
+            serde_json::from_value(value).map_err(format_error)
+        }
+        let bare = value.is_array();
+        Ok(match kind.ok_or_else(unknown_shape)? {
+            ExportKind::Accounts => SavedExport::Accounts(decode(value)?),
+            ExportKind::Categories => SavedExport::Categories(decode(value)?),
+            ExportKind::Transactions if bare => SavedExport::Transactions(TransactionsResponse {
+                creator: String::new(),
+                transactions: decode(value)?,
+            }),
+            ExportKind::Transactions => SavedExport::Transactions(decode(value)?),
+            ExportKind::Portfolio if bare => SavedExport::Portfolio(ExportPortfolioResponse {
+                securities: decode(value)?,
+            }),
+            ExportKind::Portfolio => SavedExport::Portfolio(decode(value)?),
+        })
+    }
+
+    /// NDJSON as written by `--format ndjson`: one item per line, transactions
+    /// preceded by a `{"creator": ...}` header.
+    fn from_ndjson(bytes: &[u8]) -> Result<Self, OfflineError> {
+        let text = std::str::from_utf8(bytes).map_err(format_error)?;
+        let mut creator = None;
+        let mut items = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let value: serde_json::Value = serde_json::from_str(line)
+                .map_err(|e| OfflineError::Format(format!("line {}: {e}", i + 1)))?;
+            match value.get("creator").and_then(|c| c.as_str()) {
+                Some(header) if value.as_object().is_some_and(|map| map.len() == 1) => {
+                    creator = Some(header.to_string());
+                }
+                _ => items.push(value),
+            }
+        }
+        if items.is_empty() {
+            if let Some(creator) = creator {
+                return Ok(SavedExport::Transactions(TransactionsResponse {
+                    creator,
+                    transactions: Vec::new(),
+                }));
+            }
+        }
+        match Self::from_json(serde_json::Value::Array(items))? {
+            SavedExport::Transactions(mut response) => {
+                response.creator = creator.unwrap_or_default();
+                Ok(SavedExport::Transactions(response))
+            }
+            export => Ok(export),
+        }
+    }
+}
+
+/// Kind of a response dictionary, by its top-level keys.
+fn response_kind<'a>(mut keys: impl Iterator<Item = &'a str>) -> Option<ExportKind> {
+    keys.find_map(|key| match key {
+        "transactions" => Some(ExportKind::Transactions),
+        "securities" => Some(ExportKind::Portfolio),
+        _ => None,
+    })
+}
+
+/// Kind of a list export, by the keys of its first item.
+fn item_kind<'a>(keys: impl Iterator<Item = &'a str>) -> Option<ExportKind> {
+    let keys: Vec<&str> = keys.collect();
+    let has = |key| keys.contains(&key);
+    // Transactions also carry `accountNumber`, and accounts and categories
+    // both carry `indentation`, so the order matters.
+    if has("bookingDate") {
+        Some(ExportKind::Transactions)
+    } else if has("assetClass") || has("isin") {
+        Some(ExportKind::Portfolio)
+    } else if has("accountNumber") {
+        Some(ExportKind::Accounts)
+    } else if has("indentation") {
+        Some(ExportKind::Categories)
+    } else {
+        None
+    }
+}
+
+fn unknown_shape() -> OfflineError {
+    OfflineError::Format(
+        "not a MoneyMoney export (expected accounts, categories, transactions or portfolio; \
+         empty lists can't be told apart)"
+            .to_string(),
+    )
+}
+
+fn format_error(e: impl std::fmt::Display) -> OfflineError {
+    OfflineError::Format(e.to_string())
+}
+
+/// A set of saved exports, at most one of each kind.
+#[derive(Debug, Clone, Default)]
+pub struct SavedExports {
+    accounts: Option<Vec<MoneymoneyAccount>>,
+    categories: Option<Vec<MoneymoneyCategory>>,
+    transactions: Option<TransactionsResponse>,
+    portfolio: Option<ExportPortfolioResponse>,
+}
+
+impl SavedExports {
+    /// No exports; add them with [`SavedExports::insert`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read every file in `paths` (see [`SavedExport::read`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns the first read error, or [`OfflineError::Duplicate`] if two
+    /// files hold the same kind of export.
+    pub fn load<I, P>(paths: I) -> Result<Self, OfflineError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut saved = Self::new();
+        for path in paths {
+            saved.insert(SavedExport::read(path)?)?;
+        }
+        Ok(saved)
+    }
+
+    /// Add an export.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OfflineError::Duplicate`] if an export of the same kind was
+    /// already added.
+    pub fn insert(&mut self, export: SavedExport) -> Result<(), OfflineError> {
+        let kind = export.kind();
+        let taken = match export {
+            SavedExport::Accounts(v) => self.accounts.replace(v).is_some(),
+            SavedExport::Categories(v) => self.categories.replace(v).is_some(),
+            SavedExport::Transactions(v) => self.transactions.replace(v).is_some(),
+            SavedExport::Portfolio(v) => self.portfolio.replace(v).is_some(),
+        };
+        if taken {
+            return Err(OfflineError::Duplicate(kind));
+        }
+        Ok(())
+    }
+
+    /// Whether an export of `kind` was loaded.
+    pub fn contains(&self, kind: ExportKind) -> bool {
+        match kind {
+            ExportKind::Accounts => self.accounts.is_some(),
+            ExportKind::Categories => self.categories.is_some(),
+            ExportKind::Transactions => self.transactions.is_some(),
+            ExportKind::Portfolio => self.portfolio.is_some(),
+        }
+    }
+
+    /// The saved accounts, including groups.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OfflineError::Missing`] if no accounts export was loaded.
+    pub fn accounts(&self) -> Result<&[MoneymoneyAccount], OfflineError> {
+        self.accounts
+            .as_deref()
+            .ok_or(OfflineError::Missing(ExportKind::Accounts))
+    }
+
+    /// The saved categories, including groups.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OfflineError::Missing`] if no categories export was loaded.
+    pub fn categories(&self) -> Result<&[MoneymoneyCategory], OfflineError> {
+        self.categories
+            .as_deref()
+            .ok_or(OfflineError::Missing(ExportKind::Categories))
+    }
+
+    /// The saved transactions that match `params`.
+    ///
+    /// Transactions are kept if they were booked within `from_date..=to_date`.
+    /// `from_account` is resolved like [`MoneymoneyAccount::matches`] against
+    /// the saved accounts (an account group selects its accounts); without
+    /// saved accounts only a UUID works. `from_category` matches the
+    /// category's UUID, path or name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OfflineError::Missing`] if no transactions export was loaded
+    /// and [`OfflineError::UnknownAccount`] if `from_account` can't be
+    /// resolved.
+    pub fn transactions(
+        &self,
+        params: &ExportTransactionsParams,
+    ) -> Result<TransactionsResponse, OfflineError> {
+        let response = self
+            .transactions
+            .as_ref()
+            .ok_or(OfflineError::Missing(ExportKind::Transactions))?;
+        let accounts = match &params.from_account {
+            Some(ident) => Some(self.account_uuids(ident)?),
+            None => None,
+        };
+        let category = params.from_category.as_deref().map(str::trim);
+        let category_uuid = category.and_then(|ident| {
+            let categories = self.categories.as_deref()?;
+            crate::export_categories::find_category(categories, ident).map(|c| c.uuid)
+        });
+        let keep = |t: &MoneymoneyTransaction| {
+            let day = t.booking_date.date_naive();
+            day >= params.from_date
+                && params.to_date.is_none_or(|to| day <= to)
+                && accounts
+                    .as_ref()
+                    .is_none_or(|uuids| uuids.contains(&t.account_uuid))
+                && category.is_none_or(|ident| match category_uuid {
+                    Some(uuid) => t.category_uuid == uuid,
+                    None => category_matches(t, ident),
+                })
+        };
+        Ok(TransactionsResponse {
+            creator: response.creator.clone(),
+            transactions: response
+                .transactions
+                .iter()
+                .filter(|t| keep(t))
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// The saved securities that match `params`.
+    ///
+    /// `from_account` matches the holding account's UUID or name, or any
+    /// saved account it identifies; `from_asset_class` matches the asset
+    /// class exactly.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OfflineError::Missing`] if no portfolio export was loaded,
+    /// and [`OfflineError::UnknownAccount`] if `from_account` matches neither
+    /// a saved account nor the holding account of any security.
+    pub fn portfolio(
+        &self,
+        params: &ExportPortfolioParams,
+    ) -> Result<ExportPortfolioResponse, OfflineError> {
+        let response = self
+            .portfolio
+            .as_ref()
+            .ok_or(OfflineError::Missing(ExportKind::Portfolio))?;
+        let held_by = |s: &Security, ident: &str| {
+            s.account_name == ident.trim()
+                || s.account_uuid
+                    .to_string()
+                    .eq_ignore_ascii_case(ident.trim())
+        };
+        let accounts = match params.from_account.as_deref() {
+            Some(ident) => match self.account_uuids(ident) {
+                Ok(uuids) => Some(uuids),
+                Err(_) if response.securities.iter().any(|s| held_by(s, ident)) => None,
+                Err(e) => return Err(e),
+            },
+            None => None,
+        };
+        let keep = |s: &Security| {
+            params.from_account.as_deref().is_none_or(|ident| {
+                held_by(s, ident)
+                    || accounts
+                        .as_ref()
+                        .is_some_and(|uuids| uuids.contains(&s.account_uuid))
+            }) && params
+                .from_asset_class
+                .as_deref()
+                .is_none_or(|class| s.asset_class == class)
+        };
+        Ok(ExportPortfolioResponse {
+            securities: response
+                .securities
+                .iter()
+                .filter(|s| keep(s))
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// UUIDs of the accounts `ident` selects: the matching account, or every
+    /// account below a matching group.
+    fn account_uuids(&self, ident: &str) -> Result<Vec<Uuid>, OfflineError> {
+        let Some(accounts) = self.accounts.as_deref() else {
+            return Uuid::parse_str(ident.trim())
+                .map(|uuid| vec![uuid])
+                .map_err(|_| OfflineError::UnknownAccount(ident.to_string()));
+        };
+        let Some(start) = accounts.iter().position(|a| a.matches(ident)) else {
+            return Err(OfflineError::UnknownAccount(ident.to_string()));
+        };
+        let selected = &accounts[start];
+        let mut uuids = vec![selected.uuid];
+        if selected.group {
+            uuids.extend(
+                accounts[start + 1..]
+                    .iter()
+                    .take_while(|a| a.indentation > selected.indentation)
+                    .map(|a| a.uuid),
+            );
+        }
+        Ok(uuids)
+    }
+}
+
+/// `from_category` without saved categories: the transaction's category
+/// UUID, path or last path segment.
+fn category_matches(transaction: &MoneymoneyTransaction, ident: &str) -> bool {
+    transaction
+        .category_uuid
+        .to_string()
+        .eq_ignore_ascii_case(ident)
+        || transaction
+            .category
+            .as_deref()
+            .is_some_and(|path| path == ident || path.rsplit('\\').next() == Some(ident))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{cash, date, giro, transaction, CASH_UUID, GIRO_UUID};
+
+    fn saved() -> SavedExports {
+        let mut group = giro();
+        group.name = "Privat".to_string();
+        group.group = true;
+        group.indentation = 0;
+        group.uuid = Uuid::new_v4();
+        group.iban = None;
+        group.account_number = String::new();
+        let mut food = transaction(1, GIRO_UUID, date(2024, 1, 5), "REWE", -42.0);
+        food.category = Some("Lebensmittel\\Supermarkt".to_string());
+        let mut saved = SavedExports::new();
+        saved
+            .insert(SavedExport::Accounts(vec![group, giro(), cash()]))
+            .unwrap();
+        saved
+            .insert(SavedExport::Transactions(TransactionsResponse {
+                creator: "MoneyMoney".to_string(),
+                transactions: vec![
+                    food,
+                    transaction(2, GIRO_UUID, date(2024, 2, 1), "Miete", -900.0),
+                    transaction(3, CASH_UUID, date(2024, 3, 1), "Bäcker", -3.5),
+                ],
+            }))
+            .unwrap();
+        saved
+    }
+
+    fn ids(response: &TransactionsResponse) -> Vec<u64> {
+        response.transactions.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn test_read_plist_transactions() {
+        let bytes = include_bytes!("../tests/fixtures/transaction_exports/synthetic_minimal.plist");
+        let SavedExport::Transactions(response) = SavedExport::from_bytes(bytes).unwrap() else {
+            panic!("expected transactions");
+        };
+        assert_eq!(response.creator, "MoneyMoney 2.4.71");
+        assert_eq!(response.transactions.len(), 1);
+    }
+
+    #[test]
+    fn test_read_cli_json_and_ndjson() {
+        // `export accounts` drops icons from its JSON.
+        let mut accounts = serde_json::to_value(vec![giro(), cash()]).unwrap();
+        for account in accounts.as_array_mut().unwrap() {
+            account.as_object_mut().unwrap().remove("icon");
+        }
+        let export = SavedExport::from_bytes(accounts.to_string().as_bytes()).unwrap();
+        let SavedExport::Accounts(accounts) = export else {
+            panic!("expected accounts");
+        };
+        assert_eq!(accounts[0].balance.as_ref().unwrap().amount, 1234.56);
+
+        let t = transaction(7, GIRO_UUID, date(2024, 1, 5), "REWE", -42.0);
+        let ndjson =
+            format!("{{\"creator\":\"MoneyMoney\"}}\n{}\n", serde_json::to_string(&t).unwrap());
+        let SavedExport::Transactions(response) =
+            SavedExport::from_bytes(ndjson.as_bytes()).unwrap()
+        else {
+            panic!("expected transactions");
+        };
+        assert_eq!(response.creator, "MoneyMoney");
+        assert_eq!(ids(&response), [7]);
+
+        assert!(matches!(SavedExport::from_bytes(b"[]"), Err(OfflineError::Format(_))));
+    }
+
+    #[test]
+    fn test_insert_duplicate_and_missing() {
+        let mut saved = saved();
+        assert!(matches!(
+            saved.insert(SavedExport::Accounts(Vec::new())),
+            Err(OfflineError::Duplicate(ExportKind::Accounts))
+        ));
+        assert!(matches!(saved.categories(), Err(OfflineError::Missing(ExportKind::Categories))));
+    }
+
+    #[test]
+    fn test_transactions_filters() {
+        let saved = saved();
+        let all = ExportTransactionsParams::new(date(2024, 1, 1));
+        assert_eq!(ids(&saved.transactions(&all).unwrap()), [1, 2, 3]);
+
+        let range = ExportTransactionsParams::new(date(2024, 1, 6)).to_date(date(2024, 2, 28));
+        assert_eq!(ids(&saved.transactions(&range).unwrap()), [2]);
+
+        let giro = all.clone().from_account("DE89 3704 0044 0532 0130 00");
+        assert_eq!(ids(&saved.transactions(&giro).unwrap()), [1, 2]);
+        let group = all.clone().from_account("Privat");
+        assert_eq!(ids(&saved.transactions(&group).unwrap()), [1, 2, 3]);
+        assert!(matches!(
+            saved.transactions(&all.clone().from_account("Sparbuch")),
+            Err(OfflineError::UnknownAccount(_))
+        ));
+
+        let leaf = all.clone().from_category("Supermarkt");
+        assert_eq!(ids(&saved.transactions(&leaf).unwrap()), [1]);
+        let path = all.from_category("Lebensmittel\\Supermarkt");
+        assert_eq!(ids(&saved.transactions(&path).unwrap()), [1]);
+    }
+
+    #[test]
+    fn test_portfolio_filters() {
+        let mut saved = saved();
+        let depot = Uuid::new_v4();
+        let securities = serde_json::json!({"securities": [
+            {"uuid": Uuid::new_v4(), "name": "MSCI World", "quantity": 10.0,
+             "accountUuid": depot, "accountName": "Depot", "marketValue": 900.0},
+        ]});
+        saved
+            .insert(SavedExport::Portfolio(serde_json::from_value(securities).unwrap()))
+            .unwrap();
+        let count =
+            |params: ExportPortfolioParams| saved.portfolio(&params).map(|r| r.securities.len());
+
+        assert_eq!(count(ExportPortfolioParams::new()).unwrap(), 1);
+        assert_eq!(count(ExportPortfolioParams::new().from_account("Depot")).unwrap(), 1);
+        assert_eq!(count(ExportPortfolioParams::new().from_account(depot.to_string())).unwrap(), 1);
+        assert_eq!(count(ExportPortfolioParams::new().from_account("Privat")).unwrap(), 0);
+        assert!(matches!(
+            count(ExportPortfolioParams::new().from_account("Sparbuch")),
+            Err(OfflineError::UnknownAccount(_))
+        ));
+    }
+}
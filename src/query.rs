@@ -0,0 +1,1160 @@
+//! A small query language for transactions.
+//!
+//! ```text
+//! amount < -100 and category ~ "Food\*" and payee:"REWE" and date >= 2024-01-01
+//! ```
+//!
+//! A query is a boolean expression of comparisons `field op value`, combined
+//! with `and`, `or`, `not` and parentheses. Comparisons next to each other
+//! without a keyword are joined with `and`. Keywords and field names are
+//! case-insensitive; values are bare words or `"quoted strings"` (with `\"`
+//! and `\\` escapes).
+//!
+//! Every field has a type that decides which operators and values it takes:
+//!
+//! | Type | Fields | Operators | Values |
+//! |---|---|---|---|
+//! | text | `payee` (`name`), `purpose`, `category`, `comment`, `currency`, `iban` (`account-number`, `counterpart`), `bank-code`, `booking-text`, `creditor-id`, `mandate-reference`, `end-to-end-reference` | `=`, `!=`, `~`, `:` | any |
+//! | number | `amount`, `id` | `=`, `!=`, `<`, `<=`, `>`, `>=` | decimal, e.g. `-12.50` |
+//! | date | `date` (`booking-date`), `value-date` | `=`, `!=`, `<`, `<=`, `>`, `>=` | any [`DateRange`] expression |
+//! | boolean | `booked`, `pending`, `checkmark` (`checked`) | `=`, `!=` | `true`/`false`, `yes`/`no`, `on`/`off` |
+//!
+//! Text comparisons ignore case. `~` matches a glob pattern against the whole
+//! value (`*` any run of characters, `?` one character; backslashes are
+//! literal, so `Food\*` matches every subcategory of `Food`), `:` matches a
+//! substring. A missing text value compares as empty.
+//!
+//! A date value stands for a range of days: `date = 2024-05` keeps May,
+//! `date >= last-month` keeps everything from the first of last month on,
+//! `date < 2024` everything before 2024. A boolean field on its own, like
+//! `booked`, means `booked = true`.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use chrono::NaiveDate;
+//! use moneymoney::export_transactions::ExportTransactionsParams;
+//! use moneymoney::query::Query;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let query = Query::parse(r#"amount < -100 and payee:"REWE" and date >= 2024-01-01"#)?;
+//! let params = ExportTransactionsParams::new(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+//! let response = moneymoney::export_transactions(params)?;
+//! for t in query.filter(&response.transactions) {
+//!     println!("{} {:.2}", t.name, t.amount);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use chrono::NaiveDate;
+
+use crate::date_range::DateRange;
+use crate::export_transactions::MoneymoneyTransaction;
+
+/// A parsed query, ready to be evaluated against transactions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    expr: Expr,
+}
+
+impl Query {
+    /// Parse `input`, resolving relative dates against the local date.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueryError`] pointing at the offending position.
+    pub fn parse(input: &str) -> Result<Self, QueryError> {
+        Self::parse_at(input, chrono::Local::now().date_naive())
+    }
+
+    /// Parse `input`, resolving relative dates (`30d`, `last-month`) against
+    /// `today`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueryError`] pointing at the offending position.
+    pub fn parse_at(input: &str, today: NaiveDate) -> Result<Self, QueryError> {
+        let tokens = lex(input)?;
+        let mut parser = Parser {
+            input,
+            tokens,
+            pos: 0,
+            today,
+        };
+        let expr = parser.parse_query()?;
+        Ok(Self { expr })
+    }
+
+    /// The syntax tree.
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// Whether `t` satisfies the query.
+    pub fn matches(&self, t: &MoneymoneyTransaction) -> bool {
+        self.expr.matches(t)
+    }
+
+    /// The transactions in `transactions` that satisfy the query.
+    pub fn filter<'a>(
+        &'a self,
+        transactions: &'a [MoneymoneyTransaction],
+    ) -> impl Iterator<Item = &'a MoneymoneyTransaction> + 'a {
+        transactions.iter().filter(|t| self.matches(t))
+    }
+
+    /// Booking date bounds every match lies within, from `date` comparisons
+    /// that the whole query depends on (joined by `and` at the top level).
+    ///
+    /// Use them to narrow the export before evaluating the query.
+    pub fn date_bounds(&self) -> (Option<NaiveDate>, Option<NaiveDate>) {
+        let mut bounds = (None, None);
+        self.expr.collect_date_bounds(&mut bounds);
+        bounds
+    }
+}
+
+impl std::str::FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Query {
+    /// The canonical form of the query, which parses back to the same tree.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.expr.fmt(f)
+    }
+}
+
+/// A query syntax tree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// Both sides hold.
+    And(Box<Expr>, Box<Expr>),
+    /// Either side holds.
+    Or(Box<Expr>, Box<Expr>),
+    /// The inner expression doesn't hold.
+    Not(Box<Expr>),
+    /// A single `field op value` comparison.
+    Compare(Comparison),
+}
+
+/// `field op value`, type-checked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    /// The transaction field.
+    pub field: Field,
+    /// The operator, valid for the field's type.
+    pub op: Op,
+    /// The value, of the field's type.
+    pub value: Value,
+}
+
+/// Transaction fields a query can refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// Payee (`name`).
+    Payee,
+    /// Purpose.
+    Purpose,
+    /// Category path.
+    Category,
+    /// Comment.
+    Comment,
+    /// Currency code.
+    Currency,
+    /// Counterpart IBAN or account number.
+    Iban,
+    /// Counterpart bank code.
+    BankCode,
+    /// Booking text.
+    BookingText,
+    /// SEPA creditor identifier.
+    CreditorId,
+    /// SEPA mandate reference.
+    MandateReference,
+    /// SEPA end-to-end reference.
+    EndToEndReference,
+    /// Signed amount.
+    Amount,
+    /// Transaction id.
+    Id,
+    /// Booking date.
+    Date,
+    /// Value date.
+    ValueDate,
+    /// Booked (not pending).
+    Booked,
+    /// Pending (not yet booked).
+    Pending,
+    /// Checkmark set.
+    Checkmark,
+}
+
+/// The type of a [`Field`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    /// Compared case-insensitively, with `~` globs and `:` substrings.
+    Text,
+    /// Compared numerically.
+    Number,
+    /// Compared by calendar day against a [`DateRange`].
+    Date,
+    /// `true` or `false`.
+    Boolean,
+}
+
+impl Field {
+    const ALL: [(&'static str, Field); 23] = [
+        ("payee", Field::Payee),
+        ("name", Field::Payee),
+        ("purpose", Field::Purpose),
+        ("category", Field::Category),
+        ("comment", Field::Comment),
+        ("currency", Field::Currency),
+        ("iban", Field::Iban),
+        ("accountnumber", Field::Iban),
+        ("counterpart", Field::Iban),
+        ("bankcode", Field::BankCode),
+        ("bookingtext", Field::BookingText),
+        ("creditorid", Field::CreditorId),
+        ("mandatereference", Field::MandateReference),
+        ("endtoendreference", Field::EndToEndReference),
+        ("amount", Field::Amount),
+        ("id", Field::Id),
+        ("date", Field::Date),
+        ("bookingdate", Field::Date),
+        ("valuedate", Field::ValueDate),
+        ("booked", Field::Booked),
+        ("pending", Field::Pending),
+        ("checkmark", Field::Checkmark),
+        ("checked", Field::Checkmark),
+    ];
+
+    /// Look up a field by name; `-`, `_` and case are ignored, so
+    /// `value-date`, `value_date` and `valueDate` are the same field.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let key: String = name
+            .chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .flat_map(char::to_lowercase)
+            .collect();
+        Self::ALL
+            .iter()
+            .find(|(n, _)| *n == key)
+            .map(|(_, field)| *field)
+    }
+
+    /// The canonical name, as written by [`Query`]'s `Display`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Payee => "payee",
+            Field::Purpose => "purpose",
+            Field::Category => "category",
+            Field::Comment => "comment",
+            Field::Currency => "currency",
+            Field::Iban => "iban",
+            Field::BankCode => "bank-code",
+            Field::BookingText => "booking-text",
+            Field::CreditorId => "creditor-id",
+            Field::MandateReference => "mandate-reference",
+            Field::EndToEndReference => "end-to-end-reference",
+            Field::Amount => "amount",
+            Field::Id => "id",
+            Field::Date => "date",
+            Field::ValueDate => "value-date",
+            Field::Booked => "booked",
+            Field::Pending => "pending",
+            Field::Checkmark => "checkmark",
+        }
+    }
+
+    /// The field's type.
+    pub fn field_type(self) -> FieldType {
+        match self {
+            Field::Amount | Field::Id => FieldType::Number,
+            Field::Date | Field::ValueDate => FieldType::Date,
+            Field::Booked | Field::Pending | Field::Checkmark => FieldType::Boolean,
+            _ => FieldType::Text,
+        }
+    }
+
+    fn text(self, t: &MoneymoneyTransaction) -> &str {
+        let value = match self {
+            Field::Payee => Some(t.name.as_str()),
+            Field::Purpose => t.purpose.as_deref(),
+            Field::Category => t.category.as_deref(),
+            Field::Comment => t.comment.as_deref(),
+            Field::Currency => Some(t.currency.as_str()),
+            Field::Iban => t.account_number.as_deref(),
+            Field::BankCode => t.bank_code.as_deref(),
+            Field::BookingText => t.booking_text.as_deref(),
+            Field::CreditorId => t.creditor_id.as_deref(),
+            Field::MandateReference => t.mandate_reference.as_deref(),
+            Field::EndToEndReference => t.end_to_end_reference.as_deref(),
+            _ => None,
+        };
+        value.unwrap_or("")
+    }
+
+    fn number(self, t: &MoneymoneyTransaction) -> f64 {
+        match self {
+            Field::Id => t.id as f64,
+            _ => t.amount,
+        }
+    }
+
+    fn date(self, t: &MoneymoneyTransaction) -> NaiveDate {
+        match self {
+            Field::ValueDate => t.value_date.date_naive(),
+            _ => t.booking_date.date_naive(),
+        }
+    }
+
+    fn boolean(self, t: &MoneymoneyTransaction) -> bool {
+        match self {
+            Field::Pending => !t.booked,
+            Field::Checkmark => t.checkmark,
+            _ => t.booked,
+        }
+    }
+}
+
+/// Comparison operators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// `=` (also `==`)
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+    /// `~`, glob match
+    Glob,
+    /// `:`, substring match
+    Contains,
+}
+
+impl Op {
+    /// The operator as written in a query.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Glob => "~",
+            Op::Contains => ":",
+        }
+    }
+
+    fn allowed(field_type: FieldType) -> &'static [Op] {
+        match field_type {
+            FieldType::Text => &[Op::Eq, Op::Ne, Op::Glob, Op::Contains],
+            FieldType::Number | FieldType::Date => {
+                &[Op::Eq, Op::Ne, Op::Lt, Op::Le, Op::Gt, Op::Ge]
+            }
+            FieldType::Boolean => &[Op::Eq, Op::Ne],
+        }
+    }
+}
+
+/// A typed comparison value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// For text fields.
+    Text(String),
+    /// For number fields.
+    Number(f64),
+    /// For date fields; a single day is a one-day range.
+    Date(DateRange),
+    /// For boolean fields.
+    Boolean(bool),
+}
+
+impl Expr {
+    /// Whether `t` satisfies the expression.
+    pub fn matches(&self, t: &MoneymoneyTransaction) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(t) && b.matches(t),
+            Expr::Or(a, b) => a.matches(t) || b.matches(t),
+            Expr::Not(e) => !e.matches(t),
+            Expr::Compare(c) => c.matches(t),
+        }
+    }
+
+    fn collect_date_bounds(&self, bounds: &mut (Option<NaiveDate>, Option<NaiveDate>)) {
+        match self {
+            Expr::And(a, b) => {
+                a.collect_date_bounds(bounds);
+                b.collect_date_bounds(bounds);
+            }
+            Expr::Compare(Comparison {
+                field: Field::Date,
+                op,
+                value: Value::Date(range),
+            }) => {
+                let lower = match op {
+                    Op::Eq | Op::Ge => Some(range.start),
+                    Op::Gt => range.end.succ_opt(),
+                    _ => None,
+                };
+                let upper = match op {
+                    Op::Eq | Op::Le => Some(range.end),
+                    Op::Lt => range.start.pred_opt(),
+                    _ => None,
+                };
+                if let Some(lower) = lower {
+                    bounds.0 = Some(bounds.0.map_or(lower, |b| b.max(lower)));
+                }
+                if let Some(upper) = upper {
+                    bounds.1 = Some(bounds.1.map_or(upper, |b| b.min(upper)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Or(..) => 0,
+            Expr::And(..) => 1,
+            Expr::Not(_) | Expr::Compare(_) => 2,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operand = |f: &mut fmt::Formatter<'_>, e: &Expr, min: u8| {
+            if e.precedence() < min {
+                write!(f, "({e})")
+            } else {
+                write!(f, "{e}")
+            }
+        };
+        match self {
+            Expr::And(a, b) => {
+                operand(f, a, 1)?;
+                f.write_str(" and ")?;
+                operand(f, b, 2)
+            }
+            Expr::Or(a, b) => {
+                operand(f, a, 0)?;
+                f.write_str(" or ")?;
+                operand(f, b, 1)
+            }
+            Expr::Not(e) => {
+                f.write_str("not ")?;
+                operand(f, e, 2)
+            }
+            Expr::Compare(c) => write!(f, "{c}"),
+        }
+    }
+}
+
+impl Comparison {
+    /// Whether `t` satisfies the comparison.
+    pub fn matches(&self, t: &MoneymoneyTransaction) -> bool {
+        match &self.value {
+            Value::Text(expected) => {
+                let actual = self.field.text(t).to_lowercase();
+                let expected = expected.to_lowercase();
+                match self.op {
+                    Op::Eq => actual == expected,
+                    Op::Ne => actual != expected,
+                    Op::Glob => glob_match(&expected, &actual),
+                    Op::Contains => actual.contains(&expected),
+                    _ => false,
+                }
+            }
+            Value::Number(expected) => {
+                let actual = self.field.number(t);
+                let equal = (actual - expected).abs() < 1e-9;
+                match self.op {
+                    Op::Eq => equal,
+                    Op::Ne => !equal,
+                    Op::Lt => actual < *expected && !equal,
+                    Op::Le => actual < *expected || equal,
+                    Op::Gt => actual > *expected && !equal,
+                    Op::Ge => actual > *expected || equal,
+                    _ => false,
+                }
+            }
+            Value::Date(range) => {
+                let day = self.field.date(t);
+                match self.op {
+                    Op::Eq => range.contains(day),
+                    Op::Ne => !range.contains(day),
+                    Op::Lt => day < range.start,
+                    Op::Le => day <= range.end,
+                    Op::Gt => day > range.end,
+                    Op::Ge => day >= range.start,
+                    _ => false,
+                }
+            }
+            Value::Boolean(expected) => {
+                let actual = self.field.boolean(t);
+                match self.op {
+                    Op::Eq => actual == *expected,
+                    Op::Ne => actual != *expected,
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let field = self.field.name();
+        let op = self.op.symbol();
+        match &self.value {
+            Value::Text(s) => {
+                let escaped = s.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "{field} {op} \"{escaped}\"")
+            }
+            Value::Number(n) => write!(f, "{field} {op} {n}"),
+            Value::Date(range) if range.start == range.end => {
+                write!(f, "{field} {op} {}", range.start)
+            }
+            Value::Date(range) => write!(f, "{field} {op} {}..{}", range.start, range.end),
+            Value::Boolean(b) => write!(f, "{field} {op} {b}"),
+        }
+    }
+}
+
+/// Whole-string glob match with `*` and `?`; both inputs are lowercase.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried at.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((sp, st)) => {
+                    p = sp + 1;
+                    t = st + 1;
+                    star = Some((sp, st + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// A query that doesn't parse, with the position of the problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    message: String,
+    input: String,
+    position: usize,
+}
+
+impl QueryError {
+    /// What went wrong.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Character offset of the problem in the query (its length at the end).
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for QueryError {
+    /// The message, then the query with a caret under the problem.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let caret = " ".repeat(self.position);
+        write!(f, "{}\n  {}\n  {caret}^", self.message, self.input)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Op(Op),
+    /// `!`, a synonym for `not`.
+    Bang,
+    Word(String),
+    Quoted(String),
+}
+
+/// A token and the character offset it starts at.
+type Spanned = (Token, usize);
+
+fn lex(input: &str) -> Result<Vec<Spanned>, QueryError> {
+    let chars: Vec<char> = input.chars().collect();
+    let error = |message: String, position| QueryError {
+        message,
+        input: input.to_string(),
+        position,
+    };
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let next = chars.get(i + 1).copied();
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '=' if next == Some('=') => {
+                i += 1;
+                Token::Op(Op::Eq)
+            }
+            '=' => Token::Op(Op::Eq),
+            '!' if next == Some('=') => {
+                i += 1;
+                Token::Op(Op::Ne)
+            }
+            '!' => Token::Bang,
+            '<' if next == Some('=') => {
+                i += 1;
+                Token::Op(Op::Le)
+            }
+            '<' => Token::Op(Op::Lt),
+            '>' if next == Some('=') => {
+                i += 1;
+                Token::Op(Op::Ge)
+            }
+            '>' => Token::Op(Op::Gt),
+            '~' => Token::Op(Op::Glob),
+            ':' => Token::Op(Op::Contains),
+            '"' | '\'' => {
+                let quote = c;
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(error("unterminated string".to_string(), start)),
+                        Some(&ch) if ch == quote => break,
+                        Some('\\') if matches!(chars.get(i + 1), Some(&n) if n == quote || n == '\\') =>
+                        {
+                            value.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&ch) => {
+                            value.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                Token::Quoted(value)
+            }
+            _ => {
+                while i < chars.len() && !is_delimiter(chars[i]) {
+                    i += 1;
+                }
+                tokens.push((Token::Word(chars[start..i].iter().collect()), start));
+                continue;
+            }
+        };
+        tokens.push((token, start));
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "()=!<>~:\"'".contains(c)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Spanned>,
+    pos: usize,
+    today: NaiveDate,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>, position: usize) -> QueryError {
+        QueryError {
+            message: message.into(),
+            input: self.input.to_string(),
+            position,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    /// Offset of the next token, or the end of the input.
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or_else(|| self.input.chars().count(), |(_, at)| *at)
+    }
+
+    fn keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn parse_query(&mut self) -> Result<Expr, QueryError> {
+        if self.tokens.is_empty() {
+            return Err(self.error("empty query", 0));
+        }
+        let expr = self.parse_or()?;
+        match self.peek() {
+            None => Ok(expr),
+            Some(Token::RParen) => Err(self.error("unmatched `)`", self.offset())),
+            Some(_) => {
+                Err(self.error("expected `and`, `or` or the end of the query", self.offset()))
+            }
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_and()?;
+        while self.keyword("or") {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_not()?;
+        loop {
+            if self.keyword("and") {
+                self.pos += 1;
+            } else if !self.starts_operand() {
+                return Ok(left);
+            }
+            let right = self.parse_not()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+    }
+
+    /// Whether the next token can start an operand (for an implicit `and`).
+    fn starts_operand(&self) -> bool {
+        match self.peek() {
+            Some(Token::LParen | Token::Bang) => true,
+            Some(Token::Word(_)) => !self.keyword("or"),
+            _ => false,
+        }
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, QueryError> {
+        if self.keyword("not") || self.peek() == Some(&Token::Bang) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, QueryError> {
+        let at = self.offset();
+        match self.tokens.get(self.pos).cloned() {
+            Some((Token::LParen, _)) => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(self.error(
+                        format!("expected `)` to close `(` at column {}", at + 1),
+                        self.offset(),
+                    ));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some((Token::Word(name), _)) => {
+                if ["and", "or"].iter().any(|k| name.eq_ignore_ascii_case(k)) {
+                    return Err(self.error(format!("expected a comparison before `{name}`"), at));
+                }
+                self.pos += 1;
+                let field = Field::from_name(&name).ok_or_else(|| {
+                    self.error(
+                        format!(
+                            "unknown field `{name}`; expected one of payee, purpose, category, \
+                             comment, currency, iban, bank-code, booking-text, creditor-id, \
+                             mandate-reference, end-to-end-reference, amount, id, date, \
+                             value-date, booked, pending, checkmark"
+                        ),
+                        at,
+                    )
+                })?;
+                self.parse_comparison(field)
+            }
+            Some((Token::Quoted(value), _)) => {
+                Err(self.error(format!("expected a field name, found the string \"{value}\""), at))
+            }
+            Some((Token::Op(op), _)) => {
+                Err(self.error(format!("expected a field name before `{}`", op.symbol()), at))
+            }
+            Some((Token::RParen, _)) => Err(self.error("expected a comparison before `)`", at)),
+            Some((Token::Bang, _)) => unreachable!("handled in parse_not"),
+            None => Err(self.error("expected a comparison at the end of the query", at)),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: Field) -> Result<Expr, QueryError> {
+        let field_type = field.field_type();
+        let op_at = self.offset();
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            _ if field_type == FieldType::Boolean => {
+                return Ok(Expr::Compare(Comparison {
+                    field,
+                    op: Op::Eq,
+                    value: Value::Boolean(true),
+                }));
+            }
+            _ => {
+                return Err(
+                    self.error(format!("expected an operator after `{}`", field.name()), op_at)
+                )
+            }
+        };
+        self.pos += 1;
+        let allowed = Op::allowed(field_type);
+        if !allowed.contains(&op) {
+            let names: Vec<&str> = allowed.iter().map(|op| op.symbol()).collect();
+            return Err(self.error(
+                format!(
+                    "`{}` doesn't work on {} field `{}`; use one of {}",
+                    op.symbol(),
+                    type_name(field_type),
+                    field.name(),
+                    names.join(" ")
+                ),
+                op_at,
+            ));
+        }
+        let value_at = self.offset();
+        let raw = match self.tokens.get(self.pos).cloned() {
+            Some((Token::Word(w) | Token::Quoted(w), _)) => w,
+            _ => {
+                return Err(self.error(
+                    format!("expected a value after `{} {}`", field.name(), op.symbol()),
+                    value_at,
+                ))
+            }
+        };
+        self.pos += 1;
+        let value = match field_type {
+            FieldType::Text => Value::Text(raw),
+            FieldType::Number => Value::Number(
+                raw.parse()
+                    .ok()
+                    .filter(|n: &f64| n.is_finite())
+                    .ok_or_else(|| {
+                        self.error(
+                            format!("expected a number for `{}`, found `{raw}`", field.name()),
+                            value_at,
+                        )
+                    })?,
+            ),
+            FieldType::Date => Value::Date(DateRange::parse(&raw, self.today).map_err(|_| {
+                self.error(
+                    format!(
+                        "expected a date for `{}`, found `{raw}` (try YYYY-MM-DD, 2024-05, \
+                         2024-Q3, 30d or last-month)",
+                        field.name()
+                    ),
+                    value_at,
+                )
+            })?),
+            FieldType::Boolean => Value::Boolean(parse_bool(&raw).ok_or_else(|| {
+                self.error(
+                    format!("expected true or false for `{}`, found `{raw}`", field.name()),
+                    value_at,
+                )
+            })?),
+        };
+        Ok(Expr::Compare(Comparison { field, op, value }))
+    }
+}
+
+fn type_name(field_type: FieldType) -> &'static str {
+    match field_type {
+        FieldType::Text => "text",
+        FieldType::Number => "number",
+        FieldType::Date => "date",
+        FieldType::Boolean => "boolean",
+    }
+}
+
+fn parse_bool(raw: &str) -> Option<bool> {
+    match raw.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{date, transaction, GIRO_UUID};
+
+    fn parse(input: &str) -> Result<Query, QueryError> {
+        Query::parse_at(input, date(2024, 3, 14))
+    }
+
+    /// The canonical form of `input`.
+    fn canonical(input: &str) -> String {
+        let query = parse(input).unwrap_or_else(|e| panic!("{input}: {e}"));
+        let printed = query.to_string();
+        assert_eq!(parse(&printed).unwrap(), query, "{printed} doesn't round-trip");
+        printed
+    }
+
+    /// The error message and caret position for `input`.
+    fn error(input: &str) -> (String, usize) {
+        let err = parse(input).expect_err(input);
+        (err.message().to_string(), err.position())
+    }
+
+    #[test]
+    fn test_parses_comparisons() {
+        for (input, expected) in [
+            ("amount < -100", "amount < -100"),
+            ("amount<=-12.5", "amount <= -12.5"),
+            ("amount > 0", "amount > 0"),
+            ("AMOUNT >= 1e3", "amount >= 1000"),
+            ("id == 42", "id = 42"),
+            ("id != 42", "id != 42"),
+            ("payee:REWE", "payee : \"REWE\""),
+            ("name = 'Deutsche Bahn'", "payee = \"Deutsche Bahn\""),
+            (r#"category ~ "Food\\*""#, r#"category ~ "Food\\*""#),
+            (r"category ~ Food\*", r#"category ~ "Food\\*""#),
+            (r#"purpose : "say \"hi\"""#, r#"purpose : "say \"hi\"""#),
+            ("value_date = 2024-05-17", "value-date = 2024-05-17"),
+            ("valueDate = 2024-05-17", "value-date = 2024-05-17"),
+            ("booking-date >= 2024-01-01", "date >= 2024-01-01"),
+            ("date = 2024-05", "date = 2024-05-01..2024-05-31"),
+            ("date < last-month", "date < 2024-02-01..2024-02-29"),
+            ("date >= 2024-01..2024-Q1", "date >= 2024-01-01..2024-03-31"),
+            ("booked", "booked = true"),
+            ("booked = no", "booked = false"),
+            ("checkmark != off", "checkmark != false"),
+            ("checked = YES", "checkmark = true"),
+            ("pending", "pending = true"),
+            ("account-number : DE89", "iban : \"DE89\""),
+            ("creditor_id = DE98ZZZ09999999999", "creditor-id = \"DE98ZZZ09999999999\""),
+            ("currency = ''", "currency = \"\""),
+        ] {
+            assert_eq!(canonical(input), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_precedence_and_grouping() {
+        for (input, expected) in [
+            ("booked and pending", "booked = true and pending = true"),
+            ("booked pending", "booked = true and pending = true"),
+            ("booked && pending", ""),
+            (
+                "booked or pending and checkmark",
+                "booked = true or pending = true and checkmark = true",
+            ),
+            (
+                "(booked or pending) and checkmark",
+                "(booked = true or pending = true) and checkmark = true",
+            ),
+            (
+                "booked AND (pending OR checkmark)",
+                "booked = true and (pending = true or checkmark = true)",
+            ),
+            ("not booked", "not booked = true"),
+            ("!booked", "not booked = true"),
+            ("not not booked", "not not booked = true"),
+            ("not (booked or pending)", "not (booked = true or pending = true)"),
+            ("not booked or pending", "not booked = true or pending = true"),
+            ("((booked))", "booked = true"),
+            (
+                "booked or (pending or checkmark)",
+                "booked = true or (pending = true or checkmark = true)",
+            ),
+            (
+                "booked and (pending and checkmark)",
+                "booked = true and (pending = true and checkmark = true)",
+            ),
+        ] {
+            if expected.is_empty() {
+                assert!(parse(input).is_err(), "{input}");
+            } else {
+                assert_eq!(canonical(input), expected, "{input}");
+            }
+        }
+
+        let Expr::Or(left, right) = parse("booked or pending and checkmark")
+            .unwrap()
+            .expr()
+            .clone()
+        else {
+            panic!("expected `or` at the top");
+        };
+        assert!(matches!(*left, Expr::Compare(_)));
+        assert!(matches!(*right, Expr::And(..)));
+    }
+
+    #[test]
+    fn test_the_example_query() {
+        let query = parse(
+            r#"amount < -100 and category ~ "Food\\*" and payee:"REWE" and date >= 2024-01-01"#,
+        )
+        .unwrap();
+        assert_eq!(
+            query.to_string(),
+            r#"amount < -100 and category ~ "Food\\*" and payee : "REWE" and date >= 2024-01-01"#
+        );
+    }
+
+    #[test]
+    fn test_errors_point_at_the_problem() {
+        for (input, message, position) in [
+            ("", "empty query", 0),
+            ("   ", "empty query", 0),
+            ("paye:REWE", "unknown field `paye`", 0),
+            ("amount", "expected an operator after `amount`", 6),
+            ("amount <", "expected a value after `amount <`", 8),
+            ("amount < and", "expected a number for `amount`, found `and`", 9),
+            ("amount < abc", "expected a number for `amount`, found `abc`", 9),
+            ("amount < 1,5", "expected a number for `amount`, found `1,5`", 9),
+            ("amount ~ 5", "`~` doesn't work on number field `amount`", 7),
+            ("payee < x", "`<` doesn't work on text field `payee`", 6),
+            ("booked > true", "`>` doesn't work on boolean field `booked`", 7),
+            ("date : 2024", "`:` doesn't work on date field `date`", 5),
+            ("booked = maybe", "expected true or false for `booked`, found `maybe`", 9),
+            ("date >= soon", "expected a date for `date`, found `soon`", 8),
+            ("date >= 2024-13", "expected a date for `date`, found `2024-13`", 8),
+            ("payee:\"REWE", "unterminated string", 6),
+            ("(booked", "expected `)` to close `(` at column 1", 7),
+            ("booked)", "unmatched `)`", 6),
+            ("()", "expected a comparison before `)`", 1),
+            ("booked and", "expected a comparison at the end of the query", 10),
+            ("booked or or pending", "expected a comparison before `or`", 10),
+            ("and booked", "expected a comparison before `and`", 0),
+            ("not", "expected a comparison at the end of the query", 3),
+            ("= 5", "expected a field name before `=`", 0),
+            ("\"payee\" = x", "expected a field name, found the string \"payee\"", 0),
+            ("booked true", "unknown field `true`", 7),
+            ("Straße:x und amount", "unknown field `Straße`", 0),
+            ("payee:ü amount", "expected an operator after `amount`", 14),
+        ] {
+            let (actual, at) = error(input);
+            assert!(actual.starts_with(message), "{input}: {actual}");
+            assert_eq!(at, position, "{input}: {actual}");
+        }
+    }
+
+    #[test]
+    fn test_error_display_has_a_caret() {
+        let err = parse("amount < -100 and paye:REWE").unwrap_err();
+        let text = err.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("unknown field `paye`"));
+        assert_eq!(lines[1], "  amount < -100 and paye:REWE");
+        assert_eq!(lines[2], "                    ^");
+    }
+
+    #[test]
+    fn test_matches_transactions() {
+        let mut t = transaction(7, GIRO_UUID, date(2024, 2, 10), "REWE Markt", -123.45);
+        t.category = Some("Food\\Groceries".to_string());
+        t.purpose = Some("Einkauf \"Wocheneinkauf\"".to_string());
+        t.value_date = t.booking_date + chrono::Days::new(2);
+
+        for (input, expected) in [
+            ("amount < -100", true),
+            ("amount < -123.45", false),
+            ("amount <= -123.45", true),
+            ("amount = -123.45", true),
+            ("amount >= -123.45 and amount > -200", true),
+            ("id = 7", true),
+            ("payee:rewe", true),
+            ("payee = \"rewe markt\"", true),
+            ("payee = rewe", false),
+            ("payee ~ \"R?WE*\"", true),
+            ("payee ~ REWE", false),
+            (r"category ~ Food\*", true),
+            (r"category ~ Travel\*", false),
+            ("comment = ''", true),
+            ("comment:x", false),
+            ("purpose:wocheneinkauf", true),
+            ("date = 2024-02", true),
+            ("date = 2024-02-11", false),
+            ("date != 2024-02-10", false),
+            ("date >= 2024-02-10 and date <= 2024-02-10", true),
+            ("date < 2024-02", false),
+            ("date > 2024-01", true),
+            ("date > 2024-02", false),
+            ("date >= last-month", true),
+            ("value-date = 2024-02-12", true),
+            ("booked and not pending", true),
+            ("checkmark", false),
+            ("checkmark = false", true),
+            ("payee:aldi or payee:rewe", true),
+            ("not (payee:aldi or payee:rewe)", false),
+        ] {
+            assert_eq!(parse(input).unwrap().matches(&t), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*c", "abbbc"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(glob_match("a?c", "abc"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(!glob_match("a*d", "abc"));
+        assert!(glob_match("*ß", "straß"));
+        assert!(glob_match(r"food\*", r"food\groceries"));
+        assert!(!glob_match(r"food\*", "foodstuff"));
+    }
+
+    #[test]
+    fn test_date_bounds() {
+        let bounds = |input: &str| parse(input).unwrap().date_bounds();
+        assert_eq!(bounds("amount < 0"), (None, None));
+        assert_eq!(bounds("date >= 2024-01-01"), (Some(date(2024, 1, 1)), None));
+        assert_eq!(bounds("date = 2024-Q1"), (Some(date(2024, 1, 1)), Some(date(2024, 3, 31))));
+        assert_eq!(
+            bounds("date > 2023 and payee:x and date < 2024-03"),
+            (Some(date(2024, 1, 1)), Some(date(2024, 2, 29)))
+        );
+        assert_eq!(bounds("date >= 2024-01 and date >= 2024-02"), (Some(date(2024, 2, 1)), None));
+        // Under `or` and `not` a date doesn't bound the matches.
+        assert_eq!(bounds("date >= 2024 or payee:x"), (None, None));
+        assert_eq!(bounds("not date >= 2024"), (None, None));
+        assert_eq!(bounds("value-date >= 2024"), (None, None));
+    }
+}
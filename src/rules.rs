@@ -0,0 +1,473 @@
+//! Rule-based categorization.
+//!
+//! A [`RuleSet`] is an ordered list of rules read from a TOML or YAML file.
+//! Each rule has conditions (`when`) and assignments (`set`); the first rule
+//! whose conditions all hold for a transaction decides the category,
+//! comment and/or checkmark it gets. Unlike MoneyMoney's own rules, they can
+//! match creditor IDs, mandate references and amount ranges.
+//!
+//! ```toml
+//! [[rules]]
+//! name = "Streaming"
+//! when = { name = "netflix|spotify", amount = { max = 0 } }
+//! set = { category = "Abos\\Streaming", checkmark = true }
+//!
+//! [[rules]]
+//! name = "Rent"
+//! when.creditor-id = "DE98ZZZ09999999999"
+//! when.amount = { min = -1250, max = -1150 }
+//! when.account = "Girokonto"
+//! set.category = "Wohnen\\Miete"
+//! set.comment = "Miete"
+//! ```
+//!
+//! The same file in YAML is a `rules:` list of mappings with the same keys.
+//!
+//! Conditions:
+//!
+//! | Key | Matches |
+//! |---|---|
+//! | `name` (`payee`), `purpose`, `booking-text` | regular expression, case-insensitive; a missing value doesn't match |
+//! | `amount` | a number (exact) or `{ min, max }` (inclusive, signed, either may be left out) |
+//! | `account-number` | counterparty IBAN or account number; spaces and case ignored |
+//! | `creditor-id`, `mandate-reference` | exact value, case-insensitive |
+//! | `account` | the transaction's own account by name, IBAN, account number or UUID |
+//!
+//! A rule needs at least one condition and one assignment. Rules without a
+//! `name` are called `rule 1`, `rule 2`, ... by their position.
+//!
+//! Requires the `rules` feature (enabled by `cli`).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use chrono::NaiveDate;
+//! use moneymoney::export_transactions::ExportTransactionsParams;
+//! use moneymoney::rules::RuleSet;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let rules = RuleSet::load("rules.toml")?;
+//! let params = ExportTransactionsParams::new(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+//! let transactions = moneymoney::export_transactions(params)?.transactions;
+//! let accounts = moneymoney::export_accounts()?;
+//! for assignment in rules.plan(&transactions, &accounts) {
+//!     println!("{}: {}", assignment.rule.name, assignment.transaction.name);
+//!     moneymoney::set_transaction(assignment.change)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Deserializer};
+use thiserror::Error;
+
+use crate::export_accounts::MoneymoneyAccount;
+use crate::export_transactions::MoneymoneyTransaction;
+use crate::filter::is_uncategorized;
+use crate::set_transaction::SetTransactionParams;
+
+/// Errors while reading a rule file.
+#[derive(Debug, Error)]
+pub enum RulesError {
+    /// The rule file could not be read.
+    #[error("failed to read {path}: {source}")]
+    Io {
+        /// The rule file.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+
+    /// The TOML is malformed or has unknown keys.
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+
+    /// The YAML is malformed or has unknown keys.
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+
+    /// A rule can never match or changes nothing.
+    #[error("{rule}: {message}")]
+    Invalid {
+        /// The rule's name.
+        rule: String,
+        /// What is wrong with it.
+        message: &'static str,
+    },
+}
+
+/// Ordered rules; the first match wins.
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+/// Layout of a rule file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+/// One rule: what it matches and what it assigns.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Name shown in plans; `rule N` if the file gives none.
+    #[serde(default)]
+    pub name: String,
+    /// Conditions that must all hold.
+    pub when: Conditions,
+    /// What a matching transaction gets.
+    pub set: Actions,
+}
+
+/// Conditions of a rule; unset ones match everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Conditions {
+    /// Pattern the payee must match.
+    #[serde(default, alias = "payee", deserialize_with = "pattern")]
+    pub name: Option<Regex>,
+    /// Pattern the purpose must match.
+    #[serde(default, deserialize_with = "pattern")]
+    pub purpose: Option<Regex>,
+    /// Pattern the booking text (e.g. `LASTSCHRIFT`) must match.
+    #[serde(default, deserialize_with = "pattern")]
+    pub booking_text: Option<Regex>,
+    /// Amount or amount range.
+    pub amount: Option<AmountMatch>,
+    /// Counterparty IBAN or account number.
+    pub account_number: Option<String>,
+    /// SEPA creditor identifier of a direct debit.
+    pub creditor_id: Option<String>,
+    /// SEPA mandate reference of a direct debit.
+    pub mandate_reference: Option<String>,
+    /// The transaction's own account (name, IBAN, account number or UUID).
+    pub account: Option<String>,
+}
+
+/// `amount = -9.99` or `amount = { min = -20, max = -5 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum AmountMatch {
+    /// This amount exactly.
+    Exact(f64),
+    /// Inclusive signed bounds.
+    Range {
+        /// Smallest amount, e.g. `-1250` for expenses up to 1250.
+        min: Option<f64>,
+        /// Largest amount.
+        max: Option<f64>,
+    },
+}
+
+/// What a matching transaction gets.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Actions {
+    /// Category name, UUID or backslash-separated path.
+    pub category: Option<String>,
+    /// Comment to set.
+    pub comment: Option<String>,
+    /// Checkmark to set.
+    pub checkmark: Option<bool>,
+}
+
+/// A change a rule makes to one transaction.
+#[derive(Debug, Clone)]
+pub struct Assignment<'a> {
+    /// The transaction.
+    pub transaction: &'a MoneymoneyTransaction,
+    /// The first rule that matched it.
+    pub rule: &'a Rule,
+    /// The fields to change; values already in place are left out.
+    pub change: SetTransactionParams,
+}
+
+/// Deserialize an optional case-insensitive regular expression.
+fn pattern<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Regex>, D::Error> {
+    let Some(raw) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    RegexBuilder::new(&raw)
+        .case_insensitive(true)
+        .build()
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+impl RuleSet {
+    /// Read a rule file: YAML for `.yaml` and `.yml`, TOML otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RulesError`] if the file can't be read or parsed, or a
+    /// rule is invalid.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RulesError> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path).map_err(|source| RulesError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => Self::from_yaml(&raw),
+            _ => Self::from_toml(&raw),
+        }
+    }
+
+    /// Parse rules from TOML (`[[rules]]` tables).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RulesError`] if the TOML doesn't parse or a rule is invalid.
+    pub fn from_toml(raw: &str) -> Result<Self, RulesError> {
+        Self::new(toml::from_str::<RuleFile>(raw)?.rules)
+    }
+
+    /// Parse rules from YAML (a `rules:` list).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RulesError`] if the YAML doesn't parse or a rule is invalid.
+    pub fn from_yaml(raw: &str) -> Result<Self, RulesError> {
+        Self::new(serde_yaml::from_str::<RuleFile>(raw)?.rules)
+    }
+
+    /// Name unnamed rules and check that each has conditions and assignments.
+    fn new(mut rules: Vec<Rule>) -> Result<Self, RulesError> {
+        for (i, rule) in rules.iter_mut().enumerate() {
+            if rule.name.is_empty() {
+                rule.name = format!("rule {}", i + 1);
+            }
+            let invalid = |message| RulesError::Invalid {
+                rule: rule.name.clone(),
+                message,
+            };
+            if rule.when.is_empty() {
+                return Err(invalid("`when` needs at least one condition"));
+            }
+            if rule.set == Actions::default() {
+                return Err(invalid("`set` needs a category, comment or checkmark"));
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    /// The rules in file order.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Whether a rule has an `account` condition, which needs the exported
+    /// accounts to evaluate.
+    pub fn uses_accounts(&self) -> bool {
+        self.rules.iter().any(|rule| rule.when.account.is_some())
+    }
+
+    /// The first rule matching `t`. `accounts` resolves `account`
+    /// conditions.
+    pub fn first_match(
+        &self,
+        t: &MoneymoneyTransaction,
+        accounts: &[MoneymoneyAccount],
+    ) -> Option<&Rule> {
+        self.rules
+            .iter()
+            .find(|rule| rule.when.matches(t, accounts))
+    }
+
+    /// The changes for the uncategorized `transactions`: the first matching
+    /// rule's assignments that differ from what is there already.
+    pub fn plan<'a>(
+        &'a self,
+        transactions: &'a [MoneymoneyTransaction],
+        accounts: &[MoneymoneyAccount],
+    ) -> Vec<Assignment<'a>> {
+        transactions
+            .iter()
+            .filter(|t| is_uncategorized(t))
+            .filter_map(|transaction| {
+                let rule = self.first_match(transaction, accounts)?;
+                let change = rule.set.change(transaction)?;
+                Some(Assignment {
+                    transaction,
+                    rule,
+                    change,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Conditions {
+    /// Whether no condition is set.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.purpose.is_none()
+            && self.booking_text.is_none()
+            && self.amount.is_none()
+            && self.account_number.is_none()
+            && self.creditor_id.is_none()
+            && self.mandate_reference.is_none()
+            && self.account.is_none()
+    }
+
+    /// Whether every set condition holds for `t`.
+    pub fn matches(&self, t: &MoneymoneyTransaction, accounts: &[MoneymoneyAccount]) -> bool {
+        let pattern = |regex: &Option<Regex>, value: Option<&str>| match regex {
+            None => true,
+            Some(regex) => value.is_some_and(|value| regex.is_match(value)),
+        };
+        let exact = |expected: &Option<String>, value: Option<&str>| match expected {
+            None => true,
+            Some(expected) => {
+                value.is_some_and(|value| value.trim().eq_ignore_ascii_case(expected.trim()))
+            }
+        };
+        let compact = |s: &str| s.replace(' ', "");
+        pattern(&self.name, Some(&t.name))
+            && pattern(&self.purpose, t.purpose.as_deref())
+            && pattern(&self.booking_text, t.booking_text.as_deref())
+            && self.amount.is_none_or(|amount| amount.matches(t.amount))
+            && self.account_number.as_deref().is_none_or(|expected| {
+                t.account_number
+                    .as_deref()
+                    .is_some_and(|number| compact(number).eq_ignore_ascii_case(&compact(expected)))
+            })
+            && exact(&self.creditor_id, t.creditor_id.as_deref())
+            && exact(&self.mandate_reference, t.mandate_reference.as_deref())
+            && self.account.as_deref().is_none_or(|ident| {
+                accounts
+                    .iter()
+                    .any(|a| a.uuid == t.account_uuid && a.matches(ident))
+            })
+    }
+}
+
+impl AmountMatch {
+    /// Whether `amount` is this amount or in this range.
+    pub fn matches(self, amount: f64) -> bool {
+        const EPSILON: f64 = 1e-9;
+        match self {
+            AmountMatch::Exact(expected) => (amount - expected).abs() < EPSILON,
+            AmountMatch::Range { min, max } => {
+                min.is_none_or(|min| amount > min - EPSILON)
+                    && max.is_none_or(|max| amount < max + EPSILON)
+            }
+        }
+    }
+}
+
+impl Actions {
+    /// The parameters that change `t`, or `None` if it already has
+    /// everything. A category is always assigned.
+    pub fn change(&self, t: &MoneymoneyTransaction) -> Option<SetTransactionParams> {
+        let mut change = SetTransactionParams::new(t.id);
+        change.category_to = self.category.clone();
+        change.comment_to = self
+            .comment
+            .clone()
+            .filter(|comment| t.comment.as_deref().unwrap_or_default() != comment);
+        change.checkmark_to = self
+            .checkmark
+            .filter(|checkmark| *checkmark != t.checkmark)
+            .map(|checkmark| if checkmark { "on" } else { "off" }.to_string());
+        let unchanged = change.category_to.is_none()
+            && change.comment_to.is_none()
+            && change.checkmark_to.is_none();
+        (!unchanged).then_some(change)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{cash, date, giro, transaction, CASH_UUID, GIRO_UUID};
+
+    const RULES: &str = r#"
+[[rules]]
+name = "Streaming"
+when = { name = "^netflix", amount = { max = 0 } }
+set = { category = "Abos\\Streaming", checkmark = true }
+
+[[rules]]
+name = "Rent"
+when.creditor-id = "DE98ZZZ09999999999"
+when.amount = { min = -1250, max = -1150 }
+set.category = "Wohnen\\Miete"
+set.comment = "Miete"
+
+[[rules]]
+when = { account = "Bargeld", booking-text = "auszahlung" }
+set = { comment = "Bargeld" }
+"#;
+
+    #[test]
+    fn test_plans_first_matching_rule() {
+        let rules = RuleSet::from_toml(RULES).unwrap();
+        assert_eq!(rules.rules()[2].name, "rule 3");
+        assert!(rules.uses_accounts());
+
+        let day = date(2024, 3, 1);
+        let netflix = transaction(1, GIRO_UUID, day, "NETFLIX.COM", -12.99);
+        let mut rent = transaction(2, GIRO_UUID, day, "Hausverwaltung", -1200.0);
+        rent.creditor_id = Some("de98zzz09999999999".to_string());
+        let mut too_much = rent.clone();
+        too_much.id = 3;
+        too_much.amount = -1300.0;
+        let mut categorized = transaction(4, GIRO_UUID, day, "Netflix", -12.99);
+        categorized.category = Some("Abos".to_string());
+        categorized.category_uuid = uuid::Uuid::new_v4();
+        let mut atm = transaction(5, CASH_UUID, day, "Automat", 100.0);
+        atm.booking_text = Some("Auszahlung".to_string());
+        let mut commented = atm.clone();
+        commented.id = 6;
+        commented.comment = Some("Bargeld".to_string());
+        let transactions = [netflix, rent, too_much, categorized, atm, commented];
+
+        let plan = rules.plan(&transactions, &[giro(), cash()]);
+        let planned: Vec<(u64, &str)> = plan
+            .iter()
+            .map(|a| (a.transaction.id, a.rule.name.as_str()))
+            .collect();
+        assert_eq!(planned, [(1, "Streaming"), (2, "Rent"), (5, "rule 3")]);
+        assert_eq!(plan[0].change.category_to.as_deref(), Some("Abos\\Streaming"));
+        assert_eq!(plan[0].change.checkmark_to.as_deref(), Some("on"));
+        assert_eq!(plan[1].change.comment_to.as_deref(), Some("Miete"));
+        assert_eq!(plan[2].change.category_to, None);
+
+        // Without the accounts, `account` conditions can't hold.
+        assert_eq!(rules.plan(&transactions, &[]).len(), 2);
+    }
+
+    #[test]
+    fn test_reads_yaml_and_rejects_invalid_rules() {
+        let rules = RuleSet::from_yaml(
+            "rules:\n  - when:\n      account-number: DE89 3704 0044 0532 0130 00\n      amount: -50\n    set:\n      category: Sparen\n",
+        )
+        .unwrap();
+        let mut transfer = transaction(1, GIRO_UUID, date(2024, 3, 1), "Sparplan", -50.0);
+        transfer.account_number = Some("DE89370400440532013000".to_string());
+        assert!(rules.first_match(&transfer, &[]).is_some());
+        transfer.amount = -50.5;
+        assert!(rules.first_match(&transfer, &[]).is_none());
+
+        let err =
+            RuleSet::from_toml("[[rules]]\nwhen = {}\nset = { comment = \"x\" }").unwrap_err();
+        assert_eq!(err.to_string(), "rule 1: `when` needs at least one condition");
+        let err =
+            RuleSet::from_toml("[[rules]]\nname = \"A\"\nwhen.name = \"x\"\nset = {}").unwrap_err();
+        assert_eq!(err.to_string(), "A: `set` needs a category, comment or checkmark");
+        let err =
+            RuleSet::from_toml("[[rules]]\nwhen.name = \"(\"\nset.comment = \"x\"").unwrap_err();
+        assert!(matches!(err, RulesError::Toml(_)), "{err}");
+        let err =
+            RuleSet::from_toml("[[rules]]\nwhen.iban = \"x\"\nset.comment = \"x\"").unwrap_err();
+        assert!(err.to_string().contains("unknown field `iban`"), "{err}");
+    }
+}
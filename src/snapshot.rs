@@ -0,0 +1,623 @@
+//! Exports kept over time.
+//!
+//! MoneyMoney only knows current balances and market values. A
+//! [`SnapshotStore`] saves a [`SyncData`] export under a timestamp so balance
+//! and portfolio history can be analyzed later with [`Snapshot::load`]:
+//!
+//! ```text
+//! <root>/snapshots/20240601T120000Z.json   manifest: time, window, payload hashes
+//! <root>/objects/<sha256>.json             accounts, categories, transactions, securities
+//! ```
+//!
+//! Payloads are stored once per content hash, so a snapshot whose accounts
+//! or categories didn't change costs only its manifest. Account icons are
+//! not kept. [`Retention`] thins out old snapshots the way backup tools do.
+//!
+//! Requires the `snapshot` feature (enabled by `cli`).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use moneymoney::snapshot::SnapshotStore;
+//!
+//! # fn main() -> Result<(), moneymoney::snapshot::SnapshotError> {
+//! let store = SnapshotStore::new("snapshots");
+//! for info in store.list()? {
+//!     let snapshot = store.load(&info.id)?;
+//!     let total: f64 = snapshot
+//!         .accounts
+//!         .iter()
+//!         .filter(|a| !a.group)
+//!         .filter_map(|a| a.balance.as_ref())
+//!         .map(|b| b.amount)
+//!         .sum();
+//!     println!("{} {total:.2}", info.taken_at.date_naive());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::export_accounts::MoneymoneyAccount;
+use crate::export_categories::MoneymoneyCategory;
+use crate::export_portfolio::{ExportPortfolioResponse, Security};
+use crate::export_transactions::{MoneymoneyTransaction, TransactionsResponse};
+use crate::fs::write_atomic;
+use crate::offline::{SavedExport, SavedExports};
+use crate::sync::SyncData;
+
+/// Errors while saving or reading snapshots.
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    /// A file or directory could not be read or written.
+    #[error("failed to access {path}: {source}")]
+    Io {
+        /// The file or directory that failed.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+
+    /// A manifest or payload is not valid JSON of the expected shape.
+    #[error("{path}: {source}")]
+    Json {
+        /// The file that failed.
+        path: PathBuf,
+        /// The underlying error.
+        source: serde_json::Error,
+    },
+
+    /// [`Snapshot::load`] was given a file that isn't a snapshot manifest.
+    #[error("{0} is not a snapshot manifest")]
+    NotASnapshot(PathBuf),
+
+    /// No snapshot has this id.
+    #[error("no snapshot {0:?}")]
+    NotFound(String),
+
+    /// A snapshot with this id (its timestamp) already exists.
+    #[error("snapshot {0} already exists")]
+    Exists(String),
+}
+
+/// One stored payload.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Payload {
+    /// SHA-256 of the stored JSON, also its file name in `objects/`.
+    pub sha256: String,
+    /// Number of items in it.
+    pub items: usize,
+}
+
+/// A snapshot's manifest: when it was taken and what it holds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SnapshotInfo {
+    /// Timestamp id, e.g. `20240601T120000Z`; the manifest's file name wins.
+    #[serde(default)]
+    pub id: String,
+    /// When the exports were taken.
+    pub taken_at: DateTime<Utc>,
+    /// Start of the transaction window; see [`SyncData::from_date`].
+    pub from_date: NaiveDate,
+    /// All accounts, including groups.
+    pub accounts: Payload,
+    /// All categories, including groups.
+    pub categories: Payload,
+    /// Transactions of all accounts from `from_date` on.
+    pub transactions: Payload,
+    /// Securities of all portfolio accounts.
+    pub securities: Payload,
+}
+
+impl SnapshotInfo {
+    /// The hashes of the four payloads.
+    fn hashes(&self) -> [&str; 4] {
+        [
+            &self.accounts.sha256,
+            &self.categories.sha256,
+            &self.transactions.sha256,
+            &self.securities.sha256,
+        ]
+    }
+}
+
+/// A loaded snapshot.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Its manifest.
+    pub info: SnapshotInfo,
+    /// All accounts, including groups, with the balances of `info.taken_at`.
+    pub accounts: Vec<MoneymoneyAccount>,
+    /// All categories, including groups.
+    pub categories: Vec<MoneymoneyCategory>,
+    /// Transactions of all accounts from `info.from_date` on.
+    pub transactions: Vec<MoneymoneyTransaction>,
+    /// Securities of all portfolio accounts, with the market values of `info.taken_at`.
+    pub securities: Vec<Security>,
+}
+
+impl Snapshot {
+    /// Load the snapshot whose manifest is `manifest`
+    /// (`<root>/snapshots/<id>.json`), with its payloads from `<root>/objects`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotError::NotASnapshot`] if `manifest` isn't a snapshot
+    /// manifest, and [`SnapshotError::Io`] or [`SnapshotError::Json`] if it
+    /// or a payload can't be read.
+    pub fn load(manifest: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        let manifest = manifest.as_ref();
+        let bytes = std::fs::read(manifest).map_err(|source| io_error(manifest, source))?;
+        let mut info: SnapshotInfo = serde_json::from_slice(&bytes)
+            .map_err(|_| SnapshotError::NotASnapshot(manifest.to_path_buf()))?;
+        info.id = manifest
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let objects = manifest
+            .parent()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new("."))
+            .join(OBJECTS);
+        Ok(Snapshot {
+            accounts: read_object(&objects, &info.accounts)?,
+            categories: read_object(&objects, &info.categories)?,
+            transactions: read_object(&objects, &info.transactions)?,
+            securities: read_object(&objects, &info.securities)?,
+            info,
+        })
+    }
+
+    /// The four exports, to answer export calls with [`SavedExports`].
+    pub fn into_exports(self) -> [SavedExport; 4] {
+        [
+            SavedExport::Accounts(self.accounts),
+            SavedExport::Categories(self.categories),
+            SavedExport::Transactions(TransactionsResponse {
+                creator: String::new(),
+                transactions: self.transactions,
+            }),
+            SavedExport::Portfolio(ExportPortfolioResponse {
+                securities: self.securities,
+            }),
+        ]
+    }
+}
+
+impl From<Snapshot> for SavedExports {
+    fn from(snapshot: Snapshot) -> Self {
+        let mut saved = SavedExports::new();
+        for export in snapshot.into_exports() {
+            saved.insert(export).expect("each kind is inserted once");
+        }
+        saved
+    }
+}
+
+/// How many snapshots [`SnapshotStore::prune`] keeps.
+///
+/// A snapshot is kept if any rule selects it: one of the `last` newest, or
+/// the newest of one of the `daily` most recent days (weeks, months) that
+/// have snapshots. Days, weeks and months are UTC. All zero keeps everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Retention {
+    /// Keep the newest snapshots.
+    pub last: usize,
+    /// Keep the newest snapshot of this many days.
+    pub daily: usize,
+    /// Keep the newest snapshot of this many ISO weeks.
+    pub weekly: usize,
+    /// Keep the newest snapshot of this many months.
+    pub monthly: usize,
+}
+
+impl Retention {
+    /// Whether this retention keeps everything.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The snapshots in `snapshots` this retention doesn't keep.
+    pub fn expired<'a>(&self, snapshots: &'a [SnapshotInfo]) -> Vec<&'a SnapshotInfo> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        let mut newest_first: Vec<&SnapshotInfo> = snapshots.iter().collect();
+        newest_first.sort_by_key(|s| std::cmp::Reverse(s.taken_at));
+        let mut keep: HashSet<&str> = newest_first
+            .iter()
+            .take(self.last)
+            .map(|s| s.id.as_str())
+            .collect();
+        let mut bucketed = |count: usize, bucket: &dyn Fn(NaiveDate) -> (i32, u32)| {
+            let mut seen = Vec::new();
+            for snapshot in &newest_first {
+                let key = bucket(snapshot.taken_at.date_naive());
+                if seen.len() == count {
+                    break;
+                }
+                if !seen.contains(&key) {
+                    seen.push(key);
+                    keep.insert(snapshot.id.as_str());
+                }
+            }
+        };
+        bucketed(self.daily, &|day| (day.year(), day.ordinal()));
+        bucketed(self.weekly, &|day| {
+            let week = day.iso_week();
+            (week.year(), week.week())
+        });
+        bucketed(self.monthly, &|day| (day.year(), day.month()));
+        snapshots
+            .iter()
+            .filter(|s| !keep.contains(s.id.as_str()))
+            .collect()
+    }
+}
+
+/// What [`SnapshotStore::save`] wrote.
+#[derive(Debug, Clone)]
+pub struct Saved {
+    /// The new snapshot's manifest.
+    pub info: SnapshotInfo,
+    /// How many of its four payloads weren't stored before.
+    pub new_payloads: usize,
+}
+
+const SNAPSHOTS: &str = "snapshots";
+const OBJECTS: &str = "objects";
+
+/// A directory of snapshots; see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    root: PathBuf,
+}
+
+impl SnapshotStore {
+    /// The store in `root`; it is created by the first [`SnapshotStore::save`].
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The store's directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Save `data` as the snapshot taken at `taken_at`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotError::Exists`] if a snapshot was taken in the same
+    /// second, and [`SnapshotError::Io`] if the store can't be written.
+    pub fn save(&self, data: &SyncData, taken_at: DateTime<Utc>) -> Result<Saved, SnapshotError> {
+        let id = taken_at.format("%Y%m%dT%H%M%SZ").to_string();
+        let manifest = self.manifest_path(&id);
+        if manifest.exists() {
+            return Err(SnapshotError::Exists(id));
+        }
+        let mut new_payloads = 0;
+        let mut store = |items: serde_json::Value, count: usize| {
+            let (payload, new) = self.store_object(items, count)?;
+            new_payloads += usize::from(new);
+            Ok::<_, SnapshotError>(payload)
+        };
+        let info = SnapshotInfo {
+            id,
+            taken_at,
+            from_date: data.from_date,
+            accounts: store(without_icons(&data.accounts), data.accounts.len())?,
+            categories: store(without_icons(&data.categories), data.categories.len())?,
+            transactions: store(to_json(&data.transactions), data.transactions.len())?,
+            securities: store(to_json(&data.securities), data.securities.len())?,
+        };
+        let json = serde_json::to_vec_pretty(&info).expect("manifests serialize");
+        write_atomic(&manifest, &json, io_error)?;
+        Ok(Saved { info, new_payloads })
+    }
+
+    /// All snapshots, oldest first. A store that doesn't exist yet is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotError::Io`] or [`SnapshotError::Json`] if a
+    /// manifest can't be read.
+    pub fn list(&self) -> Result<Vec<SnapshotInfo>, SnapshotError> {
+        let dir = self.root.join(SNAPSHOTS);
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(source) => return Err(io_error(&dir, source)),
+        };
+        let mut snapshots = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|source| io_error(&dir, source))?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            snapshots.push(self.read_info(&path)?);
+        }
+        snapshots.sort_by(|a, b| a.taken_at.cmp(&b.taken_at).then(a.id.cmp(&b.id)));
+        Ok(snapshots)
+    }
+
+    /// The manifest of snapshot `id`, of the newest one for `latest`, or of
+    /// the one `N` before the newest for `latest~N`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotError::NotFound`] if there is no such snapshot.
+    pub fn find(&self, id: &str) -> Result<SnapshotInfo, SnapshotError> {
+        if let Some(back) = id.strip_prefix("latest") {
+            let back = match back.strip_prefix('~') {
+                Some(n) => n.parse().ok(),
+                None => back.is_empty().then_some(0),
+            };
+            if let Some(back) = back {
+                let mut snapshots = self.list()?;
+                let index = snapshots.len().checked_sub(back + 1);
+                return index
+                    .map(|i| snapshots.swap_remove(i))
+                    .ok_or_else(|| SnapshotError::NotFound(id.to_string()));
+            }
+        }
+        let path = self.manifest_path(id);
+        if !path.is_file() {
+            return Err(SnapshotError::NotFound(id.to_string()));
+        }
+        self.read_info(&path)
+    }
+
+    /// Load snapshot `id` (or `latest`, `latest~N`), see [`Snapshot::load`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotError::NotFound`] if there is no such snapshot, or
+    /// the error of reading it.
+    pub fn load(&self, id: &str) -> Result<Snapshot, SnapshotError> {
+        let info = self.find(id)?;
+        Snapshot::load(self.manifest_path(&info.id))
+    }
+
+    /// Delete the snapshots `retention` doesn't keep, then the payloads no
+    /// remaining snapshot refers to. Returns the deleted snapshots.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotError::Io`] if a file can't be deleted.
+    pub fn prune(&self, retention: &Retention) -> Result<Vec<SnapshotInfo>, SnapshotError> {
+        let snapshots = self.list()?;
+        let expired: Vec<SnapshotInfo> =
+            retention.expired(&snapshots).into_iter().cloned().collect();
+        if expired.is_empty() {
+            return Ok(expired);
+        }
+        for info in &expired {
+            let path = self.manifest_path(&info.id);
+            std::fs::remove_file(&path).map_err(|source| io_error(&path, source))?;
+        }
+        let referenced: HashSet<&str> = snapshots
+            .iter()
+            .filter(|s| !expired.iter().any(|e| e.id == s.id))
+            .flat_map(SnapshotInfo::hashes)
+            .collect();
+        let objects = self.root.join(OBJECTS);
+        let entries = std::fs::read_dir(&objects).map_err(|source| io_error(&objects, source))?;
+        for entry in entries {
+            let path = entry.map_err(|source| io_error(&objects, source))?.path();
+            let hash = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default();
+            if !referenced.contains(hash) {
+                std::fs::remove_file(&path).map_err(|source| io_error(&path, source))?;
+            }
+        }
+        Ok(expired)
+    }
+
+    fn manifest_path(&self, id: &str) -> PathBuf {
+        self.root.join(SNAPSHOTS).join(format!("{id}.json"))
+    }
+
+    fn read_info(&self, path: &Path) -> Result<SnapshotInfo, SnapshotError> {
+        let bytes = std::fs::read(path).map_err(|source| io_error(path, source))?;
+        let mut info: SnapshotInfo =
+            serde_json::from_slice(&bytes).map_err(|source| SnapshotError::Json {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        info.id = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(info)
+    }
+
+    /// Store `items` under their hash; `true` if they weren't stored yet.
+    fn store_object(
+        &self,
+        items: serde_json::Value,
+        count: usize,
+    ) -> Result<(Payload, bool), SnapshotError> {
+        let json = serde_json::to_vec(&items).expect("JSON values serialize");
+        let sha256 = format!("{:x}", Sha256::digest(&json));
+        let path = self.root.join(OBJECTS).join(format!("{sha256}.json"));
+        let new = !path.exists();
+        if new {
+            write_atomic(&path, &json, io_error)?;
+        }
+        Ok((
+            Payload {
+                sha256,
+                items: count,
+            },
+            new,
+        ))
+    }
+}
+
+fn read_object<T: DeserializeOwned>(
+    objects: &Path,
+    payload: &Payload,
+) -> Result<Vec<T>, SnapshotError> {
+    let path = objects.join(format!("{}.json", payload.sha256));
+    let bytes = std::fs::read(&path).map_err(|source| io_error(&path, source))?;
+    serde_json::from_slice(&bytes).map_err(|source| SnapshotError::Json { path, source })
+}
+
+fn to_json<T: Serialize>(items: &[T]) -> serde_json::Value {
+    serde_json::to_value(items).expect("exports serialize")
+}
+
+/// Accounts and categories without their icon bytes, which would make every
+/// payload differ as soon as MoneyMoney re-encodes an icon.
+fn without_icons<T: Serialize>(items: &[T]) -> serde_json::Value {
+    let mut value = to_json(items);
+    if let Some(items) = value.as_array_mut() {
+        for item in items
+            .iter_mut()
+            .filter_map(serde_json::Value::as_object_mut)
+        {
+            item.remove("icon");
+        }
+    }
+    value
+}
+
+fn io_error(path: &Path, source: std::io::Error) -> SnapshotError {
+    SnapshotError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{cash, date, giro, transaction, GIRO_UUID};
+    use chrono::TimeZone;
+
+    fn data(balance: f64) -> SyncData {
+        let mut giro = giro();
+        giro.balance.as_mut().unwrap().amount = balance;
+        SyncData {
+            from_date: date(2024, 3, 1),
+            accounts: vec![giro, cash()],
+            categories: Vec::new(),
+            transactions: vec![transaction(1, GIRO_UUID, date(2024, 3, 5), "REWE", -42.0)],
+            securities: Vec::new(),
+        }
+    }
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, day, hour, 0, 0).unwrap()
+    }
+
+    fn store(name: &str) -> SnapshotStore {
+        let root =
+            std::env::temp_dir().join(format!("moneymoney-snapshot-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        SnapshotStore::new(root)
+    }
+
+    #[test]
+    fn test_save_dedups_and_loads() {
+        let store = store("save");
+        let first = store.save(&data(100.0), at(1, 12)).unwrap();
+        // The empty categories and securities share one payload.
+        assert_eq!(first.new_payloads, 3);
+        assert_eq!(first.info.id, "20240301T120000Z");
+        let second = store.save(&data(100.0), at(2, 12)).unwrap();
+        assert_eq!(second.new_payloads, 0);
+        let third = store.save(&data(90.0), at(3, 12)).unwrap();
+        assert_eq!(third.new_payloads, 1);
+        assert!(matches!(store.save(&data(90.0), at(3, 12)), Err(SnapshotError::Exists(_))));
+
+        let ids: Vec<String> = store.list().unwrap().into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, ["20240301T120000Z", "20240302T120000Z", "20240303T120000Z"]);
+        let latest = store.load("latest").unwrap();
+        assert_eq!(latest.info, third.info);
+        assert_eq!(store.find("latest~2").unwrap(), first.info);
+        assert!(matches!(store.find("latest~3"), Err(SnapshotError::NotFound(_))));
+        assert_eq!(latest.accounts[0].balance.as_ref().unwrap().amount, 90.0);
+        assert_eq!(latest.transactions[0].name, "REWE");
+
+        let saved = SavedExports::from(latest);
+        assert_eq!(saved.accounts().unwrap().len(), 2);
+        assert!(matches!(
+            Snapshot::load(
+                store
+                    .root()
+                    .join(OBJECTS)
+                    .join(format!("{}.json", third.info.accounts.sha256))
+            ),
+            Err(SnapshotError::NotASnapshot(_))
+        ));
+        std::fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn test_retention_and_prune() {
+        let store = store("prune");
+        for (day, hour, balance) in [
+            (1, 8, 1.0),
+            (1, 20, 2.0),
+            (2, 8, 3.0),
+            (9, 8, 4.0),
+            (10, 8, 5.0),
+        ] {
+            store.save(&data(balance), at(day, hour)).unwrap();
+        }
+        let snapshots = store.list().unwrap();
+        let expired = |retention: Retention| -> Vec<String> {
+            retention
+                .expired(&snapshots)
+                .into_iter()
+                .map(|s| s.id.clone())
+                .collect()
+        };
+        assert!(expired(Retention::default()).is_empty());
+        assert_eq!(
+            expired(Retention {
+                last: 1,
+                daily: 2,
+                ..Default::default()
+            }),
+            ["20240301T080000Z", "20240301T200000Z", "20240302T080000Z"]
+        );
+        // Weeks: 10 March is a Sunday, so 9 and 10 March share a week.
+        assert_eq!(
+            expired(Retention {
+                weekly: 2,
+                ..Default::default()
+            }),
+            ["20240301T080000Z", "20240301T200000Z", "20240309T080000Z"]
+        );
+
+        let deleted = store
+            .prune(&Retention {
+                last: 2,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(deleted.len(), 3);
+        let objects = std::fs::read_dir(store.root().join(OBJECTS))
+            .unwrap()
+            .count();
+        // Two account payloads, the transactions and the empty list.
+        assert_eq!(objects, 4);
+        assert_eq!(store.load("20240309T080000Z").unwrap().accounts.len(), 2);
+        std::fs::remove_dir_all(store.root()).unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+//! Item-by-item decoding of list responses.
+//!
+//! [`crate::call_action_plist`] deserializes a whole response into typed
+//! structs before returning it. The `*_each` export functions use
+//! [`call_action_plist_each`] instead, which parses the response into a
+//! [`plist::Value`] tree and then converts one list item at a time, handing
+//! each to a callback before converting the next.
+//!
+//! The parsed tree is still held in memory in full, so this does not lower
+//! peak memory below the response itself; it saves the second, typed copy
+//! of the items, and lets the CLI write each item as soon as it is converted
+//! instead of building one big JSON value.
+
+use std::ops::ControlFlow;
+
+use serde::de::DeserializeOwned;
+
+use crate::{call_action, Error, MoneymoneyActions};
+
+/// What a streamed response yields: the `creator` first, then the items.
+pub(crate) enum Streamed<T> {
+    /// The response-level `creator` string.
+    Creator(String),
+    /// One list item.
+    Item(T),
+}
+
+/// Call `action` and pass each list item to `f` as it is converted.
+///
+/// The response is either a plist array of items (`list_key` is `None`) or a
+/// dictionary with the items under `list_key` and optionally a `creator`
+/// string; other dictionary keys are ignored. Returning
+/// [`ControlFlow::Break`] from `f` stops early without an error.
+pub(crate) fn call_action_plist_each<T, F>(
+    action: MoneymoneyActions,
+    list_key: Option<&'static str>,
+    f: F,
+) -> Result<(), Error>
+where
+    T: DeserializeOwned,
+    F: FnMut(Streamed<T>) -> ControlFlow<()>,
+{
+    match call_action(action)? {
+        Some(plist) => decode_each(plist.as_bytes(), list_key, f),
+        None => Err(Error::EmptyPlist),
+    }
+}
+
+pub(crate) fn decode_each<T, F>(
+    bytes: &[u8],
+    list_key: Option<&'static str>,
+    mut f: F,
+) -> Result<(), Error>
+where
+    T: DeserializeOwned,
+    F: FnMut(Streamed<T>) -> ControlFlow<()>,
+{
+    // Parsed in full first; only the conversion into `T` is item by item.
+    let value: plist::Value = plist::from_bytes(bytes)?;
+    let items = match (value, list_key) {
+        (plist::Value::Array(items), None) => items,
+        (plist::Value::Dictionary(mut dict), Some(key)) => {
+            if let Some(creator) = dict.remove("creator").and_then(plist::Value::into_string) {
+                if f(Streamed::Creator(creator)).is_break() {
+                    return Ok(());
+                }
+            }
+            match dict.remove(key) {
+                Some(plist::Value::Array(items)) => items,
+                Some(_) => return Err(unexpected(&format!("`{key}` to be an array"))),
+                None => Vec::new(),
+            }
+        }
+        (_, Some(key)) => return Err(unexpected(&format!("a dictionary with `{key}`"))),
+        (_, None) => return Err(unexpected("an array")),
+    };
+    for item in items {
+        let item: T = plist::from_value(&item)?;
+        if f(Streamed::Item(item)).is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn unexpected(expected: &str) -> Error {
+    Error::Plist(serde::de::Error::custom(format!(
+        "unexpected response shape, expected {expected}"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Item {
+        id: u64,
+    }
+
+    const RESPONSE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+  <key>extra</key><dict><key>x</key><integer>1</integer></dict>
+  <key>transactions</key>
+  <array>
+    <dict><key>id</key><integer>1</integer></dict>
+    <dict><key>id</key><integer>2</integer></dict>
+    <dict><key>id</key><integer>3</integer></dict>
+  </array>
+  <key>creator</key><string>MoneyMoney 2.4</string>
+</dict>
+</plist>"#;
+
+    fn collect(bytes: &[u8], list_key: Option<&'static str>, limit: usize) -> Vec<String> {
+        let mut seen = Vec::new();
+        decode_each::<Item, _>(bytes, list_key, |event| {
+            seen.push(match event {
+                Streamed::Creator(c) => format!("creator {c}"),
+                Streamed::Item(item) => format!("item {}", item.id),
+            });
+            if seen.len() >= limit {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .unwrap();
+        seen
+    }
+
+    #[test]
+    fn test_dictionary_response() {
+        assert_eq!(
+            collect(RESPONSE.as_bytes(), Some("transactions"), usize::MAX),
+            ["creator MoneyMoney 2.4", "item 1", "item 2", "item 3"]
+        );
+    }
+
+    #[test]
+    fn test_array_response_and_early_stop() {
+        let array = r#"<plist version="1.0"><array>
+            <dict><key>id</key><integer>7</integer></dict>
+            <dict><key>id</key><integer>8</integer></dict>
+        </array></plist>"#;
+        assert_eq!(collect(array.as_bytes(), None, usize::MAX), ["item 7", "item 8"]);
+        assert_eq!(
+            collect(RESPONSE.as_bytes(), Some("transactions"), 2),
+            ["creator MoneyMoney 2.4", "item 1"]
+        );
+    }
+
+    #[test]
+    fn test_malformed_item_is_an_error() {
+        let bad = r#"<plist version="1.0"><array>
+            <dict><key>id</key><string>one</string></dict>
+        </array></plist>"#;
+        let result = decode_each::<Item, _>(bad.as_bytes(), None, |_| ControlFlow::Continue(()));
+        assert!(matches!(result, Err(Error::Plist(_))));
+
+        let result =
+            decode_each::<Item, _>(RESPONSE.as_bytes(), None, |_| ControlFlow::Continue(()));
+        assert!(matches!(result, Err(Error::Plist(_))));
+    }
+}
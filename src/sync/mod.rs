@@ -0,0 +1,73 @@
+//! Mirror MoneyMoney data into a local store.
+//!
+//! A sync run exports everything once ([`SyncData::fetch`]) and writes it into
+//! a store that can be queried without MoneyMoney:
+//!
+//! - `sqlite` - normalized SQLite tables with change tracking and balance
+//!   snapshots per run (requires the `sqlite` feature, enabled by `cli`)
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "sqlite")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use moneymoney::sync::{sqlite::SqliteMirror, SyncData};
+//!
+//! let mut mirror = SqliteMirror::open("moneymoney.sqlite")?;
+//! let from_date = mirror.incremental_from_date(chrono::Utc::now().date_naive())?;
+//! let data = SyncData::fetch(from_date)?;
+//! let run = mirror.sync(&data, chrono::Utc::now())?;
+//! println!("{} new, {} changed, {} deleted", run.inserted, run.changed, run.deleted);
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "sqlite"))]
+//! # fn main() {}
+//! ```
+
+use chrono::NaiveDate;
+
+use crate::export_accounts::MoneymoneyAccount;
+use crate::export_categories::MoneymoneyCategory;
+use crate::export_portfolio::{ExportPortfolioParams, Security};
+use crate::export_transactions::{ExportTransactionsParams, MoneymoneyTransaction};
+use crate::Error;
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Everything one sync run writes: accounts, categories, securities and the
+/// transactions booked on or after `from_date`.
+///
+/// Transactions before `from_date` are left alone by the stores; in
+/// particular they are never considered deleted.
+#[derive(Debug)]
+pub struct SyncData {
+    /// Start of the transaction window that was exported.
+    pub from_date: NaiveDate,
+    /// All accounts, including groups.
+    pub accounts: Vec<MoneymoneyAccount>,
+    /// All categories, including groups.
+    pub categories: Vec<MoneymoneyCategory>,
+    /// Transactions of all accounts from `from_date` on.
+    pub transactions: Vec<MoneymoneyTransaction>,
+    /// Securities of all portfolio accounts.
+    pub securities: Vec<Security>,
+}
+
+impl SyncData {
+    /// Export accounts, categories, securities and transactions since `from_date`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`enum@Error`] if any of the four exports fails.
+    pub fn fetch(from_date: NaiveDate) -> Result<Self, Error> {
+        Ok(Self {
+            from_date,
+            accounts: crate::export_accounts()?,
+            categories: crate::export_categories()?,
+            transactions: crate::export_transactions(ExportTransactionsParams::new(from_date))?
+                .transactions,
+            securities: crate::export_portfolio(ExportPortfolioParams::new())?.securities,
+        })
+    }
+}
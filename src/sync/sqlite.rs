@@ -0,0 +1,798 @@
+//! SQLite mirror of MoneyMoney data.
+//!
+//! [`SqliteMirror::sync`] upserts a [`SyncData`] into normalized tables keyed
+//! by MoneyMoney's UUIDs and transaction ids, so the mirror can be re-synced
+//! any number of times and queried with plain SQL (or Grafana) in between:
+//!
+//! | Table | Contents |
+//! |-------|----------|
+//! | `sync_runs` | One row per run: time, transaction window and counts |
+//! | `accounts` | Accounts (including groups) with their latest balance |
+//! | `balance_snapshots` | Balance of every account per run |
+//! | `categories` | Categories (including groups) with budgets |
+//! | `securities` | Current portfolio holdings; sold positions are removed |
+//! | `transactions` | Transactions, with the runs that first saw, last changed and deleted them |
+//! | `transaction_changes` | Which transactions each run inserted, changed or deleted |
+//!
+//! A transaction counts as deleted when it was in the mirror, lies inside the
+//! run's window (`booking_date >= from_date`) and is missing from the export.
+//! Deleted rows are kept with `deleted_run` set; if the transaction shows up
+//! again it is undeleted and recorded as changed.
+//!
+//! Timestamps are stored as RFC 3339 UTC strings (`2024-03-02T12:00:00Z`),
+//! which sort and compare correctly as text, also against plain dates.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use chrono::{DateTime, Days, NaiveDate, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, ToSql};
+use serde::Serialize;
+use thiserror::Error;
+
+use super::SyncData;
+use crate::export_transactions::MoneymoneyTransaction;
+
+/// Schema version stored in `PRAGMA user_version`.
+pub const SCHEMA_VERSION: i64 = 1;
+
+/// Days of history an incremental run re-exports.
+pub const INCREMENTAL_DAYS: u64 = 90;
+
+/// Errors of the SQLite mirror.
+#[derive(Debug, Error)]
+pub enum SyncError {
+    /// A SQLite call failed.
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    /// The database was written by a newer version of this crate.
+    #[error(
+        "database schema version {found} is newer than the supported version {SCHEMA_VERSION}"
+    )]
+    UnsupportedSchema {
+        /// `user_version` found in the database.
+        found: i64,
+    },
+}
+
+/// Summary of one sync run, as stored in `sync_runs`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncRun {
+    /// Run id, referenced by the `*_run` columns.
+    pub id: i64,
+    /// When the run happened.
+    pub synced_at: DateTime<Utc>,
+    /// Start of the transaction window.
+    pub from_date: NaiveDate,
+    /// Number of accounts written.
+    pub accounts: u64,
+    /// Number of categories written.
+    pub categories: u64,
+    /// Number of securities written.
+    pub securities: u64,
+    /// Transactions that were new.
+    pub inserted: u64,
+    /// Transactions whose fields changed (or that reappeared after deletion).
+    pub changed: u64,
+    /// Transactions that disappeared from the window.
+    pub deleted: u64,
+    /// Transactions that were already up to date.
+    pub unchanged: u64,
+}
+
+const SCHEMA: &str = "
+CREATE TABLE sync_runs (
+    id INTEGER PRIMARY KEY,
+    synced_at TEXT NOT NULL,
+    from_date TEXT NOT NULL,
+    accounts INTEGER NOT NULL,
+    categories INTEGER NOT NULL,
+    securities INTEGER NOT NULL,
+    inserted INTEGER NOT NULL,
+    changed INTEGER NOT NULL,
+    deleted INTEGER NOT NULL,
+    unchanged INTEGER NOT NULL
+);
+
+CREATE TABLE accounts (
+    uuid TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    type TEXT NOT NULL,
+    account_number TEXT NOT NULL,
+    bank_code TEXT NOT NULL,
+    sub_account TEXT,
+    iban TEXT,
+    bic TEXT,
+    owner TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    is_group INTEGER NOT NULL,
+    is_portfolio INTEGER NOT NULL,
+    indentation INTEGER NOT NULL,
+    comment TEXT,
+    balance_amount REAL,
+    balance_currency TEXT,
+    balance_date TEXT,
+    refresh_timestamp TEXT NOT NULL,
+    last_seen_run INTEGER NOT NULL REFERENCES sync_runs (id)
+);
+
+CREATE TABLE balance_snapshots (
+    run_id INTEGER NOT NULL REFERENCES sync_runs (id),
+    account_uuid TEXT NOT NULL REFERENCES accounts (uuid),
+    amount REAL NOT NULL,
+    currency TEXT NOT NULL,
+    PRIMARY KEY (run_id, account_uuid)
+);
+
+CREATE TABLE categories (
+    uuid TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    is_default INTEGER NOT NULL,
+    is_group INTEGER NOT NULL,
+    indentation INTEGER NOT NULL,
+    budget_amount REAL,
+    budget_available REAL,
+    budget_period TEXT,
+    last_seen_run INTEGER NOT NULL REFERENCES sync_runs (id)
+);
+
+CREATE TABLE securities (
+    uuid TEXT PRIMARY KEY,
+    account_uuid TEXT NOT NULL,
+    account_name TEXT NOT NULL,
+    name TEXT NOT NULL,
+    isin TEXT NOT NULL,
+    wkn TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    asset_class TEXT NOT NULL,
+    quantity REAL NOT NULL,
+    currency TEXT NOT NULL,
+    market_price REAL NOT NULL,
+    market_value REAL NOT NULL,
+    purchase_price REAL NOT NULL,
+    purchase_value REAL NOT NULL,
+    profit REAL NOT NULL,
+    profit_percent REAL NOT NULL,
+    last_seen_run INTEGER NOT NULL REFERENCES sync_runs (id)
+);
+
+CREATE TABLE transactions (
+    id INTEGER PRIMARY KEY,
+    account_uuid TEXT NOT NULL,
+    category_uuid TEXT NOT NULL,
+    booking_date TEXT NOT NULL,
+    value_date TEXT NOT NULL,
+    name TEXT NOT NULL,
+    amount REAL NOT NULL,
+    currency TEXT NOT NULL,
+    booked INTEGER NOT NULL,
+    checkmark INTEGER NOT NULL,
+    category TEXT,
+    purpose TEXT,
+    comment TEXT,
+    booking_text TEXT,
+    account_number TEXT,
+    bank_code TEXT,
+    transaction_code INTEGER,
+    text_key_extension INTEGER,
+    purpose_code TEXT,
+    booking_key TEXT,
+    primanota_number TEXT,
+    batch_reference TEXT,
+    end_to_end_reference TEXT,
+    mandate_reference TEXT,
+    creditor_id TEXT,
+    return_reason TEXT,
+    first_seen_run INTEGER NOT NULL REFERENCES sync_runs (id),
+    changed_run INTEGER NOT NULL REFERENCES sync_runs (id),
+    deleted_run INTEGER REFERENCES sync_runs (id)
+);
+CREATE INDEX transactions_booking_date ON transactions (booking_date);
+CREATE INDEX transactions_account_uuid ON transactions (account_uuid);
+
+CREATE TABLE transaction_changes (
+    run_id INTEGER NOT NULL REFERENCES sync_runs (id),
+    transaction_id INTEGER NOT NULL,
+    change TEXT NOT NULL CHECK (change IN ('inserted', 'changed', 'deleted')),
+    PRIMARY KEY (run_id, transaction_id)
+);
+";
+
+/// Data columns of `transactions`, in the order [`transaction_values`] binds them.
+const TRANSACTION_COLUMNS: [&str; 26] = [
+    "id",
+    "account_uuid",
+    "category_uuid",
+    "booking_date",
+    "value_date",
+    "name",
+    "amount",
+    "currency",
+    "booked",
+    "checkmark",
+    "category",
+    "purpose",
+    "comment",
+    "booking_text",
+    "account_number",
+    "bank_code",
+    "transaction_code",
+    "text_key_extension",
+    "purpose_code",
+    "booking_key",
+    "primanota_number",
+    "batch_reference",
+    "end_to_end_reference",
+    "mandate_reference",
+    "creditor_id",
+    "return_reason",
+];
+
+/// A SQLite database mirroring MoneyMoney.
+pub struct SqliteMirror {
+    conn: Connection,
+}
+
+impl SqliteMirror {
+    /// Open (or create) the mirror at `path`, creating the tables if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SyncError`] if the file can't be opened or was created by a
+    /// newer schema version.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SyncError> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// Open a mirror that only lives in memory (for tests and dry runs).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SyncError`] if SQLite fails to set up the tables.
+    pub fn open_in_memory() -> Result<Self, SyncError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, SyncError> {
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        match version {
+            0 => {
+                conn.execute_batch(&format!(
+                    "BEGIN;{SCHEMA}PRAGMA user_version = {SCHEMA_VERSION};COMMIT;"
+                ))?;
+            }
+            SCHEMA_VERSION => {}
+            found => return Err(SyncError::UnsupportedSchema { found }),
+        }
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        Ok(Self { conn })
+    }
+
+    /// The underlying connection, for queries against the mirror.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// The most recent run, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SyncError`] if the query fails.
+    pub fn last_run(&self) -> Result<Option<SyncRun>, SyncError> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT id, synced_at, from_date, accounts, categories, securities, \
+                 inserted, changed, deleted, unchanged \
+                 FROM sync_runs ORDER BY id DESC LIMIT 1",
+                [],
+                |row| {
+                    Ok(SyncRun {
+                        id: row.get(0)?,
+                        synced_at: row.get(1)?,
+                        from_date: row.get(2)?,
+                        accounts: row.get(3)?,
+                        categories: row.get(4)?,
+                        securities: row.get(5)?,
+                        inserted: row.get(6)?,
+                        changed: row.get(7)?,
+                        deleted: row.get(8)?,
+                        unchanged: row.get(9)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// Start of the transaction window for the next run.
+    ///
+    /// An empty mirror gets the full history (from 1970); afterwards the
+    /// export starts [`INCREMENTAL_DAYS`] days before the last run (or before
+    /// `today`, if that is earlier), which is enough to pick up late bookings
+    /// and edits without a gap after a long pause.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SyncError`] if the query fails.
+    pub fn incremental_from_date(&self, today: NaiveDate) -> Result<NaiveDate, SyncError> {
+        Ok(match self.last_run()? {
+            Some(last) => last.synced_at.date_naive().min(today) - Days::new(INCREMENTAL_DAYS),
+            None => NaiveDate::from_ymd_opt(1970, 1, 1).expect("valid date"),
+        })
+    }
+
+    /// Write `data` as a new run in one database transaction.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SyncError`] if a statement fails; the mirror is left unchanged.
+    pub fn sync(
+        &mut self,
+        data: &SyncData,
+        synced_at: DateTime<Utc>,
+    ) -> Result<SyncRun, SyncError> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO sync_runs (synced_at, from_date, accounts, categories, securities, \
+             inserted, changed, deleted, unchanged) VALUES (?1, ?2, 0, 0, 0, 0, 0, 0, 0)",
+            params![timestamp(&synced_at), data.from_date],
+        )?;
+        let mut run = SyncRun {
+            id: tx.last_insert_rowid(),
+            synced_at,
+            from_date: data.from_date,
+            accounts: 0,
+            categories: 0,
+            securities: 0,
+            inserted: 0,
+            changed: 0,
+            deleted: 0,
+            unchanged: 0,
+        };
+
+        {
+            let mut upsert = tx.prepare(
+                "INSERT INTO accounts (uuid, name, type, account_number, bank_code, sub_account, \
+                 iban, bic, owner, currency, is_group, is_portfolio, indentation, comment, \
+                 balance_amount, balance_currency, balance_date, refresh_timestamp, last_seen_run) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, \
+                 ?17, ?18, ?19) \
+                 ON CONFLICT (uuid) DO UPDATE SET name = excluded.name, type = excluded.type, \
+                 account_number = excluded.account_number, bank_code = excluded.bank_code, \
+                 sub_account = excluded.sub_account, iban = excluded.iban, bic = excluded.bic, \
+                 owner = excluded.owner, currency = excluded.currency, \
+                 is_group = excluded.is_group, is_portfolio = excluded.is_portfolio, \
+                 indentation = excluded.indentation, comment = excluded.comment, \
+                 balance_amount = excluded.balance_amount, \
+                 balance_currency = excluded.balance_currency, \
+                 balance_date = excluded.balance_date, \
+                 refresh_timestamp = excluded.refresh_timestamp, \
+                 last_seen_run = excluded.last_seen_run",
+            )?;
+            let mut snapshot = tx.prepare(
+                "INSERT INTO balance_snapshots (run_id, account_uuid, amount, currency) \
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for a in &data.accounts {
+                let uuid = a.uuid.to_string();
+                upsert.execute(params![
+                    uuid,
+                    a.name,
+                    a.r#type.as_str(),
+                    a.account_number,
+                    a.bank_code,
+                    a.sub_account,
+                    a.iban,
+                    a.bic,
+                    a.owner,
+                    a.currency,
+                    a.group,
+                    a.portfolio,
+                    a.indentation,
+                    a.comment,
+                    a.balance.as_ref().map(|b| b.amount),
+                    a.balance.as_ref().map(|b| b.currency.code()),
+                    a.balance_date.as_ref().map(timestamp),
+                    timestamp(&a.refresh_timestamp),
+                    run.id,
+                ])?;
+                if let Some(balance) = a.balance.as_ref().filter(|_| !a.group) {
+                    snapshot.execute(params![
+                        run.id,
+                        uuid,
+                        balance.amount,
+                        balance.currency.code()
+                    ])?;
+                }
+                run.accounts += 1;
+            }
+        }
+
+        {
+            let mut upsert = tx.prepare(
+                "INSERT INTO categories (uuid, name, currency, is_default, is_group, indentation, \
+                 budget_amount, budget_available, budget_period, last_seen_run) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10) \
+                 ON CONFLICT (uuid) DO UPDATE SET name = excluded.name, \
+                 currency = excluded.currency, is_default = excluded.is_default, \
+                 is_group = excluded.is_group, indentation = excluded.indentation, \
+                 budget_amount = excluded.budget_amount, \
+                 budget_available = excluded.budget_available, \
+                 budget_period = excluded.budget_period, last_seen_run = excluded.last_seen_run",
+            )?;
+            for c in &data.categories {
+                upsert.execute(params![
+                    c.uuid.to_string(),
+                    c.name,
+                    c.currency.code(),
+                    c.default,
+                    c.group,
+                    c.indentation,
+                    c.budget.as_ref().map(|b| b.amount),
+                    c.budget.as_ref().map(|b| b.available),
+                    c.budget.as_ref().map(|b| b.period.as_str()),
+                    run.id,
+                ])?;
+                run.categories += 1;
+            }
+        }
+
+        {
+            let mut upsert = tx.prepare(
+                "INSERT INTO securities (uuid, account_uuid, account_name, name, isin, wkn, \
+                 symbol, asset_class, quantity, currency, market_price, market_value, \
+                 purchase_price, purchase_value, profit, profit_percent, last_seen_run) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, \
+                 ?17) \
+                 ON CONFLICT (uuid) DO UPDATE SET account_uuid = excluded.account_uuid, \
+                 account_name = excluded.account_name, name = excluded.name, \
+                 isin = excluded.isin, wkn = excluded.wkn, symbol = excluded.symbol, \
+                 asset_class = excluded.asset_class, quantity = excluded.quantity, \
+                 currency = excluded.currency, market_price = excluded.market_price, \
+                 market_value = excluded.market_value, purchase_price = excluded.purchase_price, \
+                 purchase_value = excluded.purchase_value, profit = excluded.profit, \
+                 profit_percent = excluded.profit_percent, last_seen_run = excluded.last_seen_run",
+            )?;
+            for s in &data.securities {
+                upsert.execute(params![
+                    s.uuid.to_string(),
+                    s.account_uuid.to_string(),
+                    s.account_name,
+                    s.name,
+                    s.isin,
+                    s.wkn,
+                    s.symbol,
+                    s.asset_class,
+                    s.quantity,
+                    s.currency,
+                    s.market_price,
+                    s.market_value,
+                    s.purchase_price,
+                    s.purchase_value,
+                    s.profit,
+                    s.profit_percent,
+                    run.id,
+                ])?;
+                run.securities += 1;
+            }
+            tx.execute("DELETE FROM securities WHERE last_seen_run != ?1", params![run.id])?;
+        }
+
+        {
+            let columns = TRANSACTION_COLUMNS.join(", ");
+            let placeholders = (1..=TRANSACTION_COLUMNS.len())
+                .map(|i| format!("?{i}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let run_param = TRANSACTION_COLUMNS.len() + 1;
+            let data_columns = &TRANSACTION_COLUMNS[1..];
+            let updates = data_columns
+                .iter()
+                .map(|c| format!("{c} = excluded.{c}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let current = data_columns
+                .iter()
+                .map(|c| format!("transactions.{c}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let incoming = data_columns
+                .iter()
+                .map(|c| format!("excluded.{c}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            // The WHERE clause turns unchanged rows into no-ops, so `changes()`
+            // tells changed and unchanged transactions apart.
+            let mut upsert = tx.prepare(&format!(
+                "INSERT INTO transactions ({columns}, first_seen_run, changed_run) \
+                 VALUES ({placeholders}, ?{run_param}, ?{run_param}) \
+                 ON CONFLICT (id) DO UPDATE SET {updates}, changed_run = excluded.changed_run, \
+                 deleted_run = NULL \
+                 WHERE ({current}) IS NOT ({incoming}) OR transactions.deleted_run IS NOT NULL"
+            ))?;
+            let mut exists = tx.prepare("SELECT 1 FROM transactions WHERE id = ?1")?;
+            let mut record = tx.prepare(
+                "INSERT INTO transaction_changes (run_id, transaction_id, change) \
+                 VALUES (?1, ?2, ?3)",
+            )?;
+
+            let mut seen = HashSet::new();
+            for t in &data.transactions {
+                if !seen.insert(t.id) {
+                    continue;
+                }
+                let existed = exists.exists(params![t.id as i64])?;
+                let mut values = transaction_values(t);
+                values.push(Box::new(run.id));
+                let refs: Vec<&dyn ToSql> = values.iter().map(|v| v.as_ref()).collect();
+                let written = upsert.execute(refs.as_slice())? > 0;
+                let change = match (existed, written) {
+                    (false, _) => {
+                        run.inserted += 1;
+                        "inserted"
+                    }
+                    (true, true) => {
+                        run.changed += 1;
+                        "changed"
+                    }
+                    (true, false) => {
+                        run.unchanged += 1;
+                        continue;
+                    }
+                };
+                record.execute(params![run.id, t.id as i64, change])?;
+            }
+
+            let mut window = tx.prepare(
+                "SELECT id FROM transactions WHERE deleted_run IS NULL AND booking_date >= ?1",
+            )?;
+            let gone: Vec<i64> = window
+                .query_map(params![data.from_date], |row| row.get(0))?
+                .filter(|id| !matches!(id, Ok(id) if seen.contains(&(*id as u64))))
+                .collect::<Result<_, _>>()?;
+            let mut delete =
+                tx.prepare("UPDATE transactions SET deleted_run = ?1 WHERE id = ?2")?;
+            for id in gone {
+                delete.execute(params![run.id, id])?;
+                record.execute(params![run.id, id, "deleted"])?;
+                run.deleted += 1;
+            }
+        }
+
+        tx.execute(
+            "UPDATE sync_runs SET accounts = ?2, categories = ?3, securities = ?4, inserted = ?5, \
+             changed = ?6, deleted = ?7, unchanged = ?8 WHERE id = ?1",
+            params![
+                run.id,
+                run.accounts,
+                run.categories,
+                run.securities,
+                run.inserted,
+                run.changed,
+                run.deleted,
+                run.unchanged,
+            ],
+        )?;
+        tx.commit()?;
+        Ok(run)
+    }
+}
+
+fn timestamp(at: &DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Values for [`TRANSACTION_COLUMNS`], in order.
+fn transaction_values(t: &MoneymoneyTransaction) -> Vec<Box<dyn ToSql + '_>> {
+    vec![
+        Box::new(t.id as i64),
+        Box::new(t.account_uuid.to_string()),
+        Box::new(t.category_uuid.to_string()),
+        Box::new(timestamp(&t.booking_date)),
+        Box::new(timestamp(&t.value_date)),
+        Box::new(&t.name),
+        Box::new(t.amount),
+        Box::new(&t.currency),
+        Box::new(t.booked),
+        Box::new(t.checkmark),
+        Box::new(&t.category),
+        Box::new(&t.purpose),
+        Box::new(&t.comment),
+        Box::new(&t.booking_text),
+        Box::new(&t.account_number),
+        Box::new(&t.bank_code),
+        Box::new(t.transaction_code.map(|c| c as i64)),
+        Box::new(t.text_key_extension.map(|c| c as i64)),
+        Box::new(&t.purpose_code),
+        Box::new(&t.booking_key),
+        Box::new(&t.primanota_number),
+        Box::new(&t.batch_reference),
+        Box::new(&t.end_to_end_reference),
+        Box::new(&t.mandate_reference),
+        Box::new(&t.creditor_id),
+        Box::new(&t.return_reason),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export_categories::{MoneymoneyCategory, MoneymoneyCategoryBudget};
+    use crate::export_portfolio::Security;
+    use crate::test_fixtures::{self, date, transaction, uuid, CARD_UUID, GIRO_UUID};
+    use chrono::TimeZone;
+
+    fn category() -> MoneymoneyCategory {
+        MoneymoneyCategory {
+            uuid: uuid("0f0e0d0c-0b0a-4908-8706-050403020100"),
+            name: "Lebensmittel".to_string(),
+            budget: Some(MoneymoneyCategoryBudget {
+                amount: 400.0,
+                available: 120.5,
+                period: "monthly".to_string(),
+            }),
+            currency: iso_currency::Currency::EUR,
+            default: false,
+            group: false,
+            icon: plist::Data::new(Vec::new()),
+            indentation: 0,
+        }
+    }
+
+    fn security(quantity: f64) -> Security {
+        Security {
+            uuid: uuid("7d1c2b3a-4e5f-4a6b-8c7d-9e0f1a2b3c4d"),
+            name: "iShares Core MSCI World".to_string(),
+            isin: "IE00B4L5Y983".to_string(),
+            wkn: "A0RPWH".to_string(),
+            symbol: "EUNL".to_string(),
+            quantity,
+            account_uuid: uuid(GIRO_UUID),
+            account_name: "Depot".to_string(),
+            market_price: 90.0,
+            currency: "EUR".to_string(),
+            market_value: quantity * 90.0,
+            purchase_price: 70.0,
+            purchase_value: quantity * 70.0,
+            profit: quantity * 20.0,
+            profit_percent: 28.57,
+            asset_class: "ETF".to_string(),
+        }
+    }
+
+    fn data(transactions: Vec<MoneymoneyTransaction>, securities: Vec<Security>) -> SyncData {
+        SyncData {
+            from_date: date(2024, 3, 1),
+            accounts: vec![test_fixtures::giro(), test_fixtures::credit_card()],
+            categories: vec![category()],
+            transactions,
+            securities,
+        }
+    }
+
+    fn at(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 4, day, 6, 0, 0).unwrap()
+    }
+
+    fn count(mirror: &SqliteMirror, sql: &str) -> i64 {
+        mirror
+            .connection()
+            .query_row(sql, [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_initial_sync() {
+        let mut mirror = SqliteMirror::open_in_memory().unwrap();
+        assert_eq!(mirror.last_run().unwrap(), None);
+        assert_eq!(mirror.incremental_from_date(date(2024, 4, 1)).unwrap(), date(1970, 1, 1));
+
+        let rewe = transaction(1, GIRO_UUID, date(2024, 3, 2), "REWE", -45.67);
+        let hotel = transaction(2, CARD_UUID, date(2024, 3, 5), "Hotel", -120.5);
+        let run = mirror
+            .sync(&data(vec![rewe, hotel], vec![security(10.0)]), at(1))
+            .unwrap();
+
+        assert_eq!(run.id, 1);
+        assert_eq!(run.accounts, 2);
+        assert_eq!(run.categories, 1);
+        assert_eq!(run.securities, 1);
+        assert_eq!(run.inserted, 2);
+        assert_eq!(run.changed + run.deleted + run.unchanged, 0);
+        assert_eq!(mirror.last_run().unwrap(), Some(run));
+        assert_eq!(mirror.incremental_from_date(date(2024, 4, 1)).unwrap(), date(2024, 1, 2));
+        // Half a year later the window still reaches back before the last run.
+        assert_eq!(mirror.incremental_from_date(date(2024, 10, 1)).unwrap(), date(2024, 1, 2));
+
+        let (kind, booking_date): (String, String) = mirror
+            .connection()
+            .query_row(
+                "SELECT a.type, t.booking_date FROM transactions t \
+                 JOIN accounts a ON a.uuid = t.account_uuid WHERE t.id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(kind, "Giro account");
+        assert_eq!(booking_date, "2024-03-02T12:00:00Z");
+        assert_eq!(count(&mirror, "SELECT COUNT(*) FROM categories WHERE budget_amount = 400"), 1);
+        assert_eq!(count(&mirror, "SELECT COUNT(*) FROM balance_snapshots"), 2);
+    }
+
+    #[test]
+    fn test_incremental_sync_tracks_changes() {
+        let mut mirror = SqliteMirror::open_in_memory().unwrap();
+        let rewe = transaction(1, GIRO_UUID, date(2024, 3, 2), "REWE", -45.67);
+        let hotel = transaction(2, CARD_UUID, date(2024, 3, 5), "Hotel", -120.5);
+        let old = transaction(3, GIRO_UUID, date(2024, 2, 1), "Miete", -900.0);
+        mirror
+            .sync(&data(vec![rewe.clone(), hotel.clone(), old], vec![security(10.0)]), at(1))
+            .unwrap();
+
+        // Hotel got categorized, REWE is unchanged, a new transaction appeared,
+        // and `old` lies before the window so it must not count as deleted.
+        let mut hotel_changed = hotel.clone();
+        hotel_changed.category = Some("Reisen".to_string());
+        let coffee = transaction(4, GIRO_UUID, date(2024, 3, 20), "Café", -3.2);
+        let run = mirror
+            .sync(&data(vec![rewe.clone(), hotel_changed, coffee], vec![]), at(2))
+            .unwrap();
+        assert_eq!((run.inserted, run.changed, run.deleted, run.unchanged), (1, 1, 0, 1));
+        assert_eq!(count(&mirror, "SELECT changed_run FROM transactions WHERE id = 2"), 2);
+        assert_eq!(count(&mirror, "SELECT COUNT(*) FROM securities"), 0);
+
+        // REWE disappears (e.g. a reversed pending booking).
+        let run = mirror
+            .sync(&data(vec![hotel.clone()], vec![]), at(3))
+            .unwrap();
+        assert_eq!(run.deleted, 2);
+        assert_eq!(run.changed, 1);
+        assert_eq!(count(&mirror, "SELECT deleted_run FROM transactions WHERE id = 1"), 3);
+        assert_eq!(
+            count(&mirror, "SELECT COUNT(*) FROM transactions WHERE deleted_run IS NULL"),
+            2
+        );
+
+        // It comes back: undeleted and recorded as a change.
+        let run = mirror
+            .sync(&data(vec![rewe, hotel], vec![]), at(4))
+            .unwrap();
+        assert_eq!((run.inserted, run.changed, run.deleted), (0, 1, 0));
+        assert_eq!(
+            count(
+                &mirror,
+                "SELECT COUNT(*) FROM transactions WHERE id = 1 AND deleted_run IS NULL"
+            ),
+            1
+        );
+        assert_eq!(
+            count(&mirror, "SELECT COUNT(*) FROM transaction_changes WHERE transaction_id = 1"),
+            3
+        );
+        assert_eq!(count(&mirror, "SELECT COUNT(*) FROM balance_snapshots"), 8);
+    }
+
+    #[test]
+    fn test_reopen_keeps_data() {
+        let path =
+            std::env::temp_dir().join(format!("moneymoney-sync-{}.sqlite", uuid::Uuid::new_v4()));
+        {
+            let mut mirror = SqliteMirror::open(&path).unwrap();
+            mirror.sync(&data(vec![], vec![]), at(1)).unwrap();
+        }
+        let mirror = SqliteMirror::open(&path).unwrap();
+        assert_eq!(mirror.last_run().unwrap().map(|r| r.id), Some(1));
+        drop(mirror);
+
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch("PRAGMA user_version = 99").unwrap();
+        drop(conn);
+        assert!(matches!(
+            SqliteMirror::open(&path),
+            Err(SyncError::UnsupportedSchema { found: 99 })
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+}
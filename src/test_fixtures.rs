@@ -0,0 +1,112 @@
+//! Shared constructors for unit tests that need accounts and transactions
+//! without a running MoneyMoney.
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use uuid::Uuid;
+
+use crate::export_accounts::{AccountBalance, MoneymoneyAccount, MoneymoneyAccountType};
+use crate::export_transactions::MoneymoneyTransaction;
+
+pub(crate) const GIRO_UUID: &str = "adcf1f45-4add-4e74-9958-a9907e0f8060";
+pub(crate) const CARD_UUID: &str = "5b1e1c0e-3f36-4a52-8d8f-0c2b8f1d2e11";
+pub(crate) const CASH_UUID: &str = "9c3b7a51-6f0e-4c61-b1a4-2f5d3e8a7c22";
+
+pub(crate) fn uuid(s: &str) -> Uuid {
+    Uuid::parse_str(s).expect("valid fixture uuid")
+}
+
+pub(crate) fn account(
+    name: &str,
+    r#type: MoneymoneyAccountType,
+    uuid_str: &str,
+    balance: f64,
+) -> MoneymoneyAccount {
+    MoneymoneyAccount {
+        account_number: "0532013000".to_string(),
+        attributes: plist::Dictionary::new(),
+        balance: Some(AccountBalance {
+            amount: balance,
+            currency: iso_currency::Currency::EUR,
+        }),
+        bank_code: "37040044".to_string(),
+        currency: "EUR".to_string(),
+        group: false,
+        icon: plist::Data::new(Vec::new()),
+        indentation: 1,
+        name: name.to_string(),
+        owner: "Erika Mustermann".to_string(),
+        portfolio: false,
+        refresh_timestamp: Utc.with_ymd_and_hms(2024, 3, 31, 18, 0, 0).unwrap(),
+        r#type,
+        uuid: uuid(uuid_str),
+        sub_account: None,
+        iban: Some("DE89370400440532013000".to_string()),
+        bic: Some("COBADEFFXXX".to_string()),
+        comment: None,
+        balance_date: None,
+    }
+}
+
+pub(crate) fn giro() -> MoneymoneyAccount {
+    account("Girokonto", MoneymoneyAccountType::Giro, GIRO_UUID, 1234.56)
+}
+
+pub(crate) fn credit_card() -> MoneymoneyAccount {
+    let mut a = account("Kreditkarte", MoneymoneyAccountType::CreditCard, CARD_UUID, -250.0);
+    a.account_number = "4111111111111111".to_string();
+    a.iban = None;
+    a.bic = None;
+    a.bank_code = String::new();
+    a
+}
+
+pub(crate) fn cash() -> MoneymoneyAccount {
+    let mut a = account("Bargeld", MoneymoneyAccountType::Cash, CASH_UUID, 80.0);
+    a.account_number = String::new();
+    a.iban = None;
+    a.bic = None;
+    a.bank_code = String::new();
+    a
+}
+
+pub(crate) fn transaction(
+    id: u64,
+    account_uuid: &str,
+    date: NaiveDate,
+    name: &str,
+    amount: f64,
+) -> MoneymoneyTransaction {
+    let at = Utc.from_utc_datetime(&date.and_hms_opt(12, 0, 0).unwrap());
+    MoneymoneyTransaction {
+        id,
+        booking_date: at,
+        value_date: at,
+        name: name.to_string(),
+        account_number: None,
+        bank_code: None,
+        transaction_code: None,
+        text_key_extension: None,
+        purpose_code: None,
+        booking_key: None,
+        primanota_number: None,
+        batch_reference: None,
+        end_to_end_reference: None,
+        creditor_id: None,
+        return_reason: None,
+        category: None,
+        purpose: None,
+        booking_text: None,
+        amount,
+        currency: "EUR".to_string(),
+        account_uuid: uuid(account_uuid),
+        booked: true,
+        category_uuid: Uuid::nil(),
+        checkmark: false,
+        mandate_reference: None,
+        comment: None,
+    }
+}
+
+pub(crate) fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).expect("valid fixture date")
+}
@@ -0,0 +1,521 @@
+//! Follow new and changed transactions.
+//!
+//! A [`Watcher`] exports the transactions of a sliding window (the last 30
+//! days by default) and compares them with a [`Cursor`] of what it saw last
+//! time, turning the differences into [`WatchEvent`]s: `new` transactions,
+//! `changed` ones (pending → booked, recategorized, commented, ...) and ones
+//! that `disappeared`. Transactions that merely slide out of the window are
+//! forgotten silently.
+//!
+//! The cursor stores each transaction's id, booking date, booked state and
+//! a short hash per compared field, never the values themselves, so it can
+//! live in a state directory without duplicating bank data. Persist it with
+//! [`Cursor::save`] after the events were handled; events are delivered at
+//! least once. An event that couldn't be handled can be handed back with
+//! [`Watcher::redeliver`] so that the next poll reports it again.
+//!
+//! Banks often book a pending card payment under a new id. A pending
+//! transaction that disappears in the same poll as a new one appears on the
+//! same account with the same amount, at most [`CORRELATION_DAYS`] apart, is
+//! reported as one `changed` event with `previousId` set instead of a
+//! `disappeared` and a `new` event.
+//!
+//! Requires the `watch` feature (enabled by `cli`).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use moneymoney::watch::{Cursor, Watcher};
+//!
+//! # fn main() -> Result<(), moneymoney::watch::WatchError> {
+//! let mut watcher = Watcher::new(Cursor::load("watch.json")?).window_days(14);
+//! loop {
+//!     for event in watcher.poll()? {
+//!         println!("{event:?}");
+//!     }
+//!     watcher.cursor().save("watch.json")?;
+//!     std::thread::sleep(std::time::Duration::from_secs(300));
+//! }
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::diff::transaction_fields;
+use crate::export_transactions::{ExportTransactionsParams, MoneymoneyTransaction};
+use crate::fs::write_atomic;
+
+/// How many days a booked transaction may be dated from the pending one it
+/// replaces.
+pub const CORRELATION_DAYS: u64 = 7;
+
+/// Errors while polling or persisting the cursor.
+#[derive(Debug, Error)]
+pub enum WatchError {
+    /// The cursor file could not be read or written.
+    #[error("failed to access {path}: {source}")]
+    Io {
+        /// The cursor file.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+
+    /// The cursor file is not a valid cursor.
+    #[error("{path}: {source}")]
+    Json {
+        /// The cursor file.
+        path: PathBuf,
+        /// The underlying error.
+        source: serde_json::Error,
+    },
+
+    /// Exporting the transactions failed.
+    #[error(transparent)]
+    Export(#[from] crate::Error),
+}
+
+/// Something that happened to a transaction since the last poll.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(
+    tag = "event",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum WatchEvent {
+    /// A transaction that wasn't there before.
+    New {
+        /// The transaction.
+        transaction: MoneymoneyTransaction,
+    },
+    /// A transaction with different fields than before.
+    Changed {
+        /// The id it had before, if the bank booked it under a new one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        previous_id: Option<u64>,
+        /// The changed fields by their JSON names, e.g. `booked` or `category`.
+        fields: Vec<String>,
+        /// The transaction as it is now.
+        transaction: MoneymoneyTransaction,
+    },
+    /// A transaction that is gone although its date is still in the window.
+    Disappeared {
+        /// Its id.
+        id: u64,
+        /// Its last known booking date.
+        booking_date: NaiveDate,
+    },
+}
+
+impl WatchEvent {
+    /// `new`, `changed` or `disappeared`, as in the `event` field.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WatchEvent::New { .. } => "new",
+            WatchEvent::Changed { .. } => "changed",
+            WatchEvent::Disappeared { .. } => "disappeared",
+        }
+    }
+
+    /// The id of the transaction the event is about.
+    pub fn id(&self) -> u64 {
+        match self {
+            WatchEvent::New { transaction } | WatchEvent::Changed { transaction, .. } => {
+                transaction.id
+            }
+            WatchEvent::Disappeared { id, .. } => *id,
+        }
+    }
+}
+
+/// What the cursor remembers of a transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Seen {
+    /// Its booking date, to tell sliding out of the window from disappearing.
+    pub booking_date: NaiveDate,
+    /// Whether it was booked, as only pending transactions change ids.
+    pub booked: bool,
+    /// A hash per compared field, by JSON name (plus `accountUuid`).
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Seen {
+    fn of(transaction: &MoneymoneyTransaction) -> Self {
+        let mut fields: BTreeMap<String, String> = transaction_fields(transaction)
+            .into_iter()
+            .map(|(field, value)| (field.to_string(), hash(&value.to_string())))
+            .collect();
+        fields.insert("accountUuid".to_string(), hash(&transaction.account_uuid.to_string()));
+        Seen {
+            booking_date: transaction.booking_date.date_naive(),
+            booked: transaction.booked,
+            fields,
+        }
+    }
+
+    /// Names of the fields whose hashes differ.
+    fn changed_fields(&self, now: &Seen) -> Vec<String> {
+        let mut fields: Vec<String> = now
+            .fields
+            .iter()
+            .filter(|(field, hash)| self.fields.get(*field) != Some(hash))
+            .map(|(field, _)| field.clone())
+            .collect();
+        fields.extend(
+            self.fields
+                .keys()
+                .filter(|field| !now.fields.contains_key(*field))
+                .cloned(),
+        );
+        fields
+    }
+
+    /// Whether `self`, a pending transaction, could have been booked as `now`.
+    fn replaced_by(&self, now: &Seen) -> bool {
+        let same = |field: &str| self.fields.get(field) == now.fields.get(field);
+        !self.booked
+            && same("accountUuid")
+            && same("amount")
+            && days_apart(self.booking_date, now.booking_date) <= CORRELATION_DAYS
+    }
+}
+
+/// The first 16 hex digits of the SHA-256 of `value`.
+fn hash(value: &str) -> String {
+    let digest = format!("{:x}", Sha256::digest(value.as_bytes()));
+    digest[..16].to_string()
+}
+
+fn days_apart(a: NaiveDate, b: NaiveDate) -> u64 {
+    (a - b).num_days().unsigned_abs()
+}
+
+/// The transactions seen by the last poll.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Cursor {
+    /// When the last poll happened; `None` before the first one.
+    pub polled_at: Option<DateTime<Utc>>,
+    /// The transactions in the window then, by id.
+    pub seen: BTreeMap<u64, Seen>,
+}
+
+impl Cursor {
+    /// Read a cursor written by [`Cursor::save`]; an empty one if `path`
+    /// doesn't exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WatchError::Io`] if the file can't be read and
+    /// [`WatchError::Json`] if it isn't a cursor.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, WatchError> {
+        let path = path.as_ref();
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Cursor::default()),
+            Err(source) => return Err(io_error(path, source)),
+        };
+        serde_json::from_slice(&bytes).map_err(|source| WatchError::Json {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Write the cursor through a temporary file, creating the directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WatchError::Io`] if the file can't be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), WatchError> {
+        let path = path.as_ref();
+        let json = serde_json::to_vec(self).map_err(|source| WatchError::Json {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        write_atomic(path, &json, io_error)
+    }
+}
+
+fn io_error(path: &Path, source: std::io::Error) -> WatchError {
+    WatchError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// Turns successive transaction exports into [`WatchEvent`]s.
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    cursor: Cursor,
+    window_days: u64,
+    emit_existing: bool,
+}
+
+impl Watcher {
+    /// Continue from `cursor`, watching the last 30 days.
+    pub fn new(cursor: Cursor) -> Self {
+        Self {
+            cursor,
+            window_days: 30,
+            emit_existing: false,
+        }
+    }
+
+    /// Watch transactions booked in the last `days` days.
+    pub fn window_days(mut self, days: u64) -> Self {
+        self.window_days = days;
+        self
+    }
+
+    /// Report every transaction of the first poll as `new`. By default the
+    /// first poll only fills the cursor.
+    pub fn emit_existing(mut self, emit_existing: bool) -> Self {
+        self.emit_existing = emit_existing;
+        self
+    }
+
+    /// What the watcher has seen; save it after handling the events.
+    pub fn cursor(&self) -> &Cursor {
+        &self.cursor
+    }
+
+    /// Forget that `event` was reported: restore what `previous`, the cursor
+    /// before the poll, knew about its transactions, so that the next poll
+    /// reports the event again.
+    pub fn redeliver(&mut self, event: &WatchEvent, previous: &Cursor) {
+        let previous_id = match event {
+            WatchEvent::Changed { previous_id, .. } => *previous_id,
+            _ => None,
+        };
+        for id in std::iter::once(event.id()).chain(previous_id) {
+            match previous.seen.get(&id) {
+                Some(seen) => self.cursor.seen.insert(id, seen.clone()),
+                None => self.cursor.seen.remove(&id),
+            };
+        }
+    }
+
+    /// The start of the window on `today`.
+    pub fn from_date(&self, today: NaiveDate) -> NaiveDate {
+        today - Days::new(self.window_days)
+    }
+
+    /// Export the window's transactions from MoneyMoney and compare them
+    /// with the cursor.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WatchError::Export`] if the export fails; the cursor is
+    /// unchanged then.
+    pub fn poll(&mut self) -> Result<Vec<WatchEvent>, WatchError> {
+        let from_date = self.from_date(chrono::Local::now().date_naive());
+        let response = crate::export_transactions(ExportTransactionsParams::new(from_date))?;
+        Ok(self.observe(&response.transactions, from_date, Utc::now()))
+    }
+
+    /// Compare `transactions`, all booked on or after `from_date`, with the
+    /// cursor and advance it. Events are ordered by booking date, followed
+    /// by the disappeared transactions.
+    pub fn observe(
+        &mut self,
+        transactions: &[MoneymoneyTransaction],
+        from_date: NaiveDate,
+        now: DateTime<Utc>,
+    ) -> Vec<WatchEvent> {
+        let current: BTreeMap<u64, Seen> =
+            transactions.iter().map(|t| (t.id, Seen::of(t))).collect();
+        let first = self.cursor.polled_at.is_none();
+        self.cursor.polled_at = Some(now);
+        let previous = std::mem::replace(&mut self.cursor.seen, current);
+        if first && !self.emit_existing {
+            return Vec::new();
+        }
+        let current = &self.cursor.seen;
+        let mut vanished: BTreeMap<u64, &Seen> = previous
+            .iter()
+            .filter(|(id, seen)| seen.booking_date >= from_date && !current.contains_key(id))
+            .map(|(id, seen)| (*id, seen))
+            .collect();
+
+        let mut sorted: Vec<&MoneymoneyTransaction> = transactions.iter().collect();
+        sorted.sort_by_key(|t| (t.booking_date, t.id));
+        let mut events = Vec::new();
+        for transaction in sorted {
+            let seen = &current[&transaction.id];
+            if let Some(before) = previous.get(&transaction.id) {
+                let fields = before.changed_fields(seen);
+                if !fields.is_empty() {
+                    events.push(WatchEvent::Changed {
+                        previous_id: None,
+                        fields,
+                        transaction: transaction.clone(),
+                    });
+                }
+                continue;
+            }
+            let pending = vanished
+                .iter()
+                .filter(|(_, before)| before.replaced_by(seen))
+                .min_by_key(|(_, before)| days_apart(before.booking_date, seen.booking_date))
+                .map(|(id, _)| *id);
+            match pending.and_then(|id| vanished.remove_entry(&id)) {
+                Some((previous_id, before)) => events.push(WatchEvent::Changed {
+                    previous_id: Some(previous_id),
+                    fields: before.changed_fields(seen),
+                    transaction: transaction.clone(),
+                }),
+                None => events.push(WatchEvent::New {
+                    transaction: transaction.clone(),
+                }),
+            }
+        }
+        events.extend(
+            vanished
+                .into_iter()
+                .map(|(id, seen)| WatchEvent::Disappeared {
+                    id,
+                    booking_date: seen.booking_date,
+                }),
+        );
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{date, transaction, CASH_UUID, GIRO_UUID};
+    use chrono::TimeZone;
+
+    fn at(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, day, 12, 0, 0).unwrap()
+    }
+
+    fn kinds(events: &[WatchEvent]) -> Vec<(&'static str, u64)> {
+        events.iter().map(|e| (e.kind(), e.id())).collect()
+    }
+
+    #[test]
+    fn test_first_poll_fills_the_cursor() {
+        let rewe = transaction(1, GIRO_UUID, date(2024, 3, 1), "REWE", -42.0);
+        let mut watcher = Watcher::new(Cursor::default());
+        assert!(watcher
+            .observe(std::slice::from_ref(&rewe), date(2024, 2, 1), at(1))
+            .is_empty());
+        assert_eq!(watcher.cursor().seen.len(), 1);
+        assert!(watcher
+            .observe(std::slice::from_ref(&rewe), date(2024, 2, 1), at(2))
+            .is_empty());
+
+        let mut watcher = Watcher::new(Cursor::default()).emit_existing(true);
+        let events = watcher.observe(&[rewe], date(2024, 2, 1), at(1));
+        assert_eq!(kinds(&events), [("new", 1)]);
+    }
+
+    #[test]
+    fn test_new_changed_and_disappeared() {
+        let rewe = transaction(1, GIRO_UUID, date(2024, 3, 1), "REWE", -42.0);
+        let gone = transaction(2, GIRO_UUID, date(2024, 3, 2), "Storno", -5.0);
+        let old = transaction(3, GIRO_UUID, date(2024, 2, 1), "Alt", -1.0);
+        let mut watcher = Watcher::new(Cursor::default());
+        watcher.observe(&[old, rewe.clone(), gone], date(2024, 2, 1), at(3));
+
+        let mut recategorized = rewe;
+        recategorized.category = Some("Lebensmittel".to_string());
+        recategorized.comment = Some("Wocheneinkauf".to_string());
+        let salary = transaction(4, GIRO_UUID, date(2024, 3, 4), "Gehalt", 2500.0);
+        // `old` slid out of the window and is not reported.
+        let events = watcher.observe(&[recategorized, salary], date(2024, 2, 5), at(5));
+        assert_eq!(kinds(&events), [("changed", 1), ("new", 4), ("disappeared", 2)]);
+        let WatchEvent::Changed { fields, .. } = &events[0] else {
+            panic!("expected a change");
+        };
+        assert_eq!(fields, &["category", "comment"]);
+        assert_eq!(watcher.cursor().seen.keys().copied().collect::<Vec<_>>(), [1, 4]);
+    }
+
+    #[test]
+    fn test_pending_rebooked_under_new_id_is_one_change() {
+        let mut pending = transaction(10, GIRO_UUID, date(2024, 3, 1), "AMAZON", -19.99);
+        pending.booked = false;
+        let mut other_account = transaction(11, CASH_UUID, date(2024, 3, 1), "AMAZON", -19.99);
+        other_account.booked = false;
+        let mut watcher = Watcher::new(Cursor::default());
+        watcher.observe(&[pending, other_account], date(2024, 2, 1), at(1));
+
+        let mut booked = transaction(20, GIRO_UUID, date(2024, 3, 3), "AMAZON EU SARL", -19.99);
+        booked.booked = true;
+        let events = watcher.observe(&[booked], date(2024, 2, 1), at(3));
+        assert_eq!(kinds(&events), [("changed", 20), ("disappeared", 11)]);
+        let WatchEvent::Changed {
+            previous_id,
+            fields,
+            ..
+        } = &events[0]
+        else {
+            panic!("expected a change");
+        };
+        assert_eq!(*previous_id, Some(10));
+        assert_eq!(fields, &["booked", "bookingDate", "name", "valueDate"]);
+
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["event"], "changed");
+        assert_eq!(json["previousId"], 10);
+        let json = serde_json::to_value(&events[1]).unwrap();
+        assert_eq!(json["bookingDate"], "2024-03-01");
+    }
+
+    #[test]
+    fn test_redelivered_events_are_reported_again() {
+        let mut pending = transaction(10, GIRO_UUID, date(2024, 3, 1), "AMAZON", -19.99);
+        pending.booked = false;
+        let rewe = transaction(1, GIRO_UUID, date(2024, 3, 1), "REWE", -42.0);
+        let gone = transaction(2, GIRO_UUID, date(2024, 3, 2), "Storno", -5.0);
+        let mut watcher = Watcher::new(Cursor::default());
+        watcher.observe(&[pending, rewe.clone(), gone], date(2024, 2, 1), at(1));
+
+        let mut recategorized = rewe;
+        recategorized.category = Some("Lebensmittel".to_string());
+        let booked = transaction(20, GIRO_UUID, date(2024, 3, 3), "AMAZON EU SARL", -19.99);
+        let salary = transaction(4, GIRO_UUID, date(2024, 3, 4), "Gehalt", 2500.0);
+        let now = [recategorized, booked, salary];
+        let before = watcher.cursor().clone();
+        let events = watcher.observe(&now, date(2024, 2, 1), at(2));
+        let expected = [
+            ("changed", 1),
+            ("changed", 20),
+            ("new", 4),
+            ("disappeared", 2),
+        ];
+        assert_eq!(kinds(&events), expected);
+        for event in &events {
+            watcher.redeliver(event, &before);
+        }
+        assert_eq!(watcher.cursor().seen, before.seen);
+        assert_eq!(kinds(&watcher.observe(&now, date(2024, 2, 1), at(3))), expected);
+        assert!(watcher.observe(&now, date(2024, 2, 1), at(4)).is_empty());
+    }
+
+    #[test]
+    fn test_cursor_round_trips() {
+        let path = std::env::temp_dir()
+            .join(format!("moneymoney-watch-{}", std::process::id()))
+            .join("watch.json");
+        assert_eq!(Cursor::load(&path).unwrap(), Cursor::default());
+        let mut watcher = Watcher::new(Cursor::default());
+        let rewe = transaction(1, GIRO_UUID, date(2024, 3, 1), "REWE", -42.0);
+        watcher.observe(&[rewe], date(2024, 2, 1), at(1));
+        watcher.cursor().save(&path).unwrap();
+        let raw = std::fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("REWE"));
+        assert_eq!(&Cursor::load(&path).unwrap(), watcher.cursor());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}